@@ -0,0 +1,112 @@
+//! End-to-end walk through the commands with in-memory adapters
+//!
+//! This only uses the public API, the same way an application embedding the crate would:
+//!
+//! ```sh
+//! cargo run --example in_memory
+//! ```
+
+use std::sync::Arc;
+
+use chrono::{Months, Utc};
+use rust_loyalty_service::{
+    adapters::{database::memory::MemoryDatabase, event_publisher::memory::MemoryPublisher},
+    commands::{
+        add_points::{AddPointsEvent, AddPointsRequest},
+        get_loyalty::GetLoyaltyRequest,
+        redeem_points::RedeemPointsRequest,
+        DomainLogic,
+    },
+    domain::Money,
+    ports::member::{self, Member, MemberPort},
+};
+use tower::{BoxError, Service, ServiceExt};
+use uuid::Uuid;
+
+/// Member service knowing a single member, who joined two years ago
+struct SingleMember(Uuid);
+
+#[async_trait::async_trait]
+impl MemberPort for SingleMember {
+    async fn get_member(&self, member_id: Uuid) -> Result<Member, member::Error> {
+        if member_id != self.0 {
+            return Err(member::Error::MemberDoesNotExist(member_id));
+        }
+        Ok(Member {
+            member_id,
+            active_member: true,
+            membership_since: Utc::now() - Months::new(24),
+            ..Default::default()
+        })
+    }
+
+    async fn get_members(
+        &self,
+        member_ids: &[Uuid],
+    ) -> Result<Vec<Result<Member, member::Error>>, member::Error> {
+        let mut members = Vec::with_capacity(member_ids.len());
+        for member_id in member_ids {
+            members.push(self.get_member(*member_id).await);
+        }
+        Ok(members)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    let member_id = Uuid::new_v4();
+    let event_publisher = MemoryPublisher::default();
+    let mut domain = DomainLogic::new(
+        Arc::new(MemoryDatabase::default()),
+        Arc::new(SingleMember(member_id)),
+    )
+    .with_event_publisher(Arc::new(event_publisher.clone()));
+
+    let added = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+        .await?
+        .call(AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: Money::new(4250, "EUR")?,
+            },
+            occurred_at: None,
+            order_reference: None,
+            idempotency_key: None,
+        })
+        .await?;
+    println!(
+        "{:?} member earned {} points",
+        added.tier,
+        added.new_loyalty_points - added.old_loyalty_points
+    );
+
+    let redeemed = ServiceExt::<RedeemPointsRequest>::ready(&mut domain)
+        .await?
+        .call(RedeemPointsRequest {
+            member_id,
+            loyalty_points: 100,
+            reward: "Free coffee".to_string(),
+            idempotency_key: None,
+        })
+        .await?;
+    println!("{} points left", redeemed.new_loyalty_points);
+
+    let loyalty = ServiceExt::<GetLoyaltyRequest>::ready(&mut domain)
+        .await?
+        .call(GetLoyaltyRequest {
+            member_id,
+            recent_events: 10,
+        })
+        .await?;
+    for event in loyalty.recent_events {
+        println!(
+            "{} {:>+5} {}",
+            event.created_at.to_rfc3339(),
+            event.delta_points,
+            event.reason
+        );
+    }
+    println!("{} events published", event_publisher.events().len());
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use crate::{
+    domain::Campaign,
+    ports::campaign::{CampaignPort, Error},
+};
+use chrono::{DateTime, Utc};
+
+/// Campaign adapter backed by a fixed list of campaigns
+#[derive(Clone, Debug, Default)]
+pub struct StaticCampaigns {
+    campaigns: Vec<Campaign>,
+}
+
+impl StaticCampaigns {
+    pub fn with_campaign(mut self, campaign: Campaign) -> Self {
+        self.campaigns.push(campaign);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl CampaignPort for StaticCampaigns {
+    async fn get_active_campaigns(&self, at: DateTime<Utc>) -> Result<Vec<Campaign>, Error> {
+        Ok(self
+            .campaigns
+            .iter()
+            .filter(|campaign| campaign.is_active(at))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures;
+    use chrono::Duration;
+    use speculoos::prelude::*;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_get_active_campaigns() {
+        let campaign = Campaign {
+            campaign_id: Uuid::new_v4(),
+            name: "Double points".to_string(),
+            starts_at: fixtures::now(),
+            ends_at: fixtures::now() + Duration::days(1),
+            channel: None,
+            segment: None,
+            multiplier: 2,
+        };
+        let campaigns = StaticCampaigns::default().with_campaign(campaign.clone());
+
+        let res = campaigns.get_active_campaigns(fixtures::now()).await;
+        assert_that!(res).is_ok().is_equal_to(vec![campaign]);
+
+        // Campaigns end before their end date
+        let res = campaigns
+            .get_active_campaigns(fixtures::now() + Duration::days(1))
+            .await;
+        assert_that!(res).is_ok().is_empty();
+    }
+}
@@ -0,0 +1,3 @@
+//! Adapters for the campaign port
+
+pub mod memory;
@@ -0,0 +1,119 @@
+use crate::{
+    adapters::database::memory::ErasedPoisonError,
+    domain::CaseLock,
+    ports::case_lock::{CaseLockPort, Error},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCaseLocks {
+    locks: Arc<Mutex<HashMap<Uuid, CaseLock>>>,
+}
+
+#[async_trait::async_trait]
+impl CaseLockPort for MemoryCaseLocks {
+    async fn get_lock(&self, member_id: Uuid) -> Result<Option<CaseLock>, Error> {
+        Ok(self.locks.lock()?.get(&member_id).cloned())
+    }
+
+    async fn acquire_lock(&self, case_lock: CaseLock) -> Result<(), Error> {
+        let mut locks = self.locks.lock()?;
+        if let Some(existing) = locks.get(&case_lock.member_id) {
+            if existing.holder != case_lock.holder && existing.is_active(case_lock.acquired_at) {
+                return Err(Error::AlreadyLocked(existing.clone()));
+            }
+        }
+        locks.insert(case_lock.member_id, case_lock);
+
+        Ok(())
+    }
+
+    async fn release_lock(&self, member_id: Uuid, holder: &str) -> Result<(), Error> {
+        let mut locks = self.locks.lock()?;
+        if locks
+            .get(&member_id)
+            .is_some_and(|existing| existing.holder == holder)
+        {
+            locks.remove(&member_id);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(err: PoisonError<T>) -> Self {
+        Self::Adapter(Box::new(ErasedPoisonError(err.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+
+    fn case_lock(member_id: Uuid, holder: &str, ttl: Duration) -> CaseLock {
+        let now = Utc::now();
+        CaseLock {
+            member_id,
+            holder: holder.to_string(),
+            acquired_at: now,
+            expires_at: now + ttl,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acquire_release() {
+        let locks = MemoryCaseLocks::default();
+        let member_id = Uuid::new_v4();
+        let res = locks
+            .acquire_lock(case_lock(member_id, "agent-1", Duration::minutes(15)))
+            .await;
+        assert_that!(res).is_ok();
+
+        // Another agent cannot acquire the lock, but the holder can extend it
+        let res = locks
+            .acquire_lock(case_lock(member_id, "agent-2", Duration::minutes(15)))
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::AlreadyLocked(_)));
+        let res = locks
+            .acquire_lock(case_lock(member_id, "agent-1", Duration::minutes(30)))
+            .await;
+        assert_that!(res).is_ok();
+
+        // Only the holder can release the lock
+        let res = locks.release_lock(member_id, "agent-2").await;
+        assert_that!(res).is_ok();
+        let res = locks.get_lock(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|case_lock| case_lock.is_some());
+        let res = locks.release_lock(member_id, "agent-1").await;
+        assert_that!(res).is_ok();
+        let res = locks.get_lock(member_id).await;
+        assert_that!(res).is_ok().is_none();
+    }
+
+    #[tokio::test]
+    async fn test_acquire_expired() {
+        let locks = MemoryCaseLocks::default();
+        let member_id = Uuid::new_v4();
+        let res = locks
+            .acquire_lock(case_lock(member_id, "agent-1", Duration::zero()))
+            .await;
+        assert_that!(res).is_ok();
+
+        // Expired locks can be taken over
+        let res = locks
+            .acquire_lock(case_lock(member_id, "agent-2", Duration::minutes(15)))
+            .await;
+        assert_that!(res).is_ok();
+    }
+}
@@ -0,0 +1,3 @@
+//! Adapters for the case lock port
+
+pub mod memory;
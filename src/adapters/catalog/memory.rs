@@ -0,0 +1,58 @@
+use crate::{
+    domain::Reward,
+    ports::catalog::{CatalogPort, Error},
+};
+use uuid::Uuid;
+
+/// Catalog adapter backed by a fixed list of rewards
+#[derive(Clone, Debug, Default)]
+pub struct StaticCatalog {
+    rewards: Vec<Reward>,
+}
+
+impl StaticCatalog {
+    pub fn with_reward(mut self, reward: Reward) -> Self {
+        self.rewards.push(reward);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl CatalogPort for StaticCatalog {
+    async fn get_reward(&self, reward_id: Uuid) -> Result<Reward, Error> {
+        self.rewards
+            .iter()
+            .find(|reward| reward.reward_id == reward_id)
+            .cloned()
+            .ok_or(Error::RewardDoesNotExist(reward_id))
+    }
+
+    async fn get_rewards(&self) -> Result<Vec<Reward>, Error> {
+        Ok(self.rewards.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    #[tokio::test]
+    async fn test_get_reward() {
+        let reward = Reward {
+            reward_id: Uuid::new_v4(),
+            name: "Free coffee".to_string(),
+            points_cost: 100,
+            eligible_tiers: Vec::new(),
+        };
+        let catalog = StaticCatalog::default().with_reward(reward.clone());
+
+        let res = catalog.get_reward(reward.reward_id).await;
+        assert_that!(res).is_ok().is_equal_to(reward);
+
+        let res = catalog.get_reward(Uuid::nil()).await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::RewardDoesNotExist(_)));
+    }
+}
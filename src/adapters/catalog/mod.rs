@@ -0,0 +1,3 @@
+//! Adapters for the catalog port
+
+pub mod memory;
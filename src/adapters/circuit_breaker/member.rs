@@ -0,0 +1,17 @@
+use super::CircuitBreaker;
+use crate::ports::member::{Error, Member, MemberPort};
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+impl<P> MemberPort for CircuitBreaker<P>
+where
+    P: MemberPort + Send + Sync,
+{
+    async fn get_member(&self, member_id: Uuid) -> Result<Member, Error> {
+        self.call(self.inner.get_member(member_id)).await
+    }
+
+    async fn get_members(&self, member_ids: &[Uuid]) -> Result<Vec<Result<Member, Error>>, Error> {
+        self.call(self.inner.get_members(member_ids)).await
+    }
+}
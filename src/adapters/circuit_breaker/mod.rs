@@ -0,0 +1,229 @@
+//! Circuit breaking for calls to other services
+//!
+//! [`CircuitBreaker`] wraps a member adapter and stops calling it after repeated transient
+//! failures, so requests fail fast with [`Error::CircuitOpen`] instead of waiting for timeouts.
+//! After a cool-down, a single trial call decides whether to close the circuit again.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::ports::member::Error;
+
+mod member;
+
+/// Port adapter failing fast while `inner` is down
+///
+/// Clones share the same state, so a circuit breaker can be shared between domain logic
+/// instances.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker<P> {
+    inner: P,
+    /// Consecutive transient failures opening the circuit
+    failure_threshold: u32,
+    /// How long the circuit stays open before a trial call
+    cooldown: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Calls go through
+    Closed { failures: u32 },
+    /// Calls fail fast until the cool-down ends
+    Open { until: Instant },
+    /// A trial call is in flight, and other calls fail fast
+    ///
+    /// If the trial call never completes, e.g. because it was cancelled, another one is allowed
+    /// after a cool-down.
+    HalfOpen { since: Instant },
+}
+
+impl<P> CircuitBreaker<P> {
+    /// Open the circuit after `failure_threshold` consecutive transient failures of `inner`, for
+    /// `cooldown`
+    pub fn new(inner: P, failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner,
+            failure_threshold,
+            cooldown,
+            state: Arc::new(Mutex::new(State::Closed { failures: 0 })),
+        }
+    }
+
+    /// Run a call to the inner adapter, unless the circuit is open
+    async fn call<T>(
+        &self,
+        call: impl Future<Output = Result<T, Error>> + Send,
+    ) -> Result<T, Error> {
+        self.acquire_at(Instant::now())?;
+        let res = call.await;
+        self.record_at(Instant::now(), res.as_ref().is_err_and(Error::is_retryable));
+        res
+    }
+
+    /// Check whether a call can go through, moving to half-open once the cool-down ended
+    fn acquire_at(&self, now: Instant) -> Result<(), Error> {
+        let mut state = self.lock();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(Error::CircuitOpen),
+            State::HalfOpen { since } if now < since + self.cooldown => Err(Error::CircuitOpen),
+            State::Open { .. } | State::HalfOpen { .. } => {
+                tracing::info!("circuit half-open, trying the member service again");
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    /// Record the outcome of a call
+    ///
+    /// Only the trial call closes the circuit: calls that started before it opened and succeed
+    /// later do not.
+    fn record_at(&self, now: Instant, failed: bool) {
+        let mut state = self.lock();
+        *state = match (*state, failed) {
+            (State::Closed { .. }, false) => State::Closed { failures: 0 },
+            (State::Open { until }, false) => State::Open { until },
+            (State::HalfOpen { .. }, false) => {
+                tracing::info!("circuit closed, the member service is back");
+                State::Closed { failures: 0 }
+            }
+            (State::Closed { failures }, true) if failures + 1 < self.failure_threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, true) => {
+                tracing::warn!(cooldown = ?self.cooldown, "circuit opened after repeated failures");
+                State::Open {
+                    until: now + self.cooldown,
+                }
+            }
+        };
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state is a single value, so it is consistent even after a panic
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::member::{MemberPort, MockMemberPort};
+    use speculoos::prelude::*;
+    use uuid::Uuid;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_state() {
+        let breaker = CircuitBreaker::new((), 2, COOLDOWN);
+        let start = Instant::now();
+
+        // A success resets the count of consecutive failures
+        breaker.record_at(start, true);
+        breaker.record_at(start, false);
+        breaker.record_at(start, true);
+        assert_that!(breaker.acquire_at(start)).is_ok();
+
+        // The second consecutive failure opens the circuit until the cool-down ends
+        breaker.record_at(start, true);
+        assert_that!(breaker.acquire_at(start + COOLDOWN / 2))
+            .is_err()
+            .matches(|err| matches!(err, Error::CircuitOpen));
+
+        // A single trial call goes through after the cool-down, and its failure opens the
+        // circuit again
+        let trial = start + COOLDOWN;
+        assert_that!(breaker.acquire_at(trial)).is_ok();
+        assert_that!(breaker.acquire_at(trial)).is_err();
+        breaker.record_at(trial, true);
+        assert_that!(breaker.acquire_at(trial + COOLDOWN / 2)).is_err();
+
+        // A successful trial call closes the circuit
+        let trial = trial + COOLDOWN;
+        assert_that!(breaker.acquire_at(trial)).is_ok();
+        breaker.record_at(trial, false);
+        assert_that!(*breaker.lock()).is_equal_to(State::Closed { failures: 0 });
+    }
+
+    #[test]
+    fn test_state_late_success() {
+        let breaker = CircuitBreaker::new((), 1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record_at(start, true);
+
+        // A call that started before the circuit opened succeeds
+        breaker.record_at(start, false);
+
+        // The circuit stays open until the cool-down ends
+        assert_that!(breaker.acquire_at(start + COOLDOWN / 2))
+            .is_err()
+            .matches(|err| matches!(err, Error::CircuitOpen));
+        assert_that!(breaker.acquire_at(start + COOLDOWN)).is_ok();
+    }
+
+    #[test]
+    fn test_state_abandoned_trial() {
+        let breaker = CircuitBreaker::new((), 1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record_at(start, true);
+
+        // The trial call never completes
+        assert_that!(breaker.acquire_at(start + COOLDOWN)).is_ok();
+
+        // Another trial call is allowed after a cool-down
+        assert_that!(breaker.acquire_at(start + COOLDOWN * 3 / 2)).is_err();
+        assert_that!(breaker.acquire_at(start + COOLDOWN * 2)).is_ok();
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        // GIVEN a member service timing out, behind a circuit opening after 2 failures
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .times(2)
+            .returning(|_| Err(Error::Unavailable("timeout".into())));
+        let member = CircuitBreaker::new(member, 2, COOLDOWN);
+
+        // WHEN fetching a member three times
+        let mut results = Vec::new();
+        for _ in 0..3 {
+            results.push(member.get_member(Uuid::new_v4()).await);
+        }
+
+        // THEN the third call fails fast without calling the member service
+        assert_that!(results[1])
+            .is_err()
+            .matches(|err| matches!(err, Error::Unavailable(_)));
+        assert_that!(results[2])
+            .is_err()
+            .matches(|err| matches!(err, Error::CircuitOpen));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_fatal() {
+        // GIVEN a member service that does not know the members
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .times(3)
+            .returning(|member_id| Err(Error::MemberDoesNotExist(member_id)));
+        let member = CircuitBreaker::new(member, 2, COOLDOWN);
+
+        // WHEN fetching members three times
+        for _ in 0..3 {
+            let _ = member.get_member(Uuid::new_v4()).await;
+        }
+
+        // THEN the circuit stays closed, as the service answered
+        assert_that!(*member.lock()).is_equal_to(State::Closed { failures: 0 });
+    }
+}
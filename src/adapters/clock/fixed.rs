@@ -0,0 +1,51 @@
+use crate::ports::clock::ClockPort;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Clock that only moves when told to
+///
+/// This is meant for tests, e.g. to check tier boundaries on a given date. Clones share the same
+/// time.
+#[derive(Clone, Debug)]
+pub struct FixedClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl ClockPort for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use speculoos::prelude::*;
+
+    #[test]
+    fn test_now() {
+        let now = Utc.with_ymd_and_hms(2024, 2, 28, 12, 0, 0).unwrap();
+        let clock = FixedClock::new(now);
+
+        clock.clone().advance(Duration::days(1));
+
+        assert_that!(clock.now()).is_equal_to(Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,4 @@
+//! Adapters for the clock port
+
+pub mod fixed;
+pub mod system;
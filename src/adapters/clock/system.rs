@@ -0,0 +1,12 @@
+use crate::ports::clock::ClockPort;
+use chrono::{DateTime, Utc};
+
+/// Time from the system clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl ClockPort for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
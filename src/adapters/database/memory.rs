@@ -1,9 +1,15 @@
 use crate::{
-    domain::{Loyalty, LoyaltyEvent},
+    domain::{
+        BalanceChange, CampaignRun, DomainEvent, EventNote, ExpiringLot, Loyalty, LoyaltyEvent,
+        MemberOverride, MemberRestriction, OutboxEntry, PartnerTransfer, PendingLot, PointsHold,
+        TierChange, TierEvaluation,
+    },
+    points::apply_delta,
     ports::database::{DatabasePort, Error},
 };
+use chrono::{DateTime, Utc};
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{HashMap, HashSet, VecDeque},
     sync::{Arc, Mutex, PoisonError},
 };
 use uuid::Uuid;
@@ -11,68 +17,1036 @@ use uuid::Uuid;
 #[derive(Clone, Debug)]
 pub struct MemoryDatabase {
     loyalties: Arc<Mutex<HashMap<Uuid, Loyalty>>>,
+    awaiting_approval: Arc<Mutex<HashMap<Uuid, Vec<LoyaltyEvent>>>>,
+    overrides: Arc<Mutex<HashMap<Uuid, Vec<MemberOverride>>>>,
+    restrictions: Arc<Mutex<HashMap<Uuid, Vec<MemberRestriction>>>>,
+    notes: Arc<Mutex<HashMap<Uuid, Vec<EventNote>>>>,
+    partner_transfers: Arc<Mutex<HashMap<Uuid, Vec<PartnerTransfer>>>>,
+    campaigns: Arc<Mutex<HashMap<Uuid, CampaignRun>>>,
+    /// Points issued by each campaign, counted separately from runs saved by each worker
+    campaign_points: Arc<Mutex<HashMap<Uuid, u64>>>,
+    /// Members who received their welcome bonus
+    ///
+    /// This is always locked after `loyalties`, and kept when loyalty records are evicted.
+    welcome_bonuses: Arc<Mutex<HashSet<Uuid>>>,
+    /// Balance change feed
+    ///
+    /// This is always locked after `loyalties`, so positions follow the order of the changes.
+    changes: Arc<Mutex<BalanceChanges>>,
+    /// Domain events waiting to be published
+    ///
+    /// This is always locked after `loyalties`, so entries are added along with the changes.
+    outbox: Arc<Mutex<Outbox>>,
+    /// Maximum number of members with a loyalty record
+    max_members: Option<usize>,
+    /// Maximum number of events kept for each member
+    max_events_per_member: Option<usize>,
+    /// Use of loyalty records, to evict the least recently used ones
+    ///
+    /// This is always locked after `loyalties`.
+    retention: Arc<Mutex<Retention>>,
+}
+
+/// Position of the latest balance change of each member
+#[derive(Debug, Default)]
+struct BalanceChanges {
+    last_sequence: u64,
+    members: HashMap<Uuid, u64>,
+}
+
+impl BalanceChanges {
+    fn record(&mut self, member_id: Uuid) {
+        self.last_sequence += 1;
+        self.members.insert(member_id, self.last_sequence);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Outbox {
+    last_outbox_id: u64,
+    entries: VecDeque<OutboxEntry>,
+}
+
+impl Outbox {
+    fn extend(&mut self, events: impl IntoIterator<Item = DomainEvent>) {
+        for event in events {
+            self.last_outbox_id += 1;
+            self.entries.push_back(OutboxEntry {
+                outbox_id: self.last_outbox_id,
+                event,
+            });
+        }
+    }
+}
+
+/// Number of loyalty records and events dropped by a [`MemoryDatabase`] to stay within its limits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvictionStats {
+    pub evicted_members: u64,
+    pub evicted_events: u64,
+}
+
+#[derive(Debug, Default)]
+struct Retention {
+    /// Incremented on every use of a loyalty record
+    last_use: u64,
+    /// Last use of each member's loyalty record
+    members: HashMap<Uuid, u64>,
+    stats: EvictionStats,
+}
+
+impl Retention {
+    fn touch(&mut self, member_id: Uuid) {
+        self.last_use += 1;
+        self.members.insert(member_id, self.last_use);
+    }
 }
 
 #[async_trait::async_trait]
 impl DatabasePort for MemoryDatabase {
     async fn get_loyalty_points(&self, member_id: Uuid) -> Result<Loyalty, Error> {
-        let loyalty = self
+        let loyalties = self.loyalties.lock()?;
+        let loyalty = match loyalties.get(&member_id) {
+            Some(loyalty) => {
+                self.retention.lock()?.touch(member_id);
+                loyalty.clone()
+            }
+            None => Loyalty::new(member_id),
+        };
+
+        Ok(loyalty)
+    }
+    async fn get_loyalty_points_batch(&self, member_ids: &[Uuid]) -> Result<Vec<Loyalty>, Error> {
+        let loyalties = self.loyalties.lock()?;
+        let mut retention = self.retention.lock()?;
+        let loyalties = member_ids
+            .iter()
+            .map(|member_id| match loyalties.get(member_id) {
+                Some(loyalty) => {
+                    retention.touch(*member_id);
+                    loyalty.clone()
+                }
+                None => Loyalty::new(*member_id),
+            })
+            .collect();
+
+        Ok(loyalties)
+    }
+
+    async fn get_loyalty_events(&self, member_id: Uuid) -> Result<Vec<LoyaltyEvent>, Error> {
+        // Events are appended when registered, so they are already in chronological order
+        let loyalties = self.loyalties.lock()?;
+        let events = match loyalties.get(&member_id) {
+            Some(loyalty) => {
+                self.retention.lock()?.touch(member_id);
+                loyalty.events.clone()
+            }
+            None => Vec::new(),
+        };
+
+        Ok(events)
+    }
+
+    async fn register_loyalty_event(
+        &self,
+        member_id: Uuid,
+        event: LoyaltyEvent,
+        expected_version: Option<u64>,
+    ) -> Result<Loyalty, Error> {
+        self.register_loyalty_event_with_outbox(member_id, event, expected_version, Vec::new())
+            .await
+    }
+
+    async fn register_loyalty_events_batch(
+        &self,
+        events: Vec<(Uuid, LoyaltyEvent)>,
+    ) -> Result<Vec<Loyalty>, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+
+        // Apply the events to copies first, so a rejected event leaves everything untouched
+        let mut updated: HashMap<Uuid, Loyalty> = HashMap::new();
+        let member_ids = events
+            .iter()
+            .map(|(member_id, _)| *member_id)
+            .collect::<Vec<_>>();
+        for (member_id, event) in events {
+            let loyalty = updated.entry(member_id).or_insert_with(|| {
+                loyalties
+                    .get(&member_id)
+                    .cloned()
+                    .unwrap_or_else(|| Loyalty::new(member_id))
+            });
+            apply_event(loyalty, event)?;
+            loyalty.version += 1;
+        }
+
+        let mut changes = self.changes.lock()?;
+        for (member_id, loyalty) in updated {
+            loyalties.insert(member_id, loyalty);
+            changes.record(member_id);
+        }
+        drop(changes);
+        member_ids
+            .into_iter()
+            .map(|member_id| self.retain(&mut loyalties, member_id))
+            .collect()
+    }
+
+    async fn register_loyalty_event_with_outbox(
+        &self,
+        member_id: Uuid,
+        event: LoyaltyEvent,
+        expected_version: Option<u64>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        let current_version = loyalties
+            .get(&member_id)
+            .map(|loyalty| loyalty.version)
+            .unwrap_or(0);
+        match expected_version {
+            Some(expected_version) if expected_version != current_version => {
+                return Err(Error::Conflict {
+                    member_id,
+                    expected_version,
+                    current_version,
+                })
+            }
+            _ => (),
+        }
+        register_event(&mut loyalties, member_id, event)?;
+        let loyalty = self.retain(&mut loyalties, member_id)?;
+        self.changes.lock()?.record(member_id);
+        self.outbox.lock()?.extend(outbox);
+
+        Ok(loyalty)
+    }
+
+    async fn compact_events(&self, member_id: Uuid, snapshot: LoyaltyEvent) -> Result<(), Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        if let Some(loyalty) = loyalties.get_mut(&member_id) {
+            loyalty
+                .events
+                .retain(|event| event.sequence > snapshot.sequence);
+            loyalty.events.insert(0, snapshot);
+            loyalty.version += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn mature_points(
+        &self,
+        until: DateTime<Utc>,
+        queue_matured: bool,
+    ) -> Result<Vec<(Loyalty, u32)>, Error> {
+        let mut matured = Vec::new();
+        let mut loyalties = self.loyalties.lock()?;
+        let mut changes = self.changes.lock()?;
+        let mut outbox = self.outbox.lock()?;
+        for loyalty in loyalties.values_mut() {
+            let (due, pending): (Vec<_>, Vec<_>) = loyalty
+                .pending_lots
+                .drain(..)
+                .partition(|lot| lot.matures_at <= until);
+            loyalty.pending_lots = pending;
+            if due.is_empty() {
+                continue;
+            }
+
+            let points: u32 = due.iter().map(|lot| lot.points).sum();
+            loyalty.pending_points -= points;
+            loyalty.points += points;
+            for lot in due {
+                if let Some(expires_at) = lot.expires_at {
+                    add_expiring_lot(loyalty, lot.event_id, lot.points, expires_at);
+                }
+            }
+            loyalty.version += 1;
+            changes.record(loyalty.member_id);
+            if queue_matured {
+                outbox.extend([DomainEvent::PointsMatured {
+                    member_id: loyalty.member_id,
+                    loyalty_points: points,
+                }]);
+            }
+            matured.push((loyalty.clone(), points));
+        }
+
+        Ok(matured)
+    }
+
+    async fn get_expired_lots(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, ExpiringLot)>, Error> {
+        let lots = self
             .loyalties
             .lock()?
+            .values()
+            .flat_map(|loyalty| {
+                loyalty
+                    .expiring_lots
+                    .iter()
+                    .filter(|lot| lot.expires_at <= until)
+                    .map(|lot| (loyalty.member_id, lot.clone()))
+            })
+            .collect();
+
+        Ok(lots)
+    }
+
+    async fn get_balance_changes(
+        &self,
+        checkpoint: u64,
+        limit: usize,
+    ) -> Result<Vec<BalanceChange>, Error> {
+        let loyalties = self.loyalties.lock()?;
+        let changes = self.changes.lock()?;
+        let mut members = changes
+            .members
+            .iter()
+            .filter(|(_, sequence)| **sequence > checkpoint)
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(_, sequence)| **sequence);
+
+        let balance_changes = members
+            .into_iter()
+            .take(limit)
+            .filter_map(|(member_id, sequence)| {
+                let loyalty = loyalties.get(member_id)?;
+                Some(BalanceChange {
+                    member_id: *member_id,
+                    points: loyalty.points,
+                    pending_points: loyalty.pending_points,
+                    sequence: *sequence,
+                })
+            })
+            .collect();
+
+        Ok(balance_changes)
+    }
+
+    async fn register_event_for_approval(
+        &self,
+        member_id: Uuid,
+        event: LoyaltyEvent,
+    ) -> Result<(), Error> {
+        self.awaiting_approval
+            .lock()?
+            .entry(member_id)
+            .or_default()
+            .push(event);
+
+        Ok(())
+    }
+
+    async fn get_events_awaiting_approval(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<LoyaltyEvent>, Error> {
+        let events = self
+            .awaiting_approval
+            .lock()?
             .get(&member_id)
             .cloned()
-            .unwrap_or_else(|| Loyalty::new(member_id));
+            .unwrap_or_default();
+
+        Ok(events)
+    }
+
+    async fn approve_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        // Keep the lock on events awaiting approval, so the event cannot be approved twice
+        let mut awaiting_approval = self.awaiting_approval.lock()?;
+        let events = awaiting_approval.entry(member_id).or_default();
+        let index = events
+            .iter()
+            .position(|event| event.event_id == event_id)
+            .ok_or(Error::EventDoesNotExist(event_id))?;
+
+        let mut loyalties = self.loyalties.lock()?;
+        register_event(&mut loyalties, member_id, events[index].clone())?;
+        let loyalty = self.retain(&mut loyalties, member_id)?;
+        self.changes.lock()?.record(member_id);
+        self.outbox.lock()?.extend(outbox);
+        events.remove(index);
 
         Ok(loyalty)
     }
-    async fn register_loyalty_event(
+
+    async fn reject_event(&self, member_id: Uuid, event_id: Uuid) -> Result<(), Error> {
+        let mut awaiting_approval = self.awaiting_approval.lock()?;
+        let events = awaiting_approval.entry(member_id).or_default();
+        let index = events
+            .iter()
+            .position(|event| event.event_id == event_id)
+            .ok_or(Error::EventDoesNotExist(event_id))?;
+        events.remove(index);
+
+        Ok(())
+    }
+
+    async fn get_expired_escrow_events(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        let events = self
+            .awaiting_approval
+            .lock()?
+            .iter()
+            .flat_map(|(member_id, events)| {
+                let mut expired: Vec<_> = events
+                    .iter()
+                    .filter(|event| {
+                        event
+                            .escrow_expires_at
+                            .is_some_and(|expires_at| expires_at <= until)
+                    })
+                    .collect();
+                expired.sort_by_key(|event| event.escrow_expires_at);
+                expired
+                    .into_iter()
+                    .map(|event| (*member_id, event.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn get_unverified_events(&self) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        let events = self
+            .loyalties
+            .lock()?
+            .values()
+            .flat_map(|loyalty| {
+                loyalty
+                    .events
+                    .iter()
+                    .filter(|event| event.tier_unverified.is_some())
+                    .map(|event| (loyalty.member_id, event.clone()))
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn reconcile_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        adjustment: Option<LoyaltyEvent>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        let loyalty = loyalties
+            .get_mut(&member_id)
+            .ok_or(Error::EventDoesNotExist(event_id))?;
+        let index = loyalty
+            .events
+            .iter()
+            .position(|event| event.event_id == event_id && event.tier_unverified.is_some())
+            .ok_or(Error::EventDoesNotExist(event_id))?;
+
+        let changed = adjustment.is_some();
+        if let Some(adjustment) = adjustment {
+            apply_adjustment(loyalty, event_id, adjustment)?;
+        }
+        loyalty.events[index].tier_unverified = None;
+        loyalty.version += 1;
+        if changed {
+            self.changes.lock()?.record(member_id);
+        }
+        self.outbox.lock()?.extend(outbox);
+
+        self.retain(&mut loyalties, member_id)
+    }
+
+    async fn reverse_event(
         &self,
         member_id: Uuid,
+        event_id: Uuid,
+        mut reversal: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        let loyalty = loyalties
+            .get_mut(&member_id)
+            .ok_or(Error::EventDoesNotExist(event_id))?;
+        let index = loyalty
+            .events
+            .iter()
+            .position(|event| event.event_id == event_id && event.snapshot.is_none())
+            .ok_or(Error::EventDoesNotExist(event_id))?;
+        if loyalty.events[index].linked_event_id.is_some() {
+            return Err(Error::EventAlreadyLinked(event_id));
+        }
+
+        let reversal_id = reversal.event_id;
+        reversal.linked_event_id = Some(event_id);
+        apply_adjustment(loyalty, event_id, reversal)?;
+        loyalty.events[index].linked_event_id = Some(reversal_id);
+        loyalty.version += 1;
+        self.changes.lock()?.record(member_id);
+        self.outbox.lock()?.extend(outbox);
+
+        self.retain(&mut loyalties, member_id)
+    }
+
+    async fn place_hold(&self, member_id: Uuid, hold: PointsHold) -> Result<Loyalty, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        let loyalty = loyalties
+            .entry(member_id)
+            .or_insert_with(|| Loyalty::new(member_id));
+        let new_points =
+            loyalty
+                .points
+                .checked_sub(hold.points)
+                .ok_or(Error::NegativePointsTotal {
+                    current_points: loyalty.points,
+                    delta_points: -i32::try_from(hold.points).unwrap_or(i32::MAX),
+                })?;
+
+        loyalty.points = new_points;
+        loyalty.held_points += hold.points;
+        loyalty.holds.push(hold);
+        loyalty.version += 1;
+        self.changes.lock()?.record(member_id);
+
+        self.retain(&mut loyalties, member_id)
+    }
+
+    async fn capture_hold(
+        &self,
+        member_id: Uuid,
+        hold_id: Uuid,
         event: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
     ) -> Result<Loyalty, Error> {
-        let loyalty = match self.loyalties.lock()?.entry(member_id) {
-            // Loyalty already exists
-            Entry::Occupied(mut entry) => {
-                let loyalty = entry.get_mut();
-                let new_points = loyalty.points as i32 + event.delta_points;
-                // Return an error if this would make the number of loyalty points negative
-                if new_points < 0 {
-                    return Err(Error::NegativePointsTotal {
-                        current_points: loyalty.points,
-                        delta_points: event.delta_points,
-                    });
-                }
+        let mut loyalties = self.loyalties.lock()?;
+        let loyalty = loyalties
+            .get_mut(&member_id)
+            .ok_or(Error::HoldDoesNotExist(hold_id))?;
+        let index = loyalty
+            .holds
+            .iter()
+            .position(|hold| hold.hold_id == hold_id)
+            .ok_or(Error::HoldDoesNotExist(hold_id))?;
+        let hold_points = loyalty.holds[index].points;
+        if event.delta_points.checked_neg() != i32::try_from(hold_points).ok() {
+            return Err(Error::Adapter(
+                format!(
+                    "event removes {} points, hold {} has {}",
+                    -event.delta_points, hold_id, hold_points
+                )
+                .into(),
+            ));
+        }
 
-                loyalty.points = new_points as u32;
-                loyalty.events.push(event);
-                loyalty.clone()
+        // Give the points back first, so the event spends them from the expiring lots
+        loyalty.holds.remove(index);
+        loyalty.held_points -= hold_points;
+        loyalty.points += hold_points;
+        apply_event(loyalty, event)?;
+        loyalty.version += 1;
+        self.changes.lock()?.record(member_id);
+        self.outbox.lock()?.extend(outbox);
+
+        self.retain(&mut loyalties, member_id)
+    }
+
+    async fn release_hold(&self, member_id: Uuid, hold_id: Uuid) -> Result<Loyalty, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        let loyalty = loyalties
+            .get_mut(&member_id)
+            .ok_or(Error::HoldDoesNotExist(hold_id))?;
+        let index = loyalty
+            .holds
+            .iter()
+            .position(|hold| hold.hold_id == hold_id)
+            .ok_or(Error::HoldDoesNotExist(hold_id))?;
+
+        let hold = loyalty.holds.remove(index);
+        loyalty.held_points -= hold.points;
+        loyalty.points += hold.points;
+        loyalty.version += 1;
+        self.changes.lock()?.record(member_id);
+
+        self.retain(&mut loyalties, member_id)
+    }
+
+    async fn register_welcome_bonus(
+        &self,
+        member_id: Uuid,
+        bonus: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        let mut loyalties = self.loyalties.lock()?;
+        let mut welcome_bonuses = self.welcome_bonuses.lock()?;
+        if welcome_bonuses.contains(&member_id) {
+            return Err(Error::WelcomeBonusAlreadyGranted(member_id));
+        }
+
+        register_event(&mut loyalties, member_id, bonus)?;
+        welcome_bonuses.insert(member_id);
+        let loyalty = self.retain(&mut loyalties, member_id)?;
+        self.changes.lock()?.record(member_id);
+        self.outbox.lock()?.extend(outbox);
+
+        Ok(loyalty)
+    }
+
+    async fn get_member_overrides(&self, member_id: Uuid) -> Result<Vec<MemberOverride>, Error> {
+        let overrides = self
+            .overrides
+            .lock()?
+            .get(&member_id)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(overrides)
+    }
+
+    async fn register_member_override(&self, member_override: MemberOverride) -> Result<(), Error> {
+        self.overrides
+            .lock()?
+            .entry(member_override.member_id)
+            .or_default()
+            .push(member_override);
+
+        Ok(())
+    }
+
+    async fn get_member_restrictions(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<MemberRestriction>, Error> {
+        let restrictions = self
+            .restrictions
+            .lock()?
+            .get(&member_id)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(restrictions)
+    }
+
+    async fn register_member_restriction(
+        &self,
+        restriction: MemberRestriction,
+    ) -> Result<(), Error> {
+        self.restrictions
+            .lock()?
+            .entry(restriction.member_id)
+            .or_default()
+            .push(restriction);
+
+        Ok(())
+    }
+
+    async fn get_campaign_events(
+        &self,
+        campaign_id: Uuid,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        let events = self
+            .loyalties
+            .lock()?
+            .values()
+            .flat_map(|loyalty| {
+                loyalty
+                    .events
+                    .iter()
+                    .filter(|event| event.campaign_id == Some(campaign_id))
+                    .map(|event| (loyalty.member_id, event.clone()))
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn get_campaign_run(&self, campaign_id: Uuid) -> Result<Option<CampaignRun>, Error> {
+        Ok(self.campaigns.lock()?.get(&campaign_id).cloned())
+    }
+
+    async fn save_campaign_run(&self, run: CampaignRun) -> Result<(), Error> {
+        self.campaigns.lock()?.insert(run.campaign_id, run);
+
+        Ok(())
+    }
+
+    async fn reserve_campaign_budget(
+        &self,
+        campaign_id: Uuid,
+        points: u32,
+        budget: u64,
+    ) -> Result<bool, Error> {
+        let mut campaign_points = self.campaign_points.lock()?;
+        let issued = campaign_points.entry(campaign_id).or_default();
+        if *issued + points as u64 > budget {
+            return Ok(false);
+        }
+        *issued += points as u64;
+
+        Ok(true)
+    }
+
+    async fn release_campaign_budget(&self, campaign_id: Uuid, points: u32) -> Result<(), Error> {
+        if let Some(issued) = self.campaign_points.lock()?.get_mut(&campaign_id) {
+            *issued = issued.saturating_sub(points as u64);
+        }
+
+        Ok(())
+    }
+
+    async fn get_member_ids(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<Uuid>, Error> {
+        let mut member_ids: Vec<_> = self
+            .loyalties
+            .lock()?
+            .keys()
+            .filter(|member_id| after.is_none_or(|after| **member_id > after))
+            .copied()
+            .collect();
+        member_ids.sort();
+        member_ids.truncate(limit);
+
+        Ok(member_ids)
+    }
+
+    async fn save_tier_evaluation(
+        &self,
+        evaluation: TierEvaluation,
+        queue_tier_change: bool,
+    ) -> Result<Option<TierEvaluation>, Error> {
+        let member_id = evaluation.member_id;
+        let new_tier = evaluation.tier.clone();
+        let mut loyalties = self.loyalties.lock()?;
+        let previous = loyalties
+            .entry(member_id)
+            .or_insert_with(|| Loyalty::new(member_id))
+            .tier
+            .replace(evaluation);
+        self.retain(&mut loyalties, member_id)?;
+
+        match &previous {
+            Some(previous) if queue_tier_change && previous.tier != new_tier => {
+                self.outbox
+                    .lock()?
+                    .extend([DomainEvent::TierChanged(TierChange {
+                        member_id,
+                        old_tier: previous.tier.clone(),
+                        new_tier,
+                    })]);
             }
-            // Loyalty does not exist
-            Entry::Vacant(entry) => {
-                let mut loyalty = Loyalty::new(member_id);
-                // Return an error if this would make the number of loyalty points negative
-                if event.delta_points < 0 {
-                    return Err(Error::NegativePointsTotal {
-                        current_points: loyalty.points,
-                        delta_points: event.delta_points,
-                    });
+            _ => (),
+        }
+
+        Ok(previous)
+    }
+
+    async fn get_outbox_entries(&self, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+        let entries = self
+            .outbox
+            .lock()?
+            .entries
+            .iter()
+            .take(limit)
+            .cloned()
+            .collect();
+
+        Ok(entries)
+    }
+
+    async fn remove_outbox_entry(&self, outbox_id: u64) -> Result<(), Error> {
+        self.outbox
+            .lock()?
+            .entries
+            .retain(|entry| entry.outbox_id != outbox_id);
+
+        Ok(())
+    }
+
+    async fn get_event_notes(&self, member_id: Uuid) -> Result<Vec<EventNote>, Error> {
+        let notes = self
+            .notes
+            .lock()?
+            .get(&member_id)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(notes)
+    }
+
+    async fn register_event_note(&self, note: EventNote) -> Result<(), Error> {
+        self.notes
+            .lock()?
+            .entry(note.member_id)
+            .or_default()
+            .push(note);
+
+        Ok(())
+    }
+
+    async fn get_partner_transfers(&self, member_id: Uuid) -> Result<Vec<PartnerTransfer>, Error> {
+        let transfers = self
+            .partner_transfers
+            .lock()?
+            .get(&member_id)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(transfers)
+    }
+
+    async fn save_partner_transfer(&self, transfer: PartnerTransfer) -> Result<(), Error> {
+        let mut partner_transfers = self.partner_transfers.lock()?;
+        let transfers = partner_transfers.entry(transfer.member_id).or_default();
+        match transfers
+            .iter_mut()
+            .find(|existing| existing.transfer_id == transfer.transfer_id)
+        {
+            Some(existing) => *existing = transfer,
+            None => transfers.push(transfer),
+        }
+
+        Ok(())
+    }
+}
+
+/// Register a loyalty event against the stored loyalties
+fn register_event(
+    loyalties: &mut HashMap<Uuid, Loyalty>,
+    member_id: Uuid,
+    event: LoyaltyEvent,
+) -> Result<(), Error> {
+    let loyalty = loyalties
+        .entry(member_id)
+        .or_insert_with(|| Loyalty::new(member_id));
+    apply_event(loyalty, event)?;
+    loyalty.version += 1;
+
+    Ok(())
+}
+
+/// Apply a loyalty event to a member's loyalty
+fn apply_event(loyalty: &mut Loyalty, mut event: LoyaltyEvent) -> Result<(), Error> {
+    // Return an error if this would make the number of loyalty points negative
+    let new_points =
+        apply_delta(loyalty.points, event.delta_points).ok_or(Error::NegativePointsTotal {
+            current_points: loyalty.points,
+            delta_points: event.delta_points,
+        })?;
+
+    match event.matures_at {
+        // Points are pending until they mature
+        Some(matures_at) if event.delta_points > 0 => {
+            loyalty.pending_points += event.delta_points as u32;
+            loyalty.pending_lots.push(PendingLot {
+                event_id: event.event_id,
+                points: event.delta_points as u32,
+                matures_at,
+                expires_at: event.expires_at,
+            });
+        }
+        _ => {
+            loyalty.points = new_points;
+            match event.expires_at {
+                Some(expires_at) if event.delta_points > 0 => add_expiring_lot(
+                    loyalty,
+                    event.event_id,
+                    event.delta_points as u32,
+                    expires_at,
+                ),
+                _ if event.delta_points < 0 => {
+                    spend_expiring_points(loyalty, event.delta_points.unsigned_abs())
                 }
-                loyalty.points = event.delta_points as u32;
-                loyalty.events.push(event);
-                entry.insert(loyalty.clone());
-                loyalty
+                _ => {}
             }
-        };
+        }
+    }
+    event.sequence = loyalty.next_sequence();
+    loyalty.events.push(event);
 
-        Ok(loyalty)
+    Ok(())
+}
+
+/// Apply an adjustment to an event's points
+///
+/// Negative adjustments are taken from the event's pending points first, then from its expiring
+/// points, so the rest of the member's lots are left untouched where possible.
+fn apply_adjustment(
+    loyalty: &mut Loyalty,
+    event_id: Uuid,
+    mut adjustment: LoyaltyEvent,
+) -> Result<(), Error> {
+    if adjustment.delta_points >= 0 {
+        return apply_event(loyalty, adjustment);
+    }
+
+    let from_pending = loyalty
+        .pending_lots
+        .iter()
+        .find(|lot| lot.event_id == event_id)
+        .map(|lot| lot.points.min(adjustment.delta_points.unsigned_abs()))
+        .unwrap_or(0);
+    let delta_points = adjustment.delta_points + from_pending as i32;
+    let new_points =
+        apply_delta(loyalty.points, delta_points).ok_or(Error::NegativePointsTotal {
+            current_points: loyalty.points,
+            delta_points,
+        })?;
+
+    if let Some(lot) = loyalty
+        .pending_lots
+        .iter_mut()
+        .find(|lot| lot.event_id == event_id)
+    {
+        lot.points -= from_pending;
     }
+    loyalty.pending_points -= from_pending;
+    loyalty.pending_lots.retain(|lot| lot.points > 0);
+
+    let mut spent = delta_points.unsigned_abs();
+    if let Some(lot) = loyalty
+        .expiring_lots
+        .iter_mut()
+        .find(|lot| lot.event_id == event_id)
+    {
+        let from_lot = lot.points.min(spent);
+        lot.points -= from_lot;
+        spent -= from_lot;
+    }
+    spend_expiring_points(loyalty, spent);
+
+    loyalty.points = new_points;
+    adjustment.sequence = loyalty.next_sequence();
+    loyalty.events.push(adjustment);
+
+    Ok(())
+}
+
+/// Track available points expiring at `expires_at`, keeping lots ordered by expiration date
+fn add_expiring_lot(loyalty: &mut Loyalty, event_id: Uuid, points: u32, expires_at: DateTime<Utc>) {
+    let index = loyalty
+        .expiring_lots
+        .partition_point(|lot| lot.expires_at <= expires_at);
+    loyalty.expiring_lots.insert(
+        index,
+        ExpiringLot {
+            event_id,
+            points,
+            expires_at,
+        },
+    );
+}
+
+/// Take spent points from the lots expiring soonest
+fn spend_expiring_points(loyalty: &mut Loyalty, mut points: u32) {
+    for lot in loyalty.expiring_lots.iter_mut() {
+        let spent = lot.points.min(points);
+        lot.points -= spent;
+        points -= spent;
+        if points == 0 {
+            break;
+        }
+    }
+    loyalty.expiring_lots.retain(|lot| lot.points > 0);
 }
 
 impl Default for MemoryDatabase {
     fn default() -> Self {
         Self {
             loyalties: Arc::new(Mutex::new(HashMap::new())),
+            awaiting_approval: Arc::new(Mutex::new(HashMap::new())),
+            overrides: Arc::new(Mutex::new(HashMap::new())),
+            restrictions: Arc::new(Mutex::new(HashMap::new())),
+            notes: Arc::new(Mutex::new(HashMap::new())),
+            partner_transfers: Arc::new(Mutex::new(HashMap::new())),
+            campaigns: Arc::new(Mutex::new(HashMap::new())),
+            campaign_points: Arc::new(Mutex::new(HashMap::new())),
+            welcome_bonuses: Arc::new(Mutex::new(HashSet::new())),
+            changes: Arc::new(Mutex::new(BalanceChanges::default())),
+            outbox: Arc::new(Mutex::new(Outbox::default())),
+            max_members: None,
+            max_events_per_member: None,
+            retention: Arc::new(Mutex::new(Retention::default())),
+        }
+    }
+}
+
+impl MemoryDatabase {
+    /// Keep at most `max_members` loyalty records, evicting the least recently used ones
+    ///
+    /// Evicted members start over with no points. This is meant for long-running development
+    /// environments, where stale members can be dropped.
+    pub fn with_max_members(mut self, max_members: usize) -> Self {
+        self.max_members = Some(max_members);
+        self
+    }
+
+    /// Keep at most the `max_events` latest events of each member
+    ///
+    /// Older events are dropped without changing the member's points, so they can no longer be
+    /// reversed or annotated.
+    pub fn with_max_events_per_member(mut self, max_events: usize) -> Self {
+        self.max_events_per_member = Some(max_events);
+        self
+    }
+
+    /// Number of members and events evicted so far
+    pub fn eviction_stats(&self) -> EvictionStats {
+        self.retention
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stats
+    }
+
+    /// Record a use of the member's loyalty, and drop what exceeds the limits
+    ///
+    /// This returns the member's loyalty after dropping old events. The member itself is never
+    /// evicted, as it was just used.
+    fn retain(
+        &self,
+        loyalties: &mut HashMap<Uuid, Loyalty>,
+        member_id: Uuid,
+    ) -> Result<Loyalty, Error> {
+        let mut retention = self.retention.lock()?;
+        retention.touch(member_id);
+
+        let loyalty = loyalties
+            .entry(member_id)
+            .or_insert_with(|| Loyalty::new(member_id));
+        if let Some(max_events) = self.max_events_per_member {
+            let excess = loyalty.events.len().saturating_sub(max_events);
+            if excess > 0 {
+                loyalty.events.drain(..excess);
+                retention.stats.evicted_events += excess as u64;
+            }
+        }
+        let loyalty = loyalty.clone();
+
+        if let Some(max_members) = self.max_members {
+            while loyalties.len() > max_members {
+                let Some(evicted_id) = loyalties
+                    .keys()
+                    .filter(|id| **id != member_id)
+                    .min_by_key(|id| retention.members.get(*id).copied().unwrap_or(0))
+                    .copied()
+                else {
+                    break;
+                };
+                loyalties.remove(&evicted_id);
+                retention.members.remove(&evicted_id);
+                retention.stats.evicted_members += 1;
+                tracing::debug!(member_id = %evicted_id, "evicted least recently used member");
+            }
         }
+
+        Ok(loyalty)
     }
 }
 
@@ -82,7 +1056,7 @@ impl Default for MemoryDatabase {
 /// and only keep the string representation instead.
 #[derive(Debug, thiserror::Error)]
 #[error("poison error: {0}")]
-pub struct ErasedPoisonError(String);
+pub struct ErasedPoisonError(pub(crate) String);
 
 /// We need to create a custom `From` implementation here for an error that's specific to this
 /// adapter.
@@ -95,6 +1069,8 @@ impl<T> From<PoisonError<T>> for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::{Tier, TransferStatus, UnverifiedTier};
+    use chrono::Duration;
     use speculoos::prelude::*;
 
     #[tokio::test]
@@ -105,11 +1081,8 @@ mod tests {
         let res = database
             .register_loyalty_event(
                 loyalty.member_id,
-                LoyaltyEvent {
-                    event_id: Uuid::new_v4(),
-                    delta_points: 5,
-                    reason: "".to_string(),
-                },
+                LoyaltyEvent::new(Uuid::new_v4(), 5, "", Utc::now()),
+                None,
             )
             .await;
         assert_that!(res).is_ok().matches(|stored_loyalty| {
@@ -128,11 +1101,8 @@ mod tests {
         let res = database
             .register_loyalty_event(
                 Uuid::new_v4(),
-                LoyaltyEvent {
-                    event_id: Uuid::new_v4(),
-                    delta_points: -5,
-                    reason: "".to_string(),
-                },
+                LoyaltyEvent::new(Uuid::new_v4(), -5, "", Utc::now()),
+                None,
             )
             .await;
         assert_that!(res)
@@ -148,11 +1118,8 @@ mod tests {
         let res = database
             .register_loyalty_event(
                 loyalty.member_id,
-                LoyaltyEvent {
-                    event_id: Uuid::new_v4(),
-                    delta_points: 5,
-                    reason: "".to_string(),
-                },
+                LoyaltyEvent::new(Uuid::new_v4(), 5, "", Utc::now()),
+                None,
             )
             .await;
         assert_that!(res).is_ok();
@@ -160,11 +1127,8 @@ mod tests {
         let res = database
             .register_loyalty_event(
                 loyalty.member_id,
-                LoyaltyEvent {
-                    event_id: Uuid::new_v4(),
-                    delta_points: -5,
-                    reason: "".to_string(),
-                },
+                LoyaltyEvent::new(Uuid::new_v4(), -5, "", Utc::now()),
+                None,
             )
             .await;
         assert_that!(res).is_ok();
@@ -172,15 +1136,760 @@ mod tests {
         let res = database
             .register_loyalty_event(
                 loyalty.member_id,
-                LoyaltyEvent {
-                    event_id: Uuid::new_v4(),
-                    delta_points: -1,
-                    reason: "".to_string(),
-                },
+                LoyaltyEvent::new(Uuid::new_v4(), -1, "", Utc::now()),
+                None,
+            )
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::NegativePointsTotal { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_sequence() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        // Register a few events, with a caller-provided sequence that should be ignored
+        for _ in 0..3 {
+            let res = database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        sequence: 42,
+                        ..LoyaltyEvent::new(Uuid::new_v4(), 5, "", Utc::now())
+                    },
+                    None,
+                )
+                .await;
+            assert_that!(res).is_ok();
+        }
+        // Sequence numbers should start at 1 and increase monotonically
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res).is_ok().matches(|loyalty| {
+            loyalty
+                .events
+                .iter()
+                .map(|e| e.sequence)
+                .collect::<Vec<_>>()
+                == vec![1, 2, 3]
+        });
+        // Other members have their own sequence
+        let res = database
+            .register_loyalty_event(
+                Uuid::new_v4(),
+                LoyaltyEvent::new(Uuid::new_v4(), 5, "", Utc::now()),
+                None,
+            )
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.events[0].sequence == 1);
+    }
+
+    #[tokio::test]
+    async fn test_event_order() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event = |delta_points| LoyaltyEvent::new(Uuid::now_v7(), delta_points, "", Utc::now());
+        // Queue an event for approval before registering others
+        let awaiting_approval = event(5000);
+        let res = database
+            .register_event_for_approval(member_id, awaiting_approval.clone())
+            .await;
+        assert_that!(res).is_ok();
+        let mut expected = Vec::new();
+        for delta_points in [5, -5] {
+            let event = event(delta_points);
+            expected.push(event.event_id);
+            let res = database
+                .register_loyalty_event(member_id, event, None)
+                .await;
+            assert_that!(res).is_ok();
+        }
+        let res = database
+            .approve_event(member_id, awaiting_approval.event_id, Vec::new())
+            .await;
+        assert_that!(res).is_ok();
+        expected.push(awaiting_approval.event_id);
+
+        // Events are returned in the order they were registered, not created
+        let res = database.get_loyalty_events(member_id).await;
+        assert_that!(res).is_ok().matches(|events| {
+            events.iter().map(|e| e.event_id).collect::<Vec<_>>() == expected
+                && events.iter().map(|e| e.sequence).collect::<Vec<_>>() == vec![1, 2, 3]
+        });
+
+        // Members without events have an empty history
+        let res = database.get_loyalty_events(Uuid::new_v4()).await;
+        assert_that!(res).is_ok().is_empty();
+    }
+
+    #[tokio::test]
+    async fn test_balance_changes() {
+        let database = MemoryDatabase::default();
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        for (member_id, matures_at) in [
+            (member_ids[0], None),
+            (member_ids[1], None),
+            (member_ids[0], Some(Utc::now())),
+        ] {
+            let res = database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        matures_at,
+                        ..LoyaltyEvent::new(Uuid::new_v4(), 5, "", Utc::now())
+                    },
+                    None,
+                )
+                .await;
+            assert_that!(res).is_ok();
+        }
+
+        // Members appear once, at the position of their latest change
+        let res = database.get_balance_changes(0, 10).await;
+        assert_that!(res).is_ok().is_equal_to(vec![
+            BalanceChange {
+                member_id: member_ids[1],
+                points: 5,
+                pending_points: 0,
+                sequence: 2,
+            },
+            BalanceChange {
+                member_id: member_ids[0],
+                points: 5,
+                pending_points: 5,
+                sequence: 3,
+            },
+        ]);
+
+        // Maturing points is a change too
+        let res = database.mature_points(Utc::now(), false).await;
+        assert_that!(res).is_ok().has_length(1);
+        let res = database.get_balance_changes(3, 10).await;
+        assert_that!(res).is_ok().is_equal_to(vec![BalanceChange {
+            member_id: member_ids[0],
+            points: 10,
+            pending_points: 0,
+            sequence: 4,
+        }]);
+        let res = database.get_balance_changes(4, 10).await;
+        assert_that!(res).is_ok().is_empty();
+    }
+
+    #[tokio::test]
+    async fn test_approve_event() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event = LoyaltyEvent::new(Uuid::new_v4(), 5000, "", Utc::now());
+        let res = database
+            .register_event_for_approval(member_id, event.clone())
+            .await;
+        assert_that!(res).is_ok();
+
+        // The event does not affect the points until approved
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 0);
+        let res = database.get_events_awaiting_approval(member_id).await;
+        assert_that!(res).is_ok().has_length(1);
+
+        let res = database
+            .approve_event(member_id, event.event_id, Vec::new())
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 5000 && loyalty.events[0].sequence == 1);
+        let res = database.get_events_awaiting_approval(member_id).await;
+        assert_that!(res).is_ok().is_empty();
+
+        // The event cannot be approved twice
+        let res = database
+            .approve_event(member_id, event.event_id, Vec::new())
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::EventDoesNotExist(_)));
+    }
+
+    #[tokio::test]
+    async fn test_reject_event() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event = LoyaltyEvent::new(Uuid::new_v4(), 5000, "", Utc::now());
+        let res = database
+            .register_event_for_approval(member_id, event.clone())
+            .await;
+        assert_that!(res).is_ok();
+
+        let res = database.reject_event(member_id, event.event_id).await;
+        assert_that!(res).is_ok();
+        let res = database.get_events_awaiting_approval(member_id).await;
+        assert_that!(res).is_ok().is_empty();
+        let res = database
+            .approve_event(member_id, event.event_id, Vec::new())
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::EventDoesNotExist(_)));
+    }
+
+    #[tokio::test]
+    async fn test_pending_points() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let now = Utc::now();
+        for (delta_points, matures_at) in [
+            (5, None),
+            (10, Some(now + Duration::days(1))),
+            (20, Some(now + Duration::days(14))),
+        ] {
+            let res = database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        matures_at,
+                        ..LoyaltyEvent::new(Uuid::new_v4(), delta_points, "", Utc::now())
+                    },
+                    None,
+                )
+                .await;
+            assert_that!(res).is_ok();
+        }
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 5 && loyalty.pending_points == 30);
+
+        // Pending points cannot be spent
+        let res = database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(Uuid::new_v4(), -6, "", Utc::now()),
+                None,
+            )
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::NegativePointsTotal { .. }));
+
+        // Only the first lot matures after 2 days, queuing an event
+        let res = database.mature_points(now + Duration::days(2), true).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|matured| matured.len() == 1 && matured[0].1 == 10);
+        let res = database.get_outbox_entries(10).await;
+        assert_that!(res).is_ok().matches(|entries| {
+            entries.len() == 1
+                && entries[0].event
+                    == DomainEvent::PointsMatured {
+                        member_id,
+                        loyalty_points: 10,
+                    }
+        });
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res).is_ok().matches(|loyalty| {
+            loyalty.points == 15 && loyalty.pending_points == 20 && loyalty.pending_lots.len() == 1
+        });
+
+        // Nothing else matures until the last lot
+        let res = database.mature_points(now + Duration::days(2), false).await;
+        assert_that!(res).is_ok().is_empty();
+    }
+
+    #[tokio::test]
+    async fn test_expiring_lots() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let now = Utc::now();
+        for (delta_points, matures_at, expires_at) in [
+            (5, None, None),
+            (10, None, Some(now + Duration::days(30))),
+            (
+                20,
+                Some(now + Duration::days(1)),
+                Some(now + Duration::days(10)),
+            ),
+            (-6, None, None),
+        ] {
+            let res = database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        matures_at,
+                        expires_at,
+                        ..LoyaltyEvent::new(Uuid::new_v4(), delta_points, "", Utc::now())
+                    },
+                    None,
+                )
+                .await;
+            assert_that!(res).is_ok();
+        }
+
+        // Pending points only expire once they mature
+        let res = database.get_expired_lots(now + Duration::days(60)).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|lots| lots.len() == 1 && lots[0].1.points == 4);
+        let res = database.mature_points(now + Duration::days(2), false).await;
+        assert_that!(res).is_ok().has_length(1);
+
+        // Spent points are taken from the lots expiring soonest
+        let res = database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(Uuid::new_v4(), -8, "", Utc::now()),
+                None,
+            )
+            .await;
+        assert_that!(res).is_ok();
+        let res = database.get_expired_lots(now + Duration::days(10)).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|lots| lots.len() == 1 && lots[0].1.points == 12);
+        let res = database.get_expired_lots(now + Duration::days(60)).await;
+        assert_that!(res).is_ok().has_length(2);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_event() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event_id = Uuid::new_v4();
+        let adjustment =
+            |delta_points| LoyaltyEvent::new(Uuid::new_v4(), delta_points, "", Utc::now());
+        for (event_id, delta_points, matures_at, tier_unverified) in [
+            (Uuid::new_v4(), 5, None, None),
+            (
+                event_id,
+                20,
+                Some(Utc::now() + Duration::days(1)),
+                Some(UnverifiedTier {
+                    tier: Tier::Platinum,
+                    purchase_amount: 1,
+                    earn_multiplier: 1,
+                }),
+            ),
+        ] {
+            let res = database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        matures_at,
+                        tier_unverified,
+                        ..LoyaltyEvent::new(event_id, delta_points, "", Utc::now())
+                    },
+                    None,
+                )
+                .await;
+            assert_that!(res).is_ok();
+        }
+        let res = database.get_unverified_events().await;
+        assert_that!(res).is_ok().matches(|events| {
+            events.len() == 1 && events[0].0 == member_id && events[0].1.event_id == event_id
+        });
+
+        // Negative adjustments are taken from the pending points first
+        let res = database
+            .reconcile_event(member_id, event_id, Some(adjustment(-22)), Vec::new())
+            .await;
+        assert_that!(res).is_ok().matches(|loyalty| {
+            loyalty.points == 3 && loyalty.pending_points == 0 && loyalty.pending_lots.is_empty()
+        });
+        let res = database.get_unverified_events().await;
+        assert_that!(res).is_ok().is_empty();
+
+        // The event cannot be reconciled twice
+        let res = database
+            .reconcile_event(member_id, event_id, None, Vec::new())
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::EventDoesNotExist(_)));
+    }
+
+    #[tokio::test]
+    async fn test_holds() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event = |delta_points, expires_at| LoyaltyEvent {
+            expires_at,
+            ..LoyaltyEvent::new(Uuid::new_v4(), delta_points, "", Utc::now())
+        };
+        let hold = |points| PointsHold {
+            hold_id: Uuid::new_v4(),
+            points,
+            reason: "".to_string(),
+            created_at: Utc::now(),
+        };
+        let res = database
+            .register_loyalty_event(
+                member_id,
+                event(100, Some(Utc::now() + Duration::days(1))),
+                None,
             )
             .await;
+        assert_that!(res).is_ok();
+
+        // Holds take from the available points
+        let first = hold(60);
+        let res = database.place_hold(member_id, first.clone()).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 40 && loyalty.held_points == 60);
+        let res = database.place_hold(member_id, hold(50)).await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::NegativePointsTotal { .. }));
+
+        // Captures must debit the hold's points, from the expiring lots
+        let res = database
+            .capture_hold(member_id, first.hold_id, event(-70, None), Vec::new())
+            .await;
+        assert_that!(res).is_err();
+        let res = database
+            .capture_hold(member_id, first.hold_id, event(-60, None), Vec::new())
+            .await;
+        assert_that!(res).is_ok().matches(|loyalty| {
+            loyalty.points == 40
+                && loyalty.held_points == 0
+                && loyalty.expiring_lots[0].points == 40
+        });
+
+        // Released points are available again
+        let second = hold(30);
+        let res = database.place_hold(member_id, second.clone()).await;
+        assert_that!(res).is_ok();
+        let res = database.release_hold(member_id, second.hold_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 40 && loyalty.holds.is_empty());
+        let res = database.release_hold(member_id, second.hold_id).await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::HoldDoesNotExist(_)));
+    }
+
+    #[tokio::test]
+    async fn test_welcome_bonus() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let bonus = || LoyaltyEvent::new(Uuid::new_v4(), 500, "", Utc::now());
+
+        let res = database
+            .register_welcome_bonus(member_id, bonus(), Vec::new())
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 500);
+
+        // The bonus is only granted once
+        let res = database
+            .register_welcome_bonus(member_id, bonus(), Vec::new())
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::WelcomeBonusAlreadyGranted(_)));
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 500);
+    }
+
+    #[tokio::test]
+    async fn test_partner_transfers() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let transfer = PartnerTransfer {
+            transfer_id: Uuid::new_v4(),
+            member_id,
+            partner: "SkyMiles".to_string(),
+            points: 1000,
+            debit_event_id: Uuid::new_v4(),
+            status: TransferStatus::Pending,
+            created_at: Utc::now(),
+        };
+        database
+            .save_partner_transfer(transfer.clone())
+            .await
+            .unwrap();
+
+        // Saving a transfer again replaces it
+        let completed = PartnerTransfer {
+            status: TransferStatus::Completed {
+                partner_reference: "SM-42".to_string(),
+            },
+            ..transfer
+        };
+        database
+            .save_partner_transfer(completed.clone())
+            .await
+            .unwrap();
+
+        let res = database.get_partner_transfers(member_id).await;
+        assert_that!(res).is_ok().is_equal_to(vec![completed]);
+        let res = database.get_partner_transfers(Uuid::new_v4()).await;
+        assert_that!(res).is_ok().is_empty();
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let database = MemoryDatabase::default();
+        let (first_id, second_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let event = |delta_points| LoyaltyEvent::new(Uuid::new_v4(), delta_points, "", Utc::now());
+
+        // Loyalties are returned for each event, after all events
+        let res = database
+            .register_loyalty_events_batch(vec![
+                (first_id, event(10)),
+                (second_id, event(5)),
+                (first_id, event(-3)),
+            ])
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalties| loyalties.iter().map(|loyalty| loyalty.points).eq([7, 5, 7]));
+
+        // A rejected event rejects the whole batch
+        let res = database
+            .register_loyalty_events_batch(vec![(second_id, event(5)), (first_id, event(-100))])
+            .await;
         assert_that!(res)
             .is_err()
             .matches(|err| matches!(err, Error::NegativePointsTotal { .. }));
+
+        let res = database
+            .get_loyalty_points_batch(&[second_id, Uuid::new_v4(), first_id])
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalties| loyalties.iter().map(|loyalty| loyalty.points).eq([5, 0, 7]));
+    }
+
+    #[tokio::test]
+    async fn test_campaign_budget() {
+        let database = MemoryDatabase::default();
+        let campaign_id = Uuid::new_v4();
+
+        // Reservations stop at the budget
+        let res = database
+            .reserve_campaign_budget(campaign_id, 600, 1000)
+            .await;
+        assert_that!(res).is_ok().is_true();
+        let res = database
+            .reserve_campaign_budget(campaign_id, 600, 1000)
+            .await;
+        assert_that!(res).is_ok().is_false();
+
+        // Released points can be reserved again
+        let res = database.release_campaign_budget(campaign_id, 600).await;
+        assert_that!(res).is_ok();
+        let res = database
+            .reserve_campaign_budget(campaign_id, 1000, 1000)
+            .await;
+        assert_that!(res).is_ok().is_true();
+    }
+
+    #[tokio::test]
+    async fn test_get_member_ids() {
+        let database = MemoryDatabase::default();
+        let mut member_ids = vec![Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let event = |delta_points| LoyaltyEvent::new(Uuid::now_v7(), delta_points, "", Utc::now());
+        for member_id in &member_ids {
+            database
+                .register_loyalty_event(*member_id, event(10), None)
+                .await
+                .unwrap();
+        }
+        member_ids.sort();
+
+        // Pages follow member IDs
+        let res = database.get_member_ids(None, 2).await;
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(member_ids[..2].to_vec());
+        let res = database.get_member_ids(Some(member_ids[1]), 2).await;
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(member_ids[2..].to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_register_expected_version() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event = || LoyaltyEvent::new(Uuid::now_v7(), 10, "", Utc::now());
+
+        // Each change increments the version, starting at 0 for new members
+        let res = database
+            .register_loyalty_event(member_id, event(), Some(0))
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.version == 1);
+        let res = database
+            .register_loyalty_event(member_id, event(), None)
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.version == 2);
+
+        // Stale versions are rejected without changing the points
+        let res = database
+            .register_loyalty_event(member_id, event(), Some(1))
+            .await;
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Conflict {
+                    expected_version: 1,
+                    current_version: 2,
+                    ..
+                }
+            )
+        });
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 20 && loyalty.version == 2);
+    }
+
+    #[tokio::test]
+    async fn test_retention_limits() {
+        let database = MemoryDatabase::default()
+            .with_max_members(2)
+            .with_max_events_per_member(2);
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let event = |delta_points| LoyaltyEvent::new(Uuid::now_v7(), delta_points, "", Utc::now());
+
+        // Only the latest events are kept, without changing the points
+        for delta_points in [10, 20, 30] {
+            database
+                .register_loyalty_event(member_ids[0], event(delta_points), None)
+                .await
+                .unwrap();
+        }
+        let res = database.get_loyalty_points(member_ids[0]).await;
+        assert_that!(res).is_ok().matches(|loyalty| {
+            loyalty.points == 60 && loyalty.events.iter().map(|event| event.sequence).eq([2, 3])
+        });
+
+        // Reading the first member makes the second one the least recently used
+        database
+            .register_loyalty_event(member_ids[1], event(10), None)
+            .await
+            .unwrap();
+        database.get_loyalty_points(member_ids[0]).await.unwrap();
+        database
+            .register_loyalty_event(member_ids[2], event(10), None)
+            .await
+            .unwrap();
+
+        let res = database.get_member_ids(None, 10).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|ids| ids.len() == 2 && !ids.contains(&member_ids[1]));
+        assert_that!(database.eviction_stats()).is_equal_to(EvictionStats {
+            evicted_members: 1,
+            evicted_events: 1,
+        });
+    }
+
+    #[tokio::test]
+    async fn test_retention_sequence() {
+        let database = MemoryDatabase::default().with_max_events_per_member(0);
+        let member_id = Uuid::new_v4();
+        let event = |delta_points| LoyaltyEvent::new(Uuid::now_v7(), delta_points, "", Utc::now());
+
+        // Every event is evicted, but sequence numbers keep counting
+        for delta_points in [10, 20] {
+            database
+                .register_loyalty_event(member_id, event(delta_points), None)
+                .await
+                .unwrap();
+        }
+        let res = database.get_loyalty_points(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.events.is_empty() && loyalty.last_sequence == 2);
+
+        let res = database
+            .register_loyalty_event(member_id, event(30), None)
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .matches(|loyalty| loyalty.points == 60 && loyalty.last_sequence == 3);
+    }
+
+    #[tokio::test]
+    async fn test_outbox() {
+        let database = MemoryDatabase::default();
+        let member_id = Uuid::new_v4();
+        let event = |delta_points| LoyaltyEvent::new(Uuid::now_v7(), delta_points, "", Utc::now());
+        let points_redeemed = |loyalty_points| DomainEvent::PointsRedeemed {
+            member_id,
+            event_id: Uuid::nil(),
+            loyalty_points,
+        };
+        let evaluation = |tier| TierEvaluation {
+            member_id,
+            tier,
+            membership_months: Some(12),
+            evaluated_at: Utc::now(),
+        };
+
+        // Rejected events do not add to the outbox
+        let res = database
+            .register_loyalty_event_with_outbox(
+                member_id,
+                event(-10),
+                None,
+                vec![points_redeemed(10)],
+            )
+            .await;
+        assert_that!(res).is_err();
+        assert_that!(database.get_outbox_entries(10).await)
+            .is_ok()
+            .is_empty();
+
+        // Tier changes are only queued when requested
+        for (tier, queue_tier_change) in [
+            (Tier::Basic, true),
+            (Tier::Silver, false),
+            (Tier::Gold, true),
+            (Tier::Gold, true),
+        ] {
+            database
+                .save_tier_evaluation(evaluation(tier), queue_tier_change)
+                .await
+                .unwrap();
+        }
+        database
+            .register_loyalty_event_with_outbox(member_id, event(0), None, vec![points_redeemed(0)])
+            .await
+            .unwrap();
+
+        let entries = database.get_outbox_entries(10).await.unwrap();
+        assert_that!(entries).is_equal_to(vec![
+            OutboxEntry {
+                outbox_id: 1,
+                event: DomainEvent::TierChanged(TierChange {
+                    member_id,
+                    old_tier: Tier::Silver,
+                    new_tier: Tier::Gold,
+                }),
+            },
+            OutboxEntry {
+                outbox_id: 2,
+                event: points_redeemed(0),
+            },
+        ]);
+
+        // Entries are removed once published
+        database.remove_outbox_entry(1).await.unwrap();
+        database.remove_outbox_entry(1).await.unwrap();
+        let res = database.get_outbox_entries(10).await;
+        assert_that!(res).is_ok().is_equal_to(entries[1..].to_vec());
     }
 }
@@ -0,0 +1,171 @@
+use crate::{
+    adapters::database::memory::ErasedPoisonError,
+    domain::{Drawing, DrawingEntry, DrawingResult},
+    ports::drawing::{DrawingPort, Error},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, PoisonError},
+};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryDrawings {
+    drawings: Arc<Mutex<HashMap<Uuid, MemoryDrawing>>>,
+}
+
+#[derive(Clone, Debug)]
+struct MemoryDrawing {
+    drawing: Drawing,
+    entries: BTreeMap<Uuid, u32>,
+}
+
+impl MemoryDrawings {
+    pub fn with_drawing(self, drawing: Drawing) -> Self {
+        self.drawings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                drawing.drawing_id,
+                MemoryDrawing {
+                    drawing,
+                    entries: BTreeMap::new(),
+                },
+            );
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl DrawingPort for MemoryDrawings {
+    async fn get_drawing(&self, drawing_id: Uuid) -> Result<Drawing, Error> {
+        self.drawings
+            .lock()?
+            .get(&drawing_id)
+            .map(|memory| memory.drawing.clone())
+            .ok_or(Error::DrawingDoesNotExist(drawing_id))
+    }
+
+    async fn register_entries(
+        &self,
+        drawing_id: Uuid,
+        member_id: Uuid,
+        entries: u32,
+    ) -> Result<(), Error> {
+        let mut drawings = self.drawings.lock()?;
+        let memory = drawings
+            .get_mut(&drawing_id)
+            .ok_or(Error::DrawingDoesNotExist(drawing_id))?;
+        if memory.drawing.result.is_some() {
+            return Err(Error::AlreadyDrawn(drawing_id));
+        }
+        *memory.entries.entry(member_id).or_default() += entries;
+
+        Ok(())
+    }
+
+    async fn get_entries(&self, drawing_id: Uuid) -> Result<Vec<DrawingEntry>, Error> {
+        let drawings = self.drawings.lock()?;
+        let memory = drawings
+            .get(&drawing_id)
+            .ok_or(Error::DrawingDoesNotExist(drawing_id))?;
+
+        Ok(memory
+            .entries
+            .iter()
+            .map(|(member_id, entries)| DrawingEntry {
+                member_id: *member_id,
+                entries: *entries,
+            })
+            .collect())
+    }
+
+    async fn register_result(&self, result: DrawingResult) -> Result<(), Error> {
+        let mut drawings = self.drawings.lock()?;
+        let memory = drawings
+            .get_mut(&result.drawing_id)
+            .ok_or(Error::DrawingDoesNotExist(result.drawing_id))?;
+        if memory.drawing.result.is_some() {
+            return Err(Error::AlreadyDrawn(result.drawing_id));
+        }
+        memory.drawing.result = Some(result);
+
+        Ok(())
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(err: PoisonError<T>) -> Self {
+        Self::Adapter(Box::new(ErasedPoisonError(err.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    fn drawing() -> Drawing {
+        Drawing {
+            drawing_id: Uuid::new_v4(),
+            points_per_entry: 100,
+            winner_count: 1,
+            result: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_accumulate() {
+        let drawing = drawing();
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let member_id = Uuid::new_v4();
+
+        for entries in [2, 3] {
+            let res = drawings
+                .register_entries(drawing.drawing_id, member_id, entries)
+                .await;
+            assert_that!(res).is_ok();
+        }
+
+        let res = drawings.get_entries(drawing.drawing_id).await;
+        assert_that!(res).is_ok().is_equal_to(vec![DrawingEntry {
+            member_id,
+            entries: 5,
+        }]);
+    }
+
+    #[tokio::test]
+    async fn test_already_drawn() {
+        let drawing = drawing();
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let result = DrawingResult {
+            drawing_id: drawing.drawing_id,
+            seed: 42,
+            winners: Vec::new(),
+        };
+
+        let res = drawings.register_result(result.clone()).await;
+        assert_that!(res).is_ok();
+
+        // Neither new entries nor a new result are accepted
+        let res = drawings
+            .register_entries(drawing.drawing_id, Uuid::new_v4(), 1)
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::AlreadyDrawn(_)));
+        let res = drawings.register_result(result).await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::AlreadyDrawn(_)));
+    }
+
+    #[tokio::test]
+    async fn test_does_not_exist() {
+        let drawings = MemoryDrawings::default();
+        let res = drawings.get_drawing(Uuid::new_v4()).await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::DrawingDoesNotExist(_)));
+    }
+}
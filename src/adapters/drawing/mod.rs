@@ -0,0 +1,3 @@
+//! Adapters for the drawing port
+
+pub mod memory;
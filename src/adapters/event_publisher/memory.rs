@@ -0,0 +1,35 @@
+use crate::{
+    domain::DomainEvent,
+    ports::event_publisher::{Error, EventPublisherPort},
+};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Publisher keeping events in memory, in publishing order
+///
+/// Clones share the same events.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryPublisher {
+    events: Arc<Mutex<Vec<DomainEvent>>>,
+}
+
+impl MemoryPublisher {
+    /// Events published so far
+    pub fn events(&self) -> Vec<DomainEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl EventPublisherPort for MemoryPublisher {
+    async fn publish(&self, event: DomainEvent) -> Result<(), Error> {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+//! Adapters for the event publisher port
+
+pub mod memory;
+pub mod noop;
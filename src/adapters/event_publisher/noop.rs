@@ -0,0 +1,15 @@
+use crate::{
+    domain::DomainEvent,
+    ports::event_publisher::{Error, EventPublisherPort},
+};
+
+/// Publisher dropping all events, for local use without downstream consumers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopPublisher;
+
+#[async_trait::async_trait]
+impl EventPublisherPort for NoopPublisher {
+    async fn publish(&self, _event: DomainEvent) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+//! Adapters for the ID generator port
+
+pub mod sequential;
+pub mod uuid_v7;
@@ -0,0 +1,35 @@
+use crate::ports::id_generator::IdGeneratorPort;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use uuid::Uuid;
+
+/// Deterministic identifiers, counting up from `00000000-0000-0000-0000-000000000001`
+///
+/// This is meant for tests, where generated identifiers need to be known in advance. Clones share
+/// the same counter.
+#[derive(Clone, Debug, Default)]
+pub struct SequentialIds {
+    last: Arc<AtomicU64>,
+}
+
+impl IdGeneratorPort for SequentialIds {
+    fn generate_id(&self) -> Uuid {
+        Uuid::from_u128(self.last.fetch_add(1, Ordering::Relaxed) as u128 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    #[test]
+    fn test_generate_id() {
+        let ids = SequentialIds::default();
+
+        assert_that!(ids.generate_id()).is_equal_to(Uuid::from_u128(1));
+        assert_that!(ids.clone().generate_id()).is_equal_to(Uuid::from_u128(2));
+    }
+}
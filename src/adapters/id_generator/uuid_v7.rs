@@ -0,0 +1,30 @@
+use crate::ports::id_generator::IdGeneratorPort;
+use uuid::Uuid;
+
+/// Time-ordered UUIDv7 identifiers
+///
+/// Identifiers generated by the same process are ordered by their creation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV7Generator;
+
+impl IdGeneratorPort for UuidV7Generator {
+    fn generate_id(&self) -> Uuid {
+        Uuid::now_v7()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    #[test]
+    fn test_generate_id() {
+        let ids = (0..100)
+            .map(|_| UuidV7Generator.generate_id())
+            .collect::<Vec<_>>();
+
+        assert_that!(ids.iter().all(|id| id.get_version_num() == 7)).is_true();
+        assert_that!(ids.windows(2).all(|ids| ids[0] < ids[1])).is_true();
+    }
+}
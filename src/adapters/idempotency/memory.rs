@@ -0,0 +1,150 @@
+use crate::{
+    adapters::database::memory::ErasedPoisonError,
+    ports::idempotency::{Error, IdempotencyPort, Reservation},
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+#[derive(Clone, Debug)]
+pub struct MemoryIdempotencyStore<T> {
+    responses: Arc<Mutex<HashMap<String, StoredResponse<T>>>>,
+}
+
+/// Response of a request, or `None` while the request is in flight
+#[derive(Clone, Debug)]
+struct StoredResponse<T> {
+    response: Option<T>,
+    expires_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+impl<T> IdempotencyPort<T> for MemoryIdempotencyStore<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    async fn reserve(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Reservation<T>, Error> {
+        let mut responses = self.responses.lock()?;
+        // Expired entries are replaced by the reservation
+        if let Some(stored) = responses.get(key).filter(|stored| stored.expires_at > now) {
+            return Ok(match &stored.response {
+                Some(response) => Reservation::Completed(response.clone()),
+                None => Reservation::InFlight,
+            });
+        }
+        responses.insert(
+            key.to_string(),
+            StoredResponse {
+                response: None,
+                expires_at,
+            },
+        );
+
+        Ok(Reservation::Reserved)
+    }
+
+    async fn store_response(
+        &self,
+        key: &str,
+        response: T,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        self.responses.lock()?.insert(
+            key.to_string(),
+            StoredResponse {
+                response: Some(response),
+                expires_at,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<(), Error> {
+        let mut responses = self.responses.lock()?;
+        if responses
+            .get(key)
+            .is_some_and(|stored| stored.response.is_none())
+        {
+            responses.remove(key);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Default for MemoryIdempotencyStore<T> {
+    fn default() -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(err: PoisonError<T>) -> Self {
+        Self::Adapter(Box::new(ErasedPoisonError(err.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use speculoos::prelude::*;
+
+    #[tokio::test]
+    async fn test_reserve_store() {
+        let store = MemoryIdempotencyStore::default();
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(5);
+
+        let res = store.reserve("key", now, expires_at).await;
+        assert_that!(res).is_ok().is_equal_to(Reservation::Reserved);
+        let res = store.reserve("key", now, expires_at).await;
+        assert_that!(res).is_ok().is_equal_to(Reservation::InFlight);
+
+        let res = store.store_response("key", 42, expires_at).await;
+        assert_that!(res).is_ok();
+        let res = store.reserve("key", now, expires_at).await;
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(Reservation::Completed(42));
+        let res = store.reserve("other", now, expires_at).await;
+        assert_that!(res).is_ok().is_equal_to(Reservation::Reserved);
+    }
+
+    #[tokio::test]
+    async fn test_release() {
+        let store = MemoryIdempotencyStore::<i32>::default();
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(5);
+
+        let _ = store.reserve("key", now, expires_at).await;
+        let res = store.release("key").await;
+        assert_that!(res).is_ok();
+
+        let res = store.reserve("key", now, expires_at).await;
+        assert_that!(res).is_ok().is_equal_to(Reservation::Reserved);
+    }
+
+    #[tokio::test]
+    async fn test_expired() {
+        let store = MemoryIdempotencyStore::default();
+        let now = Utc::now();
+        let res = store
+            .store_response("key", 42, now - Duration::minutes(5))
+            .await;
+        assert_that!(res).is_ok();
+
+        let res = store.reserve("key", now, now + Duration::minutes(5)).await;
+        assert_that!(res).is_ok().is_equal_to(Reservation::Reserved);
+    }
+}
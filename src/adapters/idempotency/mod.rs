@@ -0,0 +1,3 @@
+//! Adapters for the idempotency port
+
+pub mod memory;
@@ -0,0 +1,397 @@
+use super::Timed;
+use crate::{
+    domain::{
+        BalanceChange, CampaignRun, DomainEvent, EventNote, ExpiringLot, Loyalty, LoyaltyEvent,
+        MemberOverride, MemberRestriction, OutboxEntry, PartnerTransfer, PointsHold,
+        TierEvaluation,
+    },
+    ports::database::{DatabasePort, Error},
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+impl<P> DatabasePort for Timed<P>
+where
+    P: DatabasePort + Send + Sync,
+{
+    async fn get_loyalty_points(&self, member_id: Uuid) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner.get_loyalty_points(member_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_loyalty_points_batch(&self, member_ids: &[Uuid]) -> Result<Vec<Loyalty>, Error> {
+        self.time(
+            self.inner.get_loyalty_points_batch(member_ids),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_loyalty_events(&self, member_id: Uuid) -> Result<Vec<LoyaltyEvent>, Error> {
+        self.time(
+            self.inner.get_loyalty_events(member_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_loyalty_event(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+        expected_version: Option<u64>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner
+                .register_loyalty_event(member_id, loyalty_event, expected_version),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_loyalty_events_batch(
+        &self,
+        events: Vec<(Uuid, LoyaltyEvent)>,
+    ) -> Result<Vec<Loyalty>, Error> {
+        self.time(
+            self.inner.register_loyalty_events_batch(events),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_loyalty_event_with_outbox(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+        expected_version: Option<u64>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner.register_loyalty_event_with_outbox(
+                member_id,
+                loyalty_event,
+                expected_version,
+                outbox,
+            ),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn compact_events(&self, member_id: Uuid, snapshot: LoyaltyEvent) -> Result<(), Error> {
+        self.time(
+            self.inner.compact_events(member_id, snapshot),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn mature_points(
+        &self,
+        until: DateTime<Utc>,
+        queue_matured: bool,
+    ) -> Result<Vec<(Loyalty, u32)>, Error> {
+        self.time(
+            self.inner.mature_points(until, queue_matured),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_expired_lots(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, ExpiringLot)>, Error> {
+        self.time(self.inner.get_expired_lots(until), Error::is_retryable)
+            .await
+    }
+
+    async fn get_balance_changes(
+        &self,
+        checkpoint: u64,
+        limit: usize,
+    ) -> Result<Vec<BalanceChange>, Error> {
+        self.time(
+            self.inner.get_balance_changes(checkpoint, limit),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_event_for_approval(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+    ) -> Result<(), Error> {
+        self.time(
+            self.inner
+                .register_event_for_approval(member_id, loyalty_event),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_events_awaiting_approval(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<LoyaltyEvent>, Error> {
+        self.time(
+            self.inner.get_events_awaiting_approval(member_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn approve_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner.approve_event(member_id, event_id, outbox),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn reject_event(&self, member_id: Uuid, event_id: Uuid) -> Result<(), Error> {
+        self.time(
+            self.inner.reject_event(member_id, event_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_expired_escrow_events(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        self.time(
+            self.inner.get_expired_escrow_events(until),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_unverified_events(&self) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        self.time(self.inner.get_unverified_events(), Error::is_retryable)
+            .await
+    }
+
+    async fn reconcile_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        adjustment: Option<LoyaltyEvent>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner
+                .reconcile_event(member_id, event_id, adjustment, outbox),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn reverse_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        reversal: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner
+                .reverse_event(member_id, event_id, reversal, outbox),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn place_hold(&self, member_id: Uuid, hold: PointsHold) -> Result<Loyalty, Error> {
+        self.time(self.inner.place_hold(member_id, hold), Error::is_retryable)
+            .await
+    }
+
+    async fn capture_hold(
+        &self,
+        member_id: Uuid,
+        hold_id: Uuid,
+        event: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner.capture_hold(member_id, hold_id, event, outbox),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn release_hold(&self, member_id: Uuid, hold_id: Uuid) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner.release_hold(member_id, hold_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_welcome_bonus(
+        &self,
+        member_id: Uuid,
+        bonus: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.time(
+            self.inner.register_welcome_bonus(member_id, bonus, outbox),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_member_overrides(&self, member_id: Uuid) -> Result<Vec<MemberOverride>, Error> {
+        self.time(
+            self.inner.get_member_overrides(member_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_member_override(&self, member_override: MemberOverride) -> Result<(), Error> {
+        self.time(
+            self.inner.register_member_override(member_override),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_member_restrictions(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<MemberRestriction>, Error> {
+        self.time(
+            self.inner.get_member_restrictions(member_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn register_member_restriction(
+        &self,
+        restriction: MemberRestriction,
+    ) -> Result<(), Error> {
+        self.time(
+            self.inner.register_member_restriction(restriction),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_campaign_events(
+        &self,
+        campaign_id: Uuid,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        self.time(
+            self.inner.get_campaign_events(campaign_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_campaign_run(&self, campaign_id: Uuid) -> Result<Option<CampaignRun>, Error> {
+        self.time(
+            self.inner.get_campaign_run(campaign_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn save_campaign_run(&self, run: CampaignRun) -> Result<(), Error> {
+        self.time(self.inner.save_campaign_run(run), Error::is_retryable)
+            .await
+    }
+
+    async fn reserve_campaign_budget(
+        &self,
+        campaign_id: Uuid,
+        points: u32,
+        budget: u64,
+    ) -> Result<bool, Error> {
+        self.time(
+            self.inner
+                .reserve_campaign_budget(campaign_id, points, budget),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn release_campaign_budget(&self, campaign_id: Uuid, points: u32) -> Result<(), Error> {
+        self.time(
+            self.inner.release_campaign_budget(campaign_id, points),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_member_ids(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<Uuid>, Error> {
+        self.time(self.inner.get_member_ids(after, limit), Error::is_retryable)
+            .await
+    }
+
+    async fn save_tier_evaluation(
+        &self,
+        evaluation: TierEvaluation,
+        queue_tier_change: bool,
+    ) -> Result<Option<TierEvaluation>, Error> {
+        self.time(
+            self.inner
+                .save_tier_evaluation(evaluation, queue_tier_change),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_outbox_entries(&self, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+        self.time(self.inner.get_outbox_entries(limit), Error::is_retryable)
+            .await
+    }
+
+    async fn remove_outbox_entry(&self, outbox_id: u64) -> Result<(), Error> {
+        self.time(
+            self.inner.remove_outbox_entry(outbox_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn get_event_notes(&self, member_id: Uuid) -> Result<Vec<EventNote>, Error> {
+        self.time(self.inner.get_event_notes(member_id), Error::is_retryable)
+            .await
+    }
+
+    async fn register_event_note(&self, note: EventNote) -> Result<(), Error> {
+        self.time(self.inner.register_event_note(note), Error::is_retryable)
+            .await
+    }
+
+    async fn get_partner_transfers(&self, member_id: Uuid) -> Result<Vec<PartnerTransfer>, Error> {
+        self.time(
+            self.inner.get_partner_transfers(member_id),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    async fn save_partner_transfer(&self, transfer: PartnerTransfer) -> Result<(), Error> {
+        self.time(
+            self.inner.save_partner_transfer(transfer),
+            Error::is_retryable,
+        )
+        .await
+    }
+}
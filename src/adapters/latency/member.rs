@@ -0,0 +1,19 @@
+use super::Timed;
+use crate::ports::member::{Error, Member, MemberPort};
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+impl<P> MemberPort for Timed<P>
+where
+    P: MemberPort + Send + Sync,
+{
+    async fn get_member(&self, member_id: Uuid) -> Result<Member, Error> {
+        self.time(self.inner.get_member(member_id), Error::is_retryable)
+            .await
+    }
+
+    async fn get_members(&self, member_ids: &[Uuid]) -> Result<Vec<Result<Member, Error>>, Error> {
+        self.time(self.inner.get_members(member_ids), Error::is_retryable)
+            .await
+    }
+}
@@ -0,0 +1,269 @@
+//! Latency tracking for calls to other services
+//!
+//! [`Timed`] wraps a member or database adapter and records how long each call takes in a
+//! [`LatencyRecorder`], e.g. to check a dependency against its SLA.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+mod database;
+mod member;
+
+/// Port adapter recording the latency of every call to `inner`
+#[derive(Clone, Debug)]
+pub struct Timed<P> {
+    dependency: &'static str,
+    inner: P,
+    recorder: LatencyRecorder,
+}
+
+impl<P> Timed<P> {
+    /// Record calls to `inner` under the `dependency` name
+    pub fn new(dependency: &'static str, inner: P, recorder: LatencyRecorder) -> Self {
+        Self {
+            dependency,
+            inner,
+            recorder,
+        }
+    }
+
+    /// Run a call to the inner adapter and record its latency
+    ///
+    /// Only errors matching `is_failure` count as failures. Domain errors, such as a missing
+    /// member, are valid answers from the dependency.
+    async fn time<T, E>(
+        &self,
+        call: impl Future<Output = Result<T, E>> + Send,
+        is_failure: fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let started = Instant::now();
+        let res = call.await;
+        self.recorder.record(
+            self.dependency,
+            started.elapsed(),
+            res.as_ref().is_err_and(is_failure),
+        );
+        res
+    }
+}
+
+/// Latency of calls to each dependency over a rolling window
+///
+/// Clones share the same statistics, so a recorder can be shared between adapters and the code
+/// exposing the statistics.
+#[derive(Clone, Debug)]
+pub struct LatencyRecorder {
+    /// How long calls are kept in the statistics
+    window: Duration,
+    /// Maximum latency allowed for each dependency
+    slas: HashMap<&'static str, Duration>,
+    dependencies: Arc<Mutex<HashMap<&'static str, DependencyCalls>>>,
+}
+
+/// Calls to a dependency within the window
+#[derive(Debug, Default)]
+struct DependencyCalls {
+    calls: VecDeque<Call>,
+    /// Breaches since the recorder was created, including calls outside the window
+    total_breaches: u64,
+}
+
+#[derive(Debug)]
+struct Call {
+    at: Instant,
+    latency: Duration,
+    failed: bool,
+    breached: bool,
+}
+
+/// Statistics of the calls to a dependency within the recorder's window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyStats {
+    pub dependency: &'static str,
+    pub calls: usize,
+    /// Calls failing with a transient error, such as a timeout
+    pub failures: usize,
+    /// Calls slower than the dependency's SLA
+    pub breaches: usize,
+    /// Calls slower than the dependency's SLA since the recorder was created
+    pub total_breaches: u64,
+    pub p50_latency: Duration,
+    pub p99_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl LatencyRecorder {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            slas: HashMap::new(),
+            dependencies: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Count calls to `dependency` slower than `max_latency` as SLA breaches
+    pub fn with_sla(mut self, dependency: &'static str, max_latency: Duration) -> Self {
+        self.slas.insert(dependency, max_latency);
+        self
+    }
+
+    /// Record a call to `dependency`
+    pub fn record(&self, dependency: &'static str, latency: Duration, failed: bool) {
+        self.record_at(Instant::now(), dependency, latency, failed)
+    }
+
+    /// Statistics for `dependency`, or `None` if it was never called
+    pub fn stats(&self, dependency: &'static str) -> Option<DependencyStats> {
+        self.stats_at(Instant::now(), dependency)
+    }
+
+    /// Statistics for all dependencies that were called, ordered by name
+    pub fn all_stats(&self) -> Vec<DependencyStats> {
+        let now = Instant::now();
+        let mut names: Vec<_> = self.lock().keys().copied().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|dependency| self.stats_at(now, dependency))
+            .collect()
+    }
+
+    fn record_at(&self, now: Instant, dependency: &'static str, latency: Duration, failed: bool) {
+        let breached = self
+            .slas
+            .get(dependency)
+            .is_some_and(|max_latency| latency > *max_latency);
+        if breached {
+            tracing::warn!(
+                dependency,
+                latency_ms = latency.as_millis() as u64,
+                "dependency call breached its SLA"
+            );
+        }
+
+        let mut dependencies = self.lock();
+        let dependency_calls = dependencies.entry(dependency).or_default();
+        dependency_calls.calls.push_back(Call {
+            at: now,
+            latency,
+            failed,
+            breached,
+        });
+        if breached {
+            dependency_calls.total_breaches += 1;
+        }
+        self.prune(now, dependency_calls);
+    }
+
+    fn stats_at(&self, now: Instant, dependency: &'static str) -> Option<DependencyStats> {
+        let mut dependencies = self.lock();
+        let dependency_calls = dependencies.get_mut(dependency)?;
+        self.prune(now, dependency_calls);
+
+        let calls = &dependency_calls.calls;
+        let mut latencies: Vec<_> = calls.iter().map(|call| call.latency).collect();
+        latencies.sort();
+        Some(DependencyStats {
+            dependency,
+            calls: calls.len(),
+            failures: calls.iter().filter(|call| call.failed).count(),
+            breaches: calls.iter().filter(|call| call.breached).count(),
+            total_breaches: dependency_calls.total_breaches,
+            p50_latency: percentile(&latencies, 50),
+            p99_latency: percentile(&latencies, 99),
+            max_latency: latencies.last().copied().unwrap_or_default(),
+        })
+    }
+
+    /// Drop calls older than the window
+    fn prune(&self, now: Instant, dependency_calls: &mut DependencyCalls) {
+        while let Some(call) = dependency_calls.calls.front() {
+            if now.duration_since(call.at) <= self.window {
+                break;
+            }
+            dependency_calls.calls.pop_front();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<&'static str, DependencyCalls>> {
+        self.dependencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies
+fn percentile(latencies: &[Duration], percent: usize) -> Duration {
+    if latencies.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (latencies.len() * percent).div_ceil(100).max(1);
+    latencies[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::member::{MemberPort, MockMemberPort};
+    use speculoos::prelude::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_stats() {
+        let recorder = LatencyRecorder::new(Duration::from_secs(60))
+            .with_sla("member", Duration::from_millis(100));
+        let start = Instant::now();
+
+        // An old breach, then 10 calls from 20ms to 200ms in the window, with one failure
+        recorder.record_at(start, "member", Duration::from_millis(500), false);
+        let now = start + Duration::from_secs(120);
+        for latency in 1..=10 {
+            recorder.record_at(
+                now,
+                "member",
+                Duration::from_millis(latency * 20),
+                latency == 3,
+            );
+        }
+
+        // The old call is out of the window, but still counts in the total breaches
+        assert_that!(recorder.stats_at(now, "member")).is_equal_to(Some(DependencyStats {
+            dependency: "member",
+            calls: 10,
+            failures: 1,
+            breaches: 5,
+            total_breaches: 6,
+            p50_latency: Duration::from_millis(100),
+            p99_latency: Duration::from_millis(200),
+            max_latency: Duration::from_millis(200),
+        }));
+        assert_that!(recorder.stats_at(now, "database")).is_none();
+    }
+
+    #[tokio::test]
+    async fn test_timed() {
+        let recorder = LatencyRecorder::new(Duration::from_secs(60));
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(|member_id| {
+            if member_id.is_nil() {
+                Err(crate::ports::member::Error::Unavailable("timeout".into()))
+            } else {
+                Err(crate::ports::member::Error::MemberDoesNotExist(member_id))
+            }
+        });
+        let member = Timed::new("member", member, recorder.clone());
+
+        // Only transient errors count as failures
+        let _ = member.get_member(Uuid::nil()).await;
+        let _ = member.get_member(Uuid::new_v4()).await;
+
+        let stats = recorder.all_stats();
+        assert_that!(stats).has_length(1);
+        assert_that!(stats[0].calls).is_equal_to(2);
+        assert_that!(stats[0].failures).is_equal_to(1);
+    }
+}
@@ -1 +1,14 @@
+pub mod campaign;
+pub mod case_lock;
+pub mod catalog;
+pub mod circuit_breaker;
+pub mod clock;
 pub mod database;
+pub mod drawing;
+pub mod event_publisher;
+pub mod id_generator;
+pub mod idempotency;
+pub mod latency;
+pub mod partner_transfer;
+pub mod retry;
+pub mod segment;
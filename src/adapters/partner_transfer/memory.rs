@@ -0,0 +1,84 @@
+use crate::ports::partner_transfer::{Error, PartnerTransferPort};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, PoisonError},
+};
+use uuid::Uuid;
+
+/// Partner programs crediting transfers in memory
+///
+/// Transfers to unknown partners are rejected. Clones share the same credits.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryPartners {
+    partners: HashSet<String>,
+    /// Points credited by each transfer
+    credits: Arc<Mutex<HashMap<Uuid, u32>>>,
+}
+
+impl MemoryPartners {
+    pub fn with_partner(mut self, partner: impl Into<String>) -> Self {
+        self.partners.insert(partner.into());
+        self
+    }
+
+    /// Points credited by each transfer
+    pub fn credits(&self) -> HashMap<Uuid, u32> {
+        self.credits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl PartnerTransferPort for MemoryPartners {
+    async fn transfer(
+        &self,
+        transfer_id: Uuid,
+        partner: &str,
+        _member_id: Uuid,
+        points: u32,
+    ) -> Result<String, Error> {
+        if !self.partners.contains(partner) {
+            return Err(Error::Rejected(format!("unknown partner {partner}")));
+        }
+
+        // Retries of the same transfer are only credited once
+        self.credits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(transfer_id)
+            .or_insert(points);
+
+        Ok(transfer_id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let partners = MemoryPartners::default().with_partner("SkyMiles");
+        let transfer_id = Uuid::new_v4();
+
+        // Retries are only credited once
+        for _ in 0..2 {
+            let res = partners
+                .transfer(transfer_id, "SkyMiles", Uuid::new_v4(), 1000)
+                .await;
+            assert_that!(res).is_ok();
+        }
+        assert_that!(partners.credits()).is_equal_to(HashMap::from([(transfer_id, 1000)]));
+
+        // Unknown partners reject transfers
+        let res = partners
+            .transfer(Uuid::new_v4(), "FlyFar", Uuid::new_v4(), 1000)
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::Rejected(_)));
+    }
+}
@@ -0,0 +1,3 @@
+//! Adapters for the partner transfer port
+
+pub mod memory;
@@ -0,0 +1,312 @@
+use super::Retrying;
+use crate::{
+    domain::{
+        BalanceChange, CampaignRun, DomainEvent, EventNote, ExpiringLot, Loyalty, LoyaltyEvent,
+        MemberOverride, MemberRestriction, OutboxEntry, PartnerTransfer, PointsHold,
+        TierEvaluation,
+    },
+    ports::database::{DatabasePort, Error},
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Only reads are retried: a write failing with a transient error might still have been applied,
+/// and applying it again could credit points twice.
+#[async_trait::async_trait]
+impl<P> DatabasePort for Retrying<P>
+where
+    P: DatabasePort + Send + Sync,
+{
+    async fn get_loyalty_points(&self, member_id: Uuid) -> Result<Loyalty, Error> {
+        self.retry(|| self.inner.get_loyalty_points(member_id), is_transient)
+            .await
+    }
+
+    async fn get_loyalty_points_batch(&self, member_ids: &[Uuid]) -> Result<Vec<Loyalty>, Error> {
+        self.retry(
+            || self.inner.get_loyalty_points_batch(member_ids),
+            is_transient,
+        )
+        .await
+    }
+
+    async fn get_loyalty_events(&self, member_id: Uuid) -> Result<Vec<LoyaltyEvent>, Error> {
+        self.retry(|| self.inner.get_loyalty_events(member_id), is_transient)
+            .await
+    }
+
+    async fn register_loyalty_event(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+        expected_version: Option<u64>,
+    ) -> Result<Loyalty, Error> {
+        self.inner
+            .register_loyalty_event(member_id, loyalty_event, expected_version)
+            .await
+    }
+
+    async fn register_loyalty_events_batch(
+        &self,
+        events: Vec<(Uuid, LoyaltyEvent)>,
+    ) -> Result<Vec<Loyalty>, Error> {
+        self.inner.register_loyalty_events_batch(events).await
+    }
+
+    async fn register_loyalty_event_with_outbox(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+        expected_version: Option<u64>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.inner
+            .register_loyalty_event_with_outbox(member_id, loyalty_event, expected_version, outbox)
+            .await
+    }
+
+    async fn compact_events(&self, member_id: Uuid, snapshot: LoyaltyEvent) -> Result<(), Error> {
+        self.inner.compact_events(member_id, snapshot).await
+    }
+
+    async fn mature_points(
+        &self,
+        until: DateTime<Utc>,
+        queue_matured: bool,
+    ) -> Result<Vec<(Loyalty, u32)>, Error> {
+        self.inner.mature_points(until, queue_matured).await
+    }
+
+    async fn get_expired_lots(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, ExpiringLot)>, Error> {
+        self.retry(|| self.inner.get_expired_lots(until), is_transient)
+            .await
+    }
+
+    async fn get_balance_changes(
+        &self,
+        checkpoint: u64,
+        limit: usize,
+    ) -> Result<Vec<BalanceChange>, Error> {
+        self.retry(
+            || self.inner.get_balance_changes(checkpoint, limit),
+            is_transient,
+        )
+        .await
+    }
+
+    async fn register_event_for_approval(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+    ) -> Result<(), Error> {
+        self.inner
+            .register_event_for_approval(member_id, loyalty_event)
+            .await
+    }
+
+    async fn get_events_awaiting_approval(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<LoyaltyEvent>, Error> {
+        self.retry(
+            || self.inner.get_events_awaiting_approval(member_id),
+            is_transient,
+        )
+        .await
+    }
+
+    async fn approve_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.inner.approve_event(member_id, event_id, outbox).await
+    }
+
+    async fn reject_event(&self, member_id: Uuid, event_id: Uuid) -> Result<(), Error> {
+        self.inner.reject_event(member_id, event_id).await
+    }
+
+    async fn get_expired_escrow_events(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        self.retry(|| self.inner.get_expired_escrow_events(until), is_transient)
+            .await
+    }
+
+    async fn get_unverified_events(&self) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        self.retry(|| self.inner.get_unverified_events(), is_transient)
+            .await
+    }
+
+    async fn reconcile_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        adjustment: Option<LoyaltyEvent>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.inner
+            .reconcile_event(member_id, event_id, adjustment, outbox)
+            .await
+    }
+
+    async fn reverse_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        reversal: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.inner
+            .reverse_event(member_id, event_id, reversal, outbox)
+            .await
+    }
+
+    async fn place_hold(&self, member_id: Uuid, hold: PointsHold) -> Result<Loyalty, Error> {
+        self.inner.place_hold(member_id, hold).await
+    }
+
+    async fn capture_hold(
+        &self,
+        member_id: Uuid,
+        hold_id: Uuid,
+        event: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.inner
+            .capture_hold(member_id, hold_id, event, outbox)
+            .await
+    }
+
+    async fn release_hold(&self, member_id: Uuid, hold_id: Uuid) -> Result<Loyalty, Error> {
+        self.inner.release_hold(member_id, hold_id).await
+    }
+
+    async fn register_welcome_bonus(
+        &self,
+        member_id: Uuid,
+        bonus: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error> {
+        self.inner
+            .register_welcome_bonus(member_id, bonus, outbox)
+            .await
+    }
+
+    async fn get_member_overrides(&self, member_id: Uuid) -> Result<Vec<MemberOverride>, Error> {
+        self.retry(|| self.inner.get_member_overrides(member_id), is_transient)
+            .await
+    }
+
+    async fn register_member_override(&self, member_override: MemberOverride) -> Result<(), Error> {
+        self.inner.register_member_override(member_override).await
+    }
+
+    async fn get_member_restrictions(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<MemberRestriction>, Error> {
+        self.retry(
+            || self.inner.get_member_restrictions(member_id),
+            is_transient,
+        )
+        .await
+    }
+
+    async fn register_member_restriction(
+        &self,
+        restriction: MemberRestriction,
+    ) -> Result<(), Error> {
+        self.inner.register_member_restriction(restriction).await
+    }
+
+    async fn get_campaign_events(
+        &self,
+        campaign_id: Uuid,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error> {
+        self.retry(|| self.inner.get_campaign_events(campaign_id), is_transient)
+            .await
+    }
+
+    async fn get_campaign_run(&self, campaign_id: Uuid) -> Result<Option<CampaignRun>, Error> {
+        self.retry(|| self.inner.get_campaign_run(campaign_id), is_transient)
+            .await
+    }
+
+    async fn save_campaign_run(&self, run: CampaignRun) -> Result<(), Error> {
+        self.inner.save_campaign_run(run).await
+    }
+
+    async fn reserve_campaign_budget(
+        &self,
+        campaign_id: Uuid,
+        points: u32,
+        budget: u64,
+    ) -> Result<bool, Error> {
+        self.inner
+            .reserve_campaign_budget(campaign_id, points, budget)
+            .await
+    }
+
+    async fn release_campaign_budget(&self, campaign_id: Uuid, points: u32) -> Result<(), Error> {
+        self.inner
+            .release_campaign_budget(campaign_id, points)
+            .await
+    }
+
+    async fn get_member_ids(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<Uuid>, Error> {
+        self.retry(|| self.inner.get_member_ids(after, limit), is_transient)
+            .await
+    }
+
+    async fn save_tier_evaluation(
+        &self,
+        evaluation: TierEvaluation,
+        queue_tier_change: bool,
+    ) -> Result<Option<TierEvaluation>, Error> {
+        self.inner
+            .save_tier_evaluation(evaluation, queue_tier_change)
+            .await
+    }
+
+    async fn get_outbox_entries(&self, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+        self.retry(|| self.inner.get_outbox_entries(limit), is_transient)
+            .await
+    }
+
+    async fn remove_outbox_entry(&self, outbox_id: u64) -> Result<(), Error> {
+        self.inner.remove_outbox_entry(outbox_id).await
+    }
+
+    async fn get_event_notes(&self, member_id: Uuid) -> Result<Vec<EventNote>, Error> {
+        self.retry(|| self.inner.get_event_notes(member_id), is_transient)
+            .await
+    }
+
+    async fn register_event_note(&self, note: EventNote) -> Result<(), Error> {
+        self.inner.register_event_note(note).await
+    }
+
+    async fn get_partner_transfers(&self, member_id: Uuid) -> Result<Vec<PartnerTransfer>, Error> {
+        self.retry(|| self.inner.get_partner_transfers(member_id), is_transient)
+            .await
+    }
+
+    async fn save_partner_transfer(&self, transfer: PartnerTransfer) -> Result<(), Error> {
+        self.inner.save_partner_transfer(transfer).await
+    }
+}
+
+/// Whether retrying the same call might succeed
+///
+/// Conflicts are retryable, but only after reading the member's loyalty again, which the adapter
+/// cannot do for the caller.
+fn is_transient(err: &Error) -> bool {
+    matches!(err, Error::Unavailable(_))
+}
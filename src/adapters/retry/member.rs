@@ -0,0 +1,26 @@
+use super::Retrying;
+use crate::ports::member::{Error, Member, MemberPort};
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+impl<P> MemberPort for Retrying<P>
+where
+    P: MemberPort + Send + Sync,
+{
+    async fn get_member(&self, member_id: Uuid) -> Result<Member, Error> {
+        self.retry(|| self.inner.get_member(member_id), is_transient)
+            .await
+    }
+
+    async fn get_members(&self, member_ids: &[Uuid]) -> Result<Vec<Result<Member, Error>>, Error> {
+        self.retry(|| self.inner.get_members(member_ids), is_transient)
+            .await
+    }
+}
+
+/// Whether retrying the same call might succeed
+///
+/// An open circuit fails fast on purpose, so it is not retried.
+fn is_transient(err: &Error) -> bool {
+    matches!(err, Error::Unavailable(_))
+}
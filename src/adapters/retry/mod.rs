@@ -0,0 +1,269 @@
+//! Retries of transient errors from other services
+//!
+//! [`Retrying`] wraps a member, database or partner transfer adapter and calls it again when it
+//! fails with a transient error, waiting longer between each attempt following a [`Backoff`].
+//! Adapters mark transient errors with their `Unavailable` variant.
+
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use crate::ports::ErrorChain;
+
+mod database;
+mod member;
+mod partner_transfer;
+
+/// Exponential backoff between retries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    max_retries: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Backoff {
+    /// Retry up to `max_retries` times, waiting `initial_delay` before the first retry and twice
+    /// as long before each following one
+    pub fn new(max_retries: u32, initial_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_delay,
+            max_delay: Duration::MAX,
+        }
+    }
+
+    /// Never wait longer than `max_delay` between retries
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Maximum number of retries after the first attempt
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Delay before the retry with this index, starting at 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+}
+
+/// Function waiting for a delay
+pub(crate) type Sleep =
+    Arc<dyn Fn(Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Port adapter retrying calls to `inner` that fail with a transient error
+#[derive(Clone)]
+pub struct Retrying<P> {
+    inner: P,
+    backoff: Backoff,
+    sleep: Sleep,
+}
+
+impl<P> Retrying<P> {
+    /// Retry calls to `inner` following `backoff`
+    ///
+    /// `sleep` waits between attempts, e.g. `tokio::time::sleep`, so the adapter does not depend
+    /// on a specific runtime.
+    pub fn new<F, Fut>(inner: P, backoff: Backoff, sleep: F) -> Self
+    where
+        F: Fn(Duration) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            inner,
+            backoff,
+            sleep: Arc::new(move |delay| Box::pin(sleep(delay))),
+        }
+    }
+
+    /// Run a call to the inner adapter until it succeeds, fails with an error not matching
+    /// `is_transient`, or runs out of retries
+    async fn retry<T, E, F, Fut>(&self, mut call: F, is_transient: fn(&E) -> bool) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: std::error::Error + 'static,
+    {
+        let mut retry = 0;
+        loop {
+            match call().await {
+                Err(err) if retry < self.backoff.max_retries && is_transient(&err) => {
+                    let delay = self.backoff.delay(retry);
+                    retry += 1;
+                    tracing::debug!(
+                        error = %ErrorChain(&err),
+                        retry,
+                        ?delay,
+                        "transient error, retrying"
+                    );
+                    (self.sleep)(delay).await;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for Retrying<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retrying")
+            .field("inner", &self.inner)
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{Loyalty, LoyaltyEvent},
+        ports::{
+            database::{self, DatabasePort, MockDatabasePort},
+            member::{self, Member, MemberPort, MockMemberPort},
+        },
+    };
+    use chrono::Utc;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    };
+    use uuid::Uuid;
+
+    /// Sleep function recording the delays instead of waiting
+    fn recorded_sleep() -> (
+        Arc<Mutex<Vec<Duration>>>,
+        impl Fn(Duration) -> std::future::Ready<()> + Send + Sync + 'static,
+    ) {
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let recorded = delays.clone();
+        (delays, move |delay| {
+            recorded.lock().unwrap().push(delay);
+            std::future::ready(())
+        })
+    }
+
+    #[test]
+    fn test_backoff() {
+        let backoff =
+            Backoff::new(5, Duration::from_millis(100)).with_max_delay(Duration::from_millis(500));
+
+        let delays = (0..5).map(|retry| backoff.delay(retry)).collect::<Vec<_>>();
+
+        assert_that!(delays).is_equal_to(vec![
+            Duration::from_millis(100),
+            Duration::from_millis(200),
+            Duration::from_millis(400),
+            Duration::from_millis(500),
+            Duration::from_millis(500),
+        ]);
+    }
+
+    #[rstest]
+    #[case(2, true, 3)]
+    #[case(5, true, 3)]
+    #[case(1, false, 2)]
+    #[tokio::test]
+    async fn test_retrying_member(
+        #[case] max_retries: u32,
+        #[case] succeeds: bool,
+        #[case] calls: u32,
+    ) {
+        // GIVEN a member service failing twice before answering
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counted = attempts.clone();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |member_id| {
+            if counted.fetch_add(1, Ordering::Relaxed) < 2 {
+                return Err(member::Error::Unavailable("timeout".into()));
+            }
+            Ok(Member {
+                member_id,
+                ..Default::default()
+            })
+        });
+        let (delays, sleep) = recorded_sleep();
+        let member = Retrying::new(
+            member,
+            Backoff::new(max_retries, Duration::from_millis(10)),
+            sleep,
+        );
+
+        // WHEN fetching a member
+        let res = member.get_member(Uuid::new_v4()).await;
+
+        // THEN
+        // * transient errors are retried, up to the maximum number of retries
+        // * the delay doubles after each retry
+        assert_that!(res.is_ok()).is_equal_to(succeeds);
+        assert_that!(attempts.load(Ordering::Relaxed)).is_equal_to(calls);
+        assert_that!(*delays.lock().unwrap()).is_equal_to(
+            [Duration::from_millis(10), Duration::from_millis(20)][..calls as usize - 1].to_vec(),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retrying_member_fatal() {
+        // GIVEN a member service that does not know the member
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .times(1)
+            .returning(|member_id| Err(member::Error::MemberDoesNotExist(member_id)));
+        let (delays, sleep) = recorded_sleep();
+        let member = Retrying::new(member, Backoff::new(3, Duration::from_millis(10)), sleep);
+
+        // WHEN fetching the member
+        let res = member.get_member(Uuid::new_v4()).await;
+
+        // THEN the error is returned without retrying
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, member::Error::MemberDoesNotExist(_)));
+        assert_that!(*delays.lock().unwrap()).is_empty();
+    }
+
+    #[tokio::test]
+    async fn test_retrying_database() {
+        // GIVEN a database failing the first read, and every write
+        let member_id = Uuid::new_v4();
+        let mut database = MockDatabasePort::new();
+        let mut seq = mockall::Sequence::new();
+        database
+            .expect_get_loyalty_points()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Err(database::Error::Unavailable("timeout".into())));
+        database
+            .expect_get_loyalty_points()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|member_id| Ok(Loyalty::new(member_id)));
+        database
+            .expect_register_loyalty_event()
+            .times(1)
+            .returning(|_, _, _| Err(database::Error::Unavailable("timeout".into())));
+        let (delays, sleep) = recorded_sleep();
+        let database = Retrying::new(database, Backoff::new(3, Duration::from_millis(10)), sleep);
+
+        // WHEN reading and writing
+        let read = database.get_loyalty_points(member_id).await;
+        let write = database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(Uuid::new_v4(), 100, "SOME REASON", Utc::now()),
+                None,
+            )
+            .await;
+
+        // THEN only the read is retried
+        assert_that!(read).is_ok();
+        assert_that!(write).is_err();
+        assert_that!(*delays.lock().unwrap()).has_length(1);
+    }
+}
@@ -0,0 +1,24 @@
+use super::Retrying;
+use crate::ports::partner_transfer::{Error, PartnerTransferPort};
+use uuid::Uuid;
+
+/// Retries are safe, as partners only credit a transfer ID once
+#[async_trait::async_trait]
+impl<P> PartnerTransferPort for Retrying<P>
+where
+    P: PartnerTransferPort + Send + Sync,
+{
+    async fn transfer(
+        &self,
+        transfer_id: Uuid,
+        partner: &str,
+        member_id: Uuid,
+        points: u32,
+    ) -> Result<String, Error> {
+        self.retry(
+            || self.inner.transfer(transfer_id, partner, member_id, points),
+            Error::is_retryable,
+        )
+        .await
+    }
+}
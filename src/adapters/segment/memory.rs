@@ -0,0 +1,80 @@
+use crate::ports::segment::{Error, SegmentPort};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Segment adapter backed by a fixed set of memberships
+///
+/// Members that were not added return no segments.
+#[derive(Clone, Debug, Default)]
+pub struct StaticSegments {
+    segments: HashMap<Uuid, Vec<String>>,
+}
+
+impl StaticSegments {
+    pub fn with_member<I, S>(mut self, member_id: Uuid, segments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.segments
+            .entry(member_id)
+            .or_default()
+            .extend(segments.into_iter().map(Into::into));
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SegmentPort for StaticSegments {
+    async fn get_segments(&self, member_id: Uuid) -> Result<Vec<String>, Error> {
+        Ok(self.segments.get(&member_id).cloned().unwrap_or_default())
+    }
+
+    async fn get_segment_members(&self, segment: &str) -> Result<Vec<Uuid>, Error> {
+        let mut members = self
+            .segments
+            .iter()
+            .filter(|(_, segments)| segments.iter().any(|name| name == segment))
+            .map(|(member_id, _)| *member_id)
+            .collect::<Vec<_>>();
+        members.sort();
+
+        Ok(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    #[tokio::test]
+    async fn test_get_segments() {
+        let member_id = Uuid::new_v4();
+        let segments = StaticSegments::default()
+            .with_member(member_id, ["student"])
+            .with_member(member_id, ["newsletter"]);
+
+        let res = segments.get_segments(member_id).await;
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(vec!["student".to_string(), "newsletter".to_string()]);
+
+        // Unknown members are not part of any segment
+        let res = segments.get_segments(Uuid::new_v4()).await;
+        assert_that!(res).is_ok().is_empty();
+    }
+
+    #[tokio::test]
+    async fn test_get_segment_members() {
+        let segments = StaticSegments::default()
+            .with_member(Uuid::from_u128(2), ["student"])
+            .with_member(Uuid::from_u128(1), ["student", "newsletter"])
+            .with_member(Uuid::from_u128(3), ["newsletter"]);
+
+        let res = segments.get_segment_members("student").await;
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(vec![Uuid::from_u128(1), Uuid::from_u128(2)]);
+    }
+}
@@ -0,0 +1,3 @@
+//! Adapters for the segment port
+
+pub mod memory;
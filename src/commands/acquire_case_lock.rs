@@ -0,0 +1,112 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::CaseLock,
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Signal other support agents that a member is being worked on
+///
+/// Acquiring a lock already held by the same agent extends it. This fails if another agent holds
+/// an active lock on the member.
+pub struct AcquireCaseLockRequest {
+    pub member_id: Uuid,
+    /// Support agent taking the lock
+    pub holder: String,
+}
+
+impl<D, M> Service<AcquireCaseLockRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = CaseLock;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: AcquireCaseLockRequest) -> Self::Future {
+        let case_lock = self.case_lock();
+        let ttl = self.case_lock_ttl;
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            let case_lock_port = case_lock?;
+
+            let now = clock.now();
+            let case_lock = CaseLock {
+                member_id: req.member_id,
+                holder: req.holder,
+                acquired_at: now,
+                expires_at: now + ttl,
+            };
+            case_lock_port
+                .acquire_lock(case_lock.clone())
+                .await
+                .with_context(|| format!("acquiring case lock on member {}", req.member_id))?;
+
+            Ok(case_lock)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{case_lock::memory::MemoryCaseLocks, database::memory::MemoryDatabase},
+        ports::member::MockMemberPort,
+    };
+    use chrono::Duration;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN case locks lasting 15 minutes
+        let member_id = Uuid::new_v4();
+        let mut domain = DomainLogic::new(
+            Arc::new(MemoryDatabase::default()),
+            Arc::new(MockMemberPort::new()),
+        )
+        .with_case_locks(Arc::new(MemoryCaseLocks::default()), Duration::minutes(15));
+
+        // WHEN two agents lock the same member
+        let mut results = Vec::new();
+        for holder in ["agent-1", "agent-2"] {
+            let res = ServiceExt::<AcquireCaseLockRequest>::ready(&mut domain)
+                .await?
+                .call(AcquireCaseLockRequest {
+                    member_id,
+                    holder: holder.to_string(),
+                })
+                .await;
+            results.push(res);
+        }
+
+        // THEN only the first agent gets the lock
+        assert_that!(results[0]).is_ok().matches(|case_lock| {
+            case_lock.holder == "agent-1"
+                && case_lock.expires_at - case_lock.acquired_at == Duration::minutes(15)
+        });
+        assert_that!(results[1]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::CaseLock(crate::ports::case_lock::Error::AlreadyLocked(_))
+            )
+        });
+
+        Ok(())
+    }
+}
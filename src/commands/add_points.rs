@@ -2,31 +2,89 @@ use std::{
     borrow::Cow,
     future::Future,
     pin::Pin,
+    sync::PoisonError,
     task::{Context, Poll},
 };
 
 use crate::{
-    domain::{LoyaltyEvent, Member, Tier},
-    ports::{database::DatabasePort, member::MemberPort},
+    domain::{
+        normalize_order_reference, purchase_points, Campaign, Capability, Channel, DomainEvent,
+        Loyalty, LoyaltyEvent, Money, Rounding, Tier, TierChange, TierEvaluation, UnverifiedTier,
+    },
+    layers::{
+        capture::{Anonymize, Pseudonyms},
+        idempotency::IdempotentRequest,
+    },
+    ports::{
+        database::DatabasePort, event_publisher::EventPublisherPort, member::MemberPort,
+        ErrorChain, ResultExt,
+    },
 };
-use chrono::{DateTime, Datelike, Months, Utc};
+use chrono::{DateTime, Datelike, Duration, Utc};
 use tower::Service;
 use uuid::Uuid;
 
-use super::{DomainLogic, Error};
+use super::{
+    ensure_capability, persist_with_events, publish, retry_on_conflict, DomainLogic, Error,
+};
 
+#[derive(Clone)]
 pub struct AddPointsRequest {
-    member_id: Uuid,
-    event: AddPointsEvent,
+    pub member_id: Uuid,
+    pub event: AddPointsEvent,
+    /// When the event happened, defaulting to when the request is handled
+    ///
+    /// This decides which days of the [`HolidayCalendar`](crate::domain::HolidayCalendar) apply.
+    pub occurred_at: Option<DateTime<Utc>>,
+    /// Reference of the order for purchases, or of the invoice for membership renewals
+    ///
+    /// The same order can be reported by multiple channels, e.g. the web checkout and the ERP
+    /// feed. Purchases with the same normalized reference are reconciled following the
+    /// [`OrderReconciliation`](crate::domain::OrderReconciliation) preference. Renewals in escrow
+    /// need the invoice reference, to settle them once the invoice is paid.
+    pub order_reference: Option<String>,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
 }
 
+impl IdempotentRequest for AddPointsRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+impl Anonymize for AddPointsRequest {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        Self {
+            member_id: pseudonyms.id(self.member_id),
+            event: self.event.anonymize(pseudonyms),
+            occurred_at: self.occurred_at,
+            order_reference: self
+                .order_reference
+                .as_deref()
+                .and_then(|reference| pseudonyms.order_reference(reference)),
+            idempotency_key: self
+                .idempotency_key
+                .as_deref()
+                .map(|key| pseudonyms.idempotency_key(key)),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum AddPointsEvent {
     /// The member continues their membership for another monthg
     MembershipRenewed,
     /// The member makes a purchase in a physical store
-    InStorePurchase { purchase_amount: f64 },
+    InStorePurchase { purchase_amount: Money },
     /// The member makes a purchase online
-    OnlinePurchase { purchase_amount: f64 },
+    OnlinePurchase { purchase_amount: Money },
     /// Manually adding points, e.g. for support
     Manual {
         loyalty_points: u32,
@@ -34,7 +92,40 @@ pub enum AddPointsEvent {
     },
 }
 
+impl Anonymize for AddPointsEvent {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        match self {
+            AddPointsEvent::Manual {
+                loyalty_points,
+                reason,
+            } => AddPointsEvent::Manual {
+                loyalty_points: *loyalty_points,
+                reason: reason.as_deref().map(|reason| pseudonyms.reason(reason)),
+            },
+            event => event.clone(),
+        }
+    }
+}
+
 impl AddPointsEvent {
+    /// Sales channel for purchases
+    pub fn channel(&self) -> Option<Channel> {
+        match self {
+            AddPointsEvent::InStorePurchase { .. } => Some(Channel::InStore),
+            AddPointsEvent::OnlinePurchase { .. } => Some(Channel::Online),
+            AddPointsEvent::MembershipRenewed | AddPointsEvent::Manual { .. } => None,
+        }
+    }
+
+    /// Amount spent on purchases
+    pub fn purchase_amount(&self) -> Option<&Money> {
+        match self {
+            AddPointsEvent::InStorePurchase { purchase_amount }
+            | AddPointsEvent::OnlinePurchase { purchase_amount } => Some(purchase_amount),
+            AddPointsEvent::MembershipRenewed | AddPointsEvent::Manual { .. } => None,
+        }
+    }
+
     pub fn reason(&self) -> Cow<'static, str> {
         match self {
             AddPointsEvent::MembershipRenewed => "Membership renewed".into(),
@@ -49,14 +140,54 @@ impl AddPointsEvent {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AddPointsResponse {
     pub member_id: Uuid,
     pub tier: Tier,
+    /// Whether the tier is a fallback, because the member service was unavailable
+    pub tier_unverified: bool,
+    /// Set when the tier differs from the member's previous evaluation
+    ///
+    /// The change is also published as a [`DomainEvent::TierChanged`].
+    pub tier_change: Option<TierChange>,
     /// Previous number of loyalty points
     pub old_loyalty_points: u32,
     /// New number of loyalty points
     pub new_loyalty_points: u32,
+    /// New number of pending loyalty points, not available yet
+    pub pending_loyalty_points: u32,
+    /// Whether the points await approval, or payment confirmation for renewals, before being added
+    ///
+    /// If this is `true`, `new_loyalty_points` does not include the points from this request.
+    pub awaiting_approval: bool,
+    /// First event of the same order, if another channel already reported it
+    ///
+    /// The points then follow the [`OrderReconciliation`](crate::domain::OrderReconciliation)
+    /// preference.
+    pub duplicate_of: Option<Uuid>,
+    /// Points granted as a welcome bonus along with this request
+    ///
+    /// See [`DomainLogic::with_welcome_bonus`].
+    pub welcome_bonus_points: u32,
+}
+
+impl Anonymize for AddPointsResponse {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        Self {
+            member_id: pseudonyms.id(self.member_id),
+            ..self.clone()
+        }
+    }
+}
+
+/// Purchases reported again for an order that already earned points
+///
+/// See [`DomainLogic::duplicate_order_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DuplicateOrderStats {
+    pub duplicates: u64,
+    /// Duplicates that changed the points of the order, rather than being ignored
+    pub adjusted: u64,
 }
 
 impl<D, M> Service<AddPointsRequest> for DomainLogic<D, M>
@@ -73,83 +204,560 @@ where
     }
 
     fn call(&mut self, req: AddPointsRequest) -> Self::Future {
-        let member = self.member.clone();
-        let database = self.database.clone();
+        let domain = self.clone();
         Box::pin(async move {
-            // Fetch necessary data
-            let db_member = member.get_member(req.member_id).await?;
-            let loyalty = database.get_loyalty_points(db_member.member_id).await?;
+            // Start over with fresh data if a concurrent request changed the member's loyalty, e.g.
+            // reporting the same order
+            retry_on_conflict(domain.conflict_retries, || async {
+                // Fetch necessary data
+                let loyalty = domain
+                    .database
+                    .get_loyalty_points(req.member_id)
+                    .await
+                    .with_context(|| format!("fetching loyalty for member {}", req.member_id))?;
+                let db_member = domain
+                    .member
+                    .get_member(req.member_id)
+                    .await
+                    .with_context(|| format!("fetching member {}", req.member_id));
+
+                domain
+                    .add_points(req.clone(), loyalty, db_member)
+                    .await
+                    .map(|(response, _)| response)
+            })
+            .await
+        })
+    }
+}
+
+impl<D, M> DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    /// Add points for a member whose loyalty and member data were already fetched
+    ///
+    /// This returns the member's loyalty after the request, so multiple requests for the same
+    /// member can be chained without fetching it again. If `loyalty` is stale, this fails with a
+    /// conflict, as the checks against previous events would not be reliable.
+    pub(super) async fn add_points(
+        &self,
+        req: AddPointsRequest,
+        loyalty: Loyalty,
+        db_member: Result<crate::ports::member::Member, crate::ports::member::Error>,
+    ) -> Result<(AddPointsResponse, Loyalty), Error> {
+        let database = self.database.clone();
+        let segment = self.segment.clone();
+        let segment_multipliers = self.segment_multipliers.clone();
+        let campaign = self.campaign.clone();
+        let manual_approval_threshold = self.manual_approval_threshold;
+        let renewal_escrow = self.renewal_escrow;
+        let maturation_schedule = self.maturation_schedule.clone();
+        let purchase_rounding = self.purchase_rounding;
+        let earning_policy = self.earning_policy.clone();
+        let holiday_calendar = self.holiday_calendar.clone();
+        let expiration_policy = self.expiration_policy.clone();
+        let regional_policy = self.regional_policy.clone();
+        let order_reconciliation = self.order_reconciliation;
+        let duplicate_orders = self.duplicate_orders.clone();
+        let welcome_bonus = self.welcome_bonus;
+        let degraded_mode = self.degraded_mode.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        let now = clock.now();
+        if let Some(purchase_amount) = req.event.purchase_amount() {
+            if purchase_amount.currency() != self.program_currency {
+                return Err(Error::InvalidState(
+                    format!(
+                        "purchases in {} are not supported, the program uses {}",
+                        purchase_amount.currency(),
+                        self.program_currency
+                    )
+                    .into(),
+                ));
+            }
+        }
+        ensure_capability(database.as_ref(), req.member_id, Capability::Earn, now).await?;
+
+        let country = db_member
+            .as_ref()
+            .ok()
+            .and_then(|db_member| db_member.country.clone());
+        let segment_tags = db_member
+            .as_ref()
+            .ok()
+            .and_then(|db_member| db_member.segment_tags.clone())
+            .unwrap_or_default();
+
+        // Resolve the member's tier
+        let (tier, tier_unverified, tier_change) = match (db_member, &degraded_mode) {
+            (Ok(db_member), _) => {
+                let evaluation = evaluate_tier(&db_member, now)?;
+                let tier = evaluation.tier.clone();
+                let tier_change = save_tier_evaluation(
+                    database.as_ref(),
+                    event_publisher.as_ref(),
+                    outbox,
+                    evaluation,
+                )
+                .await?;
+                if let Some(degraded_mode) = &degraded_mode {
+                    degraded_mode.remember_tier(req.member_id, tier.clone());
+                }
+                (tier, false, tier_change)
+            }
+            // Fall back to a degraded tier for purchases if the member port is unavailable
+            (Err(err), Some(degraded_mode))
+                if err.is_retryable() && req.event.channel().is_some() =>
+            {
+                let stored_tier = loyalty
+                    .tier
+                    .as_ref()
+                    .map(|evaluation| evaluation.tier.clone());
+                match stored_tier.or_else(|| degraded_mode.fallback_tier(req.member_id)) {
+                    Some(tier) => (tier, true, None),
+                    None => return Err(err.into()),
+                }
+            }
+            (Err(err), _) => return Err(err.into()),
+        };
+
+        let member_override = database
+            .get_member_overrides(req.member_id)
+            .await?
+            .into_iter()
+            .rev()
+            .find(|member_override| member_override.is_active(now));
+        let mut segments = match segment {
+            Some(segment) => segment.get_segments(req.member_id).await?,
+            None => Vec::new(),
+        };
+        for tag in segment_tags {
+            if !segments.contains(&tag) {
+                segments.push(tag);
+            }
+        }
+        let segment_multiplier = segments
+            .iter()
+            .filter_map(|name| segment_multipliers.get(name))
+            .copied()
+            .max()
+            .unwrap_or(1);
+        let channel_multiplier = req
+            .event
+            .channel()
+            .map(|channel| earning_policy.channel_multiplier(channel))
+            .unwrap_or(1);
+        let occurred_at = req.occurred_at.unwrap_or(now);
+        let earn_day = holiday_calendar.earn_day(occurred_at, &segments);
+        let active_campaign = match (campaign, req.event.channel()) {
+            (Some(campaign), Some(channel)) if !earn_day.is_blackout() => campaign
+                .get_active_campaigns(occurred_at)
+                .await
+                .context("fetching active campaigns")?
+                .into_iter()
+                .filter(|campaign| campaign.applies_to(channel, &segments, occurred_at))
+                .max_by_key(|campaign| campaign.multiplier),
+            _ => None,
+        };
+        let campaign_multiplier = active_campaign
+            .as_ref()
+            .map_or(1, |campaign| campaign.multiplier);
+        let earn_multiplier = earn_day
+            .earn_multiplier(segment_multiplier)
+            .saturating_mul(channel_multiplier)
+            .saturating_mul(campaign_multiplier);
+
+        // Create and store the new loyalty event
+        let earn_ratio = match &member_override {
+            Some(member_override) => i32::try_from(member_override.earn_ratio).map_err(|_| {
+                Error::InvalidState(
+                    format!(
+                        "override {} has an invalid earn ratio {}",
+                        member_override.override_id, member_override.earn_ratio
+                    )
+                    .into(),
+                )
+            })?,
+            None => earning_policy.ratio(&tier),
+        };
+        // Points are capped at `i32::MAX`, as with purchase amounts
+        let mut event = create_event(
+            id_generator.generate_id(),
+            earn_ratio.saturating_mul(earn_multiplier),
+            earning_policy.renewal_points(),
+            &req.event,
+            active_campaign.as_ref(),
+            purchase_rounding,
+            now,
+        );
+        // Points only depend on the tier if there is no override
+        if tier_unverified && member_override.is_none() {
+            event.tier_unverified =
+                req.event
+                    .purchase_amount()
+                    .map(|purchase_amount| UnverifiedTier {
+                        tier: tier.clone(),
+                        purchase_amount: purchase_amount.whole_units(purchase_rounding),
+                        earn_multiplier,
+                    });
+        }
+        event.matures_at = req
+            .event
+            .channel()
+            .and_then(|channel| maturation_schedule.matures_at(channel, now));
+        let regional_profile = regional_policy.profile(country.as_deref());
+        let expires_at = regional_profile.expires_at(expiration_policy.expires_at(&tier, now), now);
+        event.expires_at = expires_at;
+        event.disclosure = regional_profile.disclosure().map(str::to_string);
+        event.escrow_expires_at = match (&req.event, renewal_escrow) {
+            (AddPointsEvent::MembershipRenewed, Some(timeout)) => Some(now + timeout),
+            _ => None,
+        };
 
-            // Create a Member object
-            let membership_months = if db_member.active_member {
-                Some(months_since(db_member.membership_since)?)
+        // Reconcile purchases of an order that another channel already reported
+        let order_reference = req
+            .event
+            .channel()
+            .and(req.order_reference.as_deref())
+            .and_then(normalize_order_reference);
+        // Renewals in escrow keep a billing reference, which could match an order reference
+        let order_events = loyalty
+            .events
+            .iter()
+            .filter(|event| event.channel.is_some() && event.escrow_expires_at.is_none())
+            .filter(|event| event.order_reference.is_some())
+            .filter(|event| event.order_reference == order_reference)
+            .collect::<Vec<_>>();
+        let duplicate_of = order_events.first().map(|event| event.event_id);
+        let mut ignored = false;
+        if let (Some(first_event_id), Some(reference)) = (duplicate_of, &order_reference) {
+            let credited_points = order_events.iter().map(|event| event.delta_points).sum();
+            let adjustment = order_reconciliation.adjustment(credited_points, event.delta_points);
+            {
+                let mut stats = duplicate_orders
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                stats.duplicates += 1;
+                stats.adjusted += adjustment.is_some() as u64;
+            }
+            tracing::info!(
+                member_id = %req.member_id,
+                order_reference = %reference,
+                %first_event_id,
+                ?adjustment,
+                "order reported again"
+            );
+            match adjustment {
+                Some(delta_points) => {
+                    event.delta_points = delta_points;
+                    event.reason = format!("Reconciled order {}", reference);
+                    event.tier_unverified = None;
+                    if delta_points < 0 {
+                        event.matures_at = None;
+                        event.expires_at = None;
+                    }
+                }
+                None => ignored = true,
+            }
+        }
+        event.order_reference = order_reference;
+        if event.escrow_expires_at.is_some() {
+            let billing_reference = req
+                .order_reference
+                .as_deref()
+                .and_then(normalize_order_reference)
+                .ok_or_else(|| {
+                    Error::InvalidState("renewals in escrow need a billing reference".into())
+                })?;
+            event.order_reference = Some(billing_reference);
+        }
+
+        let awaiting_approval = match (&req.event, manual_approval_threshold) {
+            (AddPointsEvent::Manual { loyalty_points, .. }, Some(threshold)) => {
+                *loyalty_points > threshold
+            }
+            _ => event.escrow_expires_at.is_some(),
+        };
+        let event_id = event.event_id;
+        let loyalty_points = event.delta_points.unsigned_abs();
+        let updated_loyalty = if ignored {
+            loyalty.clone()
+        } else if awaiting_approval {
+            database
+                .register_event_for_approval(req.member_id, event)
+                .await
+                .with_context(|| {
+                    format!(
+                        "registering event for approval for member {}",
+                        req.member_id
+                    )
+                })?;
+            loyalty.clone()
+        } else {
+            let points_added = DomainEvent::PointsAdded {
+                member_id: req.member_id,
+                event_id,
+                loyalty_points,
+            };
+            // Reconciling an order can take points back, which is not an addition
+            let events = if event.delta_points >= 0 {
+                vec![points_added]
             } else {
-                None
+                Vec::new()
             };
-            let member = Member::new(db_member.member_id, membership_months, loyalty.points);
+            persist_with_events(event_publisher.as_ref(), outbox, events, |events| {
+                database.register_loyalty_event_with_outbox(
+                    req.member_id,
+                    event,
+                    Some(loyalty.version),
+                    events,
+                )
+            })
+            .await
+            .with_context(|| format!("registering event for member {}", req.member_id))?
+        };
 
-            // Create and store the new loyalty event
-            let event = create_event(&member.tier(), &req.event);
-            let updated_loyalty = database
-                .register_loyalty_event(member.member_id, event)
-                .await?;
+        // Grant the welcome bonus along with the member's first points
+        let (updated_loyalty, welcome_bonus_points) = match welcome_bonus {
+            Some(points)
+                if loyalty.events.is_empty() && !ignored && !awaiting_approval && points > 0 =>
+            {
+                let bonus = LoyaltyEvent {
+                    expires_at,
+                    ..LoyaltyEvent::new(
+                        id_generator.generate_id(),
+                        i32::try_from(points).unwrap_or(i32::MAX),
+                        "Welcome bonus",
+                        now,
+                    )
+                };
+                let points_added = DomainEvent::PointsAdded {
+                    member_id: req.member_id,
+                    event_id: bonus.event_id,
+                    loyalty_points: bonus.delta_points.unsigned_abs(),
+                };
+                let res = persist_with_events(
+                    event_publisher.as_ref(),
+                    outbox,
+                    vec![points_added],
+                    |events| database.register_welcome_bonus(req.member_id, bonus, events),
+                )
+                .await
+                .with_context(|| format!("granting welcome bonus to member {}", req.member_id));
+                match res {
+                    Ok(updated_loyalty) => (updated_loyalty, points),
+                    // A concurrent request already granted it
+                    Err(crate::ports::database::Error::WelcomeBonusAlreadyGranted(_)) => {
+                        (updated_loyalty, 0)
+                    }
+                    // The points are already stored: failing would lead callers to add them again
+                    Err(err) => {
+                        tracing::warn!(
+                            member_id = %req.member_id,
+                            error = %ErrorChain(&err),
+                            "failed to grant welcome bonus"
+                        );
+                        (updated_loyalty, 0)
+                    }
+                }
+            }
+            _ => (updated_loyalty, 0),
+        };
 
-            // Return the response
-            Ok(AddPointsResponse {
-                member_id: member.member_id,
-                tier: member.tier(),
+        // Return the response
+        Ok((
+            AddPointsResponse {
+                member_id: req.member_id,
+                tier,
+                tier_unverified,
+                tier_change,
                 old_loyalty_points: loyalty.points,
                 new_loyalty_points: updated_loyalty.points,
-            })
-        })
+                pending_loyalty_points: updated_loyalty.pending_points,
+                awaiting_approval,
+                duplicate_of,
+                welcome_bonus_points,
+            },
+            updated_loyalty,
+        ))
+    }
+}
+
+/// Store a tier evaluation, and publish the tier change if it differs from the previous one
+///
+/// With the outbox, the database stores the tier change along with the evaluation instead.
+pub(super) async fn save_tier_evaluation<D>(
+    database: &D,
+    event_publisher: &(dyn EventPublisherPort + Send + Sync),
+    outbox: bool,
+    evaluation: TierEvaluation,
+) -> Result<Option<TierChange>, Error>
+where
+    D: DatabasePort,
+{
+    let member_id = evaluation.member_id;
+    let new_tier = evaluation.tier.clone();
+    let previous = database
+        .save_tier_evaluation(evaluation, outbox)
+        .await
+        .with_context(|| format!("saving tier of member {}", member_id))?;
+
+    let change = match previous {
+        Some(previous) if previous.tier != new_tier => TierChange {
+            member_id,
+            old_tier: previous.tier,
+            new_tier,
+        },
+        _ => return Ok(None),
+    };
+    tracing::info!(
+        %member_id,
+        old_tier = ?change.old_tier,
+        new_tier = ?change.new_tier,
+        "tier changed"
+    );
+    if !outbox {
+        publish(event_publisher, DomainEvent::TierChanged(change.clone())).await;
     }
+
+    Ok(Some(change))
 }
 
-/// Months since the provided date
-fn months_since(date: DateTime<Utc>) -> Result<u32, Error> {
-    let now = Utc::now();
+/// Evaluate the member's tier from their membership at `now`
+pub(super) fn evaluate_tier(
+    db_member: &crate::ports::member::Member,
+    now: DateTime<Utc>,
+) -> Result<TierEvaluation, Error> {
+    let membership_months = membership_months(db_member, now)?;
 
-    let months = (now.year() - date.year()) * 12 + date.month() as i32 - now.month() as i32;
+    Ok(TierEvaluation {
+        member_id: db_member.member_id,
+        tier: Tier::from_membership_months(membership_months),
+        membership_months,
+        evaluated_at: now,
+    })
+}
+
+/// Number of continuous months of membership at `now`, or `None` for non-members
+pub(super) fn membership_months(
+    db_member: &crate::ports::member::Member,
+    now: DateTime<Utc>,
+) -> Result<Option<u32>, Error> {
+    if db_member.active_member {
+        months_since(db_member.membership_since, now).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Number of whole months between `date` and `now`
+///
+/// A month is complete once `now` reaches the same day of the month as `date`, or the last day of
+/// a shorter month.
+fn months_since(date: DateTime<Utc>, now: DateTime<Utc>) -> Result<u32, Error> {
+    let mut months = (now.year() - date.year()) * 12 + now.month() as i32 - date.month() as i32;
+    let month_end = (now + Duration::days(1)).month() != now.month();
+    if date <= now && now.day() < date.day() && !month_end {
+        months -= 1;
+    }
 
     if months < 0 {
         return Err(Error::InvalidState(
-            format!("start date is {} month(s) in the past", -months).into(),
+            format!("start date is {} month(s) in the future", -months).into(),
         ));
     }
 
     Ok(months as u32)
 }
 
-fn create_event(tier: &Tier, input: &AddPointsEvent) -> LoyaltyEvent {
-    const MEMBERSHIP_RENEWED_POINTS: i32 = 290;
-
+/// Create the loyalty event for the input
+///
+/// The `earn_ratio` is the number of points per currency unit on purchases, and `renewal_points`
+/// the number of points for membership renewals. The `campaign` boosting a purchase is already
+/// part of `earn_ratio`, and only recorded on the event.
+fn create_event(
+    event_id: Uuid,
+    earn_ratio: i32,
+    renewal_points: i32,
+    input: &AddPointsEvent,
+    campaign: Option<&Campaign>,
+    purchase_rounding: Rounding,
+    now: DateTime<Utc>,
+) -> LoyaltyEvent {
     let delta_points = match input {
-        AddPointsEvent::MembershipRenewed => MEMBERSHIP_RENEWED_POINTS,
+        AddPointsEvent::MembershipRenewed => renewal_points,
         AddPointsEvent::InStorePurchase { purchase_amount }
         | AddPointsEvent::OnlinePurchase { purchase_amount } => {
-            *purchase_amount as i32 * tier.ratio()
+            purchase_points(purchase_amount, earn_ratio, purchase_rounding)
         }
         AddPointsEvent::Manual { loyalty_points, .. } => *loyalty_points as i32,
     };
 
     LoyaltyEvent {
-        event_id: Uuid::new_v4(),
-        delta_points,
-        reason: input.reason().to_string(),
+        campaign_id: campaign.map(|campaign| campaign.campaign_id),
+        channel: input.channel(),
+        ..LoyaltyEvent::new(event_id, delta_points, input.reason(), now)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{adapters::database::memory::MemoryDatabase, ports::member::MockMemberPort};
-    use chrono::Duration;
+    use crate::{
+        adapters::{
+            campaign::memory::StaticCampaigns, clock::fixed::FixedClock,
+            database::memory::MemoryDatabase, event_publisher::memory::MemoryPublisher,
+            id_generator::sequential::SequentialIds, segment::memory::StaticSegments,
+        },
+        domain::{
+            EarningPolicy, ExpirationPolicy, HolidayCalendar, HolidayEffect, HolidayRule,
+            MaturationSchedule, MemberOverride, OrderReconciliation, RegionalPolicy,
+            RegionalProfile,
+        },
+        ports::{
+            database::{self, MockDatabasePort},
+            member::MockMemberPort,
+        },
+        testing::fixtures::{self, Fixture, MemberBuilder},
+    };
+    use chrono::{FixedOffset, Months, NaiveDate, TimeZone};
     use mockall::predicate::*;
     use rstest::*;
     use speculoos::prelude::*;
     use std::sync::Arc;
     use tower::{BoxError, ServiceExt};
 
+    #[rstest]
+    #[case((2023, 1, 15), (2024, 1, 14), Some(11))]
+    #[case((2023, 1, 15), (2024, 1, 15), Some(12))]
+    #[case((2023, 1, 31), (2023, 2, 27), Some(0))]
+    #[case((2023, 1, 31), (2023, 2, 28), Some(1))]
+    #[case((2024, 3, 10), (2024, 3, 1), Some(0))]
+    #[case((2024, 5, 1), (2024, 3, 1), None)]
+    fn test_months_since(
+        #[case] date: (i32, u32, u32),
+        #[case] now: (i32, u32, u32),
+        #[case] expected: Option<u32>,
+    ) {
+        let date = Utc
+            .with_ymd_and_hms(date.0, date.1, date.2, 12, 0, 0)
+            .unwrap();
+        let now = Utc.with_ymd_and_hms(now.0, now.1, now.2, 12, 0, 0).unwrap();
+
+        let res = months_since(date, now);
+
+        match expected {
+            Some(expected) => assert_that!(res).is_ok().is_equal_to(expected),
+            None => {
+                assert_that!(res).is_err();
+            }
+        }
+    }
+
     /// Test all cases that generate a static number of point irregardless of tier
     #[rstest]
     #[case(AddPointsEvent::MembershipRenewed, 290)]
@@ -162,7 +770,15 @@ mod tests {
         // GIVEN a Tier and AddPointsEvent
 
         // WHEN calling `create_event`
-        let res = create_event(&tier, &input);
+        let res = create_event(
+            Uuid::nil(),
+            tier.ratio(),
+            EarningPolicy::default().renewal_points(),
+            &input,
+            None,
+            Rounding::Down,
+            Utc::now(),
+        );
 
         // THEN it should match the expected points amount
         assert_that!(res.delta_points).is_equal_to(expected);
@@ -177,19 +793,31 @@ mod tests {
     #[case(Tier::Platinum, 20)]
     fn test_create_event_variable(
         #[case] tier: Tier,
-        #[values(AddPointsEvent::InStorePurchase { purchase_amount: 1.5 }, AddPointsEvent::OnlinePurchase { purchase_amount: 1.5 })]
+        #[values(AddPointsEvent::InStorePurchase { purchase_amount: eur(150) }, AddPointsEvent::OnlinePurchase { purchase_amount: eur(150) })]
         input: AddPointsEvent,
         #[case] expected: i32,
     ) {
         // GIVEN a Tier and AddPointsEvent
 
         // WHEN calling `create_event`
-        let res = create_event(&tier, &input);
+        let res = create_event(
+            Uuid::nil(),
+            tier.ratio(),
+            EarningPolicy::default().renewal_points(),
+            &input,
+            None,
+            Rounding::Down,
+            Utc::now(),
+        );
 
         // THEN it should match the expected points amount
         assert_that!(res.delta_points).is_equal_to(expected);
     }
 
+    fn eur(minor_units: i64) -> Money {
+        Money::new(minor_units, "EUR").unwrap()
+    }
+
     #[fixture]
     fn member_id() -> Uuid {
         Uuid::new_v4()
@@ -210,34 +838,35 @@ mod tests {
                 Ok(crate::ports::member::Member {
                     active_member: true,
                     member_id,
-                    membership_since: Utc::now() - Duration::days(700),
+                    membership_since: Utc::now() - Duration::days(800),
+                    ..Default::default()
                 })
             });
         let database = MemoryDatabase::default();
         database
             .register_loyalty_event(
                 member_id,
-                LoyaltyEvent {
-                    event_id: Uuid::new_v4(),
-                    delta_points: 305,
-                    reason: "SOME REASON".to_string(),
-                },
+                LoyaltyEvent::new(Uuid::new_v4(), 305, "SOME REASON", Utc::now()),
+                None,
             )
             .await?;
 
-        let mut domain = DomainLogic {
-            member: Arc::new(member),
-            database: Arc::new(database.clone()),
-        };
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
 
         // WHEN calling the service
         let req = AddPointsRequest {
             event: AddPointsEvent::InStorePurchase {
-                purchase_amount: 3.65,
+                purchase_amount: eur(365),
             },
             member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
         };
-        let res = domain.ready().await?.call(req).await;
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
 
         // THEN
         // * It returns a valid response
@@ -245,11 +874,1180 @@ mod tests {
         assert_that!(res).is_ok().is_equal_to(AddPointsResponse {
             member_id,
             tier: Tier::Gold,
+            tier_unverified: false,
+            tier_change: None,
             old_loyalty_points: 305,
             new_loyalty_points: 350,
+            pending_loyalty_points: 0,
+            awaiting_approval: false,
+            duplicate_of: None,
+            welcome_bonus_points: 0,
         });
         Arc::into_inner(domain.member).unwrap().checkpoint();
 
         Ok(())
     }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_segment_multiplier() -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member in the `student` segment
+        // * students earn twice the points
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::gold().build().await?;
+        let segments = StaticSegments::default().with_member(member_id, ["student", "newsletter"]);
+        let mut domain = domain.with_segments(
+            Arc::new(segments),
+            [("student".to_string(), 2), ("senior".to_string(), 3)],
+        );
+
+        // WHEN calling the service with a purchase
+        let req = AddPointsRequest {
+            event: AddPointsEvent::OnlinePurchase {
+                purchase_amount: eur(300),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN the student multiplier is applied
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(90);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    #[tokio::test]
+    async fn test_call_event_publisher(
+        member_id: Uuid,
+        #[case] outbox: bool,
+    ) -> Result<(), BoxError> {
+        // GIVEN a Gold member whose stored tier is Silver, with or without the outbox
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        database
+            .save_tier_evaluation(
+                TierEvaluation {
+                    member_id,
+                    tier: Tier::Silver,
+                    membership_months: Some(23),
+                    evaluated_at: Utc::now() - Duration::days(30),
+                },
+                false,
+            )
+            .await?;
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_id_generator(Arc::new(SequentialIds::default()))
+            .with_event_publisher(Arc::new(event_publisher.clone()));
+        if outbox {
+            domain = domain.with_outbox();
+        }
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event: AddPointsEvent::MembershipRenewed,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await?;
+
+        // THEN
+        // * the response includes the tier change
+        // * both the tier change and the added points are published, or stored in the outbox
+        let tier_change = TierChange {
+            member_id,
+            old_tier: Tier::Silver,
+            new_tier: Tier::Gold,
+        };
+        assert_that!(res.tier_change).is_equal_to(Some(tier_change.clone()));
+        let expected = vec![
+            DomainEvent::TierChanged(tier_change),
+            DomainEvent::PointsAdded {
+                member_id,
+                event_id: Uuid::from_u128(1),
+                loyalty_points: 290,
+            },
+        ];
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        if outbox {
+            assert_that!(event_publisher.events()).is_empty();
+            assert_that!(outbox_events).is_equal_to(expected);
+        } else {
+            assert_that!(event_publisher.events()).is_equal_to(expected);
+            assert_that!(outbox_events).is_empty();
+        }
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_member_override(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member
+        // * an expired override, superseded by an active override
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        for (earn_ratio, expires_at) in [
+            (50, Utc::now() - Duration::days(1)),
+            (30, Utc::now() + Duration::days(1)),
+        ] {
+            database
+                .register_member_override(MemberOverride {
+                    override_id: Uuid::new_v4(),
+                    member_id,
+                    earn_ratio,
+                    reason: "SOME REASON".to_string(),
+                    granted_by: "SOME AGENT".to_string(),
+                    granted_at: Utc::now() - Duration::days(2),
+                    expires_at,
+                })
+                .await?;
+        }
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member));
+
+        // WHEN calling the service with a purchase
+        let req = AddPointsRequest {
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: eur(300),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN the active override ratio replaces the tier ratio
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(90);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(u32::MAX, None)]
+    #[case(i32::MAX as u32, Some(i32::MAX as u32))]
+    #[tokio::test]
+    async fn test_call_member_override_overflow(
+        member_id: Uuid,
+        #[case] earn_ratio: u32,
+        #[case] expected: Option<u32>,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member earning twice the points online
+        // * an override with a huge earn ratio
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        database
+            .register_member_override(MemberOverride {
+                override_id: Uuid::new_v4(),
+                member_id,
+                earn_ratio,
+                reason: "SOME REASON".to_string(),
+                granted_by: "SOME AGENT".to_string(),
+                granted_at: Utc::now() - Duration::days(2),
+                expires_at: Utc::now() + Duration::days(1),
+            })
+            .await?;
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member))
+            .with_earning_policy(
+                EarningPolicy::default().with_channel_multiplier(Channel::Online, 2),
+            );
+
+        // WHEN calling the service with a purchase
+        let req = AddPointsRequest {
+            event: AddPointsEvent::OnlinePurchase {
+                purchase_amount: eur(100),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN ratios that do not fit are rejected, and points are capped instead of wrapping
+        match expected {
+            Some(expected) => {
+                assert_that!(res)
+                    .is_ok()
+                    .map(|res| &res.new_loyalty_points)
+                    .is_equal_to(expected);
+            }
+            None => {
+                assert_that!(res)
+                    .is_err()
+                    .matches(|err| matches!(err, Error::InvalidState(_)));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(1000, false, 1000)]
+    #[case(1001, true, 0)]
+    #[tokio::test]
+    async fn test_call_manual_approval(
+        #[case] loyalty_points: u32,
+        #[case] awaiting_approval: bool,
+        #[case] expected: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN manual additions above 1000 points require approval
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain.with_manual_approval_threshold(1000);
+
+        // WHEN manually adding points
+        let req = AddPointsRequest {
+            event: AddPointsEvent::Manual {
+                loyalty_points,
+                reason: None,
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN points are only added when below the threshold
+        assert_that!(res).is_ok().matches(|res| {
+            res.awaiting_approval == awaiting_approval && res.new_loyalty_points == expected
+        });
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(expected);
+        let events = database.get_events_awaiting_approval(member_id).await?;
+        assert_that!(events).has_length(awaiting_approval as usize);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(27, 45)]
+    #[case(12, 270)]
+    #[case(13, 90)]
+    #[tokio::test]
+    async fn test_call_holiday_calendar(
+        member_id: Uuid,
+        #[case] day: u32,
+        #[case] expected: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * a member in the `student` segment, earning twice the points
+        // * no bonus on Black Friday, and triple points on member day
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let segments = StaticSegments::default().with_member(member_id, ["student"]);
+        let rule = |name: &str, day, effect| HolidayRule {
+            name: name.to_string(),
+            date: NaiveDate::from_ymd_opt(2026, 11, day).unwrap(),
+            segment: None,
+            effect,
+        };
+        let calendar = HolidayCalendar::new(
+            FixedOffset::east_opt(0).unwrap(),
+            [
+                rule("Black Friday", 27, HolidayEffect::Blackout),
+                rule("Member day", 12, HolidayEffect::Boost(3)),
+            ],
+        )?;
+        let mut domain = DomainLogic::new(Arc::new(MemoryDatabase::default()), Arc::new(member))
+            .with_segments(Arc::new(segments), [("student".to_string(), 2)])
+            .with_holiday_calendar(calendar);
+
+        // WHEN calling the service with a purchase made on a given day
+        let req = AddPointsRequest {
+            event: AddPointsEvent::OnlinePurchase {
+                purchase_amount: eur(300),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: NaiveDate::from_ymd_opt(2026, 11, day)
+                .and_then(|date| date.and_hms_opt(12, 0, 0))
+                .map(|date| date.and_utc()),
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN the day's rules apply on top of the segment multiplier
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(AddPointsEvent::InStorePurchase { purchase_amount: eur(300) }, 90)]
+    #[case(AddPointsEvent::OnlinePurchase { purchase_amount: eur(300) }, 180)]
+    #[case(AddPointsEvent::MembershipRenewed, 500)]
+    #[tokio::test]
+    async fn test_call_earning_policy(
+        #[case] event: AddPointsEvent,
+        #[case] expected: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN gold members earn 30 points per unit, doubled online, and 500 points on renewal
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::gold().build().await?;
+        let mut domain = domain.with_earning_policy(
+            EarningPolicy::default()
+                .with_tier_ratio(Tier::Gold, 30)
+                .with_channel_multiplier(Channel::Online, 2)
+                .with_renewal_points(500),
+        );
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN the points follow the policy
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(AddPointsEvent::OnlinePurchase { purchase_amount: eur(300) }, 90, Some(1))]
+    #[case(AddPointsEvent::InStorePurchase { purchase_amount: eur(300) }, 135, Some(2))]
+    #[case(AddPointsEvent::MembershipRenewed, 290, None)]
+    #[tokio::test]
+    async fn test_call_campaigns(
+        #[case] event: AddPointsEvent,
+        #[case] expected: u32,
+        #[case] expected_campaign: Option<u128>,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member
+        // * double points online and triple points in store, running now
+        // * five times the points, starting tomorrow
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::gold().build().await?;
+        let campaign = |id, channel, multiplier, starts_at| Campaign {
+            campaign_id: Uuid::from_u128(id),
+            name: format!("{}x points", multiplier),
+            starts_at,
+            ends_at: fixtures::now() + Duration::days(30),
+            channel,
+            segment: None,
+            multiplier,
+        };
+        let campaigns = StaticCampaigns::default()
+            .with_campaign(campaign(1, Some(Channel::Online), 2, fixtures::now()))
+            .with_campaign(campaign(2, Some(Channel::InStore), 3, fixtures::now()))
+            .with_campaign(campaign(3, None, 5, fixtures::now() + Duration::days(1)));
+        let mut domain = domain.with_campaigns(Arc::new(campaigns));
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN purchases are boosted by the running campaign for their channel, which is recorded
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(expected);
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events[0].campaign_id).is_equal_to(expected_campaign.map(Uuid::from_u128));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(Some("student"), None, 300, Some(1))]
+    #[case(None, Some("student"), 300, Some(1))]
+    #[case(Some("newsletter"), None, 150, None)]
+    #[case(None, None, 150, None)]
+    #[tokio::test]
+    async fn test_call_campaign_segment(
+        #[case] member_tag: Option<&str>,
+        #[case] port_segment: Option<&str>,
+        #[case] expected: u32,
+        #[case] expected_campaign: Option<u128>,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member, with segments from the member port or the segment port
+        // * double points for students, running now
+        let mut builder = MemberBuilder::gold();
+        if let Some(tag) = member_tag {
+            builder = builder.with_segment_tags([tag]);
+        }
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = builder.build().await?;
+        let campaigns = StaticCampaigns::default().with_campaign(Campaign {
+            campaign_id: Uuid::from_u128(1),
+            name: "Double points for students".to_string(),
+            starts_at: fixtures::now(),
+            ends_at: fixtures::now() + Duration::days(30),
+            channel: None,
+            segment: Some("student".to_string()),
+            multiplier: 2,
+        });
+        let segments = match port_segment {
+            Some(segment) => StaticSegments::default().with_member(member_id, [segment]),
+            None => StaticSegments::default(),
+        };
+        let mut domain = domain
+            .with_campaigns(Arc::new(campaigns))
+            .with_segments(Arc::new(segments), []);
+
+        // WHEN adding points for a purchase
+        let req = AddPointsRequest {
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: eur(1000),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN only members of the segment get the campaign's boost
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(expected);
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events[0].campaign_id).is_equal_to(expected_campaign.map(Uuid::from_u128));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(Rounding::Down, 30)]
+    #[case(Rounding::HalfUp, 40)]
+    #[case(Rounding::Up, 40)]
+    #[tokio::test]
+    async fn test_call_purchase_rounding(
+        #[case] rounding: Rounding,
+        #[case] expected: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN a basic member, earning 10 points per unit
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain.with_purchase_rounding(rounding);
+
+        // WHEN adding points for a purchase of 3.65
+        let req = AddPointsRequest {
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: eur(365),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN the amount is rounded to whole units before earning points
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("EUR", "EUR", true)]
+    #[case("JPY", "EUR", false)]
+    #[case("USD", "USD", true)]
+    #[tokio::test]
+    async fn test_call_program_currency(
+        #[case] purchase_currency: &str,
+        #[case] program_currency: &str,
+        #[case] expect_ok: bool,
+    ) -> Result<(), BoxError> {
+        // GIVEN a basic member, in a program using a given currency
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain.with_program_currency(program_currency);
+
+        // WHEN adding points for a purchase in a given currency
+        let req = AddPointsRequest {
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: Money::new(1000, purchase_currency)?,
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN purchases in other currencies are rejected without earning points
+        if expect_ok {
+            assert_that!(res).is_ok();
+        } else {
+            assert_that!(res)
+                .is_err()
+                .matches(|err| matches!(err, Error::InvalidState(_)));
+            let loyalty = database.get_loyalty_points(member_id).await?;
+            assert_that!(loyalty.events).is_empty();
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_created_at() -> Result<(), BoxError> {
+        // GIVEN a purchase that happened the day before
+        let Fixture {
+            member_id,
+            database,
+            mut domain,
+        } = MemberBuilder::basic().build().await?;
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: eur(1000),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: Some(fixtures::now() - Duration::days(1)),
+            order_reference: None,
+        };
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await?;
+
+        // THEN the event is timestamped with the clock, not when the purchase happened
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.events).has_length(1);
+        assert_that!(loyalty.events[0].created_at).is_equal_to(fixtures::now());
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(AddPointsEvent::InStorePurchase { purchase_amount: eur(1000) }, 100, 0)]
+    #[case(AddPointsEvent::OnlinePurchase { purchase_amount: eur(1000) }, 0, 100)]
+    #[case(AddPointsEvent::MembershipRenewed, 290, 0)]
+    #[tokio::test]
+    async fn test_call_maturation_schedule(
+        #[case] event: AddPointsEvent,
+        #[case] expected_points: u32,
+        #[case] expected_pending: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN online purchases mature after 14 days, and in-store ones immediately
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain.with_maturation_schedule(
+            MaturationSchedule::default()
+                .with_delay(Channel::InStore, Duration::zero())
+                .with_delay(Channel::Online, Duration::days(14)),
+        );
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN only online purchases are pending
+        assert_that!(res).is_ok().matches(|res| {
+            res.new_loyalty_points == expected_points
+                && res.pending_loyalty_points == expected_pending
+        });
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(Tier::Basic, None)]
+    #[case(Tier::Gold, Some(fixtures::now() + Duration::days(365)))]
+    #[tokio::test]
+    async fn test_call_expiration_policy(
+        #[case] tier: Tier,
+        #[case] expected_expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(), BoxError> {
+        // GIVEN points expire after a year, except in the Basic tier
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::tier(tier).build().await?;
+        let mut domain = domain.with_expiration_policy(
+            ExpirationPolicy::new(Duration::days(365)).with_tier(Tier::Basic, None),
+        );
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event: AddPointsEvent::MembershipRenewed,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await?;
+
+        // THEN the points only expire outside of the Basic tier
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.events[0].expires_at).is_equal_to(expected_expires_at);
+        assert_that!(loyalty.expiring_lots.len())
+            .is_equal_to(expected_expires_at.is_some() as usize);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(None, Some(fixtures::now() + Duration::days(365)), None)]
+    #[case(Some("FR"), None, Some("Points do not expire in France"))]
+    #[case(Some("BE"), Some(fixtures::now() + Months::new(24)), None)]
+    #[tokio::test]
+    async fn test_call_regional_policy(
+        #[case] country: Option<&str>,
+        #[case] expected_expires_at: Option<DateTime<Utc>>,
+        #[case] expected_disclosure: Option<&str>,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * points expire after a year
+        // * points never expire in France, and last at least two years in Belgium
+        let mut builder = MemberBuilder::basic();
+        if let Some(country) = country {
+            builder = builder.with_country(country);
+        }
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = builder.build().await?;
+        let mut domain = domain
+            .with_expiration_policy(ExpirationPolicy::new(Duration::days(365)))
+            .with_regional_policy(
+                RegionalPolicy::default()
+                    .with_region(
+                        "FR",
+                        RegionalProfile::default()
+                            .without_expiry()
+                            .with_disclosure("Points do not expire in France"),
+                    )
+                    .with_region(
+                        "BE",
+                        RegionalProfile::default().with_min_validity_months(24),
+                    ),
+            );
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event: AddPointsEvent::MembershipRenewed,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await?;
+
+        // THEN the expiration date and disclosure follow the member's region
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events[0].expires_at).is_equal_to(expected_expires_at);
+        assert_that!(events[0].disclosure.as_deref()).is_equal_to(expected_disclosure);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(OrderReconciliation::KeepFirst, 1500, 3000, 150, 0)]
+    #[case(OrderReconciliation::KeepHigher, 1500, 3000, 300, 1)]
+    #[case(OrderReconciliation::KeepHigher, 3000, 1500, 300, 0)]
+    #[case(OrderReconciliation::KeepLower, 3000, 1500, 150, 1)]
+    #[tokio::test]
+    async fn test_call_duplicate_order(
+        #[case] reconciliation: OrderReconciliation,
+        #[case] web_amount: i64,
+        #[case] erp_amount: i64,
+        #[case] expected_points: u32,
+        #[case] expected_adjusted: u64,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member who earned points on an order from the web checkout
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain.with_order_reconciliation(reconciliation);
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::OnlinePurchase {
+                    purchase_amount: eur(web_amount),
+                },
+                occurred_at: None,
+                order_reference: Some("ord-2024-0042".to_string()),
+                idempotency_key: None,
+            })
+            .await?;
+        let first_event_id = database.get_loyalty_events(member_id).await?[0].event_id;
+
+        // WHEN the ERP feed reports the same order, formatted differently
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::InStorePurchase {
+                    purchase_amount: eur(erp_amount),
+                },
+                occurred_at: None,
+                order_reference: Some("ORD 2024 0042".to_string()),
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN
+        // * the order earns points once, following the reconciliation preference
+        // * the duplicate is counted in the statistics
+        assert_that!(res).is_ok().matches(|res| {
+            res.duplicate_of == Some(first_event_id) && res.new_loyalty_points == expected_points
+        });
+        assert_that!(database.get_loyalty_points(member_id).await?.points)
+            .is_equal_to(expected_points);
+        assert_that!(domain.duplicate_order_stats()).is_equal_to(DuplicateOrderStats {
+            duplicates: 1,
+            adjusted: expected_adjusted,
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_order_matching_renewal() -> Result<(), BoxError> {
+        // GIVEN a member with a renewal from escrow, whose billing reference looks like an order
+        let Fixture {
+            member_id,
+            database,
+            mut domain,
+        } = MemberBuilder::basic().build().await?;
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent {
+                    order_reference: normalize_order_reference("ORD-2024-0042"),
+                    escrow_expires_at: Some(fixtures::now() + Duration::days(3)),
+                    ..LoyaltyEvent::new(Uuid::new_v4(), 500, "Membership renewed", fixtures::now())
+                },
+                None,
+            )
+            .await?;
+
+        // WHEN a purchase is reported with the same reference
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::OnlinePurchase {
+                    purchase_amount: eur(1000),
+                },
+                occurred_at: None,
+                order_reference: Some("ord-2024-0042".to_string()),
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN the purchase earns its points, as the renewal is not an order
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.duplicate_of.is_none() && res.new_loyalty_points == 600);
+        assert_that!(domain.duplicate_order_stats()).is_equal_to(DuplicateOrderStats::default());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_points_stale_order() -> Result<(), BoxError> {
+        // GIVEN a member whose loyalty was read before another channel reported an order
+        let builder = MemberBuilder::basic();
+        let db_member = builder.member();
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = builder.build().await?;
+        let stale_loyalty = database.get_loyalty_points(member_id).await?;
+        let mut service = domain.clone();
+        ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::OnlinePurchase {
+                    purchase_amount: eur(1500),
+                },
+                occurred_at: None,
+                order_reference: Some("ORD-1".to_string()),
+                idempotency_key: None,
+            })
+            .await?;
+
+        // WHEN reporting the same order from the stale loyalty
+        let res = domain
+            .add_points(
+                AddPointsRequest {
+                    member_id,
+                    event: AddPointsEvent::InStorePurchase {
+                        purchase_amount: eur(1500),
+                    },
+                    occurred_at: None,
+                    order_reference: Some("ORD-1".to_string()),
+                    idempotency_key: None,
+                },
+                stale_loyalty,
+                Ok(db_member),
+            )
+            .await;
+
+        // THEN
+        // * it fails with a conflict, to start over with fresh data
+        // * the order only earned points once
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Database(crate::ports::database::Error::Conflict { .. })
+            )
+        });
+        assert_that!(database.get_loyalty_points(member_id).await?.points).is_equal_to(150);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_welcome_bonus() -> Result<(), BoxError> {
+        // GIVEN a member without any points, with a welcome bonus of 500 points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().build().await?;
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = domain
+            .with_event_publisher(Arc::new(event_publisher.clone()))
+            .with_welcome_bonus(500);
+
+        // WHEN adding points twice
+        let mut responses = Vec::new();
+        for _ in 0..2 {
+            let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+                .await?
+                .call(AddPointsRequest {
+                    member_id,
+                    event: AddPointsEvent::OnlinePurchase {
+                        purchase_amount: eur(1500),
+                    },
+                    occurred_at: None,
+                    order_reference: None,
+                    idempotency_key: None,
+                })
+                .await?;
+            responses.push(res);
+        }
+
+        // THEN only the first points come with the bonus
+        assert_that!(responses[0])
+            .matches(|res| res.welcome_bonus_points == 500 && res.new_loyalty_points == 650);
+        assert_that!(responses[1])
+            .matches(|res| res.welcome_bonus_points == 0 && res.new_loyalty_points == 800);
+        assert_that!(database.get_loyalty_events(member_id).await?).has_length(3);
+        assert_that!(event_publisher.events()).has_length(3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_welcome_bonus_failure() -> Result<(), BoxError> {
+        // GIVEN a member without any points, and a database failing to store the welcome bonus
+        let member = MemberBuilder::basic().member();
+        let member_id = member.member_id;
+        let mut member_port = MockMemberPort::new();
+        member_port
+            .expect_get_member()
+            .returning(move |_| Ok(member.clone()));
+        let mut database = MockDatabasePort::new();
+        database
+            .expect_get_loyalty_points()
+            .returning(move |member_id| Ok(Loyalty::new(member_id)));
+        database
+            .expect_get_member_restrictions()
+            .returning(|_| Ok(Vec::new()));
+        database
+            .expect_save_tier_evaluation()
+            .returning(|_, _| Ok(None));
+        database
+            .expect_get_member_overrides()
+            .returning(|_| Ok(Vec::new()));
+        database
+            .expect_register_loyalty_event_with_outbox()
+            .times(1)
+            .returning(|member_id, event, _, _| {
+                Ok(Loyalty {
+                    points: event.delta_points.unsigned_abs(),
+                    ..Loyalty::new(member_id)
+                })
+            });
+        database
+            .expect_register_welcome_bonus()
+            .times(1)
+            .returning(|_, _, _| Err(database::Error::Unavailable("timeout".into())));
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member_port))
+            .with_clock(Arc::new(FixedClock::new(fixtures::now())))
+            .with_welcome_bonus(500);
+
+        // WHEN adding points
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::OnlinePurchase {
+                    purchase_amount: eur(1500),
+                },
+                occurred_at: None,
+                order_reference: None,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN the points are added without the bonus, rather than failing the stored request
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.welcome_bonus_points == 0 && res.new_loyalty_points == 150);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_degraded_mode(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member, with the member service going down after the first call
+        // * degraded mode defaulting to the Basic tier
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().times(1).returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        member
+            .expect_get_member()
+            .returning(|_| Err(crate::ports::member::Error::Unavailable("timeout".into())));
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_degraded_mode(Some(Tier::Basic));
+        let purchase = |member_id| AddPointsRequest {
+            event: AddPointsEvent::InStorePurchase {
+                purchase_amount: eur(100),
+            },
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+
+        // WHEN making purchases
+        // * for the Gold member while the service is up
+        // * for the Gold member while the service is down
+        // * for an unknown member while the service is down
+        let mut results = Vec::new();
+        for member_id in [member_id, member_id, Uuid::new_v4()] {
+            let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+                .await?
+                .call(purchase(member_id))
+                .await?;
+            results.push((res.tier, res.tier_unverified));
+        }
+
+        // THEN
+        // * the last-known tier is used for the Gold member
+        // * the default tier is used for the unknown member
+        // * events based on a fallback tier are unverified
+        assert_that!(results).is_equal_to(vec![
+            (Tier::Gold, false),
+            (Tier::Gold, true),
+            (Tier::Basic, true),
+        ]);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty
+            .events
+            .iter()
+            .map(|event| event.tier_unverified.clone())
+            .collect::<Vec<_>>())
+        .is_equal_to(vec![
+            None,
+            Some(UnverifiedTier {
+                tier: Tier::Gold,
+                purchase_amount: 1,
+                earn_multiplier: 1,
+            }),
+        ]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(AddPointsEvent::InStorePurchase { purchase_amount: eur(100) }, crate::ports::member::Error::MemberDoesNotExist(Uuid::nil()))]
+    #[case(AddPointsEvent::MembershipRenewed, crate::ports::member::Error::Unavailable("timeout".into()))]
+    #[tokio::test]
+    async fn test_call_degraded_mode_error(
+        member_id: Uuid,
+        #[case] event: AddPointsEvent,
+        #[case] error: crate::ports::member::Error,
+    ) -> Result<(), BoxError> {
+        // GIVEN degraded mode enabled, but a fatal error or a non-purchase event
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().return_once(move |_| Err(error));
+        let mut domain = DomainLogic::new(Arc::new(MemoryDatabase::default()), Arc::new(member))
+            .with_degraded_mode(Some(Tier::Basic));
+
+        // WHEN adding points
+        let req = AddPointsRequest {
+            event,
+            member_id,
+            idempotency_key: None,
+            occurred_at: None,
+            order_reference: None,
+        };
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN the member port error is returned
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::Member(_)));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,215 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{CaseLock, EventNote},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{foreign_case_lock, DomainLogic, Error};
+
+/// Attach an internal support note to a loyalty event
+///
+/// The event can be registered or awaiting approval.
+pub struct AnnotateEventRequest {
+    pub member_id: Uuid,
+    pub event_id: Uuid,
+    pub note: String,
+    /// Support agent writing the note
+    pub actor: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnotateEventResponse {
+    pub note: EventNote,
+    /// Warning set when another agent holds a case lock on the member
+    pub case_lock: Option<CaseLock>,
+}
+
+impl<D, M> Service<AnnotateEventRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = AnnotateEventResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: AnnotateEventRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let case_lock = self.case_lock.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            if req.note.trim().is_empty() {
+                return Err(Error::InvalidState("note is empty".into()));
+            }
+
+            // Make sure the event exists
+            let events = database
+                .get_loyalty_events(req.member_id)
+                .await
+                .with_context(|| format!("fetching events for member {}", req.member_id))?;
+            let awaiting_approval = database
+                .get_events_awaiting_approval(req.member_id)
+                .await
+                .with_context(|| {
+                    format!(
+                        "fetching events awaiting approval for member {}",
+                        req.member_id
+                    )
+                })?;
+            if !events
+                .iter()
+                .chain(&awaiting_approval)
+                .any(|event| event.event_id == req.event_id)
+            {
+                return Err(crate::ports::database::Error::EventDoesNotExist(req.event_id).into());
+            }
+
+            let case_lock =
+                foreign_case_lock(case_lock, req.member_id, &req.actor, clock.now()).await;
+            let note = EventNote {
+                note_id: id_generator.generate_id(),
+                member_id: req.member_id,
+                event_id: req.event_id,
+                note: req.note,
+                actor: req.actor,
+                created_at: clock.now(),
+            };
+            database
+                .register_event_note(note.clone())
+                .await
+                .with_context(|| format!("registering note for event {}", req.event_id))?;
+
+            Ok(AnnotateEventResponse { note, case_lock })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{case_lock::memory::MemoryCaseLocks, database::memory::MemoryDatabase},
+        commands::acquire_case_lock::AcquireCaseLockRequest,
+        domain::LoyaltyEvent,
+        ports::member::MockMemberPort,
+    };
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn request(member_id: Uuid, event_id: Uuid, note: &str) -> AnnotateEventRequest {
+        AnnotateEventRequest {
+            member_id,
+            event_id,
+            note: note.to_string(),
+            actor: "agent-42".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with an event
+        let member_id = Uuid::new_v4();
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(event_id, 100, "SOME REASON", Utc::now()),
+                None,
+            )
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN annotating the event, an unknown event, and with an empty note
+        let mut results = Vec::new();
+        for req in [
+            request(member_id, event_id, "Customer called, explained promo"),
+            request(
+                member_id,
+                Uuid::new_v4(),
+                "Customer called, explained promo",
+            ),
+            request(member_id, event_id, " "),
+        ] {
+            let res = ServiceExt::<AnnotateEventRequest>::ready(&mut domain)
+                .await?
+                .call(req)
+                .await;
+            results.push(res);
+        }
+
+        // THEN only the first note is stored, without changing the event
+        assert_that!(results[0]).is_ok();
+        assert_that!(results[1]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Database(crate::ports::database::Error::EventDoesNotExist(_))
+            )
+        });
+        assert_that!(results[2])
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let notes = database.get_event_notes(member_id).await?;
+        assert_that!(notes).has_length(1);
+        assert_that!(notes[0].event_id).is_equal_to(event_id);
+        assert_that!(notes[0].note.as_str()).is_equal_to("Customer called, explained promo");
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events[0].reason.as_str()).is_equal_to("SOME REASON");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_locked() -> Result<(), BoxError> {
+        // GIVEN a member with an event, locked by another agent
+        let member_id = Uuid::new_v4();
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(event_id, 100, "SOME REASON", Utc::now()),
+                None,
+            )
+            .await?;
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(MockMemberPort::new()))
+            .with_case_locks(Arc::new(MemoryCaseLocks::default()), Duration::minutes(15));
+        ServiceExt::<AcquireCaseLockRequest>::ready(&mut domain)
+            .await?
+            .call(AcquireCaseLockRequest {
+                member_id,
+                holder: "agent-7".to_string(),
+            })
+            .await?;
+
+        // WHEN annotating the event
+        let res = ServiceExt::<AnnotateEventRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, event_id, "Customer called"))
+            .await;
+
+        // THEN the note is stored with a warning about the lock
+        assert_that!(res).is_ok().matches(|res| {
+            res.case_lock
+                .as_ref()
+                .is_some_and(|case_lock| case_lock.holder == "agent-7")
+        });
+
+        Ok(())
+    }
+}
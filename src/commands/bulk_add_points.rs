@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::ports::{
+    database::DatabasePort,
+    member::{Member, MemberPort},
+    ResultExt,
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{
+    add_points::{AddPointsRequest, AddPointsResponse},
+    DomainLogic, Error,
+};
+
+/// Add points for many requests at once, e.g. from a nightly import of purchases
+///
+/// Requests are grouped per member and processed in order. Loyalties and members are fetched for
+/// all members at once, rather than for every request. Each request succeeds or fails on its own,
+/// as with [`AddPointsRequest`]: a failure does not stop the rest of the batch.
+///
+/// Idempotency keys are not supported for requests in a batch: requests with one fail.
+pub struct BulkAddPointsRequest {
+    pub requests: Vec<AddPointsRequest>,
+}
+
+#[derive(Debug)]
+pub struct BulkAddPointsResponse {
+    /// Result of each request, in the same order as the requests
+    pub results: Vec<Result<AddPointsResponse, Error>>,
+}
+
+impl<D, M> Service<BulkAddPointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = BulkAddPointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: BulkAddPointsRequest) -> Self::Future {
+        let domain = self.clone();
+        Box::pin(async move {
+            // Group requests per member, keeping their position in the batch
+            let mut groups: Vec<(Uuid, Vec<(usize, AddPointsRequest)>)> = Vec::new();
+            let mut group_indexes = HashMap::new();
+            for (index, request) in req.requests.into_iter().enumerate() {
+                let group_index = *group_indexes.entry(request.member_id).or_insert_with(|| {
+                    groups.push((request.member_id, Vec::new()));
+                    groups.len() - 1
+                });
+                groups[group_index].1.push((index, request));
+            }
+
+            // Fall back to fetching loyalties one by one if the batch fails
+            let member_ids = groups
+                .iter()
+                .map(|(member_id, _)| *member_id)
+                .collect::<Vec<_>>();
+            let loyalties = match domain.database.get_loyalty_points_batch(&member_ids).await {
+                Ok(loyalties) => loyalties.into_iter().map(Some).collect(),
+                Err(err) => {
+                    tracing::warn!(%err, "failed to fetch loyalties in batch");
+                    vec![None; member_ids.len()]
+                }
+            };
+            let members: Vec<Option<_>> = match domain.member.get_members(&member_ids).await {
+                Ok(members) if members.len() == member_ids.len() => {
+                    members.into_iter().map(Some).collect()
+                }
+                Ok(members) => {
+                    tracing::warn!(
+                        expected = member_ids.len(),
+                        actual = members.len(),
+                        "unexpected number of members in batch"
+                    );
+                    member_ids.iter().map(|_| None).collect()
+                }
+                Err(err) => {
+                    tracing::warn!(%err, "failed to fetch members in batch");
+                    member_ids.iter().map(|_| None).collect()
+                }
+            };
+
+            let mut results = Vec::new();
+            for (((member_id, requests), mut loyalty), mut fetched_member) in
+                groups.into_iter().zip(loyalties).zip(members)
+            {
+                // Keep the member's data between requests, and fetch it again after failures
+                let mut db_member: Option<Member> = None;
+                for (index, request) in requests {
+                    if request.idempotency_key.is_some() {
+                        results.push((
+                            index,
+                            Err(Error::InvalidState(
+                                "idempotency keys are not supported in bulk requests".into(),
+                            )),
+                        ));
+                        continue;
+                    }
+                    let current_loyalty = match loyalty.take() {
+                        Some(loyalty) => Ok(loyalty),
+                        None => domain
+                            .database
+                            .get_loyalty_points(member_id)
+                            .await
+                            .with_context(|| format!("fetching loyalty for member {}", member_id))
+                            .map_err(Error::from),
+                    };
+                    let current_member = match (&db_member, fetched_member.take()) {
+                        (Some(db_member), _) => Ok(db_member.clone()),
+                        (None, Some(fetched_member)) => fetched_member,
+                        (None, None) => domain.member.get_member(member_id).await,
+                    }
+                    .with_context(|| format!("fetching member {}", member_id));
+                    if let Ok(current_member) = &current_member {
+                        db_member = Some(current_member.clone());
+                    }
+
+                    let res = match current_loyalty {
+                        Ok(current_loyalty) => domain
+                            .add_points(request, current_loyalty, current_member)
+                            .await
+                            .map(|(response, updated_loyalty)| {
+                                loyalty = Some(updated_loyalty);
+                                response
+                            }),
+                        Err(err) => Err(err),
+                    };
+                    if let Err(err) = &res {
+                        tracing::warn!(%member_id, index, %err, "failed to add points");
+                        db_member = None;
+                    }
+                    results.push((index, res));
+                }
+            }
+            results.sort_by_key(|(index, _)| *index);
+
+            Ok(BulkAddPointsResponse {
+                results: results.into_iter().map(|(_, res)| res).collect(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{clock::fixed::FixedClock, database::memory::MemoryDatabase},
+        commands::add_points::AddPointsEvent,
+        domain::Money,
+        ports::member::MockMemberPort,
+        testing::fixtures,
+    };
+    use mockall::predicate::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn purchase(member_id: Uuid, order_reference: &str) -> AddPointsRequest {
+        AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::OnlinePurchase {
+                purchase_amount: Money::new(1500, "EUR").unwrap(),
+            },
+            occurred_at: None,
+            order_reference: Some(order_reference.to_string()),
+            idempotency_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN
+        // * a member, and an unknown member
+        // * members are looked up in a single batch
+        let member_id = Uuid::new_v4();
+        let unknown_member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_members()
+            .times(1)
+            .withf(move |member_ids| member_ids == [member_id, unknown_member_id])
+            .returning(move |_| {
+                Ok(vec![
+                    Ok(Member {
+                        member_id,
+                        active_member: true,
+                        membership_since: fixtures::now(),
+                        ..Default::default()
+                    }),
+                    Err(crate::ports::member::Error::MemberDoesNotExist(
+                        unknown_member_id,
+                    )),
+                ])
+            });
+        member.expect_get_member().never();
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_clock(Arc::new(FixedClock::new(fixtures::now())));
+
+        // WHEN adding points for three purchases, two of which are the same order
+        let res = ServiceExt::<BulkAddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(BulkAddPointsRequest {
+                requests: vec![
+                    purchase(member_id, "A-1"),
+                    purchase(unknown_member_id, "B-1"),
+                    purchase(member_id, "a1"),
+                ],
+            })
+            .await?;
+
+        // THEN
+        // * the results follow the order of the requests
+        // * the unknown member fails without affecting the others
+        // * requests see the changes of earlier requests for the same member
+        assert_that!(res.results).has_length(3);
+        assert_that!(res.results[0])
+            .is_ok()
+            .matches(|res| res.new_loyalty_points == 150 && res.duplicate_of.is_none());
+        assert_that!(res.results[1]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Member(crate::ports::member::Error::MemberDoesNotExist(_))
+            )
+        });
+        assert_that!(res.results[2])
+            .is_ok()
+            .matches(|res| res.new_loyalty_points == 150 && res.duplicate_of.is_some());
+        assert_that!(database.get_loyalty_events(member_id).await?).has_length(1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_after_failure() -> Result<(), BoxError> {
+        // GIVEN a member, looked up in a single batch
+        let member_id = Uuid::new_v4();
+        let member_port = move |_| {
+            Ok(Member {
+                member_id,
+                active_member: true,
+                membership_since: fixtures::now(),
+                ..Default::default()
+            })
+        };
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_members()
+            .times(1)
+            .returning(move |_| Ok(vec![member_port(member_id)]));
+        member.expect_get_member().times(1).returning(member_port);
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_clock(Arc::new(FixedClock::new(fixtures::now())));
+
+        // WHEN adding points for a purchase with an idempotency key, a purchase in another
+        // currency, and a valid purchase
+        let res = ServiceExt::<BulkAddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(BulkAddPointsRequest {
+                requests: vec![
+                    AddPointsRequest {
+                        idempotency_key: Some("SOME KEY".to_string()),
+                        ..purchase(member_id, "A-1")
+                    },
+                    AddPointsRequest {
+                        event: AddPointsEvent::OnlinePurchase {
+                            purchase_amount: Money::new(1500, "USD").unwrap(),
+                        },
+                        ..purchase(member_id, "A-2")
+                    },
+                    purchase(member_id, "A-3"),
+                ],
+            })
+            .await?;
+
+        // THEN
+        // * the first two requests fail
+        // * the member is fetched again after the failure
+        assert_that!(res.results).has_length(3);
+        for res in res.results[..2].iter() {
+            assert_that!(*res)
+                .is_err()
+                .matches(|err| matches!(err, Error::InvalidState(_)));
+        }
+        assert_that!(res.results[2])
+            .is_ok()
+            .matches(|res| res.new_loyalty_points == 150);
+
+        Ok(())
+    }
+}
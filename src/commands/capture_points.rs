@@ -0,0 +1,187 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DomainEvent, LoyaltyEvent},
+    layers::idempotency::IdempotentRequest,
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{persist_with_events, DomainLogic, Error};
+
+/// Deduct the points of a hold once the checkout's payment settled
+///
+/// This registers an event debiting the held points, with the hold's reason, and sends a
+/// [`DomainEvent::PointsRedeemed`]. See
+/// [`HoldPointsRequest`](super::hold_points::HoldPointsRequest).
+pub struct CapturePointsRequest {
+    pub member_id: Uuid,
+    pub hold_id: Uuid,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for CapturePointsRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturePointsResponse {
+    pub member_id: Uuid,
+    /// Event debiting the held points
+    pub event_id: Uuid,
+    pub captured_points: u32,
+    /// New number of loyalty points available
+    pub new_loyalty_points: u32,
+}
+
+impl<D, M> Service<CapturePointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = CapturePointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: CapturePointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let hold = database
+                .get_loyalty_points(req.member_id)
+                .await
+                .with_context(|| format!("fetching loyalty for member {}", req.member_id))?
+                .holds
+                .into_iter()
+                .find(|hold| hold.hold_id == req.hold_id)
+                .ok_or(crate::ports::database::Error::HoldDoesNotExist(req.hold_id))?;
+            // Holds are validated when placed, so this cannot overflow
+            let delta_points = -(hold.points as i32);
+
+            let event_id = id_generator.generate_id();
+            let event = LoyaltyEvent::new(event_id, delta_points, hold.reason, clock.now());
+            let points_redeemed = DomainEvent::PointsRedeemed {
+                member_id: req.member_id,
+                event_id,
+                loyalty_points: hold.points,
+            };
+            let updated_loyalty = persist_with_events(
+                event_publisher.as_ref(),
+                outbox,
+                vec![points_redeemed],
+                |events| database.capture_hold(req.member_id, req.hold_id, event, events),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "capturing hold {} for member {}",
+                    req.hold_id, req.member_id
+                )
+            })?;
+
+            Ok(CapturePointsResponse {
+                member_id: req.member_id,
+                event_id,
+                captured_points: hold.points,
+                new_loyalty_points: updated_loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::event_publisher::memory::MemoryPublisher,
+        commands::hold_points::HoldPointsRequest,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with 100 points, 70 of which are held
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = domain.with_event_publisher(Arc::new(event_publisher.clone()));
+        let hold = ServiceExt::<HoldPointsRequest>::ready(&mut domain)
+            .await?
+            .call(HoldPointsRequest {
+                member_id,
+                loyalty_points: 70,
+                reason: "Order 42".to_string(),
+                idempotency_key: None,
+            })
+            .await?;
+
+        // WHEN capturing the hold
+        let res = ServiceExt::<CapturePointsRequest>::ready(&mut domain)
+            .await?
+            .call(CapturePointsRequest {
+                member_id,
+                hold_id: hold.hold_id,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN
+        // * the held points are debited with an event
+        // * the hold is gone
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.captured_points == 70 && res.new_loyalty_points == 30);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.held_points).is_equal_to(0);
+        assert_that!(loyalty.events[1].delta_points).is_equal_to(-70);
+        assert_that!(loyalty.events[1].reason.as_str()).is_equal_to("Order 42");
+        assert_that!(event_publisher.events()).has_length(1);
+
+        // WHEN capturing it again
+        let res = ServiceExt::<CapturePointsRequest>::ready(&mut domain)
+            .await?
+            .call(CapturePointsRequest {
+                member_id,
+                hold_id: hold.hold_id,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN the hold does not exist anymore
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Database(crate::ports::database::Error::HoldDoesNotExist(_))
+            )
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::BalanceChange,
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use tower::Service;
+
+use super::{DomainLogic, Error};
+
+/// Balances that changed since a checkpoint, for downstream caches to sync incrementally
+///
+/// Start with a checkpoint of `0`, then pass the `checkpoint` from the previous response until
+/// there are no more changes.
+pub struct ChangesSinceRequest {
+    pub checkpoint: u64,
+    /// Maximum number of changes to return
+    pub limit: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangesSinceResponse {
+    /// Latest balance of each member that changed, oldest change first
+    pub changes: Vec<BalanceChange>,
+    /// Checkpoint for the next request
+    pub checkpoint: u64,
+}
+
+impl<D, M> Service<ChangesSinceRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ChangesSinceResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ChangesSinceRequest) -> Self::Future {
+        let database = self.database.clone();
+        Box::pin(async move {
+            let changes = database
+                .get_balance_changes(req.checkpoint, req.limit)
+                .await?;
+            let checkpoint = changes
+                .last()
+                .map(|change| change.sequence)
+                .unwrap_or(req.checkpoint);
+
+            Ok(ChangesSinceResponse {
+                changes,
+                checkpoint,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase, domain::LoyaltyEvent,
+        ports::member::MockMemberPort,
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN two members earning points
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let database = MemoryDatabase::default();
+        for (member_id, delta_points) in [(member_ids[0], 10), (member_ids[1], 20)] {
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent::new(Uuid::new_v4(), delta_points, "SOME REASON", Utc::now()),
+                    None,
+                )
+                .await?;
+        }
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN syncing one change at a time until there are no more changes
+        let mut changes = Vec::new();
+        let mut checkpoint = 0;
+        loop {
+            let res = ServiceExt::<ChangesSinceRequest>::ready(&mut domain)
+                .await?
+                .call(ChangesSinceRequest {
+                    checkpoint,
+                    limit: 1,
+                })
+                .await?;
+            if res.changes.is_empty() {
+                assert_that!(res.checkpoint).is_equal_to(checkpoint);
+                break;
+            }
+            checkpoint = res.checkpoint;
+            changes.extend(res.changes);
+        }
+
+        // THEN all balances are returned, in order
+        assert_that!(changes
+            .iter()
+            .map(|change| (change.member_id, change.points, change.sequence))
+            .collect::<Vec<_>>())
+        .is_equal_to(vec![(member_ids[0], 10, 1), (member_ids[1], 20, 2)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DomainEvent, EventNote, LoyaltyEvent},
+    layers::idempotency::IdempotentRequest,
+    ports::{database::DatabasePort, member::MemberPort, ErrorChain, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{persist_with_events, reverse_points::reverse_event, DomainLogic, Error};
+
+/// Claw back the points earned by a purchase that the payment provider charged back
+///
+/// The purchase event is reversed as with
+/// [`ReversePointsRequest`](super::reverse_points::ReversePointsRequest), up to the
+/// [`ChargebackPolicy`](crate::domain::ChargebackPolicy)'s cap. Purchases above the policy's
+/// review threshold flag the member for review with a note on the purchase event. Every
+/// chargeback sends a [`DomainEvent::ChargebackReceived`] for fraud detection.
+///
+/// Only purchase events can be charged back.
+pub struct ChargebackRequest {
+    pub member_id: Uuid,
+    /// Purchase event that was charged back
+    pub event_id: Uuid,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for ChargebackRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChargebackResponse {
+    pub member_id: Uuid,
+    /// Event compensating the purchase
+    pub reversal_event_id: Uuid,
+    /// Points taken from the member, including points written off
+    pub clawed_back_points: u32,
+    /// Points that could not be clawed back, with
+    /// [`BalancePolicy::WriteOff`](crate::domain::BalancePolicy::WriteOff)
+    pub written_off_points: u32,
+    /// New number of loyalty points
+    pub new_loyalty_points: u32,
+    /// Whether the member was flagged for review
+    pub flagged_for_review: bool,
+}
+
+impl<D, M> Service<ChargebackRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ChargebackResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ChargebackRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        let balance_policy = self.balance_policy;
+        let chargeback_policy = self.chargeback_policy;
+        Box::pin(async move {
+            let event = database
+                .get_loyalty_events(req.member_id)
+                .await
+                .with_context(|| format!("fetching events for member {}", req.member_id))?
+                .into_iter()
+                .find(|event| event.event_id == req.event_id)
+                .ok_or(crate::ports::database::Error::EventDoesNotExist(
+                    req.event_id,
+                ))?;
+            if event.channel.is_none() {
+                return Err(Error::InvalidState(
+                    format!("event {} is not a purchase", event.event_id).into(),
+                ));
+            }
+            let earned_points = u32::try_from(event.delta_points)
+                .ok()
+                .filter(|points| *points > 0)
+                .ok_or_else(|| {
+                    Error::InvalidState(
+                        format!("event {} did not earn points", event.event_id).into(),
+                    )
+                })?;
+            let clawed_back_points = chargeback_policy.clawback_points(earned_points);
+            let flagged_for_review = chargeback_policy.needs_review(earned_points);
+
+            let now = clock.now();
+            let reversal_event_id = id_generator.generate_id();
+            let reversal = LoyaltyEvent::new(
+                reversal_event_id,
+                // At most the purchase's points, so this cannot overflow
+                -(clawed_back_points as i32),
+                "Chargeback",
+                now,
+            );
+            let signal = DomainEvent::ChargebackReceived {
+                member_id: req.member_id,
+                event_id: req.event_id,
+                loyalty_points: earned_points,
+                flagged_for_review,
+            };
+            let (loyalty, written_off_points) =
+                persist_with_events(event_publisher.as_ref(), outbox, vec![signal], |events| {
+                    reverse_event(
+                        database.as_ref(),
+                        balance_policy,
+                        req.member_id,
+                        &event,
+                        reversal,
+                        events,
+                    )
+                })
+                .await?;
+
+            if flagged_for_review {
+                let res = database
+                    .register_event_note(EventNote {
+                        note_id: id_generator.generate_id(),
+                        member_id: req.member_id,
+                        event_id: req.event_id,
+                        note: format!(
+                            "Flagged for review: chargeback of a purchase earning {} points",
+                            earned_points
+                        ),
+                        actor: "chargeback".to_string(),
+                        created_at: now,
+                    })
+                    .await
+                    .with_context(|| format!("flagging member {}", req.member_id));
+                // The points are already clawed back: failing would lead callers to retry the
+                // chargeback, which the fraud signal already reported
+                if let Err(err) = res {
+                    tracing::warn!(
+                        member_id = %req.member_id,
+                        event_id = %req.event_id,
+                        error = %ErrorChain(&err),
+                        "failed to flag member for review"
+                    );
+                }
+            }
+
+            Ok(ChargebackResponse {
+                member_id: req.member_id,
+                reversal_event_id,
+                clawed_back_points,
+                written_off_points,
+                new_loyalty_points: loyalty.points,
+                flagged_for_review,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::event_publisher::memory::MemoryPublisher,
+        domain::{BalancePolicy, Channel, ChargebackPolicy, Loyalty},
+        ports::{
+            database::{self, MockDatabasePort},
+            member::MockMemberPort,
+        },
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use chrono::Utc;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[rstest]
+    #[case(300, 100, 300, false)]
+    #[case(600, 300, 400, true)]
+    #[tokio::test]
+    async fn test_call(
+        #[case] purchase_points: u32,
+        #[case] expected_points: u32,
+        #[case] expected_clawed_back: u32,
+        #[case] expected_flagged: bool,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * chargebacks capped at 400 points, flagging purchases above 500 points
+        // * a member with 100 points and a purchase
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_points(100)
+            .with_purchase(purchase_points)
+            .build()
+            .await?;
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = domain
+            .with_event_publisher(Arc::new(event_publisher.clone()))
+            .with_chargeback_policy(
+                ChargebackPolicy::default()
+                    .with_max_clawback_points(400)
+                    .with_review_threshold(500),
+            );
+        let event_id = database.get_loyalty_events(member_id).await?[1].event_id;
+
+        // WHEN the purchase is charged back
+        let res = ServiceExt::<ChargebackRequest>::ready(&mut domain)
+            .await?
+            .call(ChargebackRequest {
+                member_id,
+                event_id,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN
+        // * the points are clawed back up to the cap
+        // * large purchases flag the member for review
+        // * a fraud signal is sent
+        assert_that!(res).is_ok().matches(|res| {
+            res.clawed_back_points == expected_clawed_back
+                && res.written_off_points == 0
+                && res.new_loyalty_points == expected_points
+                && res.flagged_for_review == expected_flagged
+        });
+        let notes = database.get_event_notes(member_id).await?;
+        assert_that!(notes.len()).is_equal_to(usize::from(expected_flagged));
+        assert_that!(event_publisher.events()).is_equal_to(vec![DomainEvent::ChargebackReceived {
+            member_id,
+            event_id,
+            loyalty_points: purchase_points,
+            flagged_for_review: expected_flagged,
+        }]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_spent() -> Result<(), BoxError> {
+        // GIVEN a member who spent the points of a purchase, with write-offs enabled
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_purchase(300)
+            .with_history([-250])
+            .build()
+            .await?;
+        let mut domain = domain.with_balance_policy(BalancePolicy::WriteOff);
+        let event_id = database.get_loyalty_events(member_id).await?[0].event_id;
+
+        // WHEN the purchase is charged back
+        let res = ServiceExt::<ChargebackRequest>::ready(&mut domain)
+            .await?
+            .call(ChargebackRequest {
+                member_id,
+                event_id,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN the remaining points are clawed back, and the rest is written off
+        assert_that!(res).is_ok().matches(|res| {
+            res.clawed_back_points == 300
+                && res.written_off_points == 250
+                && res.new_loyalty_points == 0
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_not_purchase() -> Result<(), BoxError> {
+        // GIVEN a member with points that did not come from a purchase
+        let Fixture {
+            member_id,
+            database,
+            mut domain,
+        } = MemberBuilder::basic().with_points(300).build().await?;
+        let event_id = database.get_loyalty_events(member_id).await?[0].event_id;
+
+        // WHEN the event is charged back
+        let res = ServiceExt::<ChargebackRequest>::ready(&mut domain)
+            .await?
+            .call(ChargebackRequest {
+                member_id,
+                event_id,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN it fails without clawing back points
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(300);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_note_failure() -> Result<(), BoxError> {
+        // GIVEN
+        // * chargebacks flagging purchases above 500 points
+        // * a database failing to store notes
+        let member_id = Uuid::new_v4();
+        let event = LoyaltyEvent {
+            channel: Some(Channel::Online),
+            ..LoyaltyEvent::new(Uuid::new_v4(), 600, "Online purchase", Utc::now())
+        };
+        let event_id = event.event_id;
+        let mut database = MockDatabasePort::new();
+        database
+            .expect_get_loyalty_events()
+            .returning(move |_| Ok(vec![event.clone()]));
+        database
+            .expect_reverse_event()
+            .times(1)
+            .returning(|member_id, _, _, _| Ok(Loyalty::new(member_id)));
+        database
+            .expect_register_event_note()
+            .times(1)
+            .returning(|_| Err(database::Error::Unavailable("SOME ERROR".into())));
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(MockMemberPort::new()))
+            .with_chargeback_policy(ChargebackPolicy::default().with_review_threshold(500));
+
+        // WHEN the purchase is charged back
+        let res = ServiceExt::<ChargebackRequest>::ready(&mut domain)
+            .await?
+            .call(ChargebackRequest {
+                member_id,
+                event_id,
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN the chargeback succeeds, as the points were already clawed back
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.clawed_back_points == 600 && res.flagged_for_review);
+
+        Ok(())
+    }
+}
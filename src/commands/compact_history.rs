@@ -0,0 +1,343 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{HistorySnapshot, LoyaltyEvent},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Replace old events of a member with a single snapshot event, to keep reads fast
+///
+/// Only the oldest events are compacted: compaction stops at the first event with a tier that is
+/// not verified yet, as it still needs to be reconciled, or imported from another program or
+/// part of a transfer, as it is kept for audit. Events with an order reference or from a campaign
+/// are kept too, so later reports of the same order are still recognized, and campaigns can still
+/// be reversed. Previous snapshots are merged into the new one.
+pub struct CompactHistoryRequest {
+    pub member_id: Uuid,
+    /// Only compact events with a lower sequence number
+    pub before: u64,
+    /// Number of most recent events to keep verbatim
+    pub keep_last: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactHistoryResponse {
+    pub member_id: Uuid,
+    /// Number of events replaced by the snapshot
+    pub compacted_events: usize,
+    /// Summary of the compacted history
+    ///
+    /// This is `None` if there were fewer than two events to compact.
+    pub snapshot: Option<HistorySnapshot>,
+}
+
+impl<D, M> Service<CompactHistoryRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = CompactHistoryResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: CompactHistoryRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            let events = database
+                .get_loyalty_events(req.member_id)
+                .await
+                .with_context(|| format!("fetching events for member {}", req.member_id))?;
+
+            // Select the oldest events that can be compacted
+            let compactable = &events[..events.len().saturating_sub(req.keep_last)];
+            let count = compactable
+                .iter()
+                .take_while(|event| {
+                    event.sequence < req.before
+                        && event.tier_unverified.is_none()
+                        && event.external_source.is_none()
+                        && event.linked_event_id.is_none()
+                        && event.order_reference.is_none()
+                        && event.campaign_id.is_none()
+                })
+                .count();
+            if count < 2 {
+                return Ok(CompactHistoryResponse {
+                    member_id: req.member_id,
+                    compacted_events: 0,
+                    snapshot: None,
+                });
+            }
+            let compacted = &compactable[..count];
+
+            // Summarize the events
+            let mut snapshot = HistorySnapshot::default();
+            let mut delta_points = 0i64;
+            for event in compacted {
+                match &event.snapshot {
+                    Some(previous) => {
+                        snapshot.event_count += previous.event_count;
+                        for (reason, total) in &previous.totals {
+                            *snapshot.totals.entry(reason.clone()).or_default() += total;
+                        }
+                    }
+                    None => {
+                        snapshot.event_count += 1;
+                        *snapshot.totals.entry(event.reason.clone()).or_default() +=
+                            event.delta_points as i64;
+                    }
+                }
+                delta_points += event.delta_points as i64;
+            }
+            let delta_points = i32::try_from(delta_points).map_err(|_| {
+                Error::InvalidState(
+                    format!(
+                        "compacted events total {delta_points} points, more than an event holds"
+                    )
+                    .into(),
+                )
+            })?;
+
+            database
+                .compact_events(
+                    req.member_id,
+                    LoyaltyEvent {
+                        sequence: compacted[count - 1].sequence,
+                        snapshot: Some(snapshot.clone()),
+                        ..LoyaltyEvent::new(
+                            id_generator.generate_id(),
+                            delta_points,
+                            "History snapshot",
+                            clock.now(),
+                        )
+                    },
+                )
+                .await
+                .with_context(|| format!("compacting events for member {}", req.member_id))?;
+
+            Ok(CompactHistoryResponse {
+                member_id: req.member_id,
+                compacted_events: count,
+                snapshot: Some(snapshot),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase,
+        commands::add_points::{AddPointsEvent, AddPointsRequest},
+        domain::Money,
+        domain::{Tier, UnverifiedTier},
+        ports::member::MockMemberPort,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use std::{collections::BTreeMap, sync::Arc};
+    use tower::{BoxError, ServiceExt};
+
+    async fn register(
+        database: &MemoryDatabase,
+        member_id: Uuid,
+        delta_points: i32,
+        reason: &str,
+        tier_unverified: Option<UnverifiedTier>,
+    ) -> Result<(), BoxError> {
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent {
+                    tier_unverified,
+                    ..LoyaltyEvent::new(
+                        Uuid::new_v4(),
+                        delta_points,
+                        reason.to_string(),
+                        Utc::now(),
+                    )
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with 5 events
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        for (delta_points, reason) in [
+            (100, "Online purchase"),
+            (50, "Online purchase"),
+            (-30, "Drawing entry"),
+            (20, "Online purchase"),
+            (5, "Manual addition"),
+        ] {
+            register(&database, member_id, delta_points, reason, None).await?;
+        }
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN compacting events before the 4th one, then everything but the last event
+        let mut results = Vec::new();
+        for before in [4, 6] {
+            let res = ServiceExt::<CompactHistoryRequest>::ready(&mut domain)
+                .await?
+                .call(CompactHistoryRequest {
+                    member_id,
+                    before,
+                    keep_last: 1,
+                })
+                .await?;
+            results.push(res);
+        }
+
+        // THEN
+        // * the first snapshot summarizes the first 3 events
+        // * the second snapshot merges the first one with the 4th event
+        // * the last event is kept verbatim
+        assert_that!(results
+            .iter()
+            .map(|res| res.compacted_events)
+            .collect::<Vec<_>>())
+        .is_equal_to(vec![3, 2]);
+        assert_that!(results[1].snapshot).is_equal_to(Some(HistorySnapshot {
+            event_count: 4,
+            totals: BTreeMap::from([
+                ("Drawing entry".to_string(), -30),
+                ("Online purchase".to_string(), 170),
+            ]),
+        }));
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events
+            .iter()
+            .map(|event| (event.sequence, event.delta_points, event.snapshot.is_some()))
+            .collect::<Vec<_>>())
+        .is_equal_to(vec![(4, 140, true), (5, 5, false)]);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(145);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_unverified() -> Result<(), BoxError> {
+        // GIVEN a member with an unverified event after a single event
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let unverified_tier = UnverifiedTier {
+            tier: Tier::Basic,
+            purchase_amount: 1,
+            earn_multiplier: 1,
+        };
+        register(&database, member_id, 10, "Online purchase", None).await?;
+        register(
+            &database,
+            member_id,
+            10,
+            "Online purchase",
+            Some(unverified_tier),
+        )
+        .await?;
+        register(&database, member_id, 10, "Online purchase", None).await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN compacting all events
+        let res = ServiceExt::<CompactHistoryRequest>::ready(&mut domain)
+            .await?
+            .call(CompactHistoryRequest {
+                member_id,
+                before: u64::MAX,
+                keep_last: 0,
+            })
+            .await;
+
+        // THEN nothing is compacted, as the unverified event must be kept
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(CompactHistoryResponse {
+                member_id,
+                compacted_events: 0,
+                snapshot: None,
+            });
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events).has_length(3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_order_reference() -> Result<(), BoxError> {
+        // GIVEN a member with 2 events, then points earned on an order
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_history([100, 50])
+            .build()
+            .await?;
+        let mut domain = domain;
+        let purchase = |event| AddPointsRequest {
+            member_id,
+            event,
+            occurred_at: None,
+            order_reference: Some("ORD-1".to_string()),
+            idempotency_key: None,
+        };
+        let amount = Money::new(1500, "EUR")?;
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(purchase(AddPointsEvent::OnlinePurchase {
+                purchase_amount: amount,
+            }))
+            .await?;
+
+        // WHEN
+        // * compacting all events
+        // * another channel reports the same order
+        let res = ServiceExt::<CompactHistoryRequest>::ready(&mut domain)
+            .await?
+            .call(CompactHistoryRequest {
+                member_id,
+                before: u64::MAX,
+                keep_last: 0,
+            })
+            .await?;
+        let duplicate = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(purchase(AddPointsEvent::InStorePurchase {
+                purchase_amount: amount,
+            }))
+            .await?;
+
+        // THEN
+        // * compaction stops at the order's event
+        // * the order is recognized, and only earns points once
+        assert_that!(res.compacted_events).is_equal_to(2);
+        assert_that!(duplicate.duplicate_of).is_some();
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(300);
+
+        Ok(())
+    }
+}
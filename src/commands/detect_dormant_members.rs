@@ -0,0 +1,230 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DomainEvent, Loyalty},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use chrono::{DateTime, Duration, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{expire_points::EXPIRATION_REASON, publish, DomainLogic, Error};
+
+/// Number of members fetched from the database at once
+const PAGE_SIZE: usize = 100;
+
+/// Find members who reached an inactivity threshold, and publish a
+/// [`DomainEvent::MemberWentDormant`] for each of them
+///
+/// This is meant to run periodically, e.g. from a scheduled job. A member's last activity is their
+/// most recent event, ignoring expirations and history snapshots, as neither comes from the member.
+pub struct DetectDormantMembersRequest {
+    /// Time without activity after which a member is dormant
+    pub inactive_for: Duration,
+    /// Time of the previous run
+    ///
+    /// Only members who became dormant since then are reported, so each member is reported once
+    /// per period of inactivity. Without it, all dormant members are reported.
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetectDormantMembersResponse {
+    /// Members who became dormant, in member ID order
+    pub dormant: Vec<DormantMember>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DormantMember {
+    pub member_id: Uuid,
+    pub last_activity_at: DateTime<Utc>,
+    /// Available and pending points the member could lose
+    pub balance_at_risk: u32,
+}
+
+impl<D, M> Service<DetectDormantMembersRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = DetectDormantMembersResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: DetectDormantMembersRequest) -> Self::Future {
+        let database = self.database.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        Box::pin(async move {
+            let now = clock.now();
+            let mut dormant = Vec::new();
+            let mut after = None;
+            loop {
+                let member_ids = database
+                    .get_member_ids(after, PAGE_SIZE)
+                    .await
+                    .context("fetching member IDs")?;
+                let Some(last_member_id) = member_ids.last() else {
+                    break;
+                };
+                after = Some(*last_member_id);
+
+                let loyalties = database
+                    .get_loyalty_points_batch(&member_ids)
+                    .await
+                    .context("fetching loyalties")?;
+                for loyalty in loyalties {
+                    let Some(last_activity_at) = last_activity(&loyalty) else {
+                        continue;
+                    };
+                    let dormant_at = last_activity_at + req.inactive_for;
+                    if dormant_at > now || req.since.is_some_and(|since| dormant_at <= since) {
+                        continue;
+                    }
+
+                    let member = DormantMember {
+                        member_id: loyalty.member_id,
+                        last_activity_at,
+                        balance_at_risk: loyalty.points + loyalty.pending_points,
+                    };
+                    publish(
+                        event_publisher.as_ref(),
+                        DomainEvent::MemberWentDormant {
+                            member_id: member.member_id,
+                            last_activity_at: member.last_activity_at,
+                            balance_at_risk: member.balance_at_risk,
+                        },
+                    )
+                    .await;
+                    dormant.push(member);
+                }
+            }
+
+            Ok(DetectDormantMembersResponse { dormant })
+        })
+    }
+}
+
+/// Date of the member's most recent activity, or `None` if they have none
+fn last_activity(loyalty: &Loyalty) -> Option<DateTime<Utc>> {
+    loyalty
+        .events
+        .iter()
+        .filter(|event| event.snapshot.is_none() && event.reason != EXPIRATION_REASON)
+        .map(|event| event.created_at)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            clock::fixed::FixedClock, database::memory::MemoryDatabase,
+            event_publisher::memory::MemoryPublisher,
+        },
+        domain::LoyaltyEvent,
+        ports::member::MockMemberPort,
+        testing::fixtures,
+    };
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn event(delta_points: i32, reason: &str, days_ago: i64) -> LoyaltyEvent {
+        LoyaltyEvent::new(
+            Uuid::new_v4(),
+            delta_points,
+            reason.to_string(),
+            fixtures::now() - Duration::days(days_ago),
+        )
+    }
+
+    #[rstest]
+    #[case(Some(30), &[0, 3])]
+    #[case(None, &[0, 2, 3])]
+    #[tokio::test]
+    async fn test_call(
+        #[case] since_days_ago: Option<i64>,
+        #[case] expected: &[usize],
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * a member inactive for 100 days
+        // * a member active 10 days ago
+        // * a member inactive for 200 days
+        // * a member inactive for 100 days, whose points partly expired 5 days ago
+        let mut member_ids = [
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        ];
+        member_ids.sort();
+        let database = MemoryDatabase::default();
+        for (member_id, events) in member_ids.iter().zip([
+            vec![event(200, "Online purchase", 100)],
+            vec![event(200, "Online purchase", 10)],
+            vec![event(200, "Online purchase", 200)],
+            vec![
+                event(200, "Online purchase", 100),
+                event(-50, EXPIRATION_REASON, 5),
+            ],
+        ]) {
+            for event in events {
+                database
+                    .register_loyalty_event(*member_id, event, None)
+                    .await?;
+            }
+        }
+        let event_publisher = MemoryPublisher::default();
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_clock(Arc::new(FixedClock::new(fixtures::now())))
+                .with_event_publisher(Arc::new(event_publisher.clone()));
+
+        // WHEN detecting members inactive for 90 days
+        let res = ServiceExt::<DetectDormantMembersRequest>::ready(&mut domain)
+            .await?
+            .call(DetectDormantMembersRequest {
+                inactive_for: Duration::days(90),
+                since: since_days_ago.map(|days| fixtures::now() - Duration::days(days)),
+            })
+            .await?;
+
+        // THEN
+        // * members who became dormant since the previous run are reported
+        // * expirations do not count as activity
+        // * an event is published for each of them
+        let dormant = expected
+            .iter()
+            .map(|index| DormantMember {
+                member_id: member_ids[*index],
+                last_activity_at: fixtures::now()
+                    - Duration::days(if *index == 2 { 200 } else { 100 }),
+                balance_at_risk: if *index == 3 { 150 } else { 200 },
+            })
+            .collect::<Vec<_>>();
+        assert_that!(res.dormant).is_equal_to(&dormant);
+        assert_that!(event_publisher.events()).is_equal_to(
+            dormant
+                .into_iter()
+                .map(|member| DomainEvent::MemberWentDormant {
+                    member_id: member.member_id,
+                    last_activity_at: member.last_activity_at,
+                    balance_at_risk: member.balance_at_risk,
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        Ok(())
+    }
+}
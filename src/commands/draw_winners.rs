@@ -0,0 +1,234 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DrawingEntry, DrawingResult},
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Select the winners of a drawing
+pub struct DrawWinnersRequest {
+    pub drawing_id: Uuid,
+    /// Seed for the random number generator
+    ///
+    /// This is stored with the result, so anyone can reproduce the selection from the entries.
+    pub seed: u64,
+}
+
+impl<D, M> Service<DrawWinnersRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = DrawingResult;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: DrawWinnersRequest) -> Self::Future {
+        let drawing = self.drawing();
+        Box::pin(async move {
+            let drawing_port = drawing?;
+
+            let drawing = drawing_port.get_drawing(req.drawing_id).await?;
+            if drawing.result.is_some() {
+                return Err(crate::ports::drawing::Error::AlreadyDrawn(drawing.drawing_id).into());
+            }
+            let entries = drawing_port.get_entries(drawing.drawing_id).await?;
+
+            let result = DrawingResult {
+                drawing_id: drawing.drawing_id,
+                seed: req.seed,
+                winners: select_winners(entries, drawing.winner_count, req.seed),
+            };
+            drawing_port.register_result(result.clone()).await?;
+
+            Ok(result)
+        })
+    }
+}
+
+/// Select winners, weighted by their number of entries
+///
+/// Each member can only win once. Entries are sorted by member ID first, so the selection only
+/// depends on the entries and the seed, not on the order returned by the drawing port.
+fn select_winners(mut entries: Vec<DrawingEntry>, winner_count: u32, seed: u64) -> Vec<Uuid> {
+    entries.retain(|entry| entry.entries > 0);
+    entries.sort_by_key(|entry| entry.member_id);
+
+    let mut rng = SplitMix64(seed);
+    let mut total: u64 = entries.iter().map(|entry| entry.entries as u64).sum();
+    let mut winners = Vec::new();
+    while winners.len() < winner_count as usize && !entries.is_empty() {
+        let mut ticket = rng.next_u64() % total;
+        let index = entries
+            .iter()
+            .position(|entry| match ticket.checked_sub(entry.entries as u64) {
+                Some(remaining) => {
+                    ticket = remaining;
+                    false
+                }
+                None => true,
+            })
+            .expect("ticket is lower than the total number of entries");
+        let winner = entries.remove(index);
+        total -= winner.entries as u64;
+        winners.push(winner.member_id);
+    }
+
+    winners
+}
+
+/// Small, portable PRNG so drawings can be reproduced independently of this crate's dependencies
+///
+/// See <https://prng.di.unimi.it/splitmix64.c>
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{database::memory::MemoryDatabase, drawing::memory::MemoryDrawings},
+        domain::Drawing,
+        ports::{drawing::DrawingPort, member::MockMemberPort},
+    };
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn entries(counts: &[u32]) -> Vec<DrawingEntry> {
+        counts
+            .iter()
+            .map(|entries| DrawingEntry {
+                member_id: Uuid::new_v4(),
+                entries: *entries,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_splitmix64() {
+        // Reference values for seed 1234567 from the reference implementation
+        let mut rng = SplitMix64(1234567);
+        assert_that!(rng.next_u64()).is_equal_to(6457827717110365317);
+        assert_that!(rng.next_u64()).is_equal_to(3203168211198807973);
+    }
+
+    #[rstest]
+    #[case(&[1, 2, 3], 1, 1)]
+    #[case(&[1, 2, 3], 3, 3)]
+    #[case(&[1, 2, 3], 5, 3)]
+    #[case(&[1, 0, 3], 3, 2)]
+    #[case(&[], 2, 0)]
+    fn test_select_winners_count(
+        #[case] counts: &[u32],
+        #[case] winner_count: u32,
+        #[case] expected: usize,
+    ) {
+        // GIVEN a set of entries
+
+        // WHEN selecting winners
+        let res = select_winners(entries(counts), winner_count, 42);
+
+        // THEN
+        // * it returns the expected number of winners
+        // * members only win once
+        assert_that!(res).has_length(expected);
+        let mut unique = res.clone();
+        unique.sort();
+        unique.dedup();
+        assert_that!(unique).has_length(expected);
+    }
+
+    #[test]
+    fn test_select_winners_reproducible() {
+        // GIVEN a set of entries
+        let entries = entries(&[5, 1, 3, 8, 2]);
+
+        // WHEN selecting winners twice with the same seed, in a different order
+        let first = select_winners(entries.clone(), 2, 1234);
+        let second = select_winners(entries.into_iter().rev().collect(), 2, 1234);
+
+        // THEN it returns the same winners
+        assert_that!(first).is_equal_to(second);
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a drawing with entries
+        let drawing = Drawing {
+            drawing_id: Uuid::new_v4(),
+            points_per_entry: 10,
+            winner_count: 1,
+            result: None,
+        };
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let member_id = Uuid::new_v4();
+        drawings
+            .register_entries(drawing.drawing_id, member_id, 2)
+            .await?;
+        let mut domain = DomainLogic::new(
+            Arc::new(MemoryDatabase::default()),
+            Arc::new(MockMemberPort::new()),
+        )
+        .with_drawings(Arc::new(drawings.clone()));
+
+        // WHEN drawing the winners
+        let req = DrawWinnersRequest {
+            drawing_id: drawing.drawing_id,
+            seed: 42,
+        };
+        let res = ServiceExt::<DrawWinnersRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN
+        // * the only member wins
+        // * the result is stored
+        let expected = DrawingResult {
+            drawing_id: drawing.drawing_id,
+            seed: 42,
+            winners: vec![member_id],
+        };
+        assert_that!(res).is_ok().is_equal_to(&expected);
+        let stored = drawings.get_drawing(drawing.drawing_id).await?;
+        assert_that!(stored.result).is_some().is_equal_to(expected);
+
+        // AND drawing again fails
+        let req = DrawWinnersRequest {
+            drawing_id: drawing.drawing_id,
+            seed: 42,
+        };
+        let res = ServiceExt::<DrawWinnersRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::Drawing(_)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, DomainEvent, LoyaltyEvent},
+    ports::{database::DatabasePort, member::MemberPort, ErrorChain},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, retry_on_conflict, DomainLogic, Error};
+
+/// Buy entries to a drawing with loyalty points
+pub struct EnterDrawingRequest {
+    pub member_id: Uuid,
+    pub drawing_id: Uuid,
+    /// Number of entries to buy
+    pub entries: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnterDrawingResponse {
+    pub member_id: Uuid,
+    pub drawing_id: Uuid,
+    /// Previous number of loyalty points
+    pub old_loyalty_points: u32,
+    /// New number of loyalty points
+    pub new_loyalty_points: u32,
+}
+
+impl<D, M> Service<EnterDrawingRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = EnterDrawingResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: EnterDrawingRequest) -> Self::Future {
+        let member = self.member.clone();
+        let database = self.database.clone();
+        let drawing = self.drawing();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let conflict_retries = self.conflict_retries;
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let drawing_port = drawing?;
+            if req.entries == 0 {
+                return Err(Error::InvalidState("no drawing entries requested".into()));
+            }
+
+            // Fetch necessary data
+            let db_member = member.get_member(req.member_id).await?;
+            ensure_capability(
+                database.as_ref(),
+                db_member.member_id,
+                Capability::Redeem,
+                clock.now(),
+            )
+            .await?;
+            let drawing = drawing_port.get_drawing(req.drawing_id).await?;
+            if drawing.result.is_some() {
+                return Err(crate::ports::drawing::Error::AlreadyDrawn(drawing.drawing_id).into());
+            }
+
+            // Debit the points for the entries, from the balance reported as the old one
+            let cost = req
+                .entries
+                .checked_mul(drawing.points_per_entry)
+                .and_then(|cost| i32::try_from(cost).ok())
+                .ok_or_else(|| {
+                    Error::InvalidState(
+                        format!("cannot buy {} drawing entries at once", req.entries).into(),
+                    )
+                })?;
+            let (loyalty, updated_loyalty) = retry_on_conflict(conflict_retries, || async {
+                let loyalty = database.get_loyalty_points(db_member.member_id).await?;
+                let event_id = id_generator.generate_id();
+                let points_redeemed = DomainEvent::PointsRedeemed {
+                    member_id: db_member.member_id,
+                    event_id,
+                    loyalty_points: cost.unsigned_abs(),
+                };
+                let res = persist_with_events(
+                    event_publisher.as_ref(),
+                    outbox,
+                    vec![points_redeemed],
+                    |events| {
+                        database.register_loyalty_event_with_outbox(
+                            db_member.member_id,
+                            LoyaltyEvent::new(event_id, -cost, "Drawing entry", clock.now()),
+                            Some(loyalty.version),
+                            events,
+                        )
+                    },
+                )
+                .await;
+                match res {
+                    Ok(updated_loyalty) => Ok((loyalty, updated_loyalty)),
+                    Err(crate::ports::database::Error::NegativePointsTotal {
+                        current_points,
+                        ..
+                    }) => Err(Error::InsufficientPoints {
+                        available: current_points,
+                        requested: cost.unsigned_abs(),
+                    }),
+                    Err(err) => Err(err.into()),
+                }
+            })
+            .await?;
+
+            // Record the entries, and refund the points if that fails
+            if let Err(err) = drawing_port
+                .register_entries(drawing.drawing_id, db_member.member_id, req.entries)
+                .await
+            {
+                let event_id = id_generator.generate_id();
+                let points_added = DomainEvent::PointsAdded {
+                    member_id: db_member.member_id,
+                    event_id,
+                    loyalty_points: cost.unsigned_abs(),
+                };
+                let refund = persist_with_events(
+                    event_publisher.as_ref(),
+                    outbox,
+                    vec![points_added],
+                    |events| {
+                        database.register_loyalty_event_with_outbox(
+                            db_member.member_id,
+                            LoyaltyEvent::new(event_id, cost, "Drawing entry refund", clock.now()),
+                            None,
+                            events,
+                        )
+                    },
+                )
+                .await;
+                if let Err(refund_err) = refund {
+                    tracing::error!(
+                        member_id = %db_member.member_id,
+                        points = cost,
+                        error = %ErrorChain(&err),
+                        refund_error = %ErrorChain(&refund_err),
+                        "failed to refund drawing entries"
+                    );
+                }
+                return Err(err.into());
+            }
+
+            Ok(EnterDrawingResponse {
+                member_id: db_member.member_id,
+                drawing_id: drawing.drawing_id,
+                old_loyalty_points: loyalty.points,
+                new_loyalty_points: updated_loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            database::memory::MemoryDatabase, drawing::memory::MemoryDrawings,
+            id_generator::sequential::SequentialIds,
+        },
+        domain::{Drawing, DrawingEntry},
+        ports::{
+            drawing::{DrawingPort, MockDrawingPort},
+            member::MockMemberPort,
+        },
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use chrono::Utc;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[fixture]
+    fn member_id() -> Uuid {
+        Uuid::new_v4()
+    }
+
+    #[fixture]
+    fn drawing() -> Drawing {
+        Drawing {
+            drawing_id: Uuid::new_v4(),
+            points_per_entry: 50,
+            winner_count: 1,
+            result: None,
+        }
+    }
+
+    fn member_port(member_id: Uuid) -> MockMemberPort {
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        member
+    }
+
+    async fn database(member_id: Uuid, points: i32) -> Result<MemoryDatabase, BoxError> {
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(Uuid::new_v4(), points, "SOME REASON", Utc::now()),
+                None,
+            )
+            .await?;
+        Ok(database)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call(member_id: Uuid, drawing: Drawing) -> Result<(), BoxError> {
+        // GIVEN
+        // * a member with 200 points
+        // * a drawing costing 50 points per entry
+        let database = database(member_id, 200).await?;
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member_port(member_id)))
+            .with_drawings(Arc::new(drawings.clone()));
+
+        // WHEN entering the drawing 3 times
+        let req = EnterDrawingRequest {
+            member_id,
+            drawing_id: drawing.drawing_id,
+            entries: 3,
+        };
+        let res = ServiceExt::<EnterDrawingRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN
+        // * 150 points are debited
+        // * the entries are recorded
+        assert_that!(res).is_ok().is_equal_to(EnterDrawingResponse {
+            member_id,
+            drawing_id: drawing.drawing_id,
+            old_loyalty_points: 200,
+            new_loyalty_points: 50,
+        });
+        let entries = drawings.get_entries(drawing.drawing_id).await?;
+        assert_that!(entries).is_equal_to(vec![DrawingEntry {
+            member_id,
+            entries: 3,
+        }]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_outbox(member_id: Uuid, drawing: Drawing) -> Result<(), BoxError> {
+        // GIVEN a member with 200 points and the outbox enabled
+        let database = database(member_id, 200).await?;
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(member_id)))
+                .with_drawings(Arc::new(drawings))
+                .with_id_generator(Arc::new(SequentialIds::default()))
+                .with_outbox();
+
+        // WHEN entering the drawing 3 times
+        let req = EnterDrawingRequest {
+            member_id,
+            drawing_id: drawing.drawing_id,
+            entries: 3,
+        };
+        ServiceExt::<EnterDrawingRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await?;
+
+        // THEN the redeemed points are stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsRedeemed {
+            member_id,
+            event_id: Uuid::from_u128(1),
+            loyalty_points: 150,
+        }]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_not_enough_points(
+        member_id: Uuid,
+        drawing: Drawing,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member with only 100 points
+        let database = database(member_id, 100).await?;
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member_port(member_id)))
+            .with_drawings(Arc::new(drawings.clone()));
+
+        // WHEN entering the drawing 3 times
+        let req = EnterDrawingRequest {
+            member_id,
+            drawing_id: drawing.drawing_id,
+            entries: 3,
+        };
+        let res = ServiceExt::<EnterDrawingRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * no entries are recorded
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::InsufficientPoints {
+                    available: 100,
+                    requested: 150
+                }
+            )
+        });
+        let entries = drawings.get_entries(drawing.drawing_id).await?;
+        assert_that!(entries).is_empty();
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_too_many_entries(member_id: Uuid, drawing: Drawing) -> Result<(), BoxError> {
+        // GIVEN a member with 200 points
+        let database = database(member_id, 200).await?;
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(member_id)))
+                .with_drawings(Arc::new(drawings.clone()));
+
+        // WHEN buying more entries than points can represent
+        let req = EnterDrawingRequest {
+            member_id,
+            drawing_id: drawing.drawing_id,
+            entries: u32::MAX / 2,
+        };
+        let res = ServiceExt::<EnterDrawingRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * the points are left untouched
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(200);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_refund(member_id: Uuid, drawing: Drawing) -> Result<(), BoxError> {
+        // GIVEN a drawing port failing to record entries
+        let database = database(member_id, 200).await?;
+        let mut drawings = MockDrawingPort::new();
+        drawings
+            .expect_get_drawing()
+            .return_once(move |_| Ok(drawing));
+        drawings
+            .expect_register_entries()
+            .return_once(|drawing_id, _, _| {
+                Err(crate::ports::drawing::Error::AlreadyDrawn(drawing_id))
+            });
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(member_id)))
+                .with_drawings(Arc::new(drawings));
+
+        // WHEN entering the drawing
+        let req = EnterDrawingRequest {
+            member_id,
+            drawing_id: Uuid::new_v4(),
+            entries: 3,
+        };
+        let res = ServiceExt::<EnterDrawingRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * the points are refunded
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::Drawing(_)));
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(200);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_suspended(drawing: Drawing) -> Result<(), BoxError> {
+        // GIVEN a member with 200 points, who cannot redeem points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_points(200)
+            .with_restriction(Capability::Redeem)
+            .build()
+            .await?;
+        let drawings = MemoryDrawings::default().with_drawing(drawing.clone());
+        let mut domain = domain.with_drawings(Arc::new(drawings.clone()));
+
+        // WHEN entering the drawing
+        let res = ServiceExt::<EnterDrawingRequest>::ready(&mut domain)
+            .await?
+            .call(EnterDrawingRequest {
+                member_id,
+                drawing_id: drawing.drawing_id,
+                entries: 3,
+            })
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * no points are debited, and no entries are recorded
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Suspended {
+                    capability: Capability::Redeem,
+                    ..
+                }
+            )
+        });
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(200);
+        let entries = drawings.get_entries(drawing.drawing_id).await?;
+        assert_that!(entries).is_empty();
+
+        Ok(())
+    }
+}
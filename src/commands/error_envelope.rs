@@ -0,0 +1,316 @@
+//! Error model shared by all API clients
+//!
+//! Every [`Error`] maps to a single [`ErrorEnvelope`] with a stable code, so client SDKs can
+//! handle errors the same way whatever the command or transport.
+
+use super::Error;
+use crate::domain::Capability;
+
+/// Description of a failed command for API clients
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorEnvelope {
+    /// Stable identifier of the kind of error, e.g. `MEMBER_NOT_FOUND`
+    pub code: &'static str,
+    /// Human-readable description
+    ///
+    /// Adapter errors only get a generic message, as their details are internal.
+    pub message: String,
+    /// Values related to the error, such as the member ID
+    pub details: Vec<ErrorDetail>,
+    /// Whether the command might succeed if retried
+    pub retryable: bool,
+    /// Identifier of the request, to find it in logs
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorDetail {
+    pub name: &'static str,
+    pub value: String,
+}
+
+impl ErrorDetail {
+    fn new(name: &'static str, value: impl ToString) -> Self {
+        Self {
+            name,
+            value: value.to_string(),
+        }
+    }
+}
+
+const INTERNAL_MESSAGE: &str = "internal error";
+const UNAVAILABLE_MESSAGE: &str = "service temporarily unavailable";
+
+impl Error {
+    /// Describe the error for API clients
+    pub fn envelope(&self, correlation_id: Option<String>) -> ErrorEnvelope {
+        use crate::ports::{
+            campaign, case_lock, catalog, database, drawing, idempotency, member, segment,
+        };
+
+        let (code, message, details) = match self {
+            Error::Database(database::Error::NegativePointsTotal {
+                current_points,
+                delta_points,
+            }) => (
+                "INSUFFICIENT_POINTS",
+                Error::InsufficientPoints {
+                    available: *current_points,
+                    requested: delta_points.unsigned_abs(),
+                }
+                .to_string(),
+                vec![
+                    ErrorDetail::new("available", current_points),
+                    ErrorDetail::new("requested", delta_points.unsigned_abs()),
+                ],
+            ),
+            Error::Database(err @ database::Error::EventDoesNotExist(event_id)) => (
+                "EVENT_NOT_FOUND",
+                err.to_string(),
+                vec![ErrorDetail::new("event_id", event_id)],
+            ),
+            Error::Database(err @ database::Error::HoldDoesNotExist(hold_id)) => (
+                "HOLD_NOT_FOUND",
+                err.to_string(),
+                vec![ErrorDetail::new("hold_id", hold_id)],
+            ),
+            Error::Database(err @ database::Error::WelcomeBonusAlreadyGranted(member_id)) => (
+                "WELCOME_BONUS_ALREADY_GRANTED",
+                err.to_string(),
+                vec![ErrorDetail::new("member_id", member_id)],
+            ),
+            Error::Database(err @ database::Error::EventAlreadyLinked(event_id)) => (
+                "EVENT_ALREADY_LINKED",
+                err.to_string(),
+                vec![ErrorDetail::new("event_id", event_id)],
+            ),
+            Error::Database(
+                err @ database::Error::Conflict {
+                    member_id,
+                    expected_version,
+                    current_version,
+                },
+            ) => (
+                "CONFLICT",
+                err.to_string(),
+                vec![
+                    ErrorDetail::new("member_id", member_id),
+                    ErrorDetail::new("expected_version", expected_version),
+                    ErrorDetail::new("current_version", current_version),
+                ],
+            ),
+            Error::Member(err @ member::Error::MemberDoesNotExist(member_id)) => (
+                "MEMBER_NOT_FOUND",
+                err.to_string(),
+                vec![ErrorDetail::new("member_id", member_id)],
+            ),
+            Error::Catalog(err @ catalog::Error::RewardDoesNotExist(reward_id)) => (
+                "REWARD_NOT_FOUND",
+                err.to_string(),
+                vec![ErrorDetail::new("reward_id", reward_id)],
+            ),
+            Error::Drawing(err @ drawing::Error::DrawingDoesNotExist(drawing_id)) => (
+                "DRAWING_NOT_FOUND",
+                err.to_string(),
+                vec![ErrorDetail::new("drawing_id", drawing_id)],
+            ),
+            Error::Drawing(err @ drawing::Error::AlreadyDrawn(drawing_id)) => (
+                "DRAWING_ALREADY_DRAWN",
+                err.to_string(),
+                vec![ErrorDetail::new("drawing_id", drawing_id)],
+            ),
+            Error::CaseLock(err @ case_lock::Error::AlreadyLocked(case_lock)) => (
+                "MEMBER_LOCKED",
+                err.to_string(),
+                vec![
+                    ErrorDetail::new("member_id", case_lock.member_id),
+                    ErrorDetail::new("holder", &case_lock.holder),
+                    ErrorDetail::new("expires_at", case_lock.expires_at.to_rfc3339()),
+                ],
+            ),
+            Error::Idempotency(err @ idempotency::Error::InFlight(_)) => {
+                ("REQUEST_IN_PROGRESS", err.to_string(), Vec::new())
+            }
+            Error::InsufficientPoints {
+                available,
+                requested,
+            } => (
+                "INSUFFICIENT_POINTS",
+                self.to_string(),
+                vec![
+                    ErrorDetail::new("available", available),
+                    ErrorDetail::new("requested", requested),
+                ],
+            ),
+            Error::Suspended {
+                member_id,
+                capability,
+                until,
+            } => (
+                match capability {
+                    Capability::Earn => "EARN_SUSPENDED",
+                    Capability::Redeem => "REDEEM_SUSPENDED",
+                },
+                self.to_string(),
+                vec![
+                    ErrorDetail::new("member_id", member_id),
+                    ErrorDetail::new("until", until.to_rfc3339()),
+                ],
+            ),
+            Error::InvalidState(message) => ("INVALID_REQUEST", message.to_string(), Vec::new()),
+            Error::Database(database::Error::Unavailable(_))
+            | Error::Member(member::Error::Unavailable(_) | member::Error::CircuitOpen) => {
+                ("UNAVAILABLE", UNAVAILABLE_MESSAGE.to_string(), Vec::new())
+            }
+            // Adapter errors and internal failures
+            Error::Database(database::Error::Adapter(_))
+            | Error::Member(member::Error::Adapter(_))
+            | Error::Segment(segment::Error::Adapter(_))
+            | Error::Campaign(campaign::Error::Adapter(_))
+            | Error::Catalog(catalog::Error::Adapter(_))
+            | Error::Drawing(drawing::Error::Adapter(_))
+            | Error::CaseLock(case_lock::Error::Adapter(_))
+            | Error::Idempotency(idempotency::Error::Adapter(_))
+            | Error::Internal(_) => ("INTERNAL", INTERNAL_MESSAGE.to_string(), Vec::new()),
+        };
+
+        ErrorEnvelope {
+            code,
+            message,
+            details,
+            retryable: self.is_retryable(),
+            correlation_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{database, member};
+    use rstest::*;
+    use speculoos::prelude::*;
+    use uuid::Uuid;
+
+    fn envelope(
+        code: &'static str,
+        message: &str,
+        details: &[(&'static str, &str)],
+    ) -> ErrorEnvelope {
+        ErrorEnvelope {
+            code,
+            message: message.to_string(),
+            details: details
+                .iter()
+                .map(|(name, value)| ErrorDetail::new(name, value))
+                .collect(),
+            retryable: matches!(code, "UNAVAILABLE" | "CONFLICT" | "REQUEST_IN_PROGRESS"),
+            correlation_id: Some("REQUEST-1".to_string()),
+        }
+    }
+
+    #[rstest]
+    #[case(
+        member::Error::MemberDoesNotExist(Uuid::nil()).into(),
+        envelope(
+            "MEMBER_NOT_FOUND",
+            "member 00000000-0000-0000-0000-000000000000 does not exist",
+            &[("member_id", "00000000-0000-0000-0000-000000000000")],
+        ),
+    )]
+    #[case(
+        Error::InsufficientPoints { available: 40, requested: 100 },
+        envelope(
+            "INSUFFICIENT_POINTS",
+            "insufficient points: 100 requested, 40 available",
+            &[("available", "40"), ("requested", "100")],
+        ),
+    )]
+    #[case(
+        database::Error::HoldDoesNotExist(Uuid::nil()).into(),
+        envelope(
+            "HOLD_NOT_FOUND",
+            "hold 00000000-0000-0000-0000-000000000000 does not exist",
+            &[("hold_id", "00000000-0000-0000-0000-000000000000")],
+        ),
+    )]
+    #[case(
+        database::Error::EventAlreadyLinked(Uuid::nil()).into(),
+        envelope(
+            "EVENT_ALREADY_LINKED",
+            "event 00000000-0000-0000-0000-000000000000 is already linked to another event",
+            &[("event_id", "00000000-0000-0000-0000-000000000000")],
+        ),
+    )]
+    #[case(
+        database::Error::WelcomeBonusAlreadyGranted(Uuid::nil()).into(),
+        envelope(
+            "WELCOME_BONUS_ALREADY_GRANTED",
+            "member 00000000-0000-0000-0000-000000000000 already received their welcome bonus",
+            &[("member_id", "00000000-0000-0000-0000-000000000000")],
+        ),
+    )]
+    #[case(
+        database::Error::Conflict {
+            member_id: Uuid::nil(),
+            expected_version: 3,
+            current_version: 4,
+        }
+        .into(),
+        envelope(
+            "CONFLICT",
+            "loyalty of member 00000000-0000-0000-0000-000000000000 is at version 4, expected 3",
+            &[
+                ("member_id", "00000000-0000-0000-0000-000000000000"),
+                ("expected_version", "3"),
+                ("current_version", "4"),
+            ],
+        ),
+    )]
+    #[case(
+        Error::Suspended {
+            member_id: Uuid::nil(),
+            capability: Capability::Earn,
+            until: crate::testing::fixtures::now(),
+        },
+        envelope(
+            "EARN_SUSPENDED",
+            "member 00000000-0000-0000-0000-000000000000 cannot earn points until 2024-06-15 12:00:00 UTC",
+            &[
+                ("member_id", "00000000-0000-0000-0000-000000000000"),
+                ("until", "2024-06-15T12:00:00+00:00"),
+            ],
+        ),
+    )]
+    #[case(
+        Error::InvalidState("cannot transfer points to the same member".into()),
+        envelope("INVALID_REQUEST", "cannot transfer points to the same member", &[]),
+    )]
+    #[case(
+        crate::ports::idempotency::Error::InFlight("KEY".to_string()).into(),
+        envelope(
+            "REQUEST_IN_PROGRESS",
+            "a request with idempotency key KEY is already in progress",
+            &[],
+        ),
+    )]
+    #[case(
+        member::Error::Unavailable("connection timed out".into()).into(),
+        envelope("UNAVAILABLE", "service temporarily unavailable", &[]),
+    )]
+    #[case(
+        member::Error::CircuitOpen.into(),
+        envelope("UNAVAILABLE", "service temporarily unavailable", &[]),
+    )]
+    #[case(
+        database::Error::Adapter("permission denied on table loyalty".into()).into(),
+        envelope("INTERNAL", "internal error", &[]),
+    )]
+    #[case(
+        crate::ports::segment::Error::Adapter("segment not found".into()).into(),
+        envelope("INTERNAL", "internal error", &[]),
+    )]
+    fn test_envelope(#[case] err: Error, #[case] expected: ErrorEnvelope) {
+        assert_that!(err.envelope(Some("REQUEST-1".to_string()))).is_equal_to(expected);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DomainEvent, LoyaltyEvent},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{persist_with_events, DomainLogic, Error};
+
+/// Reason of the events removing expired points
+pub(super) const EXPIRATION_REASON: &str = "Points expired";
+
+/// Remove the unspent points of all lots that reached their expiration date
+///
+/// This is meant to run periodically, e.g. from a scheduled job. Expiration dates are set when
+/// points are added, based on the
+/// [`ExpirationPolicy`](crate::domain::ExpirationPolicy) of the member's tier at the time.
+pub struct ExpirePointsRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpirePointsResponse {
+    /// Lots that expired
+    pub expired: Vec<ExpiredPoints>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpiredPoints {
+    pub member_id: Uuid,
+    /// Event that added the expired points
+    pub event_id: Uuid,
+    /// Number of loyalty points removed
+    pub expired_points: u32,
+    /// New number of loyalty points
+    pub loyalty_points: u32,
+}
+
+impl<D, M> Service<ExpirePointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ExpirePointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: ExpirePointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let mut expired = Vec::new();
+            for (member_id, lot) in database.get_expired_lots(clock.now()).await? {
+                let delta_points = i32::try_from(lot.points).map_err(|_| {
+                    Error::Internal(format!("cannot expire {} points at once", lot.points).into())
+                })?;
+
+                // Removing points takes them from the lots expiring soonest, i.e. this one
+                let event_id = id_generator.generate_id();
+                let points_expired = DomainEvent::PointsExpired {
+                    member_id,
+                    event_id,
+                    loyalty_points: lot.points,
+                };
+                let loyalty = persist_with_events(
+                    event_publisher.as_ref(),
+                    outbox,
+                    vec![points_expired],
+                    |events| {
+                        database.register_loyalty_event_with_outbox(
+                            member_id,
+                            LoyaltyEvent::new(
+                                event_id,
+                                -delta_points,
+                                EXPIRATION_REASON,
+                                clock.now(),
+                            ),
+                            None,
+                            events,
+                        )
+                    },
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "expiring points of event {} for member {}",
+                        lot.event_id, member_id
+                    )
+                })?;
+
+                expired.push(ExpiredPoints {
+                    member_id,
+                    event_id: lot.event_id,
+                    expired_points: lot.points,
+                    loyalty_points: loyalty.points,
+                });
+            }
+
+            Ok(ExpirePointsResponse { expired })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{database::memory::MemoryDatabase, id_generator::sequential::SequentialIds},
+        ports::member::MockMemberPort,
+    };
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with an expired lot, an expiring lot, points that never expire, and
+        // some spent points
+        let member_id = Uuid::new_v4();
+        let expired_event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        for (event_id, delta_points, expires_at) in [
+            (Uuid::new_v4(), 50, Some(Utc::now() + Duration::days(1))),
+            (expired_event_id, 100, Some(Utc::now() - Duration::days(1))),
+            (Uuid::new_v4(), 30, None),
+            (Uuid::new_v4(), -40, None),
+        ] {
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        expires_at,
+                        ..LoyaltyEvent::new(event_id, delta_points, "SOME REASON", Utc::now())
+                    },
+                    None,
+                )
+                .await?;
+        }
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN expiring points
+        let res = ServiceExt::<ExpirePointsRequest>::ready(&mut domain)
+            .await?
+            .call(ExpirePointsRequest)
+            .await;
+
+        // THEN only the unspent points of the expired lot are removed
+        assert_that!(res).is_ok().is_equal_to(ExpirePointsResponse {
+            expired: vec![ExpiredPoints {
+                member_id,
+                event_id: expired_event_id,
+                expired_points: 60,
+                loyalty_points: 80,
+            }],
+        });
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.expiring_lots).has_length(1);
+        assert_that!(loyalty.expiring_lots[0].points).is_equal_to(50);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN a member with an expired lot and the outbox enabled
+        let member_id = Uuid::new_v4();
+        let expired_event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent {
+                    expires_at: Some(Utc::now() - Duration::days(1)),
+                    ..LoyaltyEvent::new(expired_event_id, 100, "SOME REASON", Utc::now())
+                },
+                None,
+            )
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_id_generator(Arc::new(SequentialIds::default()))
+                .with_outbox();
+
+        // WHEN expiring points
+        ServiceExt::<ExpirePointsRequest>::ready(&mut domain)
+            .await?
+            .call(ExpirePointsRequest)
+            .await?;
+
+        // THEN the expired points are stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsExpired {
+            member_id,
+            event_id: Uuid::from_u128(1),
+            loyalty_points: 100,
+        }]);
+
+        Ok(())
+    }
+}
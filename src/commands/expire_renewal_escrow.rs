@@ -0,0 +1,145 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::ports::{
+    database::{self, DatabasePort},
+    member::MemberPort,
+    ResultExt,
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Discard renewal points still in escrow after their timeout
+///
+/// This is meant to run periodically, e.g. from a scheduled job. Payments the billing service did
+/// not report on in time are treated as failed, see [`DomainLogic::with_renewal_escrow`].
+pub struct ExpireRenewalEscrowRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpireRenewalEscrowResponse {
+    /// Renewal points that were discarded
+    pub discarded: Vec<DiscardedEscrow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscardedEscrow {
+    pub member_id: Uuid,
+    pub event_id: Uuid,
+    /// Number of loyalty points the renewal would have added
+    pub loyalty_points: u32,
+}
+
+impl<D, M> Service<ExpireRenewalEscrowRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ExpireRenewalEscrowResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: ExpireRenewalEscrowRequest) -> Self::Future {
+        let database = self.database.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            let mut discarded = Vec::new();
+            let expired = database
+                .get_expired_escrow_events(clock.now())
+                .await
+                .context("fetching expired escrow")?;
+            for (member_id, event) in expired {
+                let res = database.reject_event(member_id, event.event_id).await;
+                // Settled since it was fetched
+                if let Err(database::Error::EventDoesNotExist(_)) = res {
+                    continue;
+                }
+                res.with_context(|| format!("discarding escrowed event {}", event.event_id))?;
+                discarded.push(DiscardedEscrow {
+                    member_id,
+                    event_id: event.event_id,
+                    loyalty_points: event.delta_points.unsigned_abs(),
+                });
+            }
+
+            Ok(ExpireRenewalEscrowResponse { discarded })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::clock::fixed::FixedClock,
+        commands::add_points::{AddPointsEvent, AddPointsRequest},
+        testing::fixtures::{self, Fixture, MemberBuilder},
+    };
+    use chrono::Duration;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member who renewed their membership with a 3-day escrow
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let mut domain = domain.with_renewal_escrow(Duration::days(3));
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::MembershipRenewed,
+                occurred_at: None,
+                order_reference: Some("INV-1".to_string()),
+                idempotency_key: None,
+            })
+            .await?;
+        let escrowed = database.get_events_awaiting_approval(member_id).await?;
+
+        // WHEN expiring escrow two and four days later
+        let mut results = Vec::new();
+        for days in [2, 4] {
+            let mut domain = domain.clone().with_clock(Arc::new(FixedClock::new(
+                fixtures::now() + Duration::days(days),
+            )));
+            let res = ServiceExt::<ExpireRenewalEscrowRequest>::ready(&mut domain)
+                .await?
+                .call(ExpireRenewalEscrowRequest)
+                .await;
+            results.push(res);
+        }
+
+        // THEN the renewal points are only discarded once the escrow expired
+        assert_that!(results[0])
+            .is_ok()
+            .is_equal_to(ExpireRenewalEscrowResponse {
+                discarded: Vec::new(),
+            });
+        assert_that!(results[1])
+            .is_ok()
+            .is_equal_to(ExpireRenewalEscrowResponse {
+                discarded: vec![DiscardedEscrow {
+                    member_id,
+                    event_id: escrowed[0].event_id,
+                    loyalty_points: 290,
+                }],
+            });
+        assert_that!(database.get_events_awaiting_approval(member_id).await?).is_empty();
+        assert_that!(database.get_loyalty_points(member_id).await?.points).is_equal_to(100);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{MemberOverride, Tier},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{add_points::membership_months, DomainLogic, Error};
+
+/// Inputs and rules that resolve a member's tier, for support to answer tier questions
+pub struct ExplainTierRequest {
+    pub member_id: Uuid,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplainTierResponse {
+    pub member_id: Uuid,
+    pub tier: Tier,
+    /// Whether the membership is active, as reported by the member port
+    pub active_member: bool,
+    /// Start of the current membership
+    pub membership_since: DateTime<Utc>,
+    /// Number of continuous months of membership, or `None` for non-members
+    pub membership_months: Option<u32>,
+    /// Rule that selected the tier
+    pub rule: TierRule,
+    /// Next tier, and the number of months of membership needed to reach it
+    pub next_tier: Option<(Tier, u32)>,
+    /// Active override replacing the tier's earn ratio
+    ///
+    /// Overrides do not change the tier itself.
+    pub member_override: Option<MemberOverride>,
+    /// Number of points per currency unit on purchases, before multipliers
+    pub earn_ratio: i32,
+}
+
+/// Rule that selected a member's tier
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TierRule {
+    /// The membership is not active, so there is no tier
+    InactiveMember,
+    /// The number of continuous months of membership is within this range
+    MembershipMonths { min: u32, max: Option<u32> },
+}
+
+impl<D, M> Service<ExplainTierRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ExplainTierResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ExplainTierRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let earning_policy = self.earning_policy.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            let now = clock.now();
+            let member_override = database
+                .get_member_overrides(db_member.member_id)
+                .await
+                .with_context(|| format!("fetching overrides for member {}", req.member_id))?
+                .into_iter()
+                .rev()
+                .find(|member_override| member_override.is_active(now));
+
+            let membership_months = membership_months(&db_member, now)?;
+            let tier = Tier::from_membership_months(membership_months);
+            let rule = match tier.min_membership_months() {
+                Some(min) => TierRule::MembershipMonths {
+                    min,
+                    max: tier
+                        .next()
+                        .and_then(|next| next.min_membership_months())
+                        .map(|months| months - 1),
+                },
+                None => TierRule::InactiveMember,
+            };
+            let next_tier = tier.next().and_then(|next| {
+                let months = next.min_membership_months()?;
+                Some((next, months))
+            });
+            let earn_ratio = match &member_override {
+                Some(member_override) => member_override.earn_ratio as i32,
+                None => earning_policy.ratio(&tier),
+            };
+
+            Ok(ExplainTierResponse {
+                member_id: db_member.member_id,
+                tier,
+                active_member: db_member.active_member,
+                membership_since: db_member.membership_since,
+                membership_months,
+                rule,
+                next_tier,
+                member_override,
+                earn_ratio,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{clock::fixed::FixedClock, database::memory::MemoryDatabase},
+        ports::member::MockMemberPort,
+    };
+    use chrono::{Duration, TimeZone};
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[rstest]
+    #[case(true, Tier::Gold, TierRule::MembershipMonths { min: 24, max: Some(35) }, Some((Tier::Platinum, 36)))]
+    #[case(false, Tier::None, TierRule::InactiveMember, Some((Tier::Basic, 0)))]
+    #[tokio::test]
+    async fn test_call(
+        #[case] active_member: bool,
+        #[case] expected_tier: Tier,
+        #[case] expected_rule: TierRule,
+        #[case] expected_next_tier: Option<(Tier, u32)>,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member with about two years of membership, and an override
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        database
+            .register_member_override(MemberOverride {
+                override_id: Uuid::new_v4(),
+                member_id,
+                earn_ratio: 30,
+                reason: "SOME REASON".to_string(),
+                granted_by: "agent-42".to_string(),
+                granted_at: Utc::now() - Duration::days(1),
+                expires_at: Utc::now() + Duration::days(1),
+            })
+            .await?;
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member));
+
+        // WHEN explaining the member's tier
+        let res = ServiceExt::<ExplainTierRequest>::ready(&mut domain)
+            .await?
+            .call(ExplainTierRequest { member_id })
+            .await;
+
+        // THEN it lists the rule and the override in effect
+        assert_that!(res).is_ok().matches(|res| {
+            res.tier == expected_tier
+                && res.rule == expected_rule
+                && res.next_tier == expected_next_tier
+                && res.member_override.is_some()
+                && res.earn_ratio == 30
+        });
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case((2024, 6, 14), Tier::Silver)]
+    #[case((2024, 6, 15), Tier::Gold)]
+    #[tokio::test]
+    async fn test_call_anniversary(
+        #[case] today: (i32, u32, u32),
+        #[case] expected_tier: Tier,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member since June 15th, 2022
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc.with_ymd_and_hms(2022, 6, 15, 9, 0, 0).unwrap(),
+                ..Default::default()
+            })
+        });
+        let clock = FixedClock::new(
+            Utc.with_ymd_and_hms(today.0, today.1, today.2, 9, 0, 0)
+                .unwrap(),
+        );
+        let mut domain = DomainLogic::new(Arc::new(MemoryDatabase::default()), Arc::new(member))
+            .with_clock(Arc::new(clock));
+
+        // WHEN explaining the member's tier
+        let res = ServiceExt::<ExplainTierRequest>::ready(&mut domain)
+            .await?
+            .call(ExplainTierRequest { member_id })
+            .await;
+
+        // THEN the member reaches Gold on their second anniversary
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.tier == expected_tier);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{LoyaltyEvent, Tier},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use chrono::{DateTime, Months, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{add_points::evaluate_tier, DomainLogic, Error};
+
+/// Number of past months averaged to forecast future earnings
+const HISTORY_MONTHS: u32 = 6;
+
+/// Maximum number of months to forecast
+const MAX_FORECAST_MONTHS: u32 = 24;
+
+/// Project a member's balance and upcoming tiers, e.g. to show when they reach the next tier
+///
+/// The balance assumes the member keeps earning their average monthly points over the last six
+/// months, starting from their available and pending points. It ignores redemptions and
+/// expirations. Tiers only depend on the length of the membership, so their dates are exact for
+/// members who stay active.
+pub struct ForecastPointsRequest {
+    pub member_id: Uuid,
+    /// Number of months to forecast, at most 24
+    pub months: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForecastPointsResponse {
+    pub member_id: Uuid,
+    pub tier: Tier,
+    /// Average points earned per month over the last six months
+    pub monthly_average: u32,
+    /// Projected balance at the end of each month, in chronological order
+    pub projections: Vec<PointsProjection>,
+    /// Date at which each upcoming tier is reached, in chronological order
+    ///
+    /// This is empty for members without an active membership.
+    pub tier_milestones: Vec<TierMilestone>,
+}
+
+/// Projected balance at a given date
+///
+/// The range covers one standard deviation of the monthly earnings, growing with the square root of
+/// the number of months.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointsProjection {
+    pub at: DateTime<Utc>,
+    pub loyalty_points: u32,
+    /// Lower bound of the projection, never below the current balance
+    pub low: u32,
+    /// Upper bound of the projection
+    pub high: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TierMilestone {
+    pub tier: Tier,
+    pub reached_at: DateTime<Utc>,
+}
+
+impl<D, M> Service<ForecastPointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ForecastPointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ForecastPointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            if req.months == 0 || req.months > MAX_FORECAST_MONTHS {
+                return Err(Error::InvalidState(
+                    format!(
+                        "cannot forecast {} months, expected 1 to {}",
+                        req.months, MAX_FORECAST_MONTHS
+                    )
+                    .into(),
+                ));
+            }
+
+            // Fetch necessary data
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            let loyalty = database
+                .get_loyalty_points(req.member_id)
+                .await
+                .with_context(|| format!("fetching loyalty for member {}", req.member_id))?;
+            let now = clock.now();
+            let tier = evaluate_tier(&db_member, now)?.tier;
+
+            // Fit the average and spread of monthly earnings
+            let earnings = monthly_earnings(&loyalty.events, now);
+            let mean = earnings.iter().sum::<f64>() / earnings.len() as f64;
+            let variance = earnings
+                .iter()
+                .map(|points| (points - mean).powi(2))
+                .sum::<f64>()
+                / earnings.len() as f64;
+            let std_dev = variance.sqrt();
+
+            let balance = f64::from(loyalty.points + loyalty.pending_points);
+            let projections = (1..=req.months)
+                .map(|month| {
+                    let projected = balance + mean * f64::from(month);
+                    let spread = std_dev * f64::from(month).sqrt();
+                    PointsProjection {
+                        at: now + Months::new(month),
+                        loyalty_points: projected.round() as u32,
+                        low: (projected - spread).max(balance).round() as u32,
+                        high: (projected + spread).round() as u32,
+                    }
+                })
+                .collect();
+
+            let tier_milestones = if db_member.active_member {
+                std::iter::successors(tier.next(), Tier::next)
+                    .filter_map(|tier| {
+                        let months = tier.min_membership_months()?;
+                        Some(TierMilestone {
+                            tier,
+                            reached_at: db_member.membership_since + Months::new(months),
+                        })
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            Ok(ForecastPointsResponse {
+                member_id: req.member_id,
+                tier,
+                monthly_average: mean.round() as u32,
+                projections,
+                tier_milestones,
+            })
+        })
+    }
+}
+
+/// Points earned in each of the last months before `now`, newest first
+///
+/// Snapshots are skipped, as they replace events that may span multiple months.
+fn monthly_earnings(events: &[LoyaltyEvent], now: DateTime<Utc>) -> Vec<f64> {
+    (0..HISTORY_MONTHS)
+        .map(|month| {
+            let end = now - Months::new(month);
+            let start = now - Months::new(month + 1);
+            events
+                .iter()
+                .filter(|event| event.snapshot.is_none() && event.delta_points > 0)
+                .filter(|event| start < event.created_at && event.created_at <= end)
+                .map(|event| f64::from(event.delta_points))
+                .sum()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{self, Fixture, MemberBuilder};
+    use rstest::*;
+    use speculoos::prelude::*;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a Silver member with 900 points, alternating between 100 and 200 points per month
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::tier(Tier::Silver).build().await?;
+        let mut domain = domain;
+        for month in 0..6 {
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent::new(
+                        Uuid::new_v4(),
+                        if month % 2 == 0 { 100 } else { 200 },
+                        "Online purchase",
+                        fixtures::now() - Months::new(month),
+                    ),
+                    None,
+                )
+                .await?;
+        }
+
+        // WHEN forecasting the next three months
+        let res = ServiceExt::<ForecastPointsRequest>::ready(&mut domain)
+            .await?
+            .call(ForecastPointsRequest {
+                member_id,
+                months: 3,
+            })
+            .await;
+
+        // THEN
+        // * the balance grows by the monthly average, within one standard deviation
+        // * Gold and Platinum are reached after 24 and 36 months of membership
+        let now = fixtures::now();
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(ForecastPointsResponse {
+                member_id,
+                tier: Tier::Silver,
+                monthly_average: 150,
+                projections: vec![
+                    PointsProjection {
+                        at: now + Months::new(1),
+                        loyalty_points: 1050,
+                        low: 1000,
+                        high: 1100,
+                    },
+                    PointsProjection {
+                        at: now + Months::new(2),
+                        loyalty_points: 1200,
+                        low: 1129,
+                        high: 1271,
+                    },
+                    PointsProjection {
+                        at: now + Months::new(3),
+                        loyalty_points: 1350,
+                        low: 1263,
+                        high: 1437,
+                    },
+                ],
+                tier_milestones: vec![
+                    TierMilestone {
+                        tier: Tier::Gold,
+                        reached_at: now + Months::new(12),
+                    },
+                    TierMilestone {
+                        tier: Tier::Platinum,
+                        reached_at: now + Months::new(24),
+                    },
+                ],
+            });
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(25)]
+    #[tokio::test]
+    async fn test_call_invalid_months(#[case] months: u32) -> Result<(), BoxError> {
+        // GIVEN a member
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain;
+
+        // WHEN forecasting an invalid number of months
+        let res = ServiceExt::<ForecastPointsRequest>::ready(&mut domain)
+            .await?
+            .call(ForecastPointsRequest { member_id, months })
+            .await;
+
+        // THEN it returns an error
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+
+        Ok(())
+    }
+}
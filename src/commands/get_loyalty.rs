@@ -0,0 +1,271 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{LoyaltyEvent, Member, Tier},
+    layers::capture::{Anonymize, Pseudonyms},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{
+    add_points::{evaluate_tier, save_tier_evaluation},
+    DomainLogic, Error,
+};
+
+/// Summary of a member's loyalty
+///
+/// The tier comes from the member's stored tier evaluation while it is fresh, without calling the
+/// member port. See [`DomainLogic::with_tier_max_age`].
+pub struct GetLoyaltyRequest {
+    pub member_id: Uuid,
+    /// Maximum number of recent events to return
+    pub recent_events: usize,
+}
+
+impl Anonymize for GetLoyaltyRequest {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        Self {
+            member_id: pseudonyms.id(self.member_id),
+            recent_events: self.recent_events,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GetLoyaltyResponse {
+    pub member_id: Uuid,
+    pub tier: Tier,
+    /// Number of continuous months of membership, or `None` for non-members
+    pub membership_months: Option<u32>,
+    /// When the tier and membership months were evaluated
+    pub tier_evaluated_at: DateTime<Utc>,
+    /// Current number of loyalty points available
+    pub loyalty_points: u32,
+    /// Number of loyalty points earned but not available yet
+    pub pending_loyalty_points: u32,
+    /// Number of loyalty points reserved by holds, not included in `loyalty_points`
+    pub held_loyalty_points: u32,
+    /// Most recent events, newest first
+    pub recent_events: Vec<LoyaltyEvent>,
+}
+
+impl Anonymize for GetLoyaltyResponse {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        let recent_events = self
+            .recent_events
+            .iter()
+            .map(|event| LoyaltyEvent {
+                reason: pseudonyms.reason(&event.reason),
+                order_reference: event
+                    .order_reference
+                    .as_deref()
+                    .and_then(|reference| pseudonyms.order_reference(reference)),
+                ..event.clone()
+            })
+            .collect();
+        Self {
+            member_id: pseudonyms.id(self.member_id),
+            recent_events,
+            ..self.clone()
+        }
+    }
+}
+
+impl<D, M> Service<GetLoyaltyRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = GetLoyaltyResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: GetLoyaltyRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let tier_max_age = self.tier_max_age;
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            // Fetch necessary data
+            let loyalty = database
+                .get_loyalty_points(req.member_id)
+                .await
+                .with_context(|| format!("fetching loyalty for member {}", req.member_id))?;
+
+            // Only ask the member port when the stored tier is missing or stale
+            let now = clock.now();
+            let evaluation = match loyalty.tier {
+                Some(evaluation) if !evaluation.is_stale(tier_max_age, now) => evaluation,
+                _ => {
+                    let db_member = member
+                        .get_member(req.member_id)
+                        .await
+                        .with_context(|| format!("fetching member {}", req.member_id))?;
+                    let evaluation = evaluate_tier(&db_member, now)?;
+                    save_tier_evaluation(
+                        database.as_ref(),
+                        event_publisher.as_ref(),
+                        outbox,
+                        evaluation.clone(),
+                    )
+                    .await?;
+                    evaluation
+                }
+            };
+            let member = Member::new(evaluation.member_id, evaluation.membership_months);
+
+            Ok(GetLoyaltyResponse {
+                member_id: member.member_id,
+                tier: member.tier(),
+                membership_months: evaluation.membership_months,
+                tier_evaluated_at: evaluation.evaluated_at,
+                loyalty_points: loyalty.points,
+                pending_loyalty_points: loyalty.pending_points,
+                held_loyalty_points: loyalty.held_points,
+                recent_events: loyalty
+                    .events
+                    .into_iter()
+                    .rev()
+                    .take(req.recent_events)
+                    .collect(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase, domain::TierEvaluation,
+        ports::member::MockMemberPort,
+    };
+    use chrono::Duration;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a Gold member with 3 events
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        for (delta_points, matures_at) in [
+            (100, None),
+            (50, None),
+            (20, Some(Utc::now() + Duration::days(1))),
+        ] {
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        matures_at,
+                        ..LoyaltyEvent::new(Uuid::new_v4(), delta_points, "SOME REASON", Utc::now())
+                    },
+                    None,
+                )
+                .await?;
+        }
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
+
+        // WHEN fetching the summary with the 2 most recent events
+        let res = ServiceExt::<GetLoyaltyRequest>::ready(&mut domain)
+            .await?
+            .call(GetLoyaltyRequest {
+                member_id,
+                recent_events: 2,
+            })
+            .await?;
+
+        // THEN it contains the points, tier, and recent events
+        assert_that!(res.tier).is_equal_to(Tier::Gold);
+        assert_that!(res.loyalty_points).is_equal_to(150);
+        assert_that!(res.pending_loyalty_points).is_equal_to(20);
+        assert_that!(res
+            .recent_events
+            .iter()
+            .map(|event| event.sequence)
+            .collect::<Vec<_>>())
+        .is_equal_to(vec![3, 2]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(Duration::hours(1), 0, Tier::Silver)]
+    #[case(Duration::days(2), 1, Tier::Gold)]
+    #[tokio::test]
+    async fn test_call_stored_tier(
+        #[case] evaluation_age: Duration,
+        #[case] expected_member_calls: usize,
+        #[case] expected_tier: Tier,
+    ) -> Result<(), BoxError> {
+        // GIVEN a Gold member whose stored tier evaluation is Silver
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .times(expected_member_calls)
+            .returning(move |_| {
+                Ok(crate::ports::member::Member {
+                    active_member: true,
+                    member_id,
+                    membership_since: Utc::now() - Duration::days(800),
+                    ..Default::default()
+                })
+            });
+        let database = MemoryDatabase::default();
+        database
+            .save_tier_evaluation(
+                TierEvaluation {
+                    member_id,
+                    tier: Tier::Silver,
+                    membership_months: Some(23),
+                    evaluated_at: Utc::now() - evaluation_age,
+                },
+                false,
+            )
+            .await?;
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
+
+        // WHEN fetching the summary
+        let res = ServiceExt::<GetLoyaltyRequest>::ready(&mut domain)
+            .await?
+            .call(GetLoyaltyRequest {
+                member_id,
+                recent_events: 0,
+            })
+            .await?;
+
+        // THEN a fresh evaluation is used as is, and a stale one is refreshed
+        assert_that!(res.tier).is_equal_to(&expected_tier);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.tier)
+            .is_some()
+            .matches(|evaluation| evaluation.tier == expected_tier);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{CaseLock, MemberOverride},
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{foreign_case_lock, DomainLogic, Error};
+
+/// Grant a support exception to a member
+pub struct GrantOverrideRequest {
+    pub member_id: Uuid,
+    /// Custom earn ratio on purchases
+    pub earn_ratio: u32,
+    /// Message explaining why the exception is granted
+    pub reason: String,
+    /// Support agent granting the exception
+    pub granted_by: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrantOverrideResponse {
+    pub member_override: MemberOverride,
+    /// Warning set when another agent holds a case lock on the member
+    ///
+    /// The override is still granted, but it might conflict with the other agent's work.
+    pub case_lock: Option<CaseLock>,
+}
+
+impl<D, M> Service<GrantOverrideRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = GrantOverrideResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: GrantOverrideRequest) -> Self::Future {
+        let member = self.member.clone();
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let case_lock = self.case_lock.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            let now = clock.now();
+            if req.earn_ratio > MemberOverride::MAX_EARN_RATIO {
+                return Err(Error::InvalidState(
+                    format!(
+                        "earn ratio {} is above the maximum of {}",
+                        req.earn_ratio,
+                        MemberOverride::MAX_EARN_RATIO
+                    )
+                    .into(),
+                ));
+            }
+            if req.expires_at <= now {
+                return Err(Error::InvalidState(
+                    format!("override would expire in the past: {}", req.expires_at).into(),
+                ));
+            }
+
+            // Make sure the member exists
+            let db_member = member.get_member(req.member_id).await?;
+            let case_lock = foreign_case_lock(case_lock, req.member_id, &req.granted_by, now).await;
+
+            let member_override = MemberOverride {
+                override_id: id_generator.generate_id(),
+                member_id: db_member.member_id,
+                earn_ratio: req.earn_ratio,
+                reason: req.reason,
+                granted_by: req.granted_by,
+                granted_at: now,
+                expires_at: req.expires_at,
+            };
+            database
+                .register_member_override(member_override.clone())
+                .await?;
+
+            Ok(GrantOverrideResponse {
+                member_override,
+                case_lock,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{database::memory::MemoryDatabase, id_generator::sequential::SequentialIds},
+        ports::member::MockMemberPort,
+    };
+    use chrono::Duration;
+    use mockall::predicate::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn request(member_id: Uuid, expires_at: DateTime<Utc>) -> GrantOverrideRequest {
+        GrantOverrideRequest {
+            member_id,
+            earn_ratio: 25,
+            reason: "Compensation for delayed order".to_string(),
+            granted_by: "agent-42".to_string(),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN an existing member
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .times(1)
+            .with(eq(member_id))
+            .returning(move |_| {
+                Ok(crate::ports::member::Member {
+                    active_member: true,
+                    member_id,
+                    membership_since: Utc::now(),
+                    ..Default::default()
+                })
+            });
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_id_generator(Arc::new(SequentialIds::default()));
+
+        // WHEN granting an override
+        let req = request(member_id, Utc::now() + Duration::days(30));
+        let res = ServiceExt::<GrantOverrideRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN it is stored in the database, with a generated ID
+        assert_that!(res).is_ok().matches(|res| {
+            res.member_override.override_id == Uuid::from_u128(1) && res.case_lock.is_none()
+        });
+        let stored = database.get_member_overrides(member_id).await?;
+        assert_that!(stored).has_length(1);
+        assert_that!(stored[0].earn_ratio).is_equal_to(25);
+        assert_that!(stored[0].granted_by.as_str()).is_equal_to("agent-42");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_earn_ratio_too_high() -> Result<(), BoxError> {
+        // GIVEN a member port that should not be called
+        let member = MockMemberPort::new();
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
+
+        // WHEN granting an override above the maximum earn ratio
+        let member_id = Uuid::new_v4();
+        let req = GrantOverrideRequest {
+            earn_ratio: MemberOverride::MAX_EARN_RATIO + 1,
+            ..request(member_id, Utc::now() + Duration::days(30))
+        };
+        let res = ServiceExt::<GrantOverrideRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN it fails without storing the override
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        assert_that!(database.get_member_overrides(member_id).await?).is_empty();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_expired() -> Result<(), BoxError> {
+        // GIVEN a member port that should not be called
+        let member = MockMemberPort::new();
+        let mut domain = DomainLogic::new(Arc::new(MemoryDatabase::default()), Arc::new(member));
+
+        // WHEN granting an override that already expired
+        let req = request(Uuid::new_v4(), Utc::now() - Duration::days(1));
+        let res = ServiceExt::<GrantOverrideRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN it returns an error
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+
+        Ok(())
+    }
+}
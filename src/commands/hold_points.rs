@@ -0,0 +1,226 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, PointsHold},
+    layers::idempotency::IdempotentRequest,
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, DomainLogic, Error};
+
+/// Reserve available points for a checkout, before its payment settles
+///
+/// Held points cannot be spent, but are not deducted until the hold is captured with
+/// [`CapturePointsRequest`](super::capture_points::CapturePointsRequest). Releasing the hold
+/// with [`ReleasePointsRequest`](super::release_points::ReleasePointsRequest) makes them
+/// available again.
+pub struct HoldPointsRequest {
+    pub member_id: Uuid,
+    pub loyalty_points: u32,
+    /// Message explaining the reservation, recorded in the event reason on capture
+    pub reason: String,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for HoldPointsRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HoldPointsResponse {
+    pub member_id: Uuid,
+    pub hold_id: Uuid,
+    /// New number of loyalty points available
+    pub new_loyalty_points: u32,
+    /// New number of loyalty points reserved by all holds
+    pub held_loyalty_points: u32,
+}
+
+impl<D, M> Service<HoldPointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = HoldPointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: HoldPointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            if req.loyalty_points == 0 || i32::try_from(req.loyalty_points).is_err() {
+                return Err(Error::InvalidState(
+                    format!("cannot hold {} points", req.loyalty_points).into(),
+                ));
+            }
+
+            // Make sure the member exists, and can spend the points once captured
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            let now = clock.now();
+            ensure_capability(
+                database.as_ref(),
+                db_member.member_id,
+                Capability::Redeem,
+                now,
+            )
+            .await?;
+
+            let hold_id = id_generator.generate_id();
+            let res = database
+                .place_hold(
+                    db_member.member_id,
+                    PointsHold {
+                        hold_id,
+                        points: req.loyalty_points,
+                        reason: req.reason,
+                        created_at: now,
+                    },
+                )
+                .await
+                .with_context(|| format!("placing hold for member {}", req.member_id));
+            let updated_loyalty = match res {
+                Ok(updated_loyalty) => updated_loyalty,
+                Err(crate::ports::database::Error::NegativePointsTotal {
+                    current_points, ..
+                }) => {
+                    return Err(Error::InsufficientPoints {
+                        available: current_points,
+                        requested: req.loyalty_points,
+                    })
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            Ok(HoldPointsResponse {
+                member_id: db_member.member_id,
+                hold_id,
+                new_loyalty_points: updated_loyalty.points,
+                held_loyalty_points: updated_loyalty.held_points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::redeem_points::RedeemPointsRequest,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use speculoos::prelude::*;
+    use tower::{BoxError, ServiceExt};
+
+    fn request(member_id: Uuid, loyalty_points: u32) -> HoldPointsRequest {
+        HoldPointsRequest {
+            member_id,
+            loyalty_points,
+            reason: "Order 42".to_string(),
+            idempotency_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with 100 points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let mut domain = domain;
+
+        // WHEN holding 70 points
+        let res = ServiceExt::<HoldPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, 70))
+            .await;
+
+        // THEN
+        // * the points are reserved without any event
+        // * they cannot be spent anymore
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.new_loyalty_points == 30 && res.held_loyalty_points == 70);
+        assert_that!(database.get_loyalty_events(member_id).await?).has_length(1);
+
+        let res = ServiceExt::<RedeemPointsRequest>::ready(&mut domain)
+            .await?
+            .call(RedeemPointsRequest {
+                member_id,
+                loyalty_points: 50,
+                reward: "Free coffee".to_string(),
+                idempotency_key: None,
+            })
+            .await;
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::InsufficientPoints {
+                    available: 30,
+                    requested: 50
+                }
+            )
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_insufficient_points() -> Result<(), BoxError> {
+        // GIVEN a member with 100 points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let mut domain = domain;
+
+        // WHEN holding more points than they have
+        let res = ServiceExt::<HoldPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, 150))
+            .await;
+
+        // THEN it fails without reserving anything
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::InsufficientPoints {
+                    available: 100,
+                    requested: 150
+                }
+            )
+        });
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(100);
+        assert_that!(loyalty.holds).is_empty();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, DomainEvent, ExternalSource, LoyaltyEvent},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, retry_on_conflict, DomainLogic, Error};
+
+/// Match the points from another loyalty program's statement, once per member
+///
+/// See [`DomainLogic::with_statement_matching`] for the match ratio and cap.
+pub struct ImportExternalStatementRequest {
+    pub member_id: Uuid,
+    /// Parsed statement from the other program
+    pub statement: ExternalStatement,
+}
+
+pub struct ExternalStatement {
+    /// Name of the other program
+    pub program: String,
+    /// Identifier of the statement in the other program
+    pub statement_id: String,
+    /// Points balance on the statement
+    pub points: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportExternalStatementResponse {
+    pub member_id: Uuid,
+    /// Number of points granted for the statement
+    pub matched_points: u32,
+    /// New number of loyalty points
+    pub new_loyalty_points: u32,
+}
+
+impl<D, M> Service<ImportExternalStatementRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ImportExternalStatementResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ImportExternalStatementRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let statement_matching = self.statement_matching.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let conflict_retries = self.conflict_retries;
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let statement_matching = statement_matching.ok_or_else(|| {
+                Error::InvalidState("statement imports are not configured".into())
+            })?;
+
+            // Make sure the member exists
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            ensure_capability(
+                database.as_ref(),
+                db_member.member_id,
+                Capability::Earn,
+                clock.now(),
+            )
+            .await?;
+
+            // Register the matched points, checking again if a concurrent import came first
+            let matched_points = statement_matching.matched_points(req.statement.points);
+            let loyalty = retry_on_conflict(conflict_retries, || async {
+                let loyalty = database
+                    .get_loyalty_points(db_member.member_id)
+                    .await
+                    .with_context(|| format!("fetching loyalty for member {}", req.member_id))?;
+                if let Some(source) = loyalty
+                    .events
+                    .iter()
+                    .find_map(|event| event.external_source.as_ref())
+                {
+                    return Err(Error::InvalidState(
+                        format!(
+                            "member {} already imported statement {} from {}",
+                            req.member_id, source.statement_id, source.program
+                        )
+                        .into(),
+                    ));
+                }
+
+                let event = LoyaltyEvent {
+                    external_source: Some(ExternalSource {
+                        program: req.statement.program.clone(),
+                        statement_id: req.statement.statement_id.clone(),
+                    }),
+                    ..LoyaltyEvent::new(
+                        id_generator.generate_id(),
+                        matched_points as i32,
+                        format!("Statement match from {}", req.statement.program),
+                        clock.now(),
+                    )
+                };
+                let points_added = DomainEvent::PointsAdded {
+                    member_id: db_member.member_id,
+                    event_id: event.event_id,
+                    loyalty_points: matched_points,
+                };
+                let loyalty = persist_with_events(
+                    event_publisher.as_ref(),
+                    outbox,
+                    vec![points_added],
+                    |events| {
+                        database.register_loyalty_event_with_outbox(
+                            db_member.member_id,
+                            event,
+                            Some(loyalty.version),
+                            events,
+                        )
+                    },
+                )
+                .await
+                .with_context(|| format!("registering event for member {}", req.member_id))?;
+                Ok(loyalty)
+            })
+            .await?;
+
+            Ok(ImportExternalStatementResponse {
+                member_id: db_member.member_id,
+                matched_points,
+                new_loyalty_points: loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{database::memory::MemoryDatabase, id_generator::sequential::SequentialIds},
+        domain::{Loyalty, StatementMatching},
+        ports::{database::MockDatabasePort, member::MockMemberPort},
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use chrono::Utc;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn statement(points: u32) -> ExternalStatement {
+        ExternalStatement {
+            program: "Other Rewards".to_string(),
+            statement_id: "STATEMENT-1".to_string(),
+            points,
+        }
+    }
+
+    #[rstest]
+    #[case(1000, 500)]
+    #[case(10000, 2000)]
+    #[tokio::test]
+    async fn test_call(#[case] points: u32, #[case] expected: u32) -> Result<(), BoxError> {
+        // GIVEN
+        // * an existing member
+        // * statements matched at 50%, up to 2000 points
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_statement_matching(StatementMatching {
+                ratio_percent: 50,
+                cap: 2000,
+            });
+
+        // WHEN importing a statement twice
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let res = ServiceExt::<ImportExternalStatementRequest>::ready(&mut domain)
+                .await?
+                .call(ImportExternalStatementRequest {
+                    member_id,
+                    statement: statement(points),
+                })
+                .await;
+            results.push(res);
+        }
+
+        // THEN
+        // * the points are matched on the first import, with the source program
+        // * the second import is rejected
+        assert_that!(results[0])
+            .is_ok()
+            .is_equal_to(ImportExternalStatementResponse {
+                member_id,
+                matched_points: expected,
+                new_loyalty_points: expected,
+            });
+        assert_that!(results[1])
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events).has_length(1);
+        assert_that!(events[0].external_source).is_equal_to(Some(ExternalSource {
+            program: "Other Rewards".to_string(),
+            statement_id: "STATEMENT-1".to_string(),
+        }));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN an existing member, statements matched at 50%, and the outbox enabled
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_statement_matching(StatementMatching {
+                ratio_percent: 50,
+                cap: 2000,
+            })
+            .with_id_generator(Arc::new(SequentialIds::default()))
+            .with_outbox();
+
+        // WHEN importing a statement
+        ServiceExt::<ImportExternalStatementRequest>::ready(&mut domain)
+            .await?
+            .call(ImportExternalStatementRequest {
+                member_id,
+                statement: statement(1000),
+            })
+            .await?;
+
+        // THEN the matched points are stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsAdded {
+            member_id,
+            event_id: Uuid::from_u128(1),
+            loyalty_points: 500,
+        }]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(3, false)]
+    #[case(0, true)]
+    #[tokio::test]
+    async fn test_call_conflict(
+        #[case] conflict_retries: u32,
+        #[case] expect_conflict: bool,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member whose statement is imported concurrently, after being read
+        let member_id = Uuid::new_v4();
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        let mut database = MockDatabasePort::new();
+        database
+            .expect_get_member_restrictions()
+            .returning(|_| Ok(Vec::new()));
+        let mut reads = 0;
+        database.expect_get_loyalty_points().returning(move |_| {
+            reads += 1;
+            let mut loyalty = Loyalty::new(member_id);
+            if reads > 1 {
+                loyalty.version = 1;
+                loyalty.events.push(LoyaltyEvent {
+                    sequence: 1,
+                    external_source: Some(ExternalSource {
+                        program: "Other Rewards".to_string(),
+                        statement_id: "STATEMENT-1".to_string(),
+                    }),
+                    ..LoyaltyEvent::new(
+                        Uuid::new_v4(),
+                        500,
+                        "Statement match from Other Rewards",
+                        Utc::now(),
+                    )
+                });
+            }
+            Ok(loyalty)
+        });
+        database
+            .expect_register_loyalty_event_with_outbox()
+            .times(1)
+            .returning(move |_, _, expected_version, _| {
+                Err(crate::ports::database::Error::Conflict {
+                    member_id,
+                    expected_version: expected_version.unwrap_or_default(),
+                    current_version: 1,
+                })
+            });
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member))
+            .with_statement_matching(StatementMatching {
+                ratio_percent: 50,
+                cap: 2000,
+            })
+            .with_conflict_retries(conflict_retries);
+
+        // WHEN importing the statement
+        let res = ServiceExt::<ImportExternalStatementRequest>::ready(&mut domain)
+            .await?
+            .call(ImportExternalStatementRequest {
+                member_id,
+                statement: statement(1000),
+            })
+            .await;
+
+        // THEN the retry sees the concurrent import, or the conflict is returned without retries
+        if expect_conflict {
+            assert_that!(res).is_err().matches(|err| err.is_retryable());
+        } else {
+            assert_that!(res)
+                .is_err()
+                .matches(|err| matches!(err, Error::InvalidState(_)));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_not_configured() -> Result<(), BoxError> {
+        // GIVEN statement imports that are not configured
+        let mut domain = DomainLogic::new(
+            Arc::new(MemoryDatabase::default()),
+            Arc::new(MockMemberPort::new()),
+        );
+
+        // WHEN importing a statement
+        let res = ServiceExt::<ImportExternalStatementRequest>::ready(&mut domain)
+            .await?
+            .call(ImportExternalStatementRequest {
+                member_id: Uuid::new_v4(),
+                statement: statement(1000),
+            })
+            .await;
+
+        // THEN it returns an error
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_suspended() -> Result<(), BoxError> {
+        // GIVEN a member who cannot earn points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_restriction(Capability::Earn)
+            .build()
+            .await?;
+        let mut domain = domain.with_statement_matching(StatementMatching {
+            ratio_percent: 50,
+            cap: 2000,
+        });
+
+        // WHEN importing a statement
+        let res = ServiceExt::<ImportExternalStatementRequest>::ready(&mut domain)
+            .await?
+            .call(ImportExternalStatementRequest {
+                member_id,
+                statement: statement(1000),
+            })
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * no points are matched
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Suspended {
+                    capability: Capability::Earn,
+                    ..
+                }
+            )
+        });
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::DomainEvent,
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{publish, DomainLogic, Error};
+
+/// Make all pending points that reached their maturity date available
+///
+/// This is meant to run periodically, e.g. from a scheduled job.
+pub struct MaturePointsRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaturePointsResponse {
+    /// Members that had pending points maturing
+    pub matured: Vec<MaturedPoints>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaturedPoints {
+    pub member_id: Uuid,
+    /// Number of loyalty points that became available
+    pub matured_points: u32,
+    /// New number of loyalty points
+    pub loyalty_points: u32,
+    /// Remaining number of pending loyalty points
+    pub pending_loyalty_points: u32,
+}
+
+impl<D, M> Service<MaturePointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = MaturePointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: MaturePointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            // With the outbox, the database queues the events along with the change
+            let mut matured = Vec::new();
+            for (loyalty, matured_points) in database.mature_points(clock.now(), outbox).await? {
+                if !outbox {
+                    publish(
+                        event_publisher.as_ref(),
+                        DomainEvent::PointsMatured {
+                            member_id: loyalty.member_id,
+                            loyalty_points: matured_points,
+                        },
+                    )
+                    .await;
+                }
+                matured.push(MaturedPoints {
+                    member_id: loyalty.member_id,
+                    matured_points,
+                    loyalty_points: loyalty.points,
+                    pending_loyalty_points: loyalty.pending_points,
+                });
+            }
+
+            Ok(MaturePointsResponse { matured })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase, domain::LoyaltyEvent,
+        ports::member::MockMemberPort,
+    };
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with a matured lot and a pending lot
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        for (delta_points, matures_at) in [
+            (100, Utc::now() - Duration::days(1)),
+            (50, Utc::now() + Duration::days(1)),
+        ] {
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        matures_at: Some(matures_at),
+                        ..LoyaltyEvent::new(Uuid::new_v4(), delta_points, "SOME REASON", Utc::now())
+                    },
+                    None,
+                )
+                .await?;
+        }
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN maturing points
+        let res = ServiceExt::<MaturePointsRequest>::ready(&mut domain)
+            .await?
+            .call(MaturePointsRequest)
+            .await;
+
+        // THEN only the matured lot becomes available
+        assert_that!(res).is_ok().is_equal_to(MaturePointsResponse {
+            matured: vec![MaturedPoints {
+                member_id,
+                matured_points: 100,
+                loyalty_points: 100,
+                pending_loyalty_points: 50,
+            }],
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN a member with a matured lot and the outbox enabled
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent {
+                    matures_at: Some(Utc::now() - Duration::days(1)),
+                    ..LoyaltyEvent::new(Uuid::new_v4(), 100, "SOME REASON", Utc::now())
+                },
+                None,
+            )
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_outbox();
+
+        // WHEN maturing points
+        ServiceExt::<MaturePointsRequest>::ready(&mut domain)
+            .await?
+            .call(MaturePointsRequest)
+            .await?;
+
+        // THEN the matured points are stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsMatured {
+            member_id,
+            loyalty_points: 100,
+        }]);
+
+        Ok(())
+    }
+}
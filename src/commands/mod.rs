@@ -1,19 +1,751 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration as StdDuration,
+};
 
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::{
+    adapters::{
+        clock::system::SystemClock, event_publisher::noop::NoopPublisher,
+        id_generator::uuid_v7::UuidV7Generator, retry::Sleep,
+    },
+    domain::{
+        BalancePolicy, Capability, CaseLock, ChargebackPolicy, DomainEvent, EarningPolicy,
+        ExpirationPolicy, HolidayCalendar, MaturationSchedule, OrderReconciliation, RegionalPolicy,
+        Rounding, StatementMatching, Tier,
+    },
+    ports::{
+        campaign::CampaignPort, case_lock::CaseLockPort, catalog::CatalogPort, clock::ClockPort,
+        database::DatabasePort, drawing::DrawingPort, event_publisher::EventPublisherPort,
+        id_generator::IdGeneratorPort, partner_transfer::PartnerTransferPort, segment::SegmentPort,
+        ErrorChain, ResultExt,
+    },
+};
+
+pub mod acquire_case_lock;
 pub mod add_points;
+pub mod annotate_event;
+pub mod bulk_add_points;
+pub mod capture_points;
+pub mod changes_since;
+pub mod chargeback;
+pub mod compact_history;
+pub mod detect_dormant_members;
+pub mod draw_winners;
+pub mod enter_drawing;
+pub mod error_envelope;
+pub mod expire_points;
+pub mod expire_renewal_escrow;
+pub mod explain_tier;
+pub mod forecast_points;
+pub mod get_loyalty;
+pub mod grant_override;
+pub mod hold_points;
+pub mod import_external_statement;
+pub mod mature_points;
+pub mod preview_earn_day;
+pub mod redeem_points;
+pub mod redeem_reward;
+pub mod reevaluate_tiers;
+pub mod relay_outbox;
+pub mod release_case_lock;
+pub mod release_points;
+pub mod reprocess_unverified;
+pub mod restrict_member;
+pub mod reverse_campaign;
+pub mod reverse_points;
+pub mod review_event;
+pub mod run_campaign_credit;
+pub mod settle_renewal_escrow;
+pub mod transfer_out;
+pub mod transfer_points;
+
+use add_points::DuplicateOrderStats;
 
 pub struct DomainLogic<D, M> {
     database: Arc<D>,
     member: Arc<M>,
+    /// Source of identifiers for new events and overrides
+    id_generator: Arc<dyn IdGeneratorPort + Send + Sync>,
+    /// Source of the current time
+    clock: Arc<dyn ClockPort + Send + Sync>,
+    /// Destination of events about persisted changes
+    event_publisher: Arc<dyn EventPublisherPort + Send + Sync>,
+    /// Store events in the database's outbox instead of publishing them directly
+    outbox: bool,
+    /// Optional way to wait between attempts to publish outbox events, required to relay them
+    relay_sleep: Option<Sleep>,
+    /// Optional source of marketing segments for segment-scoped earn rules
+    segment: Option<Arc<dyn SegmentPort + Send + Sync>>,
+    /// Earn multiplier on purchases per marketing segment
+    ///
+    /// When a member belongs to multiple segments, only the highest multiplier applies.
+    segment_multipliers: HashMap<String, i32>,
+    /// Optional source of promotional campaigns boosting purchases
+    campaign: Option<Arc<dyn CampaignPort + Send + Sync>>,
+    /// Points earned per tier and channel, and on membership renewals
+    earning_policy: EarningPolicy,
+    /// Days with special earn rates on purchases
+    holiday_calendar: HolidayCalendar,
+    /// Optional catalog port, required to redeem rewards
+    catalog: Option<Arc<dyn CatalogPort + Send + Sync>>,
+    /// Optional drawing port, required for drawing commands
+    drawing: Option<Arc<dyn DrawingPort + Send + Sync>>,
+    /// Optional case lock port, required for case lock commands
+    case_lock: Option<Arc<dyn CaseLockPort + Send + Sync>>,
+    /// How long case locks last
+    case_lock_ttl: Duration,
+    /// Optional partner transfer port, required to transfer points to partner programs
+    partner_transfer: Option<Arc<dyn PartnerTransferPort + Send + Sync>>,
+    /// Manual additions above this number of points must be approved before affecting the balance
+    manual_approval_threshold: Option<u32>,
+    /// How long renewal points are held in escrow, waiting for the payment to be confirmed
+    renewal_escrow: Option<Duration>,
+    /// Delay before points earned on purchases become available
+    maturation_schedule: MaturationSchedule,
+    /// How purchase amounts are rounded to whole currency units
+    purchase_rounding: Rounding,
+    /// ISO 4217 code of the only currency purchases can be made in
+    program_currency: String,
+    /// How long added points stay available
+    expiration_policy: ExpirationPolicy,
+    /// Which points to keep when multiple channels report the same order
+    order_reconciliation: OrderReconciliation,
+    /// Orders reported multiple times, shared between clones
+    duplicate_orders: Arc<Mutex<DuplicateOrderStats>>,
+    /// Regulatory constraints on points, depending on the member's country
+    regional_policy: RegionalPolicy,
+    /// Points granted along with a member's first points, if any
+    welcome_bonus: Option<u32>,
+    /// How claw-backs exceeding the member's balance are handled
+    balance_policy: BalancePolicy,
+    /// How many points chargebacks claw back, and when they flag members for review
+    chargeback_policy: ChargebackPolicy,
+    /// Matching of other programs' statements, required to import them
+    statement_matching: Option<StatementMatching>,
+    /// Fallback for purchases when the member port is temporarily unavailable
+    degraded_mode: Option<DegradedMode>,
+    /// How long a stored tier evaluation is used before asking the member port again
+    tier_max_age: Duration,
+    /// How many times a command starts over when the member's loyalty changed concurrently
+    conflict_retries: u32,
+}
+
+/// Fallback tiers to use when the member port is temporarily unavailable
+#[derive(Clone, Debug, Default)]
+struct DegradedMode {
+    /// Tier to use for members without a last-known tier
+    default_tier: Option<Tier>,
+    /// Last tier seen for each member
+    last_known_tiers: Arc<Mutex<HashMap<Uuid, Tier>>>,
+}
+
+impl DegradedMode {
+    fn remember_tier(&self, member_id: Uuid, tier: Tier) {
+        self.last_known_tiers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(member_id, tier);
+    }
+
+    fn fallback_tier(&self, member_id: Uuid) -> Option<Tier> {
+        self.last_known_tiers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&member_id)
+            .cloned()
+            .or_else(|| self.default_tier.clone())
+    }
+}
+
+impl<D, M> Clone for DomainLogic<D, M> {
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+            member: self.member.clone(),
+            id_generator: self.id_generator.clone(),
+            clock: self.clock.clone(),
+            event_publisher: self.event_publisher.clone(),
+            outbox: self.outbox,
+            relay_sleep: self.relay_sleep.clone(),
+            segment: self.segment.clone(),
+            segment_multipliers: self.segment_multipliers.clone(),
+            campaign: self.campaign.clone(),
+            earning_policy: self.earning_policy.clone(),
+            holiday_calendar: self.holiday_calendar.clone(),
+            catalog: self.catalog.clone(),
+            drawing: self.drawing.clone(),
+            case_lock: self.case_lock.clone(),
+            case_lock_ttl: self.case_lock_ttl,
+            partner_transfer: self.partner_transfer.clone(),
+            manual_approval_threshold: self.manual_approval_threshold,
+            renewal_escrow: self.renewal_escrow,
+            maturation_schedule: self.maturation_schedule.clone(),
+            purchase_rounding: self.purchase_rounding,
+            program_currency: self.program_currency.clone(),
+            expiration_policy: self.expiration_policy.clone(),
+            order_reconciliation: self.order_reconciliation,
+            duplicate_orders: self.duplicate_orders.clone(),
+            regional_policy: self.regional_policy.clone(),
+            welcome_bonus: self.welcome_bonus,
+            balance_policy: self.balance_policy,
+            chargeback_policy: self.chargeback_policy,
+            statement_matching: self.statement_matching.clone(),
+            degraded_mode: self.degraded_mode.clone(),
+            tier_max_age: self.tier_max_age,
+            conflict_retries: self.conflict_retries,
+        }
+    }
+}
+
+impl<D, M> DomainLogic<D, M> {
+    pub fn new(database: Arc<D>, member: Arc<M>) -> Self {
+        Self {
+            database,
+            member,
+            id_generator: Arc::new(UuidV7Generator),
+            clock: Arc::new(SystemClock),
+            event_publisher: Arc::new(NoopPublisher),
+            outbox: false,
+            relay_sleep: None,
+            segment: None,
+            segment_multipliers: HashMap::new(),
+            campaign: None,
+            earning_policy: EarningPolicy::default(),
+            holiday_calendar: HolidayCalendar::default(),
+            catalog: None,
+            drawing: None,
+            case_lock: None,
+            case_lock_ttl: Duration::zero(),
+            partner_transfer: None,
+            manual_approval_threshold: None,
+            renewal_escrow: None,
+            maturation_schedule: MaturationSchedule::default(),
+            purchase_rounding: Rounding::default(),
+            program_currency: "EUR".to_string(),
+            expiration_policy: ExpirationPolicy::default(),
+            order_reconciliation: OrderReconciliation::default(),
+            duplicate_orders: Arc::new(Mutex::new(DuplicateOrderStats::default())),
+            regional_policy: RegionalPolicy::default(),
+            welcome_bonus: None,
+            balance_policy: BalancePolicy::default(),
+            chargeback_policy: ChargebackPolicy::default(),
+            statement_matching: None,
+            degraded_mode: None,
+            tier_max_age: Duration::days(1),
+            conflict_retries: 3,
+        }
+    }
+
+    /// Generate identifiers with `id_generator` instead of time-ordered UUIDv7
+    pub fn with_id_generator<G>(mut self, id_generator: Arc<G>) -> Self
+    where
+        G: IdGeneratorPort + Send + Sync + 'static,
+    {
+        self.id_generator = id_generator;
+        self
+    }
+
+    /// Read the current time from `clock` instead of the system clock
+    pub fn with_clock<C>(mut self, clock: Arc<C>) -> Self
+    where
+        C: ClockPort + Send + Sync + 'static,
+    {
+        self.clock = clock;
+        self
+    }
+
+    /// Send events about added and redeemed points and tier changes to `event_publisher`
+    ///
+    /// By default, events are dropped.
+    pub fn with_event_publisher<P>(mut self, event_publisher: Arc<P>) -> Self
+    where
+        P: EventPublisherPort + Send + Sync + 'static,
+    {
+        self.event_publisher = event_publisher;
+        self
+    }
+
+    /// Store events in the database's outbox, in the same transaction as the change they describe
+    ///
+    /// By default, events are published once the change is persisted, and lost if the service
+    /// stops in between. With the outbox, they are only published by
+    /// [`RelayOutboxRequest`](relay_outbox::RelayOutboxRequest), which must run regularly.
+    pub fn with_outbox(mut self) -> Self {
+        self.outbox = true;
+        self
+    }
+
+    /// Wait with `sleep` between attempts to publish an outbox event, e.g. `tokio::time::sleep`
+    ///
+    /// This is required to relay the outbox, so an unavailable event publisher is not called again
+    /// right away. See [`RelayOutboxRequest`](relay_outbox::RelayOutboxRequest).
+    pub fn with_relay_sleep<F, Fut>(mut self, sleep: F) -> Self
+    where
+        F: Fn(StdDuration) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.relay_sleep = Some(Arc::new(move |delay| Box::pin(sleep(delay))));
+        self
+    }
+
+    /// Scope earn rules to marketing segments
+    ///
+    /// For example, a multiplier of `2` for the `student` segment means students earn twice the
+    /// points on purchases. Segment tags from the member port count along with the segments from
+    /// `segment`, for multipliers, holiday rules and campaigns alike.
+    pub fn with_segments<S>(
+        mut self,
+        segment: Arc<S>,
+        multipliers: impl IntoIterator<Item = (String, i32)>,
+    ) -> Self
+    where
+        S: SegmentPort + Send + Sync + 'static,
+    {
+        self.segment = Some(segment);
+        self.segment_multipliers = multipliers.into_iter().collect();
+        self
+    }
+
+    /// Boost points earned on purchases with the promotional campaigns from `campaign`
+    ///
+    /// When multiple campaigns apply to a purchase, only the highest multiplier counts. Campaigns
+    /// do not apply on blackout days of the [`HolidayCalendar`].
+    pub fn with_campaigns<C>(mut self, campaign: Arc<C>) -> Self
+    where
+        C: CampaignPort + Send + Sync + 'static,
+    {
+        self.campaign = Some(campaign);
+        self
+    }
+
+    /// Change the points earned per tier and channel, and on membership renewals
+    ///
+    /// By default, each tier earns its [`Tier::ratio`] on all channels.
+    pub fn with_earning_policy(mut self, earning_policy: EarningPolicy) -> Self {
+        self.earning_policy = earning_policy;
+        self
+    }
+
+    /// Boost or black out earn rates on purchases on specific days
+    ///
+    /// See [`PreviewEarnDayRequest`](preview_earn_day::PreviewEarnDayRequest) to check a date.
+    pub fn with_holiday_calendar(mut self, holiday_calendar: HolidayCalendar) -> Self {
+        self.holiday_calendar = holiday_calendar;
+        self
+    }
+
+    /// Let members redeem their points for rewards from `catalog`
+    ///
+    /// See [`RedeemRewardRequest`](redeem_reward::RedeemRewardRequest).
+    pub fn with_catalog<P>(mut self, catalog: Arc<P>) -> Self
+    where
+        P: CatalogPort + Send + Sync + 'static,
+    {
+        self.catalog = Some(catalog);
+        self
+    }
+
+    /// Enable sweepstakes drawings
+    pub fn with_drawings<P>(mut self, drawing: Arc<P>) -> Self
+    where
+        P: DrawingPort + Send + Sync + 'static,
+    {
+        self.drawing = Some(drawing);
+        self
+    }
+
+    /// Enable advisory case locks for support agents, lasting `ttl`
+    ///
+    /// Support commands acting on a member locked by another agent still succeed, but return the
+    /// lock as a warning.
+    pub fn with_case_locks<P>(mut self, case_lock: Arc<P>, ttl: Duration) -> Self
+    where
+        P: CaseLockPort + Send + Sync + 'static,
+    {
+        self.case_lock = Some(case_lock);
+        self.case_lock_ttl = ttl;
+        self
+    }
+
+    /// Let members transfer their points to partner programs through `partner_transfer`
+    ///
+    /// See [`TransferOutRequest`](transfer_out::TransferOutRequest). Partners are only called
+    /// once per transfer, so wrap the adapter in
+    /// [`Retrying`](crate::adapters::retry::Retrying) to ride out transient failures.
+    pub fn with_partner_transfers<P>(mut self, partner_transfer: Arc<P>) -> Self
+    where
+        P: PartnerTransferPort + Send + Sync + 'static,
+    {
+        self.partner_transfer = Some(partner_transfer);
+        self
+    }
+
+    /// Require approval for manual additions above `threshold` points
+    ///
+    /// See [`ReviewEventRequest`](review_event::ReviewEventRequest) to approve or reject them.
+    pub fn with_manual_approval_threshold(mut self, threshold: u32) -> Self {
+        self.manual_approval_threshold = Some(threshold);
+        self
+    }
+
+    /// Hold points for membership renewals in escrow until the billing service confirms payment
+    ///
+    /// Renewals must then carry the reference of their invoice. See
+    /// [`SettleRenewalEscrowRequest`](settle_renewal_escrow::SettleRenewalEscrowRequest) to
+    /// add or discard them once the payment succeeds or fails. Points still in escrow after
+    /// `timeout` are discarded by
+    /// [`ExpireRenewalEscrowRequest`](expire_renewal_escrow::ExpireRenewalEscrowRequest).
+    pub fn with_renewal_escrow(mut self, timeout: Duration) -> Self {
+        self.renewal_escrow = Some(timeout);
+        self
+    }
+
+    /// Keep points earned on purchases pending until the return window of their channel closes
+    ///
+    /// See [`MaturePointsRequest`](mature_points::MaturePointsRequest) to make them available.
+    pub fn with_maturation_schedule(mut self, maturation_schedule: MaturationSchedule) -> Self {
+        self.maturation_schedule = maturation_schedule;
+        self
+    }
+
+    /// Round purchase amounts to whole currency units with `rounding` before earning points
+    ///
+    /// By default, amounts are rounded down, so partial units never earn points.
+    pub fn with_purchase_rounding(mut self, rounding: Rounding) -> Self {
+        self.purchase_rounding = rounding;
+        self
+    }
+
+    /// Only earn points on purchases in `currency`, e.g. `"USD"`
+    ///
+    /// Purchases in other currencies are rejected, as points are earned per whole currency unit.
+    /// By default, purchases must be in EUR.
+    pub fn with_program_currency(mut self, currency: &str) -> Self {
+        self.program_currency = currency.to_string();
+        self
+    }
+
+    /// Expire added points that are not spent in time, depending on the member's tier
+    ///
+    /// See [`ExpirePointsRequest`](expire_points::ExpirePointsRequest) to expire them.
+    pub fn with_expiration_policy(mut self, expiration_policy: ExpirationPolicy) -> Self {
+        self.expiration_policy = expiration_policy;
+        self
+    }
+
+    /// Reconcile purchases reported multiple times for the same order with `reconciliation`
+    ///
+    /// By default, only the first report of an order earns points. See
+    /// [`AddPointsRequest::order_reference`](add_points::AddPointsRequest::order_reference).
+    pub fn with_order_reconciliation(mut self, reconciliation: OrderReconciliation) -> Self {
+        self.order_reconciliation = reconciliation;
+        self
+    }
+
+    /// Grant `points` to members along with the first points they earn
+    ///
+    /// The database port records the grant, so each member receives the bonus once, even when
+    /// their first requests run concurrently. If storing the bonus fails, the request still
+    /// succeeds without it, as its own points are already stored.
+    pub fn with_welcome_bonus(mut self, points: u32) -> Self {
+        self.welcome_bonus = Some(points);
+        self
+    }
+
+    /// Apply regional regulations, such as expiry bans, depending on the member's country
+    ///
+    /// The country comes from the member port. Points added in degraded mode follow the default
+    /// profile, as the member's country is unknown.
+    pub fn with_regional_policy(mut self, regional_policy: RegionalPolicy) -> Self {
+        self.regional_policy = regional_policy;
+        self
+    }
+
+    /// Handle claw-backs exceeding the member's balance with `balance_policy`
+    ///
+    /// By default, they fail with [`Error::InsufficientPoints`].
+    pub fn with_balance_policy(mut self, balance_policy: BalancePolicy) -> Self {
+        self.balance_policy = balance_policy;
+        self
+    }
+
+    /// Cap the points clawed back by chargebacks, and flag members for review above a threshold
+    ///
+    /// See [`ChargebackRequest`](chargeback::ChargebackRequest).
+    pub fn with_chargeback_policy(mut self, chargeback_policy: ChargebackPolicy) -> Self {
+        self.chargeback_policy = chargeback_policy;
+        self
+    }
+
+    /// Let members import a statement from another loyalty program, once
+    ///
+    /// See [`ImportExternalStatementRequest`](import_external_statement::ImportExternalStatementRequest).
+    pub fn with_statement_matching(mut self, statement_matching: StatementMatching) -> Self {
+        self.statement_matching = Some(statement_matching);
+        self
+    }
+
+    /// Keep earning points on purchases when the member port is temporarily unavailable
+    ///
+    /// Points are then computed with the member's stored or last-known tier, or `default_tier` if
+    /// there is none, and the event is marked as `tier_unverified`. Without a fallback tier, the member port
+    /// error is returned as usual.
+    ///
+    /// See [`ReprocessUnverifiedRequest`](reprocess_unverified::ReprocessUnverifiedRequest) to
+    /// reconcile these events.
+    pub fn with_degraded_mode(mut self, default_tier: Option<Tier>) -> Self {
+        self.degraded_mode = Some(DegradedMode {
+            default_tier,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Use stored tier evaluations for up to `max_age` when reading a member's loyalty
+    ///
+    /// By default, evaluations are used for a day. Adding points and
+    /// [`ReevaluateTiersRequest`](reevaluate_tiers::ReevaluateTiersRequest) refresh them.
+    pub fn with_tier_max_age(mut self, max_age: Duration) -> Self {
+        self.tier_max_age = max_age;
+        self
+    }
+
+    /// Number of times a command reads the member's loyalty again after a concurrent change
+    ///
+    /// This defaults to 3. Once retries are exhausted, the command fails with a retryable
+    /// [`Conflict`](crate::ports::database::Error::Conflict) error.
+    pub fn with_conflict_retries(mut self, retries: u32) -> Self {
+        self.conflict_retries = retries;
+        self
+    }
+
+    /// Number of purchases reported again for the same order, since the domain logic was created
+    ///
+    /// Clones of the domain logic share these statistics, e.g. to expose them as metrics.
+    pub fn duplicate_order_stats(&self) -> DuplicateOrderStats {
+        *self
+            .duplicate_orders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn catalog(&self) -> Result<Arc<dyn CatalogPort + Send + Sync>, Error> {
+        self.catalog
+            .clone()
+            .ok_or_else(|| Error::InvalidState("the reward catalog is not configured".into()))
+    }
+
+    fn drawing(&self) -> Result<Arc<dyn DrawingPort + Send + Sync>, Error> {
+        self.drawing
+            .clone()
+            .ok_or_else(|| Error::InvalidState("drawings are not configured".into()))
+    }
+
+    fn case_lock(&self) -> Result<Arc<dyn CaseLockPort + Send + Sync>, Error> {
+        self.case_lock
+            .clone()
+            .ok_or_else(|| Error::InvalidState("case locks are not configured".into()))
+    }
+
+    fn partner_transfer(&self) -> Result<Arc<dyn PartnerTransferPort + Send + Sync>, Error> {
+        self.partner_transfer
+            .clone()
+            .ok_or_else(|| Error::InvalidState("partner transfers are not configured".into()))
+    }
+
+    fn relay_sleep(&self) -> Result<Sleep, Error> {
+        self.relay_sleep
+            .clone()
+            .ok_or_else(|| Error::InvalidState("outbox relay sleep is not configured".into()))
+    }
+}
+
+/// Active case lock held by another agent than `actor`, to warn them when acting on a member
+///
+/// Case locks are advisory: if they are not configured or cannot be fetched, this returns `None`.
+async fn foreign_case_lock(
+    case_lock: Option<Arc<dyn CaseLockPort + Send + Sync>>,
+    member_id: Uuid,
+    actor: &str,
+    now: DateTime<Utc>,
+) -> Option<CaseLock> {
+    match case_lock?.get_lock(member_id).await {
+        Ok(case_lock) => {
+            case_lock.filter(|case_lock| case_lock.holder != actor && case_lock.is_active(now))
+        }
+        Err(err) => {
+            tracing::warn!(%member_id, error = %ErrorChain(&err), "failed to fetch case lock");
+            None
+        }
+    }
+}
+
+/// Fail with [`Error::Suspended`] if the member's `capability` is suspended at `now`
+///
+/// See [`RestrictMemberRequest`](restrict_member::RestrictMemberRequest).
+async fn ensure_capability<D>(
+    database: &D,
+    member_id: Uuid,
+    capability: Capability,
+    now: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    D: DatabasePort + ?Sized,
+{
+    let until = database
+        .get_member_restrictions(member_id)
+        .await
+        .with_context(|| format!("fetching restrictions for member {}", member_id))?
+        .into_iter()
+        .filter(|restriction| restriction.capability == capability && restriction.is_active(now))
+        .map(|restriction| restriction.expires_at)
+        .max();
+    match until {
+        Some(until) => Err(Error::Suspended {
+            member_id,
+            capability,
+            until,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Publish an event about a change that is already persisted
+///
+/// Failures are logged instead of returned: the change happened, and failing the command would
+/// lead callers to retry it.
+async fn publish(event_publisher: &(dyn EventPublisherPort + Send + Sync), event: DomainEvent) {
+    if let Err(err) = event_publisher.publish(event.clone()).await {
+        tracing::warn!(?event, error = %ErrorChain(&err), "failed to publish event");
+    }
+}
+
+/// Run `attempt` again while it fails because the member's loyalty changed concurrently
+///
+/// Each attempt must read the member's loyalty again, so it acts on fresh state. This gives up
+/// after `retries` retries.
+async fn retry_on_conflict<T, F>(retries: u32, mut attempt: impl FnMut() -> F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    let mut retry = 0;
+    loop {
+        match attempt().await {
+            Err(Error::Database(crate::ports::database::Error::Conflict {
+                member_id,
+                expected_version,
+                current_version,
+            })) if retry < retries => {
+                retry += 1;
+                tracing::debug!(
+                    %member_id,
+                    expected_version,
+                    current_version,
+                    retry,
+                    "loyalty changed concurrently, retrying"
+                );
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Persist a change along with the events describing it
+///
+/// With the outbox, `persist` receives the events to store in the same transaction. Otherwise, it
+/// receives no events, and they are published once the change is persisted.
+async fn persist_with_events<T, E, F>(
+    event_publisher: &(dyn EventPublisherPort + Send + Sync),
+    outbox: bool,
+    events: Vec<DomainEvent>,
+    persist: impl FnOnce(Vec<DomainEvent>) -> F,
+) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    if outbox {
+        return persist(events).await;
+    }
+
+    let res = persist(Vec::new()).await?;
+    for event in events {
+        publish(event_publisher, event).await;
+    }
+    Ok(res)
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("database port error: {0:?}")]
+    #[error("database port error")]
     Database(#[from] crate::ports::database::Error),
-    #[error("member port error: {0:?}")]
+    #[error("member port error")]
     Member(#[from] crate::ports::member::Error),
+    #[error("segment port error")]
+    Segment(#[from] crate::ports::segment::Error),
+    #[error("campaign port error")]
+    Campaign(#[from] crate::ports::campaign::Error),
+    #[error("catalog port error")]
+    Catalog(#[from] crate::ports::catalog::Error),
+    #[error("drawing port error")]
+    Drawing(#[from] crate::ports::drawing::Error),
+    #[error("case lock port error")]
+    CaseLock(#[from] crate::ports::case_lock::Error),
+    #[error("idempotency port error")]
+    Idempotency(#[from] crate::ports::idempotency::Error),
 
-    #[error("invalid state")]
+    /// The member does not have enough available points
+    #[error("insufficient points: {requested} requested, {available} available")]
+    InsufficientPoints { available: u32, requested: u32 },
+    /// Fraud operations suspended the member's capability
+    #[error("member {member_id} cannot {capability} points until {until}")]
+    Suspended {
+        member_id: Uuid,
+        capability: Capability,
+        until: DateTime<Utc>,
+    },
+    #[error("invalid state: {0}")]
     InvalidState(Cow<'static, str>),
+    /// Unexpected failure, such as a panic while handling the request
+    #[error("internal error: {0}")]
+    Internal(Cow<'static, str>),
+}
+
+impl Error {
+    /// Whether the command might succeed if retried
+    ///
+    /// Only transient port errors are retryable. Domain errors, such as a missing member or an
+    /// invalid request, will fail again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Database(err) => err.is_retryable(),
+            Error::Member(err) => err.is_retryable(),
+            Error::Idempotency(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use uuid::Uuid;
+
+    #[rstest]
+    #[case(crate::ports::database::Error::Unavailable("timeout".into()).into(), true)]
+    #[case(crate::ports::member::Error::Unavailable("timeout".into()).into(), true)]
+    #[case(crate::ports::member::Error::CircuitOpen.into(), true)]
+    #[case(crate::ports::database::Error::Adapter("permission denied".into()).into(), false)]
+    #[case(crate::ports::member::Error::MemberDoesNotExist(Uuid::nil()).into(), false)]
+    #[case(Error::InvalidState("invalid".into()), false)]
+    fn test_is_retryable(#[case] err: Error, #[case] expected: bool) {
+        assert_that!(err.is_retryable()).is_equal_to(expected);
+    }
 }
@@ -0,0 +1,150 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::EarnDay,
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use chrono::NaiveDate;
+use tower::Service;
+
+use super::{DomainLogic, Error};
+
+/// Show which days of the holiday calendar apply to purchases on a date, without earning points
+pub struct PreviewEarnDayRequest {
+    /// Date in the program's time zone
+    pub date: NaiveDate,
+    /// Marketing segments of the member to preview for
+    pub segments: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreviewEarnDayResponse {
+    pub earn_day: EarnDay,
+    /// Multiplier applied to purchases for members without a segment multiplier
+    pub earn_multiplier: i32,
+}
+
+impl<D, M> Service<PreviewEarnDayRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = PreviewEarnDayResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PreviewEarnDayRequest) -> Self::Future {
+        let earn_day = self.holiday_calendar.on_date(req.date, &req.segments);
+        Box::pin(async move {
+            let earn_multiplier = earn_day.earn_multiplier(1);
+
+            Ok(PreviewEarnDayResponse {
+                earn_day,
+                earn_multiplier,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase,
+        domain::{HolidayCalendar, HolidayEffect, HolidayRule, InvalidCalendar},
+        ports::member::MockMemberPort,
+    };
+    use chrono::FixedOffset;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn rule(name: &str, day: u32, segment: Option<&str>, effect: HolidayEffect) -> HolidayRule {
+        HolidayRule {
+            name: name.to_string(),
+            date: NaiveDate::from_ymd_opt(2026, 11, day).unwrap(),
+            segment: segment.map(ToString::to_string),
+            effect,
+        }
+    }
+
+    #[rstest]
+    #[case(27, &[], 1, 1)]
+    #[case(12, &[], 0, 1)]
+    #[case(12, &["student"], 2, 4)]
+    #[case(13, &["student"], 0, 1)]
+    #[tokio::test]
+    async fn test_call(
+        #[case] day: u32,
+        #[case] segments: &[&str],
+        #[case] expected_rules: usize,
+        #[case] expected_multiplier: i32,
+    ) -> Result<(), BoxError> {
+        // GIVEN a calendar with a blackout, and boosts on member day
+        let calendar = HolidayCalendar::new(
+            FixedOffset::west_opt(5 * 3600).unwrap(),
+            [
+                rule("Black Friday", 27, None, HolidayEffect::Blackout),
+                rule("Member day", 12, Some("student"), HolidayEffect::Boost(3)),
+                rule("Student day", 12, Some("student"), HolidayEffect::Boost(4)),
+            ],
+        )?;
+        let mut domain = DomainLogic::new(
+            Arc::new(MemoryDatabase::default()),
+            Arc::new(MockMemberPort::new()),
+        )
+        .with_holiday_calendar(calendar);
+
+        // WHEN previewing a date
+        let res = ServiceExt::<PreviewEarnDayRequest>::ready(&mut domain)
+            .await?
+            .call(PreviewEarnDayRequest {
+                date: NaiveDate::from_ymd_opt(2026, 11, day).unwrap(),
+                segments: segments.iter().map(ToString::to_string).collect(),
+            })
+            .await;
+
+        // THEN the matching rules apply, with only the highest boost
+        assert_that!(res).is_ok().matches(|res| {
+            res.earn_day.rules.len() == expected_rules && res.earn_multiplier == expected_multiplier
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_calendar() {
+        // GIVEN rules that cannot apply together
+        let offset = FixedOffset::east_opt(0).unwrap();
+
+        // WHEN creating calendars with them
+        let conflict = HolidayCalendar::new(
+            offset,
+            [
+                rule("Black Friday", 27, None, HolidayEffect::Blackout),
+                rule("Member day", 27, None, HolidayEffect::Boost(3)),
+            ],
+        );
+        let invalid_boost = HolidayCalendar::new(
+            offset,
+            [rule("Member day", 12, None, HolidayEffect::Boost(0))],
+        );
+
+        // THEN they are rejected
+        assert_that!(conflict)
+            .is_err()
+            .matches(|err| matches!(err, InvalidCalendar::Conflict { .. }));
+        assert_that!(invalid_boost)
+            .is_err()
+            .matches(|err| matches!(err, InvalidCalendar::InvalidBoost { .. }));
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, DomainEvent, Loyalty, LoyaltyEvent},
+    layers::{
+        capture::{Anonymize, Pseudonyms},
+        idempotency::IdempotentRequest,
+    },
+    ports::{
+        database::DatabasePort, event_publisher::EventPublisherPort, member::MemberPort, ResultExt,
+    },
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, DomainLogic, Error};
+
+/// Spend available points on a reward
+pub struct RedeemPointsRequest {
+    pub member_id: Uuid,
+    pub loyalty_points: u32,
+    /// Reward the points are spent on, recorded in the event reason
+    pub reward: String,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for RedeemPointsRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+impl Anonymize for RedeemPointsRequest {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        Self {
+            member_id: pseudonyms.id(self.member_id),
+            loyalty_points: self.loyalty_points,
+            reward: self.reward.clone(),
+            idempotency_key: self
+                .idempotency_key
+                .as_deref()
+                .map(|key| pseudonyms.idempotency_key(key)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedeemPointsResponse {
+    pub member_id: Uuid,
+    /// Previous number of loyalty points
+    pub old_loyalty_points: u32,
+    /// New number of loyalty points
+    pub new_loyalty_points: u32,
+}
+
+impl Anonymize for RedeemPointsResponse {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self {
+        Self {
+            member_id: pseudonyms.id(self.member_id),
+            ..self.clone()
+        }
+    }
+}
+
+impl<D, M> Service<RedeemPointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = RedeemPointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RedeemPointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let delta_points = i32::try_from(req.loyalty_points)
+                .map(|loyalty_points| -loyalty_points)
+                .map_err(|_| {
+                    Error::InvalidState(
+                        format!("cannot redeem {} points at once", req.loyalty_points).into(),
+                    )
+                })?;
+
+            // Make sure the member exists
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            let now = clock.now();
+            ensure_capability(
+                database.as_ref(),
+                db_member.member_id,
+                Capability::Redeem,
+                now,
+            )
+            .await?;
+
+            let updated_loyalty = redeem(
+                database.as_ref(),
+                event_publisher.as_ref(),
+                outbox,
+                db_member.member_id,
+                LoyaltyEvent::new(
+                    id_generator.generate_id(),
+                    delta_points,
+                    format!("Redeemed for {}", req.reward),
+                    now,
+                ),
+            )
+            .await?;
+
+            Ok(RedeemPointsResponse {
+                member_id: db_member.member_id,
+                old_loyalty_points: updated_loyalty.points + req.loyalty_points,
+                new_loyalty_points: updated_loyalty.points,
+            })
+        })
+    }
+}
+
+/// Debit the points of a redemption event, relying on the database to reject negative balances
+pub(super) async fn redeem<D>(
+    database: &D,
+    event_publisher: &(dyn EventPublisherPort + Send + Sync),
+    outbox: bool,
+    member_id: Uuid,
+    event: LoyaltyEvent,
+) -> Result<Loyalty, Error>
+where
+    D: DatabasePort + ?Sized,
+{
+    let requested = event.delta_points.unsigned_abs();
+    let points_redeemed = DomainEvent::PointsRedeemed {
+        member_id,
+        event_id: event.event_id,
+        loyalty_points: requested,
+    };
+    let res = persist_with_events(event_publisher, outbox, vec![points_redeemed], |events| {
+        database.register_loyalty_event_with_outbox(member_id, event, None, events)
+    })
+    .await
+    .with_context(|| format!("registering event for member {}", member_id));
+    match res {
+        Ok(updated_loyalty) => Ok(updated_loyalty),
+        Err(crate::ports::database::Error::NegativePointsTotal { current_points, .. }) => {
+            Err(Error::InsufficientPoints {
+                available: current_points,
+                requested,
+            })
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase,
+        ports::{event_publisher::MockEventPublisherPort, member::MockMemberPort},
+    };
+    use chrono::Utc;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[fixture]
+    fn member_id() -> Uuid {
+        Uuid::new_v4()
+    }
+
+    async fn domain_with_points(
+        member_id: Uuid,
+        loyalty_points: i32,
+    ) -> Result<(DomainLogic<MemoryDatabase, MockMemberPort>, MemoryDatabase), BoxError> {
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |_| {
+            Ok(crate::ports::member::Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(Uuid::new_v4(), loyalty_points, "SOME REASON", Utc::now()),
+                None,
+            )
+            .await?;
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
+
+        Ok((domain, database))
+    }
+
+    fn request(member_id: Uuid, loyalty_points: u32) -> RedeemPointsRequest {
+        RedeemPointsRequest {
+            member_id,
+            loyalty_points,
+            reward: "Free coffee".to_string(),
+            idempotency_key: None,
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN a member with 500 points
+        let (mut domain, database) = domain_with_points(member_id, 500).await?;
+
+        // WHEN redeeming 300 points
+        let res = ServiceExt::<RedeemPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, 300))
+            .await;
+
+        // THEN the points are debited with a redemption event
+        assert_that!(res).is_ok().is_equal_to(RedeemPointsResponse {
+            member_id,
+            old_loyalty_points: 500,
+            new_loyalty_points: 200,
+        });
+        let events = database.get_loyalty_events(member_id).await?;
+        assert_that!(events[1].reason.as_str()).is_equal_to("Redeemed for Free coffee");
+        assert_that!(events[1].delta_points).is_equal_to(-300);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_insufficient_points(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN a member with 500 points
+        let (mut domain, database) = domain_with_points(member_id, 500).await?;
+
+        // WHEN redeeming more points than available, or than an event can hold
+        let mut results = Vec::new();
+        for loyalty_points in [501, u32::MAX] {
+            let res = ServiceExt::<RedeemPointsRequest>::ready(&mut domain)
+                .await?
+                .call(request(member_id, loyalty_points))
+                .await;
+            results.push(res);
+        }
+
+        // THEN it fails without changing the points
+        assert_that!(results[0]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::InsufficientPoints {
+                    available: 500,
+                    requested: 501
+                }
+            )
+        });
+        assert_that!(results[1])
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(500);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_event_publisher_fails(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN a member with 500 points, and an event publisher that fails
+        let (domain, database) = domain_with_points(member_id, 500).await?;
+        let mut event_publisher = MockEventPublisherPort::new();
+        event_publisher.expect_publish().times(1).returning(|_| {
+            Err(crate::ports::event_publisher::Error::Adapter(
+                "SOME ERROR".into(),
+            ))
+        });
+        let mut domain = domain.with_event_publisher(Arc::new(event_publisher));
+
+        // WHEN redeeming 300 points
+        let res = ServiceExt::<RedeemPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, 300))
+            .await;
+
+        // THEN the redemption still succeeds
+        assert_that!(res).is_ok();
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(200);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, LoyaltyEvent, Tier},
+    layers::idempotency::IdempotentRequest,
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{
+    add_points::evaluate_tier, ensure_capability, redeem_points::redeem, DomainLogic, Error,
+};
+
+/// Spend available points on a reward from the catalog
+///
+/// Unlike [`RedeemPointsRequest`](super::redeem_points::RedeemPointsRequest), the cost comes
+/// from the catalog, and the member's tier must be eligible for the reward. The reward ID is
+/// recorded in the event.
+pub struct RedeemRewardRequest {
+    pub member_id: Uuid,
+    pub reward_id: Uuid,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for RedeemRewardRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedeemRewardResponse {
+    pub member_id: Uuid,
+    pub reward_id: Uuid,
+    /// Event debiting the reward's cost
+    pub event_id: Uuid,
+    pub tier: Tier,
+    /// Previous number of loyalty points
+    pub old_loyalty_points: u32,
+    /// New number of loyalty points
+    pub new_loyalty_points: u32,
+}
+
+impl<D, M> Service<RedeemRewardRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = RedeemRewardResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RedeemRewardRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let catalog = self.catalog();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let catalog = catalog?;
+            let now = clock.now();
+
+            // Fetch necessary data
+            let reward = catalog
+                .get_reward(req.reward_id)
+                .await
+                .with_context(|| format!("fetching reward {}", req.reward_id))?;
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            ensure_capability(
+                database.as_ref(),
+                db_member.member_id,
+                Capability::Redeem,
+                now,
+            )
+            .await?;
+
+            // Validate the reward for the member
+            let tier = evaluate_tier(&db_member, now)?.tier;
+            if !reward.is_available_to(&tier) {
+                return Err(Error::InvalidState(
+                    format!(
+                        "reward {} is not available to {:?} members",
+                        reward.name, tier
+                    )
+                    .into(),
+                ));
+            }
+            let delta_points = i32::try_from(reward.points_cost)
+                .map(|points_cost| -points_cost)
+                .map_err(|_| {
+                    Error::InvalidState(
+                        format!("cannot redeem {} points at once", reward.points_cost).into(),
+                    )
+                })?;
+
+            let event_id = id_generator.generate_id();
+            let updated_loyalty = redeem(
+                database.as_ref(),
+                event_publisher.as_ref(),
+                outbox,
+                db_member.member_id,
+                LoyaltyEvent {
+                    reward_id: Some(reward.reward_id),
+                    ..LoyaltyEvent::new(
+                        event_id,
+                        delta_points,
+                        format!("Redeemed for {}", reward.name),
+                        now,
+                    )
+                },
+            )
+            .await?;
+
+            Ok(RedeemRewardResponse {
+                member_id: db_member.member_id,
+                reward_id: reward.reward_id,
+                event_id,
+                tier,
+                old_loyalty_points: updated_loyalty.points + reward.points_cost,
+                new_loyalty_points: updated_loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::catalog::memory::StaticCatalog,
+        domain::Reward,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn catalog() -> StaticCatalog {
+        StaticCatalog::default()
+            .with_reward(Reward {
+                reward_id: Uuid::from_u128(1),
+                name: "Free coffee".to_string(),
+                points_cost: 100,
+                eligible_tiers: Vec::new(),
+            })
+            .with_reward(Reward {
+                reward_id: Uuid::from_u128(2),
+                name: "Lounge access".to_string(),
+                points_cost: 400,
+                eligible_tiers: vec![Tier::Gold, Tier::Platinum],
+            })
+    }
+
+    #[rstest]
+    #[case(Tier::Basic, 1, Some(200))]
+    #[case(Tier::Gold, 1, Some(200))]
+    #[case(Tier::Basic, 2, None)]
+    #[case(Tier::Gold, 2, None)]
+    #[tokio::test]
+    async fn test_call(
+        #[case] tier: Tier,
+        #[case] reward_id: u128,
+        #[case] expected_points: Option<u32>,
+    ) -> Result<(), BoxError> {
+        // GIVEN
+        // * a member with 300 points
+        // * coffee for 100 points, and lounge access for 400 points, only for Gold and above
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::tier(tier).with_points(300).build().await?;
+        let mut domain = domain.with_catalog(Arc::new(catalog()));
+
+        // WHEN redeeming a reward
+        let res = ServiceExt::<RedeemRewardRequest>::ready(&mut domain)
+            .await?
+            .call(RedeemRewardRequest {
+                member_id,
+                reward_id: Uuid::from_u128(reward_id),
+                idempotency_key: None,
+            })
+            .await;
+
+        // THEN the cost is debited, unless the tier is not eligible or the member lacks points
+        match expected_points {
+            Some(expected_points) => {
+                assert_that!(res)
+                    .is_ok()
+                    .map(|res| &res.new_loyalty_points)
+                    .is_equal_to(expected_points);
+                let events = database.get_loyalty_events(member_id).await?;
+                assert_that!(events[1].reward_id).is_equal_to(Some(Uuid::from_u128(reward_id)));
+            }
+            None => {
+                assert_that!(res).is_err();
+                assert_that!(database.get_loyalty_points(member_id).await?.points).is_equal_to(300);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_errors() -> Result<(), BoxError> {
+        // GIVEN a Gold member with 300 points
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::gold().with_points(300).build().await?;
+        let mut domain = domain.with_catalog(Arc::new(catalog()));
+
+        // WHEN redeeming a reward they cannot afford, or that does not exist
+        let mut results = Vec::new();
+        for reward_id in [2, 3] {
+            let res = ServiceExt::<RedeemRewardRequest>::ready(&mut domain)
+                .await?
+                .call(RedeemRewardRequest {
+                    member_id,
+                    reward_id: Uuid::from_u128(reward_id),
+                    idempotency_key: None,
+                })
+                .await;
+            results.push(res);
+        }
+
+        // THEN they get the matching errors
+        assert_that!(results[0]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::InsufficientPoints {
+                    available: 300,
+                    requested: 400
+                }
+            )
+        });
+        assert_that!(results[1]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Catalog(crate::ports::catalog::Error::RewardDoesNotExist(_))
+            )
+        });
+
+        Ok(())
+    }
+}
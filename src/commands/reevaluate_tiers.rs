@@ -0,0 +1,191 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::TierChange,
+    ports::{database::DatabasePort, member::MemberPort, ErrorChain, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{
+    add_points::{evaluate_tier, save_tier_evaluation},
+    DomainLogic, Error,
+};
+
+/// Number of members fetched from the database at once
+const PAGE_SIZE: usize = 100;
+
+/// Evaluate the tier of every member and store it
+///
+/// This is meant to run periodically, e.g. from a scheduled job. Members whose tier changed since
+/// their previous evaluation are reported, so the caller can notify them. Members that cannot be
+/// evaluated are reported instead of failing the whole job.
+pub struct ReevaluateTiersRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReevaluateTiersResponse {
+    /// Number of members whose tier was evaluated
+    pub evaluated: usize,
+    /// Members whose tier changed, in member ID order
+    ///
+    /// Members evaluated for the first time are not included.
+    pub changes: Vec<TierChange>,
+    /// Members that could not be evaluated, such as when the member service does not know them
+    pub failed: Vec<Uuid>,
+}
+
+impl<D, M> Service<ReevaluateTiersRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ReevaluateTiersResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: ReevaluateTiersRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let mut response = ReevaluateTiersResponse {
+                evaluated: 0,
+                changes: Vec::new(),
+                failed: Vec::new(),
+            };
+            let mut after = None;
+            loop {
+                let member_ids = database
+                    .get_member_ids(after, PAGE_SIZE)
+                    .await
+                    .context("fetching member IDs")?;
+                let Some(last_member_id) = member_ids.last() else {
+                    break;
+                };
+                after = Some(*last_member_id);
+
+                for member_id in member_ids {
+                    let res = member
+                        .get_member(member_id)
+                        .await
+                        .with_context(|| format!("fetching member {}", member_id));
+                    let evaluation = match res {
+                        Ok(db_member) => evaluate_tier(&db_member, clock.now())?,
+                        Err(err) if !err.is_retryable() => {
+                            tracing::warn!(
+                                %member_id,
+                                error = %ErrorChain(&err),
+                                "failed to evaluate tier"
+                            );
+                            response.failed.push(member_id);
+                            continue;
+                        }
+                        Err(err) => return Err(err.into()),
+                    };
+
+                    let change = save_tier_evaluation(
+                        database.as_ref(),
+                        event_publisher.as_ref(),
+                        outbox,
+                        evaluation,
+                    )
+                    .await?;
+                    response.evaluated += 1;
+                    response.changes.extend(change);
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase,
+        domain::{LoyaltyEvent, Tier, TierEvaluation},
+        ports::member::{Member, MockMemberPort},
+    };
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a Gold member last evaluated as Silver, a new member, and an unknown member
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let database = MemoryDatabase::default();
+        for member_id in member_ids {
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent::new(Uuid::new_v4(), 100, "SOME REASON", Utc::now()),
+                    None,
+                )
+                .await?;
+        }
+        database
+            .save_tier_evaluation(
+                TierEvaluation {
+                    member_id: member_ids[0],
+                    tier: Tier::Silver,
+                    membership_months: Some(22),
+                    evaluated_at: Utc::now() - Duration::days(30),
+                },
+                false,
+            )
+            .await?;
+        let unknown_member_id = member_ids[2];
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |member_id| {
+            if member_id == unknown_member_id {
+                return Err(crate::ports::member::Error::MemberDoesNotExist(member_id));
+            }
+            Ok(Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
+
+        // WHEN re-evaluating tiers
+        let res = ServiceExt::<ReevaluateTiersRequest>::ready(&mut domain)
+            .await?
+            .call(ReevaluateTiersRequest)
+            .await;
+
+        // THEN only the tier change of the evaluated member is reported
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(ReevaluateTiersResponse {
+                evaluated: 2,
+                changes: vec![TierChange {
+                    member_id: member_ids[0],
+                    old_tier: Tier::Silver,
+                    new_tier: Tier::Gold,
+                }],
+                failed: vec![unknown_member_id],
+            });
+        let loyalty = database.get_loyalty_points(member_ids[1]).await?;
+        assert_that!(loyalty.tier)
+            .is_some()
+            .matches(|evaluation| evaluation.tier == Tier::Gold);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    adapters::retry::Backoff,
+    ports::{database::DatabasePort, member::MemberPort, ErrorChain, ResultExt},
+};
+use tower::Service;
+
+use super::{DomainLogic, Error};
+
+/// Number of outbox entries fetched from the database at once
+const PAGE_SIZE: usize = 100;
+
+/// Publish the events waiting in the outbox, oldest first
+///
+/// This is meant to run periodically when the outbox is enabled, see
+/// [`DomainLogic::with_outbox`]. Events are removed from the outbox once published, so they are
+/// delivered at least once: an event might be published again if the relay stops before removing
+/// it.
+///
+/// The relay stops at the first event that cannot be published, so consumers receive events in
+/// order. That event is retried on the next run. Attempts wait with the sleep function from
+/// [`DomainLogic::with_relay_sleep`].
+pub struct RelayOutboxRequest {
+    /// Retries of each event before stopping, and delays between them
+    pub backoff: Backoff,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayOutboxResponse {
+    /// Number of events published and removed from the outbox
+    pub published: usize,
+    /// Outbox entry that could not be published, if the relay stopped early
+    pub failed_outbox_id: Option<u64>,
+}
+
+impl<D, M> Service<RelayOutboxRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = RelayOutboxResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RelayOutboxRequest) -> Self::Future {
+        let database = self.database.clone();
+        let event_publisher = self.event_publisher.clone();
+        let sleep = self.relay_sleep();
+        Box::pin(async move {
+            let sleep = sleep?;
+            let mut response = RelayOutboxResponse {
+                published: 0,
+                failed_outbox_id: None,
+            };
+            loop {
+                let entries = database
+                    .get_outbox_entries(PAGE_SIZE)
+                    .await
+                    .context("fetching outbox entries")?;
+                if entries.is_empty() {
+                    break;
+                }
+
+                for entry in entries {
+                    let mut retry = 0;
+                    while let Err(err) = event_publisher.publish(entry.event.clone()).await {
+                        tracing::warn!(
+                            outbox_id = entry.outbox_id,
+                            retry,
+                            error = %ErrorChain(&err),
+                            "failed to publish outbox event"
+                        );
+                        if retry >= req.backoff.max_retries() {
+                            response.failed_outbox_id = Some(entry.outbox_id);
+                            return Ok(response);
+                        }
+                        sleep(req.backoff.delay(retry)).await;
+                        retry += 1;
+                    }
+
+                    database
+                        .remove_outbox_entry(entry.outbox_id)
+                        .await
+                        .with_context(|| format!("removing outbox entry {}", entry.outbox_id))?;
+                    response.published += 1;
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{database::memory::MemoryDatabase, event_publisher::memory::MemoryPublisher},
+        domain::DomainEvent,
+        ports::{
+            event_publisher::{self, MockEventPublisherPort},
+            member::MockMemberPort,
+        },
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tower::{BoxError, ServiceExt};
+    use uuid::Uuid;
+
+    fn points_added(member_id: Uuid, loyalty_points: u32) -> DomainEvent {
+        DomainEvent::PointsAdded {
+            member_id,
+            event_id: Uuid::new_v4(),
+            loyalty_points,
+        }
+    }
+
+    /// Database with two events waiting in the outbox
+    async fn database(member_id: Uuid) -> Result<(MemoryDatabase, Vec<DomainEvent>), BoxError> {
+        let database = MemoryDatabase::default();
+        let events = vec![points_added(member_id, 100), points_added(member_id, 50)];
+        database
+            .register_loyalty_event_with_outbox(
+                member_id,
+                crate::domain::LoyaltyEvent::new(Uuid::new_v4(), 150, "SOME REASON", Utc::now()),
+                None,
+                events.clone(),
+            )
+            .await?;
+        Ok((database, events))
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN two events in the outbox
+        let (database, events) = database(Uuid::new_v4()).await?;
+        let event_publisher = MemoryPublisher::default();
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_event_publisher(Arc::new(event_publisher.clone()))
+                .with_outbox()
+                .with_relay_sleep(|_| std::future::ready(()));
+
+        // WHEN relaying the outbox
+        let res = ServiceExt::<RelayOutboxRequest>::ready(&mut domain)
+            .await?
+            .call(RelayOutboxRequest {
+                backoff: Backoff::new(2, Duration::from_millis(10)),
+            })
+            .await;
+
+        // THEN both events are published in order, and removed from the outbox
+        assert_that!(res).is_ok().is_equal_to(RelayOutboxResponse {
+            published: 2,
+            failed_outbox_id: None,
+        });
+        assert_that!(event_publisher.events()).is_equal_to(events);
+        assert_that!(database.get_outbox_entries(10).await?).is_empty();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_retries() -> Result<(), BoxError> {
+        // GIVEN two events in the outbox, and a publisher failing three times
+        let (database, _) = database(Uuid::new_v4()).await?;
+        let mut event_publisher = MockEventPublisherPort::new();
+        let mut calls = 0;
+        event_publisher.expect_publish().returning(move |_| {
+            calls += 1;
+            if calls <= 3 {
+                Err(event_publisher::Error::Adapter("broker unavailable".into()))
+            } else {
+                Ok(())
+            }
+        });
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let recorded = delays.clone();
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_event_publisher(Arc::new(event_publisher))
+                .with_outbox()
+                .with_relay_sleep(move |delay| {
+                    recorded.lock().unwrap().push(delay);
+                    std::future::ready(())
+                });
+
+        // WHEN relaying the outbox twice, with at most one retry per event
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let res = ServiceExt::<RelayOutboxRequest>::ready(&mut domain)
+                .await?
+                .call(RelayOutboxRequest {
+                    backoff: Backoff::new(1, Duration::from_millis(10)),
+                })
+                .await;
+            results.push(res);
+        }
+
+        // THEN
+        // * the first run stops at the first event, and the second run publishes both
+        // * each retry waits first
+        assert_that!(results[0])
+            .is_ok()
+            .is_equal_to(RelayOutboxResponse {
+                published: 0,
+                failed_outbox_id: Some(1),
+            });
+        assert_that!(results[1])
+            .is_ok()
+            .is_equal_to(RelayOutboxResponse {
+                published: 2,
+                failed_outbox_id: None,
+            });
+        assert_that!(database.get_outbox_entries(10).await?).is_empty();
+        assert_that!(*delays.lock().unwrap())
+            .is_equal_to(vec![Duration::from_millis(10), Duration::from_millis(10)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_without_sleep() -> Result<(), BoxError> {
+        // GIVEN two events in the outbox, and no sleep function
+        let (database, _) = database(Uuid::new_v4()).await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_outbox();
+
+        // WHEN relaying the outbox
+        let res = ServiceExt::<RelayOutboxRequest>::ready(&mut domain)
+            .await?
+            .call(RelayOutboxRequest {
+                backoff: Backoff::new(1, Duration::from_millis(10)),
+            })
+            .await;
+
+        // THEN the relay refuses to run, and the events stay in the outbox
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        assert_that!(database.get_outbox_entries(10).await?).has_length(2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::ports::{database::DatabasePort, member::MemberPort, ResultExt};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Release a case lock once done working on a member
+///
+/// Only the agent holding the lock can release it. Releasing a lock that expired succeeds.
+pub struct ReleaseCaseLockRequest {
+    pub member_id: Uuid,
+    /// Support agent holding the lock
+    pub holder: String,
+}
+
+impl<D, M> Service<ReleaseCaseLockRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ();
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ReleaseCaseLockRequest) -> Self::Future {
+        let case_lock = self.case_lock();
+        Box::pin(async move {
+            case_lock?
+                .release_lock(req.member_id, &req.holder)
+                .await
+                .with_context(|| format!("releasing case lock on member {}", req.member_id))?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{case_lock::memory::MemoryCaseLocks, database::memory::MemoryDatabase},
+        ports::{case_lock::CaseLockPort, member::MockMemberPort},
+    };
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member locked by an agent
+        let member_id = Uuid::new_v4();
+        let case_locks = MemoryCaseLocks::default();
+        case_locks
+            .acquire_lock(crate::domain::CaseLock {
+                member_id,
+                holder: "agent-1".to_string(),
+                acquired_at: Utc::now(),
+                expires_at: Utc::now() + Duration::minutes(15),
+            })
+            .await?;
+        let mut domain = DomainLogic::new(
+            Arc::new(MemoryDatabase::default()),
+            Arc::new(MockMemberPort::new()),
+        )
+        .with_case_locks(Arc::new(case_locks.clone()), Duration::minutes(15));
+
+        // WHEN releasing the lock
+        let res = ServiceExt::<ReleaseCaseLockRequest>::ready(&mut domain)
+            .await?
+            .call(ReleaseCaseLockRequest {
+                member_id,
+                holder: "agent-1".to_string(),
+            })
+            .await;
+
+        // THEN the member is not locked anymore
+        assert_that!(res).is_ok();
+        assert_that!(case_locks.get_lock(member_id).await?).is_none();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::ports::{database::DatabasePort, member::MemberPort, ResultExt};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Give the points of a hold back to the member, e.g. when the checkout's payment failed
+///
+/// See [`HoldPointsRequest`](super::hold_points::HoldPointsRequest).
+pub struct ReleasePointsRequest {
+    pub member_id: Uuid,
+    pub hold_id: Uuid,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReleasePointsResponse {
+    pub member_id: Uuid,
+    pub released_points: u32,
+    /// New number of loyalty points available
+    pub new_loyalty_points: u32,
+}
+
+impl<D, M> Service<ReleasePointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ReleasePointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ReleasePointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        Box::pin(async move {
+            let loyalty = database
+                .get_loyalty_points(req.member_id)
+                .await
+                .with_context(|| format!("fetching loyalty for member {}", req.member_id))?;
+            let released_points = loyalty
+                .holds
+                .iter()
+                .find(|hold| hold.hold_id == req.hold_id)
+                .map(|hold| hold.points)
+                .ok_or(crate::ports::database::Error::HoldDoesNotExist(req.hold_id))?;
+
+            let updated_loyalty = database
+                .release_hold(req.member_id, req.hold_id)
+                .await
+                .with_context(|| {
+                    format!(
+                        "releasing hold {} for member {}",
+                        req.hold_id, req.member_id
+                    )
+                })?;
+
+            Ok(ReleasePointsResponse {
+                member_id: req.member_id,
+                released_points,
+                new_loyalty_points: updated_loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::hold_points::HoldPointsRequest,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use speculoos::prelude::*;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with 100 points, 70 of which are held
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let mut domain = domain;
+        let hold = ServiceExt::<HoldPointsRequest>::ready(&mut domain)
+            .await?
+            .call(HoldPointsRequest {
+                member_id,
+                loyalty_points: 70,
+                reason: "Order 42".to_string(),
+                idempotency_key: None,
+            })
+            .await?;
+
+        // WHEN releasing the hold
+        let res = ServiceExt::<ReleasePointsRequest>::ready(&mut domain)
+            .await?
+            .call(ReleasePointsRequest {
+                member_id,
+                hold_id: hold.hold_id,
+            })
+            .await;
+
+        // THEN the points are available again, without any event
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.released_points == 70 && res.new_loyalty_points == 100);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.holds).is_empty();
+        assert_that!(loyalty.events).has_length(1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DomainEvent, LoyaltyEvent, Tier},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{add_points::membership_months, persist_with_events, DomainLogic, Error};
+
+/// Recompute the points of events based on a fallback tier, once the member port is back
+///
+/// This is meant to run periodically, e.g. from a scheduled job. See
+/// [`DomainLogic::with_degraded_mode`].
+pub struct ReprocessUnverifiedRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReprocessUnverifiedResponse {
+    /// Events whose tier is now verified
+    pub reconciled: Vec<ReconciledEvent>,
+    /// Number of events left unverified, because the member port is still unavailable
+    pub skipped: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconciledEvent {
+    pub member_id: Uuid,
+    pub event_id: Uuid,
+    /// Actual tier of the member
+    pub tier: Tier,
+    /// Points added or removed to match the actual tier
+    pub adjustment_points: i32,
+}
+
+impl<D, M> Service<ReprocessUnverifiedRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ReprocessUnverifiedResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: ReprocessUnverifiedRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let degraded_mode = self.degraded_mode.clone();
+        let earning_policy = self.earning_policy.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let mut reconciled = Vec::new();
+            let mut skipped = 0;
+            for (member_id, event) in database.get_unverified_events().await? {
+                let Some(unverified_tier) = &event.tier_unverified else {
+                    continue;
+                };
+
+                // Fetch fresh member data, leaving the event for the next run if still unavailable
+                let db_member = match member
+                    .get_member(member_id)
+                    .await
+                    .with_context(|| format!("fetching member {}", member_id))
+                {
+                    Ok(db_member) => db_member,
+                    Err(err) if err.is_retryable() => {
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                let tier =
+                    Tier::from_membership_months(membership_months(&db_member, clock.now())?);
+                if let Some(degraded_mode) = &degraded_mode {
+                    degraded_mode.remember_tier(member_id, tier.clone());
+                }
+
+                // Register the difference with the points the event should have given
+                let adjustment_points = unverified_tier.purchase_amount
+                    * earning_policy.ratio(&tier)
+                    * unverified_tier.earn_multiplier
+                    - event.delta_points;
+                let adjustment = (adjustment_points != 0).then(|| LoyaltyEvent {
+                    matures_at: event.matures_at,
+                    expires_at: event.expires_at,
+                    ..LoyaltyEvent::new(
+                        id_generator.generate_id(),
+                        adjustment_points,
+                        "Tier reconciliation",
+                        clock.now(),
+                    )
+                });
+                let events = adjustment
+                    .iter()
+                    .map(|adjustment| DomainEvent::PointsAdjusted {
+                        member_id,
+                        event_id: adjustment.event_id,
+                        delta_points: adjustment.delta_points,
+                    })
+                    .collect();
+                persist_with_events(event_publisher.as_ref(), outbox, events, |events| {
+                    database.reconcile_event(member_id, event.event_id, adjustment, events)
+                })
+                .await
+                .with_context(|| {
+                    format!(
+                        "reconciling event {} for member {}",
+                        event.event_id, member_id
+                    )
+                })?;
+
+                reconciled.push(ReconciledEvent {
+                    member_id,
+                    event_id: event.event_id,
+                    tier,
+                    adjustment_points,
+                });
+            }
+
+            Ok(ReprocessUnverifiedResponse {
+                reconciled,
+                skipped,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{database::memory::MemoryDatabase, id_generator::sequential::SequentialIds},
+        domain::UnverifiedTier,
+        ports::member::{Member, MockMemberPort},
+    };
+    use chrono::{Duration, Utc};
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN
+        // * a Gold member with a purchase earned as Basic
+        // * a member for which the member service is still unavailable
+        let gold_member_id = Uuid::new_v4();
+        let unavailable_member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let mut event_ids = Vec::new();
+        for member_id in [gold_member_id, unavailable_member_id] {
+            let event_id = Uuid::new_v4();
+            database
+                .register_loyalty_event(
+                    member_id,
+                    LoyaltyEvent {
+                        tier_unverified: Some(UnverifiedTier {
+                            tier: Tier::Basic,
+                            purchase_amount: 2,
+                            earn_multiplier: 1,
+                        }),
+                        ..LoyaltyEvent::new(event_id, 20, "SOME REASON", Utc::now())
+                    },
+                    None,
+                )
+                .await?;
+            event_ids.push(event_id);
+        }
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |member_id| {
+            if member_id == gold_member_id {
+                Ok(Member {
+                    active_member: true,
+                    member_id,
+                    membership_since: Utc::now() - Duration::days(800),
+                    ..Default::default()
+                })
+            } else {
+                Err(crate::ports::member::Error::Unavailable("timeout".into()))
+            }
+        });
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member));
+
+        // WHEN reprocessing unverified events
+        let res = ServiceExt::<ReprocessUnverifiedRequest>::ready(&mut domain)
+            .await?
+            .call(ReprocessUnverifiedRequest)
+            .await;
+
+        // THEN
+        // * the Gold member gets the missing points
+        // * the other event is left for the next run
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(ReprocessUnverifiedResponse {
+                reconciled: vec![ReconciledEvent {
+                    member_id: gold_member_id,
+                    event_id: event_ids[0],
+                    tier: Tier::Gold,
+                    adjustment_points: 10,
+                }],
+                skipped: 1,
+            });
+        let loyalty = database.get_loyalty_points(gold_member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(30);
+        let unverified = database.get_unverified_events().await?;
+        assert_that!(unverified
+            .iter()
+            .map(|(member_id, event)| (*member_id, event.event_id))
+            .collect::<Vec<_>>())
+        .is_equal_to(vec![(unavailable_member_id, event_ids[1])]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN a Gold member with a purchase earned as Basic, and the outbox enabled
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent {
+                    tier_unverified: Some(UnverifiedTier {
+                        tier: Tier::Basic,
+                        purchase_amount: 2,
+                        earn_multiplier: 1,
+                    }),
+                    ..LoyaltyEvent::new(Uuid::new_v4(), 20, "SOME REASON", Utc::now())
+                },
+                None,
+            )
+            .await?;
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |member_id| {
+            Ok(Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now() - Duration::days(800),
+                ..Default::default()
+            })
+        });
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_id_generator(Arc::new(SequentialIds::default()))
+            .with_outbox();
+
+        // WHEN reprocessing unverified events
+        ServiceExt::<ReprocessUnverifiedRequest>::ready(&mut domain)
+            .await?
+            .call(ReprocessUnverifiedRequest)
+            .await?;
+
+        // THEN the adjustment is stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsAdjusted {
+            member_id,
+            event_id: Uuid::from_u128(1),
+            delta_points: 10,
+        }]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, MemberRestriction},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{DomainLogic, Error};
+
+/// Suspend a member's capability to earn or redeem points, e.g. while fraud operations investigate
+///
+/// Commands needing the capability fail with [`Error::Suspended`] until the restriction expires.
+/// Other capabilities are not affected: a member who cannot earn points can still redeem them.
+pub struct RestrictMemberRequest {
+    pub member_id: Uuid,
+    pub capability: Capability,
+    /// Message explaining why the capability is suspended
+    pub reason: String,
+    /// Operator suspending the capability
+    pub restricted_by: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RestrictMemberResponse {
+    pub restriction: MemberRestriction,
+}
+
+impl<D, M> Service<RestrictMemberRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = RestrictMemberResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RestrictMemberRequest) -> Self::Future {
+        let member = self.member.clone();
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move {
+            let now = clock.now();
+            if req.expires_at <= now {
+                return Err(Error::InvalidState(
+                    format!("restriction would expire in the past: {}", req.expires_at).into(),
+                ));
+            }
+
+            // Make sure the member exists
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+
+            let restriction = MemberRestriction {
+                restriction_id: id_generator.generate_id(),
+                member_id: db_member.member_id,
+                capability: req.capability,
+                reason: req.reason,
+                restricted_by: req.restricted_by,
+                restricted_at: now,
+                expires_at: req.expires_at,
+            };
+            database
+                .register_member_restriction(restriction.clone())
+                .await
+                .with_context(|| format!("restricting member {}", req.member_id))?;
+
+            Ok(RestrictMemberResponse { restriction })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::clock::fixed::FixedClock,
+        commands::{
+            add_points::{AddPointsEvent, AddPointsRequest},
+            redeem_points::RedeemPointsRequest,
+        },
+        testing::fixtures::{self, Fixture, MemberBuilder},
+    };
+    use chrono::Duration;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn request(member_id: Uuid, expires_at: DateTime<Utc>) -> RestrictMemberRequest {
+        RestrictMemberRequest {
+            member_id,
+            capability: Capability::Earn,
+            reason: "Suspicious purchase pattern".to_string(),
+            restricted_by: "fraud-ops".to_string(),
+            expires_at,
+        }
+    }
+
+    fn add_points(member_id: Uuid) -> AddPointsRequest {
+        AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::Manual {
+                loyalty_points: 50,
+                reason: None,
+            },
+            occurred_at: None,
+            order_reference: None,
+            idempotency_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with 100 points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let mut domain = domain;
+
+        // WHEN suspending their earning for a week
+        let expires_at = fixtures::now() + Duration::days(7);
+        let res = ServiceExt::<RestrictMemberRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, expires_at))
+            .await;
+
+        // THEN
+        // * the restriction is stored
+        // * they cannot earn points until it expires
+        // * they can still redeem points
+        assert_that!(res).is_ok();
+        let stored = database.get_member_restrictions(member_id).await?;
+        assert_that!(stored).has_length(1);
+        assert_that!(stored[0].restricted_by.as_str()).is_equal_to("fraud-ops");
+
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(add_points(member_id))
+            .await;
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Suspended {
+                    capability: Capability::Earn,
+                    until,
+                    ..
+                } if *until == expires_at
+            )
+        });
+
+        let res = ServiceExt::<RedeemPointsRequest>::ready(&mut domain)
+            .await?
+            .call(RedeemPointsRequest {
+                member_id,
+                loyalty_points: 30,
+                reward: "Free coffee".to_string(),
+                idempotency_key: None,
+            })
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(70);
+
+        let mut domain = domain.with_clock(Arc::new(FixedClock::new(expires_at)));
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(add_points(member_id))
+            .await;
+        assert_that!(res)
+            .is_ok()
+            .map(|res| &res.new_loyalty_points)
+            .is_equal_to(120);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_expired() -> Result<(), BoxError> {
+        // GIVEN a member
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::basic().build().await?;
+        let mut domain = domain;
+
+        // WHEN adding a restriction that already expired
+        let res = ServiceExt::<RestrictMemberRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, fixtures::now() - Duration::days(1)))
+            .await;
+
+        // THEN it returns an error
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+
+        Ok(())
+    }
+}
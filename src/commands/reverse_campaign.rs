@@ -0,0 +1,375 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{DomainEvent, LoyaltyEvent},
+    ports::{database::DatabasePort, member::MemberPort, ErrorChain, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{persist_with_events, reverse_points::reverse_event, DomainLogic, Error};
+
+/// Claw back the points credited by a campaign, e.g. after targeting the wrong audience
+///
+/// Each credit is reversed as with [`ReversePointsRequest`](super::reverse_points::ReversePointsRequest),
+/// following the balance policy. Credits that were already reversed are skipped, so an
+/// interrupted reversal can run again.
+pub struct ReverseCampaignRequest {
+    pub campaign_id: Uuid,
+    /// Only list the credits that would be reversed, without changing any points
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReverseCampaignResponse {
+    pub campaign_id: Uuid,
+    /// Credits reversed, or that would be reversed for a dry run
+    pub reversals: Vec<CampaignReversal>,
+    /// Members whose credit could not be reversed, such as when they spent the points
+    pub failed: Vec<Uuid>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CampaignReversal {
+    pub member_id: Uuid,
+    /// Event that credited the campaign's points
+    pub event_id: Uuid,
+    /// Points credited by the campaign
+    pub loyalty_points: u32,
+    /// Points that could not be clawed back, always `0` for a dry run
+    pub written_off_points: u32,
+}
+
+impl<D, M> Service<ReverseCampaignRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ReverseCampaignResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ReverseCampaignRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let balance_policy = self.balance_policy;
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let credits = database
+                .get_campaign_events(req.campaign_id)
+                .await
+                .with_context(|| format!("fetching events of campaign {}", req.campaign_id))?
+                .into_iter()
+                .filter(|(_, event)| event.delta_points > 0 && event.linked_event_id.is_none());
+
+            let mut reversals = Vec::new();
+            let mut failed = Vec::new();
+            for (member_id, event) in credits {
+                let mut reversal = CampaignReversal {
+                    member_id,
+                    event_id: event.event_id,
+                    loyalty_points: event.delta_points.unsigned_abs(),
+                    written_off_points: 0,
+                };
+                if req.dry_run {
+                    reversals.push(reversal);
+                    continue;
+                }
+
+                let reversal_event = LoyaltyEvent {
+                    campaign_id: Some(req.campaign_id),
+                    ..LoyaltyEvent::new(
+                        id_generator.generate_id(),
+                        // Credits add points, so this cannot overflow
+                        -event.delta_points,
+                        format!("Reversed campaign {}", req.campaign_id),
+                        clock.now(),
+                    )
+                };
+                let points_reversed = DomainEvent::PointsReversed {
+                    member_id,
+                    event_id: reversal_event.event_id,
+                    reversed_event_id: event.event_id,
+                    delta_points: reversal_event.delta_points,
+                };
+                let res = persist_with_events(
+                    event_publisher.as_ref(),
+                    outbox,
+                    vec![points_reversed],
+                    |events| {
+                        reverse_event(
+                            database.as_ref(),
+                            balance_policy,
+                            member_id,
+                            &event,
+                            reversal_event,
+                            events,
+                        )
+                    },
+                )
+                .await;
+                match res {
+                    Ok((_, written_off_points)) => {
+                        reversal.written_off_points = written_off_points;
+                        reversals.push(reversal);
+                    }
+                    Err(err) if !err.is_retryable() => {
+                        tracing::warn!(
+                            campaign_id = %req.campaign_id,
+                            %member_id,
+                            error = %ErrorChain(&err),
+                            "failed to reverse campaign credit"
+                        );
+                        failed.push(member_id);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+
+            Ok(ReverseCampaignResponse {
+                campaign_id: req.campaign_id,
+                reversals,
+                failed,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase,
+        commands::compact_history::CompactHistoryRequest, domain::BalancePolicy,
+        ports::member::MockMemberPort,
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn event(delta_points: i32, campaign_id: Option<Uuid>) -> LoyaltyEvent {
+        LoyaltyEvent {
+            campaign_id,
+            ..LoyaltyEvent::new(Uuid::new_v4(), delta_points, "SOME REASON", Utc::now())
+        }
+    }
+
+    /// Database where two members got 500 points from the campaign, and the second spent 300
+    async fn database(
+        campaign_id: Uuid,
+        member_ids: [Uuid; 2],
+    ) -> Result<MemoryDatabase, BoxError> {
+        let database = MemoryDatabase::default();
+        for member_id in member_ids {
+            database
+                .register_loyalty_event(member_id, event(100, None), None)
+                .await?;
+            database
+                .register_loyalty_event(member_id, event(500, Some(campaign_id)), None)
+                .await?;
+        }
+        database
+            .register_loyalty_event(member_ids[1], event(-300, None), None)
+            .await?;
+        Ok(database)
+    }
+
+    #[tokio::test]
+    async fn test_call_dry_run() -> Result<(), BoxError> {
+        // GIVEN a campaign that credited two members
+        let campaign_id = Uuid::new_v4();
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let database = database(campaign_id, member_ids).await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN previewing the reversal
+        let res = ServiceExt::<ReverseCampaignRequest>::ready(&mut domain)
+            .await?
+            .call(ReverseCampaignRequest {
+                campaign_id,
+                dry_run: true,
+            })
+            .await;
+
+        // THEN both members are listed without changing their points
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.reversals.len() == 2 && res.failed.is_empty());
+        let loyalty = database.get_loyalty_points(member_ids[0]).await?;
+        assert_that!(loyalty.points).is_equal_to(600);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a campaign that credited two members, and the default balance policy
+        let campaign_id = Uuid::new_v4();
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let database = database(campaign_id, member_ids).await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN reversing the campaign twice
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let res = ServiceExt::<ReverseCampaignRequest>::ready(&mut domain)
+                .await?
+                .call(ReverseCampaignRequest {
+                    campaign_id,
+                    dry_run: false,
+                })
+                .await;
+            results.push(res);
+        }
+
+        // THEN the member who spent the points fails, and credits are only reversed once
+        assert_that!(results[0]).is_ok().matches(|res| {
+            res.reversals.len() == 1
+                && res.reversals[0].member_id == member_ids[0]
+                && res.failed == vec![member_ids[1]]
+        });
+        assert_that!(results[1])
+            .is_ok()
+            .matches(|res| res.reversals.is_empty() && res.failed == vec![member_ids[1]]);
+        let loyalty = database.get_loyalty_points(member_ids[0]).await?;
+        assert_that!(loyalty.points).is_equal_to(100);
+        let loyalty = database.get_loyalty_points(member_ids[1]).await?;
+        assert_that!(loyalty.points).is_equal_to(300);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN a campaign that credited two members, and the outbox enabled
+        let campaign_id = Uuid::new_v4();
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let database = database(campaign_id, member_ids).await?;
+        let campaign_event_id = database.get_loyalty_events(member_ids[0]).await?[1].event_id;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_outbox();
+
+        // WHEN reversing the campaign
+        ServiceExt::<ReverseCampaignRequest>::ready(&mut domain)
+            .await?
+            .call(ReverseCampaignRequest {
+                campaign_id,
+                dry_run: false,
+            })
+            .await?;
+
+        // THEN only the successful reversal is stored in the outbox
+        let reversal_event_id = database.get_loyalty_events(member_ids[0]).await?[2].event_id;
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsReversed {
+            member_id: member_ids[0],
+            event_id: reversal_event_id,
+            reversed_event_id: campaign_event_id,
+            delta_points: -500,
+        }]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_write_off() -> Result<(), BoxError> {
+        // GIVEN a campaign that credited two members, and shortfalls are written off
+        let campaign_id = Uuid::new_v4();
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let database = database(campaign_id, member_ids).await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_balance_policy(BalancePolicy::WriteOff);
+
+        // WHEN reversing the campaign
+        let res = ServiceExt::<ReverseCampaignRequest>::ready(&mut domain)
+            .await?
+            .call(ReverseCampaignRequest {
+                campaign_id,
+                dry_run: false,
+            })
+            .await;
+
+        // THEN both credits are reversed, writing off the spent points
+        assert_that!(res).is_ok().matches(|res| {
+            res.reversals.len() == 2
+                && res.failed.is_empty()
+                && res
+                    .reversals
+                    .iter()
+                    .any(|reversal| reversal.written_off_points == 200)
+        });
+        let loyalty = database.get_loyalty_points(member_ids[1]).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_after_compaction() -> Result<(), BoxError> {
+        // GIVEN a member credited by a campaign, between other events
+        let campaign_id = Uuid::new_v4();
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        for event in [
+            event(100, None),
+            event(50, None),
+            event(500, Some(campaign_id)),
+            event(20, None),
+        ] {
+            database
+                .register_loyalty_event(member_id, event, None)
+                .await?;
+        }
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN compacting the member's history, then reversing the campaign
+        let compacted = ServiceExt::<CompactHistoryRequest>::ready(&mut domain)
+            .await?
+            .call(CompactHistoryRequest {
+                member_id,
+                before: u64::MAX,
+                keep_last: 0,
+            })
+            .await?;
+        let res = ServiceExt::<ReverseCampaignRequest>::ready(&mut domain)
+            .await?
+            .call(ReverseCampaignRequest {
+                campaign_id,
+                dry_run: false,
+            })
+            .await;
+
+        // THEN
+        // * compaction stops at the campaign's credit
+        // * the credit is still reversed
+        assert_that!(compacted.compacted_events).is_equal_to(2);
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.reversals.len() == 1 && res.failed.is_empty());
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(170);
+
+        Ok(())
+    }
+}
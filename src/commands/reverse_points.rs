@@ -0,0 +1,396 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{BalancePolicy, DomainEvent, Loyalty, LoyaltyEvent},
+    layers::idempotency::IdempotentRequest,
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{persist_with_events, DomainLogic, Error};
+
+/// Compensate an event with another one giving back its points, e.g. for a purchase refund
+///
+/// Both events are linked together, so an event can only be reversed once. If the member already
+/// spent the points, the [`BalancePolicy`] decides whether the reversal fails or writes off the
+/// shortfall.
+pub struct ReversePointsRequest {
+    pub member_id: Uuid,
+    /// Event to reverse
+    pub event_id: Uuid,
+    /// Reason recorded in the reversal event, defaulting to "Reversal"
+    pub reason: Option<String>,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for ReversePointsRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.member_id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversePointsResponse {
+    pub member_id: Uuid,
+    /// Event compensating the reversed one
+    pub reversal_event_id: Uuid,
+    /// New number of loyalty points
+    pub new_loyalty_points: u32,
+    /// New number of pending loyalty points
+    pub pending_loyalty_points: u32,
+    /// Points that could not be clawed back, with [`BalancePolicy::WriteOff`]
+    pub written_off_points: u32,
+}
+
+impl<D, M> Service<ReversePointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ReversePointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ReversePointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let balance_policy = self.balance_policy;
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let event = database
+                .get_loyalty_events(req.member_id)
+                .await
+                .with_context(|| format!("fetching events for member {}", req.member_id))?
+                .into_iter()
+                .find(|event| event.event_id == req.event_id)
+                .ok_or(crate::ports::database::Error::EventDoesNotExist(
+                    req.event_id,
+                ))?;
+
+            let reversal_event_id = id_generator.generate_id();
+            let reason = req.reason.unwrap_or_else(|| "Reversal".to_string());
+            let delta_points = reversal_points(&event)?;
+            let points_reversed = DomainEvent::PointsReversed {
+                member_id: req.member_id,
+                event_id: reversal_event_id,
+                reversed_event_id: event.event_id,
+                delta_points,
+            };
+            let (loyalty, written_off_points) = persist_with_events(
+                event_publisher.as_ref(),
+                outbox,
+                vec![points_reversed],
+                |events| {
+                    reverse_event(
+                        database.as_ref(),
+                        balance_policy,
+                        req.member_id,
+                        &event,
+                        LoyaltyEvent::new(reversal_event_id, delta_points, reason, clock.now()),
+                        events,
+                    )
+                },
+            )
+            .await?;
+
+            Ok(ReversePointsResponse {
+                member_id: req.member_id,
+                reversal_event_id,
+                new_loyalty_points: loyalty.points,
+                pending_loyalty_points: loyalty.pending_points,
+                written_off_points,
+            })
+        })
+    }
+}
+
+/// Points of an event reversing all of `event`'s points
+pub(super) fn reversal_points(event: &LoyaltyEvent) -> Result<i32, Error> {
+    if event.delta_points == 0 {
+        return Err(Error::InvalidState(
+            format!("event {} has no points to reverse", event.event_id).into(),
+        ));
+    }
+    event.delta_points.checked_neg().ok_or_else(|| {
+        Error::InvalidState(format!("cannot reverse event {}", event.event_id).into())
+    })
+}
+
+/// Reverse `event` with `reversal`, adding `outbox` events to the database's outbox
+///
+/// The reversal's points are set by the caller, e.g. with [`reversal_points`]. If the member
+/// cannot afford the claw-back, this applies the balance policy. This returns the member's loyalty
+/// and the number of points written off.
+pub(super) async fn reverse_event<D>(
+    database: &D,
+    balance_policy: BalancePolicy,
+    member_id: Uuid,
+    event: &LoyaltyEvent,
+    mut reversal: LoyaltyEvent,
+    outbox: Vec<DomainEvent>,
+) -> Result<(Loyalty, u32), Error>
+where
+    D: DatabasePort,
+{
+    let delta_points = reversal.delta_points;
+    let res = database
+        .reverse_event(member_id, event.event_id, reversal.clone(), outbox.clone())
+        .await
+        .with_context(|| {
+            format!(
+                "reversing event {} for member {}",
+                event.event_id, member_id
+            )
+        });
+    let (current_points, missing_points) = match res {
+        Ok(loyalty) => return Ok((loyalty, 0)),
+        Err(crate::ports::database::Error::NegativePointsTotal {
+            current_points,
+            delta_points,
+        }) => (current_points, delta_points.unsigned_abs()),
+        Err(err) => return Err(err.into()),
+    };
+    if balance_policy == BalancePolicy::Reject {
+        return Err(Error::InsufficientPoints {
+            available: current_points,
+            requested: missing_points,
+        });
+    }
+
+    // Claw back what is available, and write off the rest along with the reversal
+    let written_off_points = missing_points - current_points;
+    reversal.delta_points = delta_points + written_off_points as i32;
+    reversal.written_off_points = Some(written_off_points);
+    let loyalty = database
+        .reverse_event(member_id, event.event_id, reversal, outbox)
+        .await
+        .with_context(|| {
+            format!(
+                "reversing event {} for member {}",
+                event.event_id, member_id
+            )
+        })?;
+
+    Ok((loyalty, written_off_points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{adapters::database::memory::MemoryDatabase, ports::member::MockMemberPort};
+    use chrono::{Duration, Utc};
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[fixture]
+    fn member_id() -> Uuid {
+        Uuid::new_v4()
+    }
+
+    fn event(event_id: Uuid, delta_points: i32) -> LoyaltyEvent {
+        LoyaltyEvent::new(event_id, delta_points, "SOME REASON", Utc::now())
+    }
+
+    fn request(member_id: Uuid, event_id: Uuid) -> ReversePointsRequest {
+        ReversePointsRequest {
+            member_id,
+            event_id,
+            reason: Some("Purchase refunded".to_string()),
+            idempotency_key: None,
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN a member with a purchase
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(member_id, event(Uuid::new_v4(), 50), None)
+            .await?;
+        database
+            .register_loyalty_event(member_id, event(event_id, 100), None)
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN reversing the purchase twice
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let res = ServiceExt::<ReversePointsRequest>::ready(&mut domain)
+                .await?
+                .call(request(member_id, event_id))
+                .await;
+            results.push(res);
+        }
+
+        // THEN the points are clawed back once, with both events linked
+        assert_that!(results[0]).is_ok().matches(|res| {
+            res.new_loyalty_points == 50
+                && res.pending_loyalty_points == 0
+                && res.written_off_points == 0
+        });
+        assert_that!(results[1]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Database(crate::ports::database::Error::EventAlreadyLinked(_))
+            )
+        });
+        let events = database.get_loyalty_events(member_id).await?;
+        let reversal_event_id = results[0].as_ref().unwrap().reversal_event_id;
+        assert_that!(events[1].linked_event_id).is_equal_to(Some(reversal_event_id));
+        assert_that!(events[2].linked_event_id).is_equal_to(Some(event_id));
+        assert_that!(events[2].delta_points).is_equal_to(-100);
+        assert_that!(events[2].reason.as_str()).is_equal_to("Purchase refunded");
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_outbox(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN a member with a purchase and the outbox enabled
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(member_id, event(event_id, 100), None)
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_outbox();
+
+        // WHEN reversing the purchase
+        let res = ServiceExt::<ReversePointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, event_id))
+            .await?;
+
+        // THEN the reversal is stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsReversed {
+            member_id,
+            event_id: res.reversal_event_id,
+            reversed_event_id: event_id,
+            delta_points: -100,
+        }]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_call_pending(member_id: Uuid) -> Result<(), BoxError> {
+        // GIVEN a member with available points and a pending purchase
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(member_id, event(Uuid::new_v4(), 50), None)
+            .await?;
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent {
+                    matures_at: Some(Utc::now() + Duration::days(14)),
+                    ..event(event_id, 100)
+                },
+                None,
+            )
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN reversing the purchase
+        let res = ServiceExt::<ReversePointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, event_id))
+            .await;
+
+        // THEN the pending points are removed, leaving the available points
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.new_loyalty_points == 50 && res.pending_loyalty_points == 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(BalancePolicy::Reject, None)]
+    #[case(BalancePolicy::WriteOff, Some(60))]
+    #[tokio::test]
+    async fn test_call_balance_policy(
+        member_id: Uuid,
+        #[case] balance_policy: BalancePolicy,
+        #[case] expected_written_off: Option<u32>,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member who spent most of the points of a purchase
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(member_id, event(event_id, 100), None)
+            .await?;
+        database
+            .register_loyalty_event(member_id, event(Uuid::new_v4(), -60), None)
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()))
+                .with_balance_policy(balance_policy);
+
+        // WHEN reversing the purchase
+        let res = ServiceExt::<ReversePointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(member_id, event_id))
+            .await;
+
+        // THEN it either fails, or claws back the remaining points and writes off the rest
+        match expected_written_off {
+            None => {
+                assert_that!(res).is_err().matches(|err| {
+                    matches!(
+                        err,
+                        Error::InsufficientPoints {
+                            available: 40,
+                            requested: 100
+                        }
+                    )
+                });
+            }
+            Some(expected_written_off) => {
+                assert_that!(res).is_ok().matches(|res| {
+                    res.new_loyalty_points == 0 && res.written_off_points == expected_written_off
+                });
+                let events = database.get_loyalty_events(member_id).await?;
+                assert_that!(events).has_length(3);
+                assert_that!(events[2].delta_points).is_equal_to(-40);
+                assert_that!(events[2].written_off_points).is_equal_to(Some(60));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, DomainEvent},
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, DomainLogic, Error};
+
+/// Approve or reject an event awaiting approval
+pub struct ReviewEventRequest {
+    pub member_id: Uuid,
+    pub event_id: Uuid,
+    pub decision: ReviewDecision,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReviewDecision {
+    /// Apply the event to the member's loyalty points
+    Approve,
+    /// Discard the event
+    Reject,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReviewEventResponse {
+    pub member_id: Uuid,
+    pub event_id: Uuid,
+    pub decision: ReviewDecision,
+    /// Number of loyalty points after the review
+    pub loyalty_points: u32,
+}
+
+impl<D, M> Service<ReviewEventRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = ReviewEventResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ReviewEventRequest) -> Self::Future {
+        let database = self.database.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let event = database
+                .get_events_awaiting_approval(req.member_id)
+                .await?
+                .into_iter()
+                .find(|event| event.event_id == req.event_id);
+            // Renewals in escrow are settled by the billing service, once the member paid
+            if event
+                .as_ref()
+                .is_some_and(|event| event.escrow_expires_at.is_some())
+            {
+                return Err(Error::InvalidState(
+                    format!("event {} awaits payment confirmation", req.event_id).into(),
+                ));
+            }
+
+            let loyalty_points = match req.decision {
+                ReviewDecision::Approve => {
+                    ensure_capability(
+                        database.as_ref(),
+                        req.member_id,
+                        Capability::Earn,
+                        clock.now(),
+                    )
+                    .await?;
+                    let points_added = event.map(|event| DomainEvent::PointsAdded {
+                        member_id: req.member_id,
+                        event_id: req.event_id,
+                        loyalty_points: event.delta_points.unsigned_abs(),
+                    });
+                    persist_with_events(
+                        event_publisher.as_ref(),
+                        outbox,
+                        points_added.into_iter().collect(),
+                        |events| database.approve_event(req.member_id, req.event_id, events),
+                    )
+                    .await?
+                    .points
+                }
+                ReviewDecision::Reject => {
+                    database.reject_event(req.member_id, req.event_id).await?;
+                    database.get_loyalty_points(req.member_id).await?.points
+                }
+            };
+
+            Ok(ReviewEventResponse {
+                member_id: req.member_id,
+                event_id: req.event_id,
+                decision: req.decision,
+                loyalty_points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::database::memory::MemoryDatabase,
+        domain::LoyaltyEvent,
+        ports::member::MockMemberPort,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use chrono::Utc;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[rstest]
+    #[case(ReviewDecision::Approve, 5000)]
+    #[case(ReviewDecision::Reject, 0)]
+    #[tokio::test]
+    async fn test_call(
+        #[case] decision: ReviewDecision,
+        #[case] expected: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN an event awaiting approval
+        let member_id = Uuid::new_v4();
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_event_for_approval(
+                member_id,
+                LoyaltyEvent::new(event_id, 5000, "SOME REASON", Utc::now()),
+            )
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN reviewing the event
+        let req = ReviewEventRequest {
+            member_id,
+            event_id,
+            decision,
+        };
+        let res = ServiceExt::<ReviewEventRequest>::ready(&mut domain)
+            .await?
+            .call(req)
+            .await;
+
+        // THEN
+        // * the points reflect the decision
+        // * the event no longer awaits approval
+        assert_that!(res).is_ok().is_equal_to(ReviewEventResponse {
+            member_id,
+            event_id,
+            decision,
+            loyalty_points: expected,
+        });
+        let events = database.get_events_awaiting_approval(member_id).await?;
+        assert_that!(events).is_empty();
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(ReviewDecision::Approve)]
+    #[case(ReviewDecision::Reject)]
+    #[tokio::test]
+    async fn test_call_escrow(#[case] decision: ReviewDecision) -> Result<(), BoxError> {
+        // GIVEN a renewal in escrow, waiting for the payment confirmation
+        let member_id = Uuid::new_v4();
+        let event_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        database
+            .register_event_for_approval(
+                member_id,
+                LoyaltyEvent {
+                    escrow_expires_at: Some(Utc::now() + chrono::Duration::days(3)),
+                    order_reference: Some("INV1".to_string()),
+                    ..LoyaltyEvent::new(event_id, 290, "Membership renewed", Utc::now())
+                },
+            )
+            .await?;
+        let mut domain =
+            DomainLogic::new(Arc::new(database.clone()), Arc::new(MockMemberPort::new()));
+
+        // WHEN reviewing the event
+        let res = ServiceExt::<ReviewEventRequest>::ready(&mut domain)
+            .await?
+            .call(ReviewEventRequest {
+                member_id,
+                event_id,
+                decision,
+            })
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * the renewal stays in escrow
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let events = database.get_events_awaiting_approval(member_id).await?;
+        assert_that!(events).has_length(1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_suspended() -> Result<(), BoxError> {
+        // GIVEN an event awaiting approval, for a member who cannot earn points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_restriction(Capability::Earn)
+            .build()
+            .await?;
+        let event_id = Uuid::new_v4();
+        database
+            .register_event_for_approval(
+                member_id,
+                LoyaltyEvent::new(event_id, 5000, "SOME REASON", Utc::now()),
+            )
+            .await?;
+        let mut domain = domain;
+
+        // WHEN approving the event
+        let res = ServiceExt::<ReviewEventRequest>::ready(&mut domain)
+            .await?
+            .call(ReviewEventRequest {
+                member_id,
+                event_id,
+                decision: ReviewDecision::Approve,
+            })
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * the event still awaits approval
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Suspended {
+                    capability: Capability::Earn,
+                    ..
+                }
+            )
+        });
+        let events = database.get_events_awaiting_approval(member_id).await?;
+        assert_that!(events).has_length(1);
+
+        Ok(())
+    }
+}
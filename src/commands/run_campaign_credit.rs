@@ -0,0 +1,518 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{CampaignRun, Capability, DomainEvent, LoyaltyEvent},
+    ports::{
+        database::DatabasePort, event_publisher::EventPublisherPort, member::MemberPort,
+        ErrorChain, ResultExt,
+    },
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, DomainLogic, Error};
+
+/// Number of members credited between two saves of the campaign's progress
+const CHUNK_SIZE: usize = 100;
+
+/// Credit the same number of points to every member of a campaign's audience
+///
+/// Progress is saved after each chunk of members under the campaign ID. Running a campaign again
+/// resumes an interrupted run, or returns the report of a completed one without crediting anyone
+/// twice. Members that cannot be credited are reported instead of failing the whole run.
+///
+/// Once the campaign's budget is exhausted, the run pauses. It can resume with a higher budget.
+pub struct RunCampaignCreditRequest {
+    pub campaign_id: Uuid,
+    pub audience: CampaignAudience,
+    /// Points to credit to each member
+    pub loyalty_points: u32,
+    /// Reason recorded in each member's event
+    pub reason: String,
+    /// Maximum number of points issued by the campaign, across all runs and workers
+    pub budget: Option<u64>,
+}
+
+/// Members targeted by a campaign
+pub enum CampaignAudience {
+    /// Explicit list of members
+    Members(Vec<Uuid>),
+    /// All members of a marketing segment, when the run starts
+    Segment(String),
+}
+
+impl<D, M> Service<RunCampaignCreditRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = CampaignRun;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RunCampaignCreditRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let segment = self.segment.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let delta_points = i32::try_from(req.loyalty_points).map_err(|_| {
+                Error::InvalidState(
+                    format!("cannot credit {} points at once", req.loyalty_points).into(),
+                )
+            })?;
+
+            // Resume the previous run of this campaign, if any
+            let previous_run = database
+                .get_campaign_run(req.campaign_id)
+                .await
+                .with_context(|| format!("fetching campaign {}", req.campaign_id))?;
+            let mut run = match previous_run {
+                Some(run)
+                    if run.loyalty_points != req.loyalty_points || run.reason != req.reason =>
+                {
+                    return Err(Error::InvalidState(
+                        format!(
+                            "campaign {} already ran with different parameters",
+                            req.campaign_id
+                        )
+                        .into(),
+                    ));
+                }
+                Some(mut run) => {
+                    if run.completed_at.is_none() {
+                        run.budget = req.budget;
+                        run.budget_exhausted = false;
+                    }
+                    run
+                }
+                None => {
+                    let audience = match req.audience {
+                        CampaignAudience::Members(members) => members,
+                        CampaignAudience::Segment(name) => segment
+                            .ok_or_else(|| {
+                                Error::InvalidState("segments are not configured".into())
+                            })?
+                            .get_segment_members(&name)
+                            .await
+                            .with_context(|| format!("fetching members of segment {}", name))?,
+                    };
+                    let mut seen = HashSet::new();
+                    let mut members = audience;
+                    members.retain(|member_id| seen.insert(*member_id));
+
+                    CampaignRun {
+                        campaign_id: req.campaign_id,
+                        loyalty_points: req.loyalty_points,
+                        reason: req.reason,
+                        members,
+                        processed: 0,
+                        failed: Vec::new(),
+                        budget: req.budget,
+                        budget_exhausted: false,
+                        completed_at: None,
+                    }
+                }
+            };
+
+            while run.processed < run.members.len() && !run.budget_exhausted {
+                let chunk_end = (run.processed + CHUNK_SIZE).min(run.members.len());
+                while run.processed < chunk_end {
+                    let member_id = run.members[run.processed];
+                    if let Some(budget) = run.budget {
+                        let reserved = database
+                            .reserve_campaign_budget(run.campaign_id, run.loyalty_points, budget)
+                            .await
+                            .with_context(|| {
+                                format!("reserving budget of campaign {}", run.campaign_id)
+                            })?;
+                        if !reserved {
+                            run.budget_exhausted = true;
+                            break;
+                        }
+                    }
+
+                    let res = credit_member(
+                        database.as_ref(),
+                        member.as_ref(),
+                        event_publisher.as_ref(),
+                        outbox,
+                        member_id,
+                        clock.now(),
+                        LoyaltyEvent {
+                            campaign_id: Some(run.campaign_id),
+                            ..LoyaltyEvent::new(
+                                id_generator.generate_id(),
+                                delta_points,
+                                run.reason.clone(),
+                                clock.now(),
+                            )
+                        },
+                    )
+                    .await;
+                    if let Err(err) = res {
+                        tracing::warn!(
+                            campaign_id = %run.campaign_id,
+                            %member_id,
+                            error = %ErrorChain(&err),
+                            "failed to credit campaign points"
+                        );
+                        run.failed.push(member_id);
+                        if run.budget.is_some() {
+                            database
+                                .release_campaign_budget(run.campaign_id, run.loyalty_points)
+                                .await
+                                .with_context(|| {
+                                    format!("releasing budget of campaign {}", run.campaign_id)
+                                })?;
+                        }
+                    }
+                    run.processed += 1;
+                }
+
+                if run.processed == run.members.len() {
+                    run.completed_at = Some(clock.now());
+                }
+                database
+                    .save_campaign_run(run.clone())
+                    .await
+                    .with_context(|| format!("saving progress of campaign {}", run.campaign_id))?;
+            }
+
+            Ok(run)
+        })
+    }
+}
+
+/// Credit a campaign's points to a member, making sure they exist and can earn points first
+async fn credit_member<D, M>(
+    database: &D,
+    member: &M,
+    event_publisher: &(dyn EventPublisherPort + Send + Sync),
+    outbox: bool,
+    member_id: Uuid,
+    now: DateTime<Utc>,
+    event: LoyaltyEvent,
+) -> Result<(), Error>
+where
+    D: DatabasePort,
+    M: MemberPort,
+{
+    member
+        .get_member(member_id)
+        .await
+        .with_context(|| format!("fetching member {}", member_id))?;
+    ensure_capability(database, member_id, Capability::Earn, now).await?;
+    let points_added = DomainEvent::PointsAdded {
+        member_id,
+        event_id: event.event_id,
+        loyalty_points: event.delta_points.unsigned_abs(),
+    };
+    persist_with_events(event_publisher, outbox, vec![points_added], |events| {
+        database.register_loyalty_event_with_outbox(member_id, event, None, events)
+    })
+    .await
+    .with_context(|| format!("registering event for member {}", member_id))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            database::memory::MemoryDatabase, id_generator::sequential::SequentialIds,
+            segment::memory::StaticSegments,
+        },
+        ports::member::{Member, MockMemberPort},
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    /// Member port where only `member_ids` exist
+    fn member_port(member_ids: Vec<Uuid>) -> MockMemberPort {
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |member_id| {
+            if !member_ids.contains(&member_id) {
+                return Err(crate::ports::member::Error::MemberDoesNotExist(member_id));
+            }
+            Ok(Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        member
+    }
+
+    fn request(campaign_id: Uuid, audience: CampaignAudience) -> RunCampaignCreditRequest {
+        RunCampaignCreditRequest {
+            campaign_id,
+            audience,
+            loyalty_points: 500,
+            reason: "Spring campaign".to_string(),
+            budget: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a list of members, with a duplicate and an unknown member
+        let campaign_id = Uuid::new_v4();
+        let member_ids = (0..CHUNK_SIZE + 2)
+            .map(|_| Uuid::new_v4())
+            .collect::<Vec<_>>();
+        let unknown_member_id = Uuid::new_v4();
+        let mut audience = member_ids.clone();
+        audience.push(member_ids[0]);
+        audience.push(unknown_member_id);
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(
+            Arc::new(database.clone()),
+            Arc::new(member_port(member_ids.clone())),
+        );
+
+        // WHEN running the campaign twice
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let res = ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+                .await?
+                .call(request(
+                    campaign_id,
+                    CampaignAudience::Members(audience.clone()),
+                ))
+                .await;
+            results.push(res);
+        }
+
+        // THEN each known member is credited once, and the second run returns the same report
+        assert_that!(results[0]).is_ok().matches(|run| {
+            run.processed == CHUNK_SIZE + 3
+                && run.credited() == CHUNK_SIZE + 2
+                && run.failed == vec![unknown_member_id]
+                && run.completed_at.is_some()
+        });
+        assert_that!(results[1])
+            .is_ok()
+            .is_equal_to(results[0].as_ref().unwrap().clone());
+        let loyalty = database.get_loyalty_points(member_ids[0]).await?;
+        assert_that!(loyalty.points).is_equal_to(500);
+        assert_that!(loyalty.events[0].campaign_id).is_equal_to(Some(campaign_id));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN a member and the outbox enabled
+        let campaign_id = Uuid::new_v4();
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(
+            Arc::new(database.clone()),
+            Arc::new(member_port(vec![member_id])),
+        )
+        .with_id_generator(Arc::new(SequentialIds::default()))
+        .with_outbox();
+
+        // WHEN running the campaign
+        ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+            .await?
+            .call(request(
+                campaign_id,
+                CampaignAudience::Members(vec![member_id]),
+            ))
+            .await?;
+
+        // THEN the credited points are stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsAdded {
+            member_id,
+            event_id: Uuid::from_u128(1),
+            loyalty_points: 500,
+        }]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_resume() -> Result<(), BoxError> {
+        // GIVEN a campaign interrupted after its first member
+        let campaign_id = Uuid::new_v4();
+        let member_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+        let database = MemoryDatabase::default();
+        database
+            .save_campaign_run(CampaignRun {
+                campaign_id,
+                loyalty_points: 500,
+                reason: "Spring campaign".to_string(),
+                members: member_ids.clone(),
+                processed: 1,
+                failed: Vec::new(),
+                budget: None,
+                budget_exhausted: false,
+                completed_at: None,
+            })
+            .await?;
+        let mut domain = DomainLogic::new(
+            Arc::new(database.clone()),
+            Arc::new(member_port(member_ids.clone())),
+        );
+
+        // WHEN running the campaign again, and with different points
+        let res = ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+            .await?
+            .call(request(campaign_id, CampaignAudience::Members(Vec::new())))
+            .await;
+        let mut changed_request = request(campaign_id, CampaignAudience::Members(Vec::new()));
+        changed_request.loyalty_points = 1000;
+        let changed_res = ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+            .await?
+            .call(changed_request)
+            .await;
+
+        // THEN only the remaining member of the stored audience is credited
+        assert_that!(res)
+            .is_ok()
+            .matches(|run| run.processed == 2 && run.completed_at.is_some());
+        assert_that!(changed_res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InvalidState(_)));
+        let loyalty = database.get_loyalty_points(member_ids[0]).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+        let loyalty = database.get_loyalty_points(member_ids[1]).await?;
+        assert_that!(loyalty.points).is_equal_to(500);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_budget() -> Result<(), BoxError> {
+        // GIVEN a campaign for three members, with a budget for two credits
+        let campaign_id = Uuid::new_v4();
+        let member_ids = vec![Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(
+            Arc::new(database.clone()),
+            Arc::new(member_port(member_ids.clone())),
+        );
+
+        // WHEN running the campaign, then again with a higher budget
+        let mut results = Vec::new();
+        for budget in [1000, 1500] {
+            let mut req = request(campaign_id, CampaignAudience::Members(member_ids.clone()));
+            req.budget = Some(budget);
+            let res = ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+                .await?
+                .call(req)
+                .await;
+            results.push(res);
+        }
+
+        // THEN the first run pauses before the last member, and the second one completes
+        assert_that!(results[0]).is_ok().matches(|run| {
+            run.processed == 2 && run.budget_exhausted && run.completed_at.is_none()
+        });
+        assert_that!(results[1]).is_ok().matches(|run| {
+            run.processed == 3 && !run.budget_exhausted && run.completed_at.is_some()
+        });
+        let loyalty = database.get_loyalty_points(member_ids[2]).await?;
+        assert_that!(loyalty.points).is_equal_to(500);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_segment() -> Result<(), BoxError> {
+        // GIVEN a segment with one member
+        let campaign_id = Uuid::new_v4();
+        let member_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+        let database = MemoryDatabase::default();
+        let mut domain = DomainLogic::new(
+            Arc::new(database.clone()),
+            Arc::new(member_port(member_ids.clone())),
+        )
+        .with_segments(
+            Arc::new(
+                StaticSegments::default()
+                    .with_member(member_ids[0], ["student"])
+                    .with_member(member_ids[1], ["newsletter"]),
+            ),
+            [],
+        );
+
+        // WHEN running a campaign for that segment
+        let res = ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+            .await?
+            .call(request(
+                campaign_id,
+                CampaignAudience::Segment("student".to_string()),
+            ))
+            .await;
+
+        // THEN only the segment's member is credited
+        assert_that!(res)
+            .is_ok()
+            .matches(|run| run.members == vec![member_ids[0]] && run.credited() == 1);
+        let loyalty = database.get_loyalty_points(member_ids[1]).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_suspended() -> Result<(), BoxError> {
+        // GIVEN a member who cannot earn points
+        let campaign_id = Uuid::new_v4();
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic()
+            .with_restriction(Capability::Earn)
+            .build()
+            .await?;
+        let mut domain = domain;
+
+        // WHEN running a campaign targeting them
+        let res = ServiceExt::<RunCampaignCreditRequest>::ready(&mut domain)
+            .await?
+            .call(request(
+                campaign_id,
+                CampaignAudience::Members(vec![member_id]),
+            ))
+            .await;
+
+        // THEN the member is reported as failed, without points
+        assert_that!(res)
+            .is_ok()
+            .matches(|run| run.failed == vec![member_id] && run.completed_at.is_some());
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{normalize_order_reference, Capability, DomainEvent},
+    ports::{database::DatabasePort, member::MemberPort, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, DomainLogic, Error};
+
+/// Add or discard a member's renewal points held in escrow, once the billing service reports on
+/// the payment of an invoice
+///
+/// This handles the payment events of the billing service. Only the renewal with the invoice's
+/// billing reference is settled. Payments confirmed after the escrow expired do not add the
+/// points, see [`DomainLogic::with_renewal_escrow`].
+pub struct SettleRenewalEscrowRequest {
+    pub member_id: Uuid,
+    /// Reference of the invoice, as given when adding the renewal points
+    pub billing_reference: String,
+    pub payment: PaymentOutcome,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    /// Add the points to the member's loyalty points
+    Confirmed,
+    /// Discard the points
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettleRenewalEscrowResponse {
+    pub member_id: Uuid,
+    pub payment: PaymentOutcome,
+    /// Events that were settled, oldest first
+    ///
+    /// Renewals whose escrow expired are not added, and are left to
+    /// [`ExpireRenewalEscrowRequest`](super::expire_renewal_escrow::ExpireRenewalEscrowRequest).
+    pub event_ids: Vec<Uuid>,
+    /// Number of loyalty points after settling
+    pub loyalty_points: u32,
+}
+
+impl<D, M> Service<SettleRenewalEscrowRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = SettleRenewalEscrowResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SettleRenewalEscrowRequest) -> Self::Future {
+        let database = self.database.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let billing_reference = normalize_order_reference(&req.billing_reference)
+                .ok_or_else(|| Error::InvalidState("empty billing reference".into()))?;
+            let now = clock.now();
+            let escrowed: Vec<_> = database
+                .get_events_awaiting_approval(req.member_id)
+                .await
+                .with_context(|| format!("fetching escrow for member {}", req.member_id))?
+                .into_iter()
+                .filter(|event| {
+                    event.order_reference.as_ref() == Some(&billing_reference)
+                        && event
+                            .escrow_expires_at
+                            .is_some_and(|expires_at| match req.payment {
+                                PaymentOutcome::Confirmed => expires_at > now,
+                                PaymentOutcome::Failed => true,
+                            })
+                })
+                .collect();
+            if req.payment == PaymentOutcome::Confirmed && !escrowed.is_empty() {
+                ensure_capability(database.as_ref(), req.member_id, Capability::Earn, now).await?;
+            }
+
+            for event in &escrowed {
+                match req.payment {
+                    PaymentOutcome::Confirmed => {
+                        let points_added = DomainEvent::PointsAdded {
+                            member_id: req.member_id,
+                            event_id: event.event_id,
+                            loyalty_points: event.delta_points.unsigned_abs(),
+                        };
+                        persist_with_events(
+                            event_publisher.as_ref(),
+                            outbox,
+                            vec![points_added],
+                            |events| database.approve_event(req.member_id, event.event_id, events),
+                        )
+                        .await
+                        .with_context(|| format!("adding escrowed event {}", event.event_id))?;
+                    }
+                    PaymentOutcome::Failed => {
+                        database
+                            .reject_event(req.member_id, event.event_id)
+                            .await
+                            .with_context(|| {
+                                format!("discarding escrowed event {}", event.event_id)
+                            })?;
+                    }
+                }
+            }
+
+            let loyalty = database
+                .get_loyalty_points(req.member_id)
+                .await
+                .with_context(|| format!("fetching loyalty for member {}", req.member_id))?;
+
+            Ok(SettleRenewalEscrowResponse {
+                member_id: req.member_id,
+                payment: req.payment,
+                event_ids: escrowed.iter().map(|event| event.event_id).collect(),
+                loyalty_points: loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{clock::fixed::FixedClock, event_publisher::memory::MemoryPublisher},
+        commands::add_points::{AddPointsEvent, AddPointsRequest},
+        domain::MemberRestriction,
+        testing::fixtures::{self, Fixture, MemberBuilder},
+    };
+    use chrono::Duration;
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    fn renewal(member_id: Uuid, billing_reference: &str) -> AddPointsRequest {
+        AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::MembershipRenewed,
+            occurred_at: None,
+            order_reference: Some(billing_reference.to_string()),
+            idempotency_key: None,
+        }
+    }
+
+    #[rstest]
+    #[case(PaymentOutcome::Confirmed, 390, 1)]
+    #[case(PaymentOutcome::Failed, 100, 0)]
+    #[tokio::test]
+    async fn test_call(
+        #[case] payment: PaymentOutcome,
+        #[case] expected_points: u32,
+        #[case] expected_published: usize,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member with 100 points, who renewed their membership twice with escrow
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = domain
+            .with_renewal_escrow(Duration::days(3))
+            .with_event_publisher(Arc::new(event_publisher.clone()));
+        for billing_reference in ["INV-1", "INV-2"] {
+            let res = ServiceExt::<AddPointsRequest>::ready(&mut domain)
+                .await?
+                .call(renewal(member_id, billing_reference))
+                .await?;
+            assert_that!(res.awaiting_approval).is_true();
+            assert_that!(res.new_loyalty_points).is_equal_to(100);
+        }
+        let escrowed = database.get_events_awaiting_approval(member_id).await?;
+
+        // WHEN the billing service reports on the payment of the first invoice
+        let res = ServiceExt::<SettleRenewalEscrowRequest>::ready(&mut domain)
+            .await?
+            .call(SettleRenewalEscrowRequest {
+                member_id,
+                billing_reference: "inv-1".to_string(),
+                payment,
+            })
+            .await;
+
+        // THEN
+        // * the renewal points are added only if the payment succeeded
+        // * the other renewal stays in escrow
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(SettleRenewalEscrowResponse {
+                member_id,
+                payment,
+                event_ids: vec![escrowed[0].event_id],
+                loyalty_points: expected_points,
+            });
+        assert_that!(database.get_events_awaiting_approval(member_id).await?)
+            .is_equal_to(vec![escrowed[1].clone()]);
+        assert_that!(event_publisher.events()).has_length(expected_published);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_confirmed_after_expiry() -> Result<(), BoxError> {
+        // GIVEN a member who renewed their membership with a 3-day escrow
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let clock = FixedClock::new(fixtures::now());
+        let mut domain = domain
+            .with_renewal_escrow(Duration::days(3))
+            .with_clock(Arc::new(clock.clone()));
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(renewal(member_id, "INV-1"))
+            .await?;
+
+        // WHEN the billing service confirms the payment four days later
+        clock.advance(Duration::days(4));
+        let res = ServiceExt::<SettleRenewalEscrowRequest>::ready(&mut domain)
+            .await?
+            .call(SettleRenewalEscrowRequest {
+                member_id,
+                billing_reference: "INV-1".to_string(),
+                payment: PaymentOutcome::Confirmed,
+            })
+            .await;
+
+        // THEN the renewal points are not added
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(SettleRenewalEscrowResponse {
+                member_id,
+                payment: PaymentOutcome::Confirmed,
+                event_ids: Vec::new(),
+                loyalty_points: 100,
+            });
+        assert_that!(database.get_events_awaiting_approval(member_id).await?).has_length(1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_suspended() -> Result<(), BoxError> {
+        // GIVEN a member who renewed their membership with escrow, then got suspended from earning
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let mut domain = domain.with_renewal_escrow(Duration::days(3));
+        ServiceExt::<AddPointsRequest>::ready(&mut domain)
+            .await?
+            .call(renewal(member_id, "INV-1"))
+            .await?;
+        database
+            .register_member_restriction(MemberRestriction {
+                restriction_id: Uuid::new_v4(),
+                member_id,
+                capability: Capability::Earn,
+                reason: "Suspicious renewals".to_string(),
+                restricted_by: "fraud-ops".to_string(),
+                restricted_at: fixtures::now(),
+                expires_at: fixtures::now() + Duration::days(7),
+            })
+            .await?;
+
+        // WHEN the billing service confirms the payment
+        let res = ServiceExt::<SettleRenewalEscrowRequest>::ready(&mut domain)
+            .await?
+            .call(SettleRenewalEscrowRequest {
+                member_id,
+                billing_reference: "INV-1".to_string(),
+                payment: PaymentOutcome::Confirmed,
+            })
+            .await;
+
+        // THEN
+        // * it returns an error
+        // * the renewal stays in escrow
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::Suspended {
+                    capability: Capability::Earn,
+                    ..
+                }
+            )
+        });
+        assert_that!(database.get_events_awaiting_approval(member_id).await?).has_length(1);
+
+        Ok(())
+    }
+}
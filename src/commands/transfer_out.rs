@@ -0,0 +1,399 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, DomainEvent, Loyalty, LoyaltyEvent, PartnerTransfer, TransferStatus},
+    ports::{
+        database::DatabasePort, event_publisher::EventPublisherPort, member::MemberPort,
+        partner_transfer, ErrorChain, ResultExt,
+    },
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, redeem_points::redeem, DomainLogic, Error};
+
+/// Convert points to a partner program's currency, e.g. airline miles
+///
+/// The points are debited before calling the partner, and given back if the partner rejects the
+/// transfer. If the partner cannot be reached, the transfer stays
+/// [`Pending`](TransferStatus::Pending), as the partner might have credited it.
+pub struct TransferOutRequest {
+    pub member_id: Uuid,
+    /// Name of the partner program
+    pub partner: String,
+    pub loyalty_points: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferOutResponse {
+    pub member_id: Uuid,
+    pub transfer_id: Uuid,
+    pub status: TransferStatus,
+    /// New number of loyalty points, including points given back by a rejected transfer
+    pub new_loyalty_points: u32,
+}
+
+impl<D, M> Service<TransferOutRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = TransferOutResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: TransferOutRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let partner_transfer = self.partner_transfer();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            let partner_transfer = partner_transfer?;
+            if req.loyalty_points == 0 {
+                return Err(Error::InvalidState("cannot transfer 0 points".into()));
+            }
+            let delta_points = i32::try_from(req.loyalty_points)
+                .map(|loyalty_points| -loyalty_points)
+                .map_err(|_| {
+                    Error::InvalidState(
+                        format!("cannot transfer {} points at once", req.loyalty_points).into(),
+                    )
+                })?;
+
+            // Make sure the member exists
+            let db_member = member
+                .get_member(req.member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.member_id))?;
+            let now = clock.now();
+            ensure_capability(
+                database.as_ref(),
+                db_member.member_id,
+                Capability::Redeem,
+                now,
+            )
+            .await?;
+
+            // Debit the points before the partner credits them
+            let mut transfer = PartnerTransfer {
+                transfer_id: id_generator.generate_id(),
+                member_id: db_member.member_id,
+                partner: req.partner,
+                points: req.loyalty_points,
+                debit_event_id: id_generator.generate_id(),
+                status: TransferStatus::Pending,
+                created_at: now,
+            };
+            let loyalty = redeem(
+                database.as_ref(),
+                event_publisher.as_ref(),
+                outbox,
+                transfer.member_id,
+                LoyaltyEvent::new(
+                    transfer.debit_event_id,
+                    delta_points,
+                    format!("Transferred to {}", transfer.partner),
+                    now,
+                ),
+            )
+            .await?;
+            if let Err(err) = database
+                .save_partner_transfer(transfer.clone())
+                .await
+                .with_context(|| format!("saving transfer {}", transfer.transfer_id))
+            {
+                // Without a record of the transfer, nobody could follow up on it
+                if let Err(compensate_err) = compensate(
+                    database.as_ref(),
+                    event_publisher.as_ref(),
+                    outbox,
+                    &transfer,
+                    id_generator.generate_id(),
+                    now,
+                )
+                .await
+                {
+                    tracing::error!(
+                        transfer_id = %transfer.transfer_id,
+                        member_id = %transfer.member_id,
+                        points = transfer.points,
+                        error = %ErrorChain(&err),
+                        compensate_error = %ErrorChain(&compensate_err),
+                        "failed to give back points of an unsaved transfer"
+                    );
+                }
+                return Err(err.into());
+            }
+
+            let res = partner_transfer
+                .transfer(
+                    transfer.transfer_id,
+                    &transfer.partner,
+                    transfer.member_id,
+                    transfer.points,
+                )
+                .await
+                .with_context(|| format!("transferring points to {}", transfer.partner));
+            let loyalty = match res {
+                Ok(partner_reference) => {
+                    transfer.status = TransferStatus::Completed { partner_reference };
+                    loyalty
+                }
+                Err(partner_transfer::Error::Rejected(reason)) => {
+                    let loyalty = compensate(
+                        database.as_ref(),
+                        event_publisher.as_ref(),
+                        outbox,
+                        &transfer,
+                        id_generator.generate_id(),
+                        now,
+                    )
+                    .await?;
+                    transfer.status = TransferStatus::Rejected { reason };
+                    loyalty
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        transfer_id = %transfer.transfer_id,
+                        error = %ErrorChain(&err),
+                        "partner transfer outcome unknown, leaving it pending"
+                    );
+                    loyalty
+                }
+            };
+
+            if transfer.status != TransferStatus::Pending {
+                // The points already moved, so failing would hide the outcome. The transfer stays
+                // pending, and needs a manual follow-up with the partner.
+                if let Err(err) = database.save_partner_transfer(transfer.clone()).await {
+                    tracing::warn!(
+                        transfer_id = %transfer.transfer_id,
+                        error = %ErrorChain(&err),
+                        "failed to save transfer status"
+                    );
+                }
+            }
+
+            Ok(TransferOutResponse {
+                member_id: transfer.member_id,
+                transfer_id: transfer.transfer_id,
+                status: transfer.status,
+                new_loyalty_points: loyalty.points,
+            })
+        })
+    }
+}
+
+/// Give the points of `transfer` back to the member, reversing its debit
+async fn compensate<D>(
+    database: &D,
+    event_publisher: &(dyn EventPublisherPort + Send + Sync),
+    outbox: bool,
+    transfer: &PartnerTransfer,
+    event_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<Loyalty, Error>
+where
+    D: DatabasePort + ?Sized,
+{
+    let points_added = DomainEvent::PointsAdded {
+        member_id: transfer.member_id,
+        event_id,
+        loyalty_points: transfer.points,
+    };
+    let loyalty = persist_with_events(event_publisher, outbox, vec![points_added], |events| {
+        database.reverse_event(
+            transfer.member_id,
+            transfer.debit_event_id,
+            LoyaltyEvent::new(
+                event_id,
+                // Checked when debiting the points
+                transfer.points as i32,
+                format!("Transfer to {} rejected", transfer.partner),
+                now,
+            ),
+            events,
+        )
+    })
+    .await
+    .with_context(|| format!("giving back points of transfer {}", transfer.transfer_id))?;
+    Ok(loyalty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            event_publisher::memory::MemoryPublisher, partner_transfer::memory::MemoryPartners,
+        },
+        commands::DomainLogic,
+        ports::{
+            database::{self, MockDatabasePort},
+            member::MockMemberPort,
+            partner_transfer::MockPartnerTransferPort,
+        },
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use rstest::*;
+    use speculoos::prelude::*;
+    use std::sync::Arc;
+    use tower::{BoxError, ServiceExt};
+
+    #[rstest]
+    #[case(Ok("SKY-1".to_string()), TransferStatus::Completed { partner_reference: "SKY-1".to_string() }, 700)]
+    #[case(Err(partner_transfer::Error::Rejected("unknown account".to_string())), TransferStatus::Rejected { reason: "unknown account".to_string() }, 1000)]
+    #[case(Err(partner_transfer::Error::Unavailable("timeout".into())), TransferStatus::Pending, 700)]
+    #[tokio::test]
+    async fn test_call(
+        #[case] partner_res: Result<String, partner_transfer::Error>,
+        #[case] expected_status: TransferStatus,
+        #[case] expected_points: u32,
+    ) -> Result<(), BoxError> {
+        // GIVEN a member with 1000 points, and a partner answering with `partner_res`
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(1000).build().await?;
+        let mut partner = MockPartnerTransferPort::new();
+        let mut partner_res = Some(partner_res);
+        partner
+            .expect_transfer()
+            .times(1)
+            .returning(move |_, _, _, _| partner_res.take().unwrap());
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = domain
+            .with_partner_transfers(Arc::new(partner))
+            .with_event_publisher(Arc::new(event_publisher.clone()));
+
+        // WHEN transferring 300 points
+        let res = ServiceExt::<TransferOutRequest>::ready(&mut domain)
+            .await?
+            .call(TransferOutRequest {
+                member_id,
+                partner: "SkyMiles".to_string(),
+                loyalty_points: 300,
+            })
+            .await?;
+
+        // THEN
+        // * the points are debited, unless the partner rejected the transfer
+        // * the transfer is stored with its status
+        // * a rejection gives the points back, publishing an event
+        assert_that!(res.status).is_equal_to(&expected_status);
+        assert_that!(res.new_loyalty_points).is_equal_to(expected_points);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(expected_points);
+        let transfers = database.get_partner_transfers(member_id).await?;
+        assert_that!(transfers).has_length(1);
+        assert_that!(transfers[0].transfer_id).is_equal_to(res.transfer_id);
+        assert_that!(transfers[0].status).is_equal_to(&expected_status);
+        let compensated = event_publisher
+            .events()
+            .iter()
+            .any(|event| matches!(event, DomainEvent::PointsAdded { .. }));
+        assert_that!(compensated).is_equal_to(expected_points == 1000);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_insufficient_points() -> Result<(), BoxError> {
+        // GIVEN a member with 100 points
+        let Fixture {
+            member_id,
+            database,
+            domain,
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let partners = MemoryPartners::default().with_partner("SkyMiles");
+        let mut domain = domain.with_partner_transfers(Arc::new(partners.clone()));
+
+        // WHEN transferring 300 points
+        let res = ServiceExt::<TransferOutRequest>::ready(&mut domain)
+            .await?
+            .call(TransferOutRequest {
+                member_id,
+                partner: "SkyMiles".to_string(),
+                loyalty_points: 300,
+            })
+            .await;
+
+        // THEN the partner is not called, and no transfer is stored
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::InsufficientPoints { .. }));
+        assert_that!(partners.credits()).is_empty();
+        assert_that!(database.get_partner_transfers(member_id).await?).is_empty();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_save_failure() -> Result<(), BoxError> {
+        // GIVEN a database failing to save transfers, and to give points back
+        let member = MemberBuilder::basic().member();
+        let member_id = member.member_id;
+        let mut member_port = MockMemberPort::new();
+        member_port
+            .expect_get_member()
+            .returning(move |_| Ok(member.clone()));
+        let mut database = MockDatabasePort::new();
+        database
+            .expect_get_member_restrictions()
+            .returning(|_| Ok(Vec::new()));
+        database
+            .expect_register_loyalty_event_with_outbox()
+            .times(1)
+            .returning(|member_id, _, _, _| {
+                Ok(Loyalty {
+                    points: 700,
+                    ..Loyalty::new(member_id)
+                })
+            });
+        database
+            .expect_save_partner_transfer()
+            .times(1)
+            .returning(|_| Err(database::Error::Unavailable("save timeout".into())));
+        database
+            .expect_reverse_event()
+            .times(1)
+            .returning(|_, _, _, _| Err(database::Error::Unavailable("reverse timeout".into())));
+        let partners = MemoryPartners::default().with_partner("SkyMiles");
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member_port))
+            .with_partner_transfers(Arc::new(partners.clone()));
+
+        // WHEN transferring 300 points
+        let res = ServiceExt::<TransferOutRequest>::ready(&mut domain)
+            .await?
+            .call(TransferOutRequest {
+                member_id,
+                partner: "SkyMiles".to_string(),
+                loyalty_points: 300,
+            })
+            .await;
+
+        // THEN
+        // * the partner is not called
+        // * the error saving the transfer is returned, rather than the compensation's
+        assert_that!(partners.credits()).is_empty();
+        assert_that!(res).is_err().matches(|err| {
+            ErrorChain(err).to_string().contains("save timeout")
+                && !ErrorChain(err).to_string().contains("reverse timeout")
+        });
+
+        Ok(())
+    }
+}
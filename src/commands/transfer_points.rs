@@ -0,0 +1,454 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    domain::{Capability, DomainEvent, LoyaltyEvent},
+    layers::idempotency::IdempotentRequest,
+    ports::{database::DatabasePort, member::MemberPort, ErrorChain, ResultExt},
+};
+use tower::Service;
+use uuid::Uuid;
+
+use super::{ensure_capability, persist_with_events, DomainLogic, Error};
+
+/// Move available points from one member to another
+///
+/// This debits the sender first, then credits the recipient with an event linking to the debit.
+/// If the credit fails, the debit is reversed, giving the points back to the sender.
+pub struct TransferPointsRequest {
+    pub from_member_id: Uuid,
+    pub to_member_id: Uuid,
+    pub loyalty_points: u32,
+    /// Key identifying retries of the same request
+    ///
+    /// See [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer).
+    pub idempotency_key: Option<String>,
+}
+
+impl IdempotentRequest for TransferPointsRequest {
+    fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    fn member_id(&self) -> Uuid {
+        self.from_member_id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferPointsResponse {
+    /// Event removing the points from the sender
+    pub debit_event_id: Uuid,
+    /// Event adding the points to the recipient
+    pub credit_event_id: Uuid,
+    /// New number of loyalty points of the sender
+    pub from_loyalty_points: u32,
+    /// New number of loyalty points of the recipient
+    pub to_loyalty_points: u32,
+}
+
+impl<D, M> Service<TransferPointsRequest> for DomainLogic<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    type Response = TransferPointsResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: TransferPointsRequest) -> Self::Future {
+        let database = self.database.clone();
+        let member = self.member.clone();
+        let id_generator = self.id_generator.clone();
+        let clock = self.clock.clone();
+        let event_publisher = self.event_publisher.clone();
+        let outbox = self.outbox;
+        Box::pin(async move {
+            if req.from_member_id == req.to_member_id {
+                return Err(Error::InvalidState(
+                    "cannot transfer points to the same member".into(),
+                ));
+            }
+            if req.loyalty_points == 0 {
+                return Err(Error::InvalidState("cannot transfer 0 points".into()));
+            }
+            let delta_points = i32::try_from(req.loyalty_points).map_err(|_| {
+                Error::InvalidState(
+                    format!("cannot transfer {} points at once", req.loyalty_points).into(),
+                )
+            })?;
+
+            // Make sure both members exist
+            let from_member = member
+                .get_member(req.from_member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.from_member_id))?;
+            let to_member = member
+                .get_member(req.to_member_id)
+                .await
+                .with_context(|| format!("fetching member {}", req.to_member_id))?;
+            let now = clock.now();
+            ensure_capability(
+                database.as_ref(),
+                from_member.member_id,
+                Capability::Redeem,
+                now,
+            )
+            .await?;
+            ensure_capability(
+                database.as_ref(),
+                to_member.member_id,
+                Capability::Earn,
+                now,
+            )
+            .await?;
+
+            let debit_event_id = id_generator.generate_id();
+            let credit_event_id = id_generator.generate_id();
+
+            // Debit the sender, relying on the database to reject negative balances
+            let res = database
+                .register_loyalty_event(
+                    from_member.member_id,
+                    LoyaltyEvent::new(
+                        debit_event_id,
+                        -delta_points,
+                        format!("Transferred to {}", to_member.member_id),
+                        now,
+                    ),
+                    None,
+                )
+                .await
+                .with_context(|| format!("debiting member {}", from_member.member_id));
+            let from_loyalty = match res {
+                Ok(from_loyalty) => from_loyalty,
+                Err(crate::ports::database::Error::NegativePointsTotal {
+                    current_points, ..
+                }) => {
+                    return Err(Error::InsufficientPoints {
+                        available: current_points,
+                        requested: req.loyalty_points,
+                    })
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            // Credit the recipient
+            let points_transferred = DomainEvent::PointsTransferred {
+                from_member_id: from_member.member_id,
+                to_member_id: to_member.member_id,
+                debit_event_id,
+                credit_event_id,
+                loyalty_points: req.loyalty_points,
+            };
+            let res = persist_with_events(
+                event_publisher.as_ref(),
+                outbox,
+                vec![points_transferred],
+                |events| {
+                    database.register_loyalty_event_with_outbox(
+                        to_member.member_id,
+                        LoyaltyEvent {
+                            linked_event_id: Some(debit_event_id),
+                            ..LoyaltyEvent::new(
+                                credit_event_id,
+                                delta_points,
+                                format!("Transferred from {}", from_member.member_id),
+                                now,
+                            )
+                        },
+                        None,
+                        events,
+                    )
+                },
+            )
+            .await
+            .with_context(|| format!("crediting member {}", to_member.member_id));
+            let to_loyalty = match res {
+                Ok(to_loyalty) => to_loyalty,
+                Err(err) => {
+                    // Give the points back to the sender, linking the debit to the reversal
+                    let reversal_event_id = id_generator.generate_id();
+                    let points_reversed = DomainEvent::PointsReversed {
+                        member_id: from_member.member_id,
+                        event_id: reversal_event_id,
+                        reversed_event_id: debit_event_id,
+                        delta_points,
+                    };
+                    let compensation = persist_with_events(
+                        event_publisher.as_ref(),
+                        outbox,
+                        vec![points_reversed],
+                        |events| {
+                            database.reverse_event(
+                                from_member.member_id,
+                                debit_event_id,
+                                LoyaltyEvent::new(
+                                    reversal_event_id,
+                                    delta_points,
+                                    format!("Reverted transfer to {}", to_member.member_id),
+                                    clock.now(),
+                                ),
+                                events,
+                            )
+                        },
+                    )
+                    .await;
+                    if let Err(compensation_err) = compensation {
+                        tracing::error!(
+                            member_id = %from_member.member_id,
+                            %debit_event_id,
+                            error = %ErrorChain(&compensation_err),
+                            "failed to revert transfer debit"
+                        );
+                    }
+                    return Err(err.into());
+                }
+            };
+
+            Ok(TransferPointsResponse {
+                debit_event_id,
+                credit_event_id,
+                from_loyalty_points: from_loyalty.points,
+                to_loyalty_points: to_loyalty.points,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            database::memory::MemoryDatabase, event_publisher::memory::MemoryPublisher,
+            id_generator::sequential::SequentialIds,
+        },
+        domain::Loyalty,
+        ports::{
+            database::MockDatabasePort,
+            member::{Member, MockMemberPort},
+        },
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use std::sync::{Arc, Mutex};
+    use tower::{BoxError, ServiceExt};
+
+    fn member_port() -> MockMemberPort {
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(|member_id| {
+            Ok(Member {
+                active_member: true,
+                member_id,
+                membership_since: Utc::now(),
+                ..Default::default()
+            })
+        });
+        member
+    }
+
+    fn request(
+        from_member_id: Uuid,
+        to_member_id: Uuid,
+        loyalty_points: u32,
+    ) -> TransferPointsRequest {
+        TransferPointsRequest {
+            from_member_id,
+            to_member_id,
+            loyalty_points,
+            idempotency_key: None,
+        }
+    }
+
+    async fn database_with_points(
+        member_id: Uuid,
+        loyalty_points: i32,
+    ) -> Result<MemoryDatabase, BoxError> {
+        let database = MemoryDatabase::default();
+        database
+            .register_loyalty_event(
+                member_id,
+                LoyaltyEvent::new(Uuid::new_v4(), loyalty_points, "SOME REASON", Utc::now()),
+                None,
+            )
+            .await?;
+        Ok(database)
+    }
+
+    #[tokio::test]
+    async fn test_call() -> Result<(), BoxError> {
+        // GIVEN a member with 500 points
+        let from_member_id = Uuid::new_v4();
+        let to_member_id = Uuid::new_v4();
+        let database = database_with_points(from_member_id, 500).await?;
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port()))
+            .with_id_generator(Arc::new(SequentialIds::default()));
+
+        // WHEN transferring 200 points to another member
+        let res = ServiceExt::<TransferPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(from_member_id, to_member_id, 200))
+            .await;
+
+        // THEN the points move, with the credit linked to the debit
+        assert_that!(res)
+            .is_ok()
+            .is_equal_to(TransferPointsResponse {
+                debit_event_id: Uuid::from_u128(1),
+                credit_event_id: Uuid::from_u128(2),
+                from_loyalty_points: 300,
+                to_loyalty_points: 200,
+            });
+        let from_events = database.get_loyalty_events(from_member_id).await?;
+        assert_that!(from_events[1].delta_points).is_equal_to(-200);
+        let to_events = database.get_loyalty_events(to_member_id).await?;
+        assert_that!(to_events[0].delta_points).is_equal_to(200);
+        assert_that!(to_events[0].linked_event_id).is_equal_to(Some(Uuid::from_u128(1)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_outbox() -> Result<(), BoxError> {
+        // GIVEN a member with 500 points and the outbox enabled
+        let from_member_id = Uuid::new_v4();
+        let to_member_id = Uuid::new_v4();
+        let database = database_with_points(from_member_id, 500).await?;
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port()))
+            .with_id_generator(Arc::new(SequentialIds::default()))
+            .with_outbox();
+
+        // WHEN transferring 200 points to another member
+        ServiceExt::<TransferPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(from_member_id, to_member_id, 200))
+            .await?;
+
+        // THEN the transfer is stored in the outbox
+        let outbox_events: Vec<_> = database
+            .get_outbox_entries(10)
+            .await?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_that!(outbox_events).is_equal_to(vec![DomainEvent::PointsTransferred {
+            from_member_id,
+            to_member_id,
+            debit_event_id: Uuid::from_u128(1),
+            credit_event_id: Uuid::from_u128(2),
+            loyalty_points: 200,
+        }]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_invalid() -> Result<(), BoxError> {
+        // GIVEN a member with 500 points
+        let from_member_id = Uuid::new_v4();
+        let to_member_id = Uuid::new_v4();
+        let database = database_with_points(from_member_id, 500).await?;
+        let mut domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port()));
+
+        // WHEN transferring more points than available, to the same member, or no points
+        let mut results = Vec::new();
+        for req in [
+            request(from_member_id, to_member_id, 501),
+            request(from_member_id, from_member_id, 100),
+            request(from_member_id, to_member_id, 0),
+        ] {
+            let res = ServiceExt::<TransferPointsRequest>::ready(&mut domain)
+                .await?
+                .call(req)
+                .await;
+            results.push(res);
+        }
+
+        // THEN it fails without moving points
+        assert_that!(results[0]).is_err().matches(|err| {
+            matches!(
+                err,
+                Error::InsufficientPoints {
+                    available: 500,
+                    requested: 501
+                }
+            )
+        });
+        for res in results[1..].iter() {
+            assert_that!(*res)
+                .is_err()
+                .matches(|err| matches!(err, Error::InvalidState(_)));
+        }
+        let loyalty = database.get_loyalty_points(from_member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(500);
+        let loyalty = database.get_loyalty_points(to_member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_call_credit_fails() -> Result<(), BoxError> {
+        // GIVEN a database that fails to credit the recipient
+        let from_member_id = Uuid::new_v4();
+        let to_member_id = Uuid::new_v4();
+        let reversed = Arc::new(Mutex::new(Vec::new()));
+        let mut database = MockDatabasePort::new();
+        database
+            .expect_get_member_restrictions()
+            .returning(|_| Ok(Vec::new()));
+        database
+            .expect_register_loyalty_event()
+            .times(1)
+            .returning(|member_id, _, _| Ok(Loyalty::new(member_id)));
+        database
+            .expect_register_loyalty_event_with_outbox()
+            .returning(|_, _, _, _| {
+                Err(crate::ports::database::Error::Adapter("SOME ERROR".into()))
+            });
+        let reversed_events = reversed.clone();
+        database.expect_reverse_event().times(1).returning(
+            move |member_id, event_id, reversal, _| {
+                reversed_events
+                    .lock()
+                    .unwrap()
+                    .push((event_id, reversal.delta_points));
+                Ok(Loyalty::new(member_id))
+            },
+        );
+        let event_publisher = MemoryPublisher::default();
+        let mut domain = DomainLogic::new(Arc::new(database), Arc::new(member_port()))
+            .with_id_generator(Arc::new(SequentialIds::default()))
+            .with_event_publisher(Arc::new(event_publisher.clone()));
+
+        // WHEN transferring points
+        let res = ServiceExt::<TransferPointsRequest>::ready(&mut domain)
+            .await?
+            .call(request(from_member_id, to_member_id, 200))
+            .await;
+
+        // THEN
+        // * it fails
+        // * the debit is reversed, and the reversal published
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::Database(_)));
+        assert_that!(*reversed.lock().unwrap()).is_equal_to(vec![(Uuid::from_u128(1), 200)]);
+        assert_that!(event_publisher.events()).is_equal_to(vec![DomainEvent::PointsReversed {
+            member_id: from_member_id,
+            event_id: Uuid::from_u128(3),
+            reversed_event_id: Uuid::from_u128(1),
+            delta_points: 200,
+        }]);
+
+        Ok(())
+    }
+}
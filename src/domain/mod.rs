@@ -1,5 +1,12 @@
+use chrono::{DateTime, Duration, FixedOffset, Months, NaiveDate, Offset, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
 use uuid::Uuid;
 
+pub use crate::points::{purchase_points, Money, MoneyError, Rounding, Tier};
+
 pub struct Member {
     /// Unique identifier for the `Member`
     ///
@@ -9,53 +16,18 @@ pub struct Member {
     ///
     /// This is set to `None` if the person is not an active member anymore.
     membership_months: Option<u32>,
-    /// Number of accrued loyalty points
-    loyalty_points: u32,
 }
 
 impl Member {
-    pub fn new(member_id: Uuid, membership_months: Option<u32>, loyalty_points: u32) -> Self {
+    pub fn new(member_id: Uuid, membership_months: Option<u32>) -> Self {
         Self {
             member_id,
             membership_months,
-            loyalty_points,
         }
     }
 
     pub fn tier(&self) -> Tier {
-        match self.membership_months {
-            // Non-members
-            None => Tier::None,
-            // First year of continuous membership
-            Some(0..=11) => Tier::Basic,
-            // Second year of continuous membership
-            Some(12..=23) => Tier::Silver,
-            // Third year of continuous membership
-            Some(24..=35) => Tier::Gold,
-            // Fourth year and more
-            Some(_) => Tier::Platinum,
-        }
-    }
-}
-
-#[derive(Debug, PartialEq, Eq)]
-pub enum Tier {
-    None,
-    Basic,
-    Silver,
-    Gold,
-    Platinum,
-}
-
-impl Tier {
-    pub fn ratio(&self) -> i32 {
-        match self {
-            Tier::None => 0,
-            Tier::Basic => 10,
-            Tier::Silver => 12,
-            Tier::Gold => 15,
-            Tier::Platinum => 20,
-        }
+        Tier::from_membership_months(self.membership_months)
     }
 }
 
@@ -64,10 +36,47 @@ impl Tier {
 pub struct Loyalty {
     pub member_id: Uuid,
 
-    /// Current amount of loyalty points
+    /// Current amount of loyalty points available
     pub points: u32,
 
-    /// Loyalty events for the user
+    /// Amount of loyalty points earned but not available yet
+    ///
+    /// This is the sum of the points in `pending_lots`.
+    pub pending_points: u32,
+
+    /// Points waiting to mature, oldest first
+    pub pending_lots: Vec<PendingLot>,
+
+    /// Amount of loyalty points reserved by holds, not included in `points`
+    ///
+    /// This is the sum of the points in `holds`.
+    pub held_points: u32,
+
+    /// Points reserved until they are captured or released, oldest first
+    pub holds: Vec<PointsHold>,
+
+    /// Available points that lapse if they are not spent, soonest first
+    ///
+    /// Spending points takes from the lots expiring soonest, so this never exceeds `points`.
+    pub expiring_lots: Vec<ExpiringLot>,
+
+    /// Latest evaluation of the member's tier, if any
+    pub tier: Option<TierEvaluation>,
+
+    /// Number of changes to the member's points or events, starting at 0
+    ///
+    /// Commands pass it back to the database port to detect concurrent changes.
+    pub version: u64,
+
+    /// Sequence number of the latest event registered for the member, or 0 if there is none
+    ///
+    /// This keeps counting when older events are dropped or compacted, so sequence numbers are
+    /// never reused.
+    pub last_sequence: u64,
+
+    /// Loyalty events for the user, in chronological order
+    ///
+    /// Events are ordered by `sequence`, the order in which the database port registered them.
     pub events: Vec<LoyaltyEvent>,
 }
 
@@ -76,15 +85,34 @@ impl Loyalty {
         Self {
             member_id,
             points: 0,
+            pending_points: 0,
+            pending_lots: Vec::default(),
+            held_points: 0,
+            holds: Vec::default(),
+            expiring_lots: Vec::default(),
+            tier: None,
+            version: 0,
+            last_sequence: 0,
             events: Vec::default(),
         }
     }
+
+    /// Assign the sequence number of the next event of this member
+    pub fn next_sequence(&mut self) -> u64 {
+        self.last_sequence += 1;
+        self.last_sequence
+    }
 }
 
 /// Details for a loyalty event
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LoyaltyEvent {
     pub event_id: Uuid,
+    /// Position of this event in the member's event log
+    ///
+    /// Sequence numbers start at 1 and increase monotonically for each member. They are assigned
+    /// by the database port when the event is registered: any value set before then is ignored.
+    pub sequence: u64,
     /// Difference in points
     ///
     /// A positive number adds points to the current total. A negative number removes from it.
@@ -93,4 +121,994 @@ pub struct LoyaltyEvent {
     ///
     /// Since the reasons could evolve over time, we log this as a string instead of an enum.
     pub reason: String,
+    /// Date at which the points become available
+    ///
+    /// Until then, the points are pending and cannot be spent. This only applies to events adding
+    /// points: if this is `None`, the points are available immediately.
+    pub matures_at: Option<DateTime<Utc>>,
+    /// Date at which the points lapse if they are not spent
+    ///
+    /// This only applies to events adding points: if this is `None`, the points never expire.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Set when the points were computed without confirming the member's tier
+    ///
+    /// This happens when the member service is unavailable and the points are based on a fallback
+    /// tier instead. These events should be reconciled once the member service is back.
+    pub tier_unverified: Option<UnverifiedTier>,
+    /// Date at which points held in escrow are discarded, unless their payment is confirmed
+    ///
+    /// This only applies to events awaiting approval, such as renewal points granted before the
+    /// billing service confirms the payment.
+    pub escrow_expires_at: Option<DateTime<Utc>>,
+    /// Set when this event summarizes older events replaced by history compaction
+    ///
+    /// The `delta_points` of a snapshot is the sum of the events it replaced.
+    pub snapshot: Option<HistorySnapshot>,
+    /// Set when the points were matched from another loyalty program's statement
+    pub external_source: Option<ExternalSource>,
+    /// Event on the other side of a transfer between members
+    ///
+    /// A transfer debits one member and credits another with two events linking to each other.
+    pub linked_event_id: Option<Uuid>,
+    /// Marketing campaign that credited the points, or boosted the points earned on a purchase
+    pub campaign_id: Option<Uuid>,
+    /// Catalog reward the points were redeemed for
+    pub reward_id: Option<Uuid>,
+    /// Regulatory disclosure required in the member's region, see [`RegionalProfile`]
+    pub disclosure: Option<String>,
+    /// Normalized reference of the order that earned the points, see [`normalize_order_reference`]
+    pub order_reference: Option<String>,
+    /// Points a reversal could not claw back, written off with [`BalancePolicy::WriteOff`]
+    ///
+    /// The reversal's `delta_points` only include the points that were clawed back.
+    pub written_off_points: Option<u32>,
+    /// Sales channel of the purchase that earned the points
+    ///
+    /// This is `None` for events that are not purchases, such as renewals or manual additions.
+    pub channel: Option<Channel>,
+    /// When the command creating the event ran, from the clock port
+    ///
+    /// Events awaiting approval keep the time they were created, not approved.
+    pub created_at: DateTime<Utc>,
+}
+
+impl LoyaltyEvent {
+    /// Event changing the points by `delta_points`, without any of the optional details
+    ///
+    /// The database port assigns the sequence number when registering the event.
+    pub fn new(
+        event_id: Uuid,
+        delta_points: i32,
+        reason: impl Into<String>,
+        created_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            event_id,
+            sequence: 0,
+            delta_points,
+            reason: reason.into(),
+            matures_at: None,
+            expires_at: None,
+            tier_unverified: None,
+            escrow_expires_at: None,
+            snapshot: None,
+            external_source: None,
+            linked_event_id: None,
+            campaign_id: None,
+            reward_id: None,
+            disclosure: None,
+            order_reference: None,
+            written_off_points: None,
+            channel: None,
+            created_at,
+        }
+    }
+}
+
+/// How the points of a purchase were computed with a fallback tier
+///
+/// This keeps enough information to compute the points again with the member's actual tier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnverifiedTier {
+    /// Fallback tier used to compute the points
+    pub tier: Tier,
+    /// Purchase amount, in whole currency units
+    pub purchase_amount: i32,
+    /// Segment multiplier applied on top of the tier's earn ratio
+    pub earn_multiplier: i32,
+}
+
+/// Summary of loyalty events replaced by history compaction
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HistorySnapshot {
+    /// Number of events summarized
+    pub event_count: u64,
+    /// Sum of `delta_points` per reason
+    pub totals: BTreeMap<String, i64>,
+}
+
+/// Statement from another loyalty program that points were matched from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExternalSource {
+    /// Name of the other program
+    pub program: String,
+    /// Identifier of the statement in the other program
+    pub statement_id: String,
+}
+
+/// How points from another program's statement are matched
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatementMatching {
+    /// Points granted per 100 points on the statement
+    pub ratio_percent: u32,
+    /// Maximum number of points granted for a statement
+    pub cap: u32,
+}
+
+impl StatementMatching {
+    /// Points granted for a statement with `external_points`
+    pub fn matched_points(&self, external_points: u32) -> u32 {
+        let points = external_points as u64 * self.ratio_percent as u64 / 100;
+        points.min(self.cap as u64) as u32
+    }
+}
+
+/// Latest points of a member in the balance change feed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub member_id: Uuid,
+    /// Amount of loyalty points available
+    pub points: u32,
+    /// Amount of loyalty points earned but not available yet
+    pub pending_points: u32,
+    /// Position of this change in the feed
+    pub sequence: u64,
+}
+
+/// Points from a single event that are not available yet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingLot {
+    pub event_id: Uuid,
+    pub points: u32,
+    pub matures_at: DateTime<Utc>,
+    /// Date at which the points lapse once available
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Remaining available points of an event that lapse at a given date
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpiringLot {
+    pub event_id: Uuid,
+    pub points: u32,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Available points reserved for a payment that has not settled yet
+///
+/// Held points cannot be spent. They are either captured, which debits them with a loyalty event,
+/// or released back to the member's available points.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointsHold {
+    pub hold_id: Uuid,
+    pub points: u32,
+    /// Message explaining the reservation, recorded in the event reason on capture
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Support exception for a single member
+///
+/// Overrides are never updated or deleted: granting a new override supersedes the previous one,
+/// which keeps the full history available for auditing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberOverride {
+    pub override_id: Uuid,
+    pub member_id: Uuid,
+    /// Custom earn ratio on purchases, replacing the ratio of the member's tier
+    pub earn_ratio: u32,
+    /// Message explaining why the exception was granted
+    pub reason: String,
+    /// Support agent who granted the exception
+    pub granted_by: String,
+    pub granted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl MemberOverride {
+    /// Highest earn ratio support agents can grant, five times the Platinum ratio
+    pub const MAX_EARN_RATIO: u32 = 100;
+
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.granted_at <= at && at < self.expires_at
+    }
+}
+
+/// Member action that fraud operations can suspend
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Adding points, and receiving transfers
+    Earn,
+    /// Redeeming points, and sending transfers
+    Redeem,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::Earn => write!(f, "earn"),
+            Capability::Redeem => write!(f, "redeem"),
+        }
+    }
+}
+
+/// Suspension of a member's capability until it expires
+///
+/// Restrictions are never removed, so they also serve as an audit trail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberRestriction {
+    pub restriction_id: Uuid,
+    pub member_id: Uuid,
+    pub capability: Capability,
+    /// Message explaining why the capability is suspended
+    pub reason: String,
+    /// Operator who suspended the capability
+    pub restricted_by: String,
+    pub restricted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl MemberRestriction {
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.restricted_at <= at && at < self.expires_at
+    }
+}
+
+/// Advisory lock taken by a support agent while working on a member
+///
+/// Locks do not prevent other agents from acting on the member: they only warn them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaseLock {
+    pub member_id: Uuid,
+    /// Support agent holding the lock
+    pub holder: String,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl CaseLock {
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.acquired_at <= at && at < self.expires_at
+    }
+}
+
+/// Internal note from support about a loyalty event
+///
+/// Notes are stored separately from events, which stay immutable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventNote {
+    pub note_id: Uuid,
+    pub member_id: Uuid,
+    pub event_id: Uuid,
+    pub note: String,
+    /// Support agent who wrote the note
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Points converted to a partner's currency, e.g. airline miles
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartnerTransfer {
+    pub transfer_id: Uuid,
+    pub member_id: Uuid,
+    /// Name of the partner program
+    pub partner: String,
+    pub points: u32,
+    /// Event debiting the points
+    pub debit_event_id: Uuid,
+    pub status: TransferStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// The points are debited, waiting for the partner to credit them
+    ///
+    /// Transfers stay pending when the partner cannot be reached, as it might still have credited
+    /// them. Nothing reconciles them automatically: they need a manual follow-up with the partner.
+    Pending,
+    /// The partner credited the points
+    Completed {
+        /// Identifier of the transfer for the partner
+        partner_reference: String,
+    },
+    /// The partner refused the transfer, and the points were given back to the member
+    Rejected { reason: String },
+}
+
+/// Progress and outcome of a bulk campaign credit
+///
+/// Runs are stored by campaign ID, so a campaign only credits its audience once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CampaignRun {
+    pub campaign_id: Uuid,
+    /// Points credited to each member
+    pub loyalty_points: u32,
+    pub reason: String,
+    /// Members to credit, without duplicates, in processing order
+    pub members: Vec<Uuid>,
+    /// Number of members from `members` already processed
+    pub processed: usize,
+    /// Processed members that could not be credited
+    pub failed: Vec<Uuid>,
+    /// Maximum number of points issued by the campaign, across all runs
+    pub budget: Option<u64>,
+    /// Set when the run paused because crediting the next member would exceed the budget
+    pub budget_exhausted: bool,
+    /// Set once all members are processed
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl CampaignRun {
+    /// Number of members credited so far
+    pub fn credited(&self) -> usize {
+        self.processed - self.failed.len()
+    }
+}
+
+/// Time-boxed promotion multiplying the points earned on purchases
+///
+/// For example, double points on online purchases in December.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Campaign {
+    pub campaign_id: Uuid,
+    /// Name of the promotion, e.g. `Double points in December`
+    pub name: String,
+    pub starts_at: DateTime<Utc>,
+    /// End of the promotion, excluded
+    pub ends_at: DateTime<Utc>,
+    /// Only apply to purchases on this channel
+    pub channel: Option<Channel>,
+    /// Only apply to members of this marketing segment
+    pub segment: Option<String>,
+    /// Multiplier on top of the member's other earn multipliers
+    pub multiplier: i32,
+}
+
+impl Campaign {
+    /// Whether the campaign runs at `at`
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.starts_at <= at && at < self.ends_at
+    }
+
+    /// Whether the campaign boosts a purchase made on `channel` at `occurred_at` by a member in
+    /// `segments`
+    pub fn applies_to(
+        &self,
+        channel: Channel,
+        segments: &[String],
+        occurred_at: DateTime<Utc>,
+    ) -> bool {
+        self.is_active(occurred_at)
+            && self.channel.is_none_or(|only| only == channel)
+            && self
+                .segment
+                .as_ref()
+                .is_none_or(|segment| segments.contains(segment))
+    }
+}
+
+/// Tier of a member as of its latest evaluation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TierEvaluation {
+    pub member_id: Uuid,
+    pub tier: Tier,
+    /// Number of continuous months of membership, or `None` for non-members
+    pub membership_months: Option<u32>,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+impl TierEvaluation {
+    /// Whether the evaluation is older than `max_age` at `now`
+    pub fn is_stale(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        self.evaluated_at + max_age < now
+    }
+}
+
+/// Member whose tier differs from the previous evaluation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TierChange {
+    pub member_id: Uuid,
+    pub old_tier: Tier,
+    pub new_tier: Tier,
+}
+
+/// Change sent to downstream consumers once it is persisted
+///
+/// Events only describe the change itself, so they can be stored in the outbox before the change
+/// is applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DomainEvent {
+    PointsAdded {
+        member_id: Uuid,
+        event_id: Uuid,
+        loyalty_points: u32,
+    },
+    PointsRedeemed {
+        member_id: Uuid,
+        event_id: Uuid,
+        loyalty_points: u32,
+    },
+    TierChanged(TierChange),
+    /// Points were moved from one member to another
+    PointsTransferred {
+        from_member_id: Uuid,
+        to_member_id: Uuid,
+        /// Event removing the points from the sender
+        debit_event_id: Uuid,
+        /// Event adding the points to the recipient
+        credit_event_id: Uuid,
+        loyalty_points: u32,
+    },
+    /// Unspent points reached their expiration date and were removed
+    PointsExpired {
+        member_id: Uuid,
+        /// Event removing the points
+        event_id: Uuid,
+        loyalty_points: u32,
+    },
+    /// Pending points reached their maturity date and became available
+    PointsMatured {
+        member_id: Uuid,
+        loyalty_points: u32,
+    },
+    /// An event was compensated by another one giving back its points, e.g. for a refund
+    PointsReversed {
+        member_id: Uuid,
+        /// Event compensating the reversed one
+        event_id: Uuid,
+        reversed_event_id: Uuid,
+        /// Points added or removed by the reversal, including points written off
+        delta_points: i32,
+    },
+    /// The points of an event were recomputed, e.g. once the member's tier was verified
+    PointsAdjusted {
+        member_id: Uuid,
+        /// Event adding or removing the difference
+        event_id: Uuid,
+        delta_points: i32,
+    },
+    /// A purchase was charged back, as a signal for fraud detection
+    ChargebackReceived {
+        member_id: Uuid,
+        /// Purchase event that was charged back
+        event_id: Uuid,
+        /// Points earned by the purchase
+        loyalty_points: u32,
+        /// Whether the member was flagged for review
+        flagged_for_review: bool,
+    },
+    /// A member reached the inactivity threshold, e.g. for CRM to send a win-back offer
+    MemberWentDormant {
+        member_id: Uuid,
+        /// Date of the member's last event
+        last_activity_at: DateTime<Utc>,
+        /// Available and pending points the member could lose
+        balance_at_risk: u32,
+    },
+}
+
+/// Domain event waiting in the outbox to be published
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboxEntry {
+    /// Position of the entry in the outbox, shared across members and starting at 1
+    pub outbox_id: u64,
+    pub event: DomainEvent,
+}
+
+/// Reward that members can redeem their points for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reward {
+    pub reward_id: Uuid,
+    pub name: String,
+    /// Number of loyalty points to redeem the reward
+    pub points_cost: u32,
+    /// Tiers that can redeem the reward, or all tiers if empty
+    pub eligible_tiers: Vec<Tier>,
+}
+
+impl Reward {
+    /// Whether members of `tier` can redeem the reward
+    pub fn is_available_to(&self, tier: &Tier) -> bool {
+        self.eligible_tiers.is_empty() || self.eligible_tiers.contains(tier)
+    }
+}
+
+/// Sweepstakes that members enter with loyalty points
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Drawing {
+    pub drawing_id: Uuid,
+    /// Cost of a single entry in loyalty points
+    pub points_per_entry: u32,
+    /// Number of winners to select
+    ///
+    /// A member can only win once, regardless of their number of entries.
+    pub winner_count: u32,
+    /// Outcome of the drawing, once winners are selected
+    pub result: Option<DrawingResult>,
+}
+
+/// Entries of a member in a drawing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrawingEntry {
+    pub member_id: Uuid,
+    pub entries: u32,
+}
+
+/// Outcome of a drawing
+///
+/// The seed is kept so the selection can be reproduced from the entries when auditing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrawingResult {
+    pub drawing_id: Uuid,
+    pub seed: u64,
+    pub winners: Vec<Uuid>,
+}
+
+/// Sales channel of a purchase
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    InStore,
+    Online,
+}
+
+/// Normalize an order reference, so channels reporting the same order with a different format
+/// match
+///
+/// This keeps ASCII letters and digits, in uppercase: ` ord-00123` becomes `ORD00123`. References
+/// without any letter or digit return `None`.
+pub fn normalize_order_reference(reference: &str) -> Option<String> {
+    let normalized = reference
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect::<String>();
+    (!normalized.is_empty()).then_some(normalized)
+}
+
+/// Which points to keep when multiple channels report the same order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrderReconciliation {
+    /// Keep the points of the first report, ignoring later ones
+    #[default]
+    KeepFirst,
+    /// Keep the highest points of all reports
+    KeepHigher,
+    /// Keep the lowest points of all reports
+    KeepLower,
+}
+
+impl OrderReconciliation {
+    /// Points to add when an order that already credited `credited_points` is reported again with
+    /// `points`, or `None` if the report is ignored
+    pub fn adjustment(&self, credited_points: i32, points: i32) -> Option<i32> {
+        let keep = match self {
+            OrderReconciliation::KeepFirst => return None,
+            OrderReconciliation::KeepHigher => points > credited_points,
+            OrderReconciliation::KeepLower => points < credited_points,
+        };
+        keep.then(|| points.checked_sub(credited_points)).flatten()
+    }
+}
+
+/// Delay before points earned on purchases become available, per sales channel
+///
+/// Channels without a delay make points available immediately.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MaturationSchedule {
+    delays: HashMap<Channel, Duration>,
+}
+
+impl MaturationSchedule {
+    pub fn with_delay(mut self, channel: Channel, delay: Duration) -> Self {
+        self.delays.insert(channel, delay);
+        self
+    }
+
+    /// Maturity date for points earned through `channel` at the given date
+    pub fn matures_at(&self, channel: Channel, earned_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.delays
+            .get(&channel)
+            .filter(|delay| **delay > Duration::zero())
+            .map(|delay| earned_at + *delay)
+    }
+}
+
+/// How long points earned by members stay available before expiring, per tier
+///
+/// Tiers without their own rule use the default lifetime. By default, points never expire.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExpirationPolicy {
+    lifetime: Option<Duration>,
+    tiers: HashMap<Tier, Option<Duration>>,
+}
+
+impl ExpirationPolicy {
+    /// Points expire `lifetime` after being earned
+    pub fn new(lifetime: Duration) -> Self {
+        Self {
+            lifetime: Some(lifetime),
+            tiers: HashMap::new(),
+        }
+    }
+
+    /// Set the lifetime of points earned in `tier`, where `None` means they never expire
+    pub fn with_tier(mut self, tier: Tier, lifetime: Option<Duration>) -> Self {
+        self.tiers.insert(tier, lifetime);
+        self
+    }
+
+    /// Expiration date for points earned in `tier` at the given date
+    pub fn expires_at(&self, tier: &Tier, earned_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.tiers
+            .get(tier)
+            .copied()
+            .unwrap_or(self.lifetime)
+            .map(|lifetime| earned_at + lifetime)
+    }
+}
+
+/// Regulatory constraints on points, per member country
+///
+/// Members without a country, or from a country without a profile, follow the default profile.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegionalPolicy {
+    default: RegionalProfile,
+    profiles: HashMap<String, RegionalProfile>,
+}
+
+impl RegionalPolicy {
+    /// Apply `default` to members without a profile for their country
+    pub fn new(default: RegionalProfile) -> Self {
+        Self {
+            default,
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// Apply `profile` to members whose country is `country`, as an ISO 3166-1 alpha-2 code
+    pub fn with_region(mut self, country: impl Into<String>, profile: RegionalProfile) -> Self {
+        self.profiles.insert(country.into(), profile);
+        self
+    }
+
+    /// Profile for members of `country`
+    pub fn profile(&self, country: Option<&str>) -> &RegionalProfile {
+        country
+            .and_then(|country| self.profiles.get(country))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Regulatory constraints of a jurisdiction
+///
+/// By default, points can expire following the [`ExpirationPolicy`], without any disclosure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionalProfile {
+    expiry_allowed: bool,
+    min_validity: Option<Months>,
+    disclosure: Option<String>,
+}
+
+impl Default for RegionalProfile {
+    fn default() -> Self {
+        Self {
+            expiry_allowed: true,
+            min_validity: None,
+            disclosure: None,
+        }
+    }
+}
+
+impl RegionalProfile {
+    /// Points never expire
+    pub fn without_expiry(mut self) -> Self {
+        self.expiry_allowed = false;
+        self
+    }
+
+    /// Points that expire stay valid for at least `months`
+    pub fn with_min_validity_months(mut self, months: u32) -> Self {
+        self.min_validity = Some(Months::new(months));
+        self
+    }
+
+    /// Stamp `disclosure` on the events adding points
+    pub fn with_disclosure(mut self, disclosure: impl Into<String>) -> Self {
+        self.disclosure = Some(disclosure.into());
+        self
+    }
+
+    /// Adjust the expiration date of points earned at `earned_at` to the region's rules
+    pub fn expires_at(
+        &self,
+        expires_at: Option<DateTime<Utc>>,
+        earned_at: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        if !self.expiry_allowed {
+            return None;
+        }
+        let min_expires_at = self
+            .min_validity
+            .and_then(|min_validity| earned_at.checked_add_months(min_validity));
+        expires_at.map(|expires_at| min_expires_at.map_or(expires_at, |min| expires_at.max(min)))
+    }
+
+    pub fn disclosure(&self) -> Option<&str> {
+        self.disclosure.as_deref()
+    }
+}
+
+/// Points earned on purchases and membership renewals
+///
+/// Tiers without their own ratio use [`Tier::ratio`], and channels without a multiplier earn the
+/// tier's ratio as is. By default, renewing a membership earns 290 points.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EarningPolicy {
+    ratios: HashMap<Tier, i32>,
+    channel_multipliers: HashMap<Channel, i32>,
+    renewal_points: i32,
+}
+
+impl Default for EarningPolicy {
+    fn default() -> Self {
+        Self {
+            ratios: HashMap::new(),
+            channel_multipliers: HashMap::new(),
+            renewal_points: 290,
+        }
+    }
+}
+
+impl EarningPolicy {
+    /// Set the number of points per currency unit on purchases in `tier`
+    pub fn with_tier_ratio(mut self, tier: Tier, ratio: i32) -> Self {
+        self.ratios.insert(tier, ratio);
+        self
+    }
+
+    /// Multiply the points earned on purchases through `channel`
+    pub fn with_channel_multiplier(mut self, channel: Channel, multiplier: i32) -> Self {
+        self.channel_multipliers.insert(channel, multiplier);
+        self
+    }
+
+    /// Set the flat number of points earned when renewing a membership
+    pub fn with_renewal_points(mut self, points: i32) -> Self {
+        self.renewal_points = points;
+        self
+    }
+
+    /// Number of points per currency unit on purchases in `tier`
+    pub fn ratio(&self, tier: &Tier) -> i32 {
+        self.ratios
+            .get(tier)
+            .copied()
+            .unwrap_or_else(|| tier.ratio())
+    }
+
+    /// Multiplier on purchases through `channel`
+    pub fn channel_multiplier(&self, channel: Channel) -> i32 {
+        self.channel_multipliers.get(&channel).copied().unwrap_or(1)
+    }
+
+    pub fn renewal_points(&self) -> i32 {
+        self.renewal_points
+    }
 }
+
+/// What to do when clawing back points would make a member's balance negative
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BalancePolicy {
+    /// Fail the claw-back
+    #[default]
+    Reject,
+    /// Claw back the available points and record the shortfall on the reversal for finance
+    WriteOff,
+}
+
+/// How chargebacks on purchases are handled
+///
+/// By default, chargebacks claw back all the points earned by the purchase, and never flag the
+/// member for review.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChargebackPolicy {
+    max_clawback_points: Option<u32>,
+    review_threshold: Option<u32>,
+}
+
+impl ChargebackPolicy {
+    /// Claw back at most `points` per chargeback
+    pub fn with_max_clawback_points(mut self, points: u32) -> Self {
+        self.max_clawback_points = Some(points);
+        self
+    }
+
+    /// Flag members for review on chargebacks of purchases that earned more than `points`
+    pub fn with_review_threshold(mut self, points: u32) -> Self {
+        self.review_threshold = Some(points);
+        self
+    }
+
+    /// Points to claw back for a purchase that earned `earned_points`
+    pub fn clawback_points(&self, earned_points: u32) -> u32 {
+        self.max_clawback_points
+            .map_or(earned_points, |max| earned_points.min(max))
+    }
+
+    /// Whether a chargeback of a purchase that earned `earned_points` needs a review
+    pub fn needs_review(&self, earned_points: u32) -> bool {
+        self.review_threshold
+            .is_some_and(|threshold| earned_points > threshold)
+    }
+}
+
+/// Days with special earn rates on purchases, such as member days or blackout dates
+///
+/// Dates are in the program's time zone, given as a fixed offset from UTC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HolidayCalendar {
+    offset: FixedOffset,
+    rules: Vec<HolidayRule>,
+}
+
+impl Default for HolidayCalendar {
+    fn default() -> Self {
+        Self {
+            offset: Utc.fix(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl HolidayCalendar {
+    /// Create a calendar, rejecting rules that cannot apply together
+    pub fn new(
+        offset: FixedOffset,
+        rules: impl IntoIterator<Item = HolidayRule>,
+    ) -> Result<Self, InvalidCalendar> {
+        let rules = rules.into_iter().collect::<Vec<_>>();
+        for (index, rule) in rules.iter().enumerate() {
+            if let HolidayEffect::Boost(multiplier) = rule.effect {
+                if multiplier < 1 {
+                    return Err(InvalidCalendar::InvalidBoost {
+                        name: rule.name.clone(),
+                        multiplier,
+                    });
+                }
+            }
+            let conflict = rules[..index].iter().find(|other| {
+                other.date == rule.date
+                    && other.segment == rule.segment
+                    && (other.effect == HolidayEffect::Blackout)
+                        != (rule.effect == HolidayEffect::Blackout)
+            });
+            if let Some(other) = conflict {
+                return Err(InvalidCalendar::Conflict {
+                    date: rule.date,
+                    names: (other.name.clone(), rule.name.clone()),
+                });
+            }
+        }
+
+        Ok(Self { offset, rules })
+    }
+
+    /// Earn rate adjustments for a purchase made at `occurred_at` by a member in `segments`
+    pub fn earn_day(&self, occurred_at: DateTime<Utc>, segments: &[String]) -> EarnDay {
+        self.on_date(
+            occurred_at.with_timezone(&self.offset).date_naive(),
+            segments,
+        )
+    }
+
+    /// Earn rate adjustments on `date` for a member in `segments`
+    pub fn on_date(&self, date: NaiveDate, segments: &[String]) -> EarnDay {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| {
+                rule.date == date
+                    && rule
+                        .segment
+                        .as_ref()
+                        .is_none_or(|segment| segments.contains(segment))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        EarnDay { date, rules }
+    }
+}
+
+/// Special earn rate on a given date
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HolidayRule {
+    /// Name of the occasion, e.g. `Black Friday`
+    pub name: String,
+    pub date: NaiveDate,
+    /// Only apply to members of this marketing segment
+    pub segment: Option<String>,
+    pub effect: HolidayEffect,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HolidayEffect {
+    /// No bonus on purchases: segment multipliers and boosts do not apply
+    Blackout,
+    /// Multiply points earned on purchases
+    ///
+    /// If multiple boosts apply on the same day, only the highest one counts.
+    Boost(i32),
+}
+
+/// Holiday rules applying to a purchase
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EarnDay {
+    /// Date of the purchase in the program's time zone
+    pub date: NaiveDate,
+    pub rules: Vec<HolidayRule>,
+}
+
+impl EarnDay {
+    /// Whether bonus multipliers are disabled on this day
+    pub fn is_blackout(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.effect == HolidayEffect::Blackout)
+    }
+
+    /// Multiplier to apply on top of `bonus_multiplier`, the member's regular bonus multiplier
+    pub fn earn_multiplier(&self, bonus_multiplier: i32) -> i32 {
+        if self.is_blackout() {
+            return 1;
+        }
+        let boost = self
+            .rules
+            .iter()
+            .filter_map(|rule| match rule.effect {
+                HolidayEffect::Boost(multiplier) => Some(multiplier),
+                HolidayEffect::Blackout => None,
+            })
+            .max()
+            .unwrap_or(1);
+
+        bonus_multiplier * boost
+    }
+}
+
+/// Reason a holiday calendar was rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidCalendar {
+    /// Boosts must multiply points by at least 1
+    InvalidBoost { name: String, multiplier: i32 },
+    /// A blackout and a boost for the same date and segment
+    Conflict {
+        date: NaiveDate,
+        names: (String, String),
+    },
+}
+
+impl fmt::Display for InvalidCalendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidCalendar::InvalidBoost { name, multiplier } => {
+                write!(f, "invalid boost {} for {}", multiplier, name)
+            }
+            InvalidCalendar::Conflict { date, names } => {
+                write!(f, "{} and {} conflict on {}", names.0, names.1, date)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidCalendar {}
@@ -0,0 +1,115 @@
+//! Slim client interface for services embedding the loyalty logic
+//!
+//! [`LoyaltyClient`] covers the commands other services need, without depending on tower or on
+//! how the commands are deployed. [`InProcessClient`] runs them against a [`DomainLogic`] in the
+//! same process, so callers only change how they build the client to switch deployment modes.
+
+use crate::{
+    commands::{
+        add_points::{AddPointsRequest, AddPointsResponse},
+        get_loyalty::{GetLoyaltyRequest, GetLoyaltyResponse},
+        redeem_points::{RedeemPointsRequest, RedeemPointsResponse},
+        DomainLogic, Error,
+    },
+    ports::{database::DatabasePort, member::MemberPort},
+};
+use tower::ServiceExt;
+
+/// Commands available to other services
+///
+/// Command futures are not `Send`, as with the [`DomainLogic`] services.
+#[async_trait::async_trait(?Send)]
+pub trait LoyaltyClient {
+    async fn add_points(&self, req: AddPointsRequest) -> Result<AddPointsResponse, Error>;
+    async fn redeem_points(&self, req: RedeemPointsRequest) -> Result<RedeemPointsResponse, Error>;
+    async fn get_loyalty(&self, req: GetLoyaltyRequest) -> Result<GetLoyaltyResponse, Error>;
+}
+
+/// [`LoyaltyClient`] calling the domain logic directly
+///
+/// Layers such as [`IdempotencyLayer`](crate::layers::idempotency::IdempotencyLayer) are not
+/// applied: the embedding service is responsible for them.
+pub struct InProcessClient<D, M> {
+    domain: DomainLogic<D, M>,
+}
+
+impl<D, M> InProcessClient<D, M> {
+    pub fn new(domain: DomainLogic<D, M>) -> Self {
+        Self { domain }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<D, M> LoyaltyClient for InProcessClient<D, M>
+where
+    D: DatabasePort + 'static,
+    M: MemberPort + 'static,
+{
+    async fn add_points(&self, req: AddPointsRequest) -> Result<AddPointsResponse, Error> {
+        // Clones share the same ports and settings
+        ServiceExt::<AddPointsRequest>::oneshot(self.domain.clone(), req).await
+    }
+
+    async fn redeem_points(&self, req: RedeemPointsRequest) -> Result<RedeemPointsResponse, Error> {
+        ServiceExt::<RedeemPointsRequest>::oneshot(self.domain.clone(), req).await
+    }
+
+    async fn get_loyalty(&self, req: GetLoyaltyRequest) -> Result<GetLoyaltyResponse, Error> {
+        ServiceExt::<GetLoyaltyRequest>::oneshot(self.domain.clone(), req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::add_points::AddPointsEvent,
+        testing::fixtures::{Fixture, MemberBuilder},
+    };
+    use speculoos::prelude::*;
+    use tower::BoxError;
+
+    #[tokio::test]
+    async fn test_in_process_client() -> Result<(), BoxError> {
+        // GIVEN a client for a member with 100 points
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::basic().with_points(100).build().await?;
+        let client = InProcessClient::new(domain);
+
+        // WHEN adding and redeeming points through the client
+        client
+            .add_points(AddPointsRequest {
+                member_id,
+                event: AddPointsEvent::Manual {
+                    loyalty_points: 50,
+                    reason: None,
+                },
+                occurred_at: None,
+                order_reference: None,
+                idempotency_key: None,
+            })
+            .await?;
+        client
+            .redeem_points(RedeemPointsRequest {
+                member_id,
+                loyalty_points: 30,
+                reward: "Free coffee".to_string(),
+                idempotency_key: None,
+            })
+            .await?;
+        let res = client
+            .get_loyalty(GetLoyaltyRequest {
+                member_id,
+                recent_events: 10,
+            })
+            .await;
+
+        // THEN the commands ran against the same domain logic
+        assert_that!(res)
+            .is_ok()
+            .matches(|res| res.loyalty_points == 120 && res.recent_events.len() == 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,508 @@
+//! Traffic capture and replay
+//!
+//! [`CaptureLayer`] records the commands handled by a service, with pseudonyms instead of member
+//! identifiers. [`replay`] runs the recorded commands against another service, e.g. one built
+//! with the adapters of a migration, and reports the responses that differ.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use tower::{Layer, Service, ServiceExt};
+use uuid::Uuid;
+
+use crate::{commands::Error, domain::normalize_order_reference};
+
+/// Value that can be recorded without identifying members
+///
+/// Identifiers are replaced with pseudonyms, and other values are kept as they are, so replayed
+/// commands behave the same way as the original ones.
+pub trait Anonymize {
+    fn anonymize(&self, pseudonyms: &mut Pseudonyms) -> Self;
+}
+
+/// Pseudonyms given to identifiers in a capture
+///
+/// The same identifier always gets the same pseudonym, so commands for the same member or order
+/// still relate to each other.
+#[derive(Debug, Default)]
+pub struct Pseudonyms {
+    ids: HashMap<Uuid, Uuid>,
+    order_references: HashMap<String, String>,
+    idempotency_keys: HashMap<String, String>,
+    reasons: HashMap<String, String>,
+}
+
+impl Pseudonyms {
+    /// Pseudonym for a member ID
+    pub fn id(&mut self, id: Uuid) -> Uuid {
+        *self.ids.entry(id).or_insert_with(Uuid::now_v7)
+    }
+
+    /// Pseudonym for an order reference, or `None` if it has no letter or digit
+    ///
+    /// References matching once normalized get the same pseudonym. Pseudonyms are already
+    /// normalized, so replayed purchases are reconciled the same way.
+    pub fn order_reference(&mut self, reference: &str) -> Option<String> {
+        let normalized = normalize_order_reference(reference)?;
+        let next = self.order_references.len() + 1;
+        let pseudonym = self
+            .order_references
+            .entry(normalized)
+            .or_insert_with(|| format!("ORDER{next}"));
+        Some(pseudonym.clone())
+    }
+
+    /// Pseudonym for an idempotency key
+    pub fn idempotency_key(&mut self, key: &str) -> String {
+        let next = self.idempotency_keys.len() + 1;
+        self.idempotency_keys
+            .entry(key.to_string())
+            .or_insert_with(|| format!("key-{next}"))
+            .clone()
+    }
+
+    /// Pseudonym for a free-text reason
+    ///
+    /// Reasons can contain identifiers, e.g. the member receiving a transfer or the reference of
+    /// a reconciled order, so they are replaced as a whole.
+    pub fn reason(&mut self, reason: &str) -> String {
+        let next = self.reasons.len() + 1;
+        self.reasons
+            .entry(reason.to_string())
+            .or_insert_with(|| format!("reason-{next}"))
+            .clone()
+    }
+}
+
+/// Command handled by a captured service
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedCall<Req, Res> {
+    /// Time between the start of the capture and the call
+    pub offset: Duration,
+    pub request: Req,
+    /// Response, or the code of the [`ErrorEnvelope`](crate::commands::error_envelope::ErrorEnvelope)
+    pub response: Result<Res, &'static str>,
+    pub latency: Duration,
+}
+
+/// Calls recorded by a [`CaptureLayer`]
+///
+/// Clones share the same calls, so a capture can be given to the layer and kept to retrieve the
+/// calls. Each capture records a single command type.
+#[derive(Debug)]
+pub struct TrafficCapture<Req, Res> {
+    started: Instant,
+    state: Arc<Mutex<CaptureState<Req, Res>>>,
+}
+
+#[derive(Debug)]
+struct CaptureState<Req, Res> {
+    pseudonyms: Pseudonyms,
+    calls: Vec<CapturedCall<Req, Res>>,
+}
+
+impl<Req, Res> TrafficCapture<Req, Res> {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Arc::new(Mutex::new(CaptureState {
+                pseudonyms: Pseudonyms::default(),
+                calls: Vec::new(),
+            })),
+        }
+    }
+
+    /// Calls recorded so far, in the order they started
+    ///
+    /// The calls are removed from the capture, but pseudonyms are kept for later calls.
+    pub fn take_calls(&self) -> Vec<CapturedCall<Req, Res>> {
+        let mut calls = std::mem::take(&mut self.lock().calls);
+        calls.sort_by_key(|call| call.offset);
+        calls
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CaptureState<Req, Res>> {
+        // Calls are only pushed once complete, so the state is consistent even after a panic
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<Req, Res> Clone for TrafficCapture<Req, Res> {
+    fn clone(&self) -> Self {
+        Self {
+            started: self.started,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<Req, Res> Default for TrafficCapture<Req, Res> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Layer recording anonymized requests and responses in a [`TrafficCapture`]
+#[derive(Debug)]
+pub struct CaptureLayer<Req, Res> {
+    capture: TrafficCapture<Req, Res>,
+}
+
+impl<Req, Res> CaptureLayer<Req, Res> {
+    pub fn new(capture: TrafficCapture<Req, Res>) -> Self {
+        Self { capture }
+    }
+}
+
+impl<Req, Res> Clone for CaptureLayer<Req, Res> {
+    fn clone(&self) -> Self {
+        Self {
+            capture: self.capture.clone(),
+        }
+    }
+}
+
+impl<S, Req, Res> Layer<S> for CaptureLayer<Req, Res> {
+    type Service = Capture<S, Req, Res>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Capture {
+            inner,
+            capture: self.capture.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Capture<S, Req, Res> {
+    inner: S,
+    capture: TrafficCapture<Req, Res>,
+}
+
+impl<S: Clone, Req, Res> Clone for Capture<S, Req, Res> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            capture: self.capture.clone(),
+        }
+    }
+}
+
+impl<S, Req, Res> Service<Req> for Capture<S, Req, Res>
+where
+    S: Service<Req, Response = Res, Error = Error>,
+    S::Future: 'static,
+    Req: Anonymize + 'static,
+    Res: Anonymize + 'static,
+{
+    type Response = Res;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let capture = self.capture.clone();
+        let started = Instant::now();
+        let request = req.anonymize(&mut capture.lock().pseudonyms);
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let res = future.await;
+            let latency = started.elapsed();
+
+            let mut state = capture.lock();
+            let response = match &res {
+                Ok(response) => Ok(response.anonymize(&mut state.pseudonyms)),
+                Err(err) => Err(err.envelope(None).code),
+            };
+            state.calls.push(CapturedCall {
+                offset: started.duration_since(capture.started),
+                request,
+                response,
+                latency,
+            });
+            drop(state);
+
+            res
+        })
+    }
+}
+
+/// Pace of a [`replay`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReplaySpeed {
+    /// Send each call as soon as the previous one completes
+    #[default]
+    Unpaced,
+    /// Keep the time between calls of the capture, divided by this factor
+    ///
+    /// For example, `2.0` replays twice as fast as the original traffic.
+    Scaled(f64),
+}
+
+/// Outcome of a [`replay`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub calls: usize,
+    /// Position of the calls whose response differs from the recorded one
+    pub mismatches: Vec<usize>,
+    /// Total latency of the recorded calls
+    pub recorded_latency: Duration,
+    /// Total latency of the replayed calls
+    pub replayed_latency: Duration,
+}
+
+/// Run captured calls against `service` in order, and compare their responses with the recorded
+/// ones
+///
+/// `sleep` waits between calls for [`ReplaySpeed::Scaled`], e.g. `tokio::time::sleep`. Calls are
+/// sent one at a time, so a slow call delays the next ones.
+///
+/// Responses containing generated identifiers or dates only match if both services generate the
+/// same ones, e.g. with [`SequentialIds`](crate::adapters::id_generator::sequential::SequentialIds)
+/// and a [`FixedClock`](crate::adapters::clock::fixed::FixedClock).
+pub async fn replay<S, Req, Res, F, Fut>(
+    service: &mut S,
+    calls: Vec<CapturedCall<Req, Res>>,
+    speed: ReplaySpeed,
+    sleep: F,
+) -> ReplayReport
+where
+    S: Service<Req, Response = Res, Error = Error>,
+    Res: PartialEq,
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let started = Instant::now();
+    let mut report = ReplayReport {
+        calls: calls.len(),
+        ..Default::default()
+    };
+    for (index, call) in calls.into_iter().enumerate() {
+        if let ReplaySpeed::Scaled(factor) = speed {
+            // Invalid factors replay without pauses
+            let wait = Duration::try_from_secs_f64(call.offset.as_secs_f64() / factor)
+                .ok()
+                .and_then(|due| due.checked_sub(started.elapsed()));
+            if let Some(wait) = wait {
+                sleep(wait).await;
+            }
+        }
+
+        let call_started = Instant::now();
+        let res = match service.ready().await {
+            Ok(service) => service.call(call.request).await,
+            Err(err) => Err(err),
+        };
+        report.recorded_latency += call.latency;
+        report.replayed_latency += call_started.elapsed();
+
+        if res.map_err(|err| err.envelope(None).code) != call.response {
+            tracing::warn!(index, "replayed response differs from the recorded one");
+            report.mismatches.push(index);
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            clock::fixed::FixedClock, database::memory::MemoryDatabase,
+            id_generator::sequential::SequentialIds,
+        },
+        commands::{
+            add_points::{AddPointsEvent, AddPointsRequest, AddPointsResponse},
+            get_loyalty::GetLoyaltyResponse,
+            DomainLogic,
+        },
+        domain::{LoyaltyEvent, Money, Tier},
+        ports::member::{self, Member, MockMemberPort},
+        testing::fixtures::{self, Fixture, MemberBuilder},
+    };
+    use speculoos::prelude::*;
+    use tower::BoxError;
+
+    fn purchase(member_id: Uuid, order_reference: &str) -> AddPointsRequest {
+        AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::OnlinePurchase {
+                purchase_amount: Money::new(1500, "EUR").unwrap(),
+            },
+            occurred_at: None,
+            order_reference: Some(order_reference.to_string()),
+            idempotency_key: Some(format!("{member_id}-{order_reference}")),
+        }
+    }
+
+    /// Capture two purchases of the same order from different channels
+    async fn capture_purchases(
+    ) -> Result<(Uuid, Vec<CapturedCall<AddPointsRequest, AddPointsResponse>>), BoxError> {
+        let Fixture {
+            member_id, domain, ..
+        } = MemberBuilder::basic().build().await?;
+        let capture = TrafficCapture::new();
+        let mut service = CaptureLayer::new(capture.clone())
+            .layer(domain.with_id_generator(Arc::new(SequentialIds::default())));
+
+        for order_reference in ["A-1", "a1"] {
+            service
+                .ready()
+                .await?
+                .call(purchase(member_id, order_reference))
+                .await?;
+        }
+
+        Ok((member_id, capture.take_calls()))
+    }
+
+    /// Domain logic with an empty database, where every member has a Basic membership if
+    /// `member_exists`
+    fn replay_domain(member_exists: bool) -> DomainLogic<MemoryDatabase, MockMemberPort> {
+        let mut member = MockMemberPort::new();
+        member.expect_get_member().returning(move |member_id| {
+            if !member_exists {
+                return Err(member::Error::MemberDoesNotExist(member_id));
+            }
+            Ok(Member {
+                member_id,
+                active_member: true,
+                membership_since: fixtures::now(),
+                ..Default::default()
+            })
+        });
+        DomainLogic::new(Arc::new(MemoryDatabase::default()), Arc::new(member))
+            .with_clock(Arc::new(FixedClock::new(fixtures::now())))
+            .with_id_generator(Arc::new(SequentialIds::default()))
+    }
+
+    #[tokio::test]
+    async fn test_capture() -> Result<(), BoxError> {
+        // GIVEN a captured service
+
+        // WHEN adding points for the same order from two channels
+        let (member_id, calls) = capture_purchases().await?;
+
+        // THEN
+        // * both calls are recorded in order
+        // * identifiers are replaced with the same pseudonyms in requests and responses
+        // * references of the same order get the same pseudonym
+        assert_that!(calls).has_length(2);
+        let pseudonym = calls[0].request.member_id;
+        assert_that!(pseudonym).is_not_equal_to(member_id);
+        for call in &calls {
+            assert_that!(call.request.member_id).is_equal_to(pseudonym);
+            assert_that!(call.request.order_reference.as_deref()).is_equal_to(Some("ORDER1"));
+            assert_that!(call.response)
+                .is_ok()
+                .matches(|res| res.member_id == pseudonym);
+        }
+        assert_that!(calls[0].request.idempotency_key.as_deref()).is_equal_to(Some("key-1"));
+        assert_that!(calls[1].request.idempotency_key.as_deref()).is_equal_to(Some("key-2"));
+        assert_that!(calls[1].offset).is_greater_than_or_equal_to(calls[0].offset);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymize_reasons() {
+        // GIVEN a manual addition, and a summary with events whose reasons contain identifiers
+        let member_id = Uuid::new_v4();
+        let other_member_id = Uuid::new_v4();
+        let request = AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::Manual {
+                loyalty_points: 100,
+                reason: Some(format!("Goodwill for order A-1 of {member_id}")),
+            },
+            occurred_at: None,
+            order_reference: None,
+            idempotency_key: None,
+        };
+        let event = |reason: String, order_reference: Option<&str>| LoyaltyEvent {
+            order_reference: order_reference.map(str::to_string),
+            ..LoyaltyEvent::new(Uuid::new_v4(), 100, reason, fixtures::now())
+        };
+        let response = GetLoyaltyResponse {
+            member_id,
+            tier: Tier::Silver,
+            membership_months: Some(12),
+            tier_evaluated_at: fixtures::now(),
+            loyalty_points: 300,
+            pending_loyalty_points: 0,
+            held_loyalty_points: 0,
+            recent_events: vec![
+                event(format!("Transferred to {other_member_id}"), None),
+                event("Reconciled order A-1".to_string(), Some("A-1")),
+                event(format!("Goodwill for order A-1 of {member_id}"), None),
+            ],
+        };
+
+        // WHEN anonymizing them
+        let mut pseudonyms = Pseudonyms::default();
+        let request = request.anonymize(&mut pseudonyms);
+        let response = response.anonymize(&mut pseudonyms);
+
+        // THEN
+        // * no member ID or order reference survives
+        // * the same reason gets the same pseudonym
+        let recorded = format!("{:?} {:?}", request.event.reason(), response);
+        for original in [
+            member_id.to_string(),
+            other_member_id.to_string(),
+            "A-1".to_string(),
+        ] {
+            assert_that!(recorded.contains(&original)).is_false();
+        }
+        assert_that!(request.event.reason().into_owned())
+            .is_equal_to(response.recent_events[2].reason.clone());
+    }
+
+    #[tokio::test]
+    async fn test_replay() -> Result<(), BoxError> {
+        // GIVEN captured purchases, and another domain logic with an empty database
+        let (_, calls) = capture_purchases().await?;
+        let mut domain = replay_domain(true);
+
+        // WHEN replaying the calls
+        let report = replay(
+            &mut domain,
+            calls,
+            ReplaySpeed::Scaled(1000.0),
+            tokio::time::sleep,
+        )
+        .await;
+
+        // THEN the responses match the recorded ones
+        assert_that!(report.calls).is_equal_to(2);
+        assert_that!(report.mismatches).is_empty();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_mismatch() -> Result<(), BoxError> {
+        // GIVEN captured purchases, and another domain logic without the member
+        let (_, calls) = capture_purchases().await?;
+        let mut domain = replay_domain(false);
+
+        // WHEN replaying the calls
+        let report = replay(&mut domain, calls, ReplaySpeed::Unpaced, tokio::time::sleep).await;
+
+        // THEN both responses differ from the recorded ones
+        assert_that!(report.mismatches).is_equal_to(vec![0, 1]);
+
+        Ok(())
+    }
+}
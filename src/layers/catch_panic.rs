@@ -0,0 +1,124 @@
+use std::{
+    any::Any,
+    future::{poll_fn, Future},
+    panic::{catch_unwind, AssertUnwindSafe},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tower::{Layer, Service};
+
+use crate::commands::Error;
+
+/// Layer converting panics into [`Error::Internal`]
+///
+/// This isolates panics to a single request, e.g. so one bad record in a batch does not bring
+/// down the whole consumer. The panic payload is logged and included in the error message.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CatchPanicLayer;
+
+impl<S> Layer<S> for CatchPanicLayer {
+    type Service = CatchPanic<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CatchPanic { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CatchPanic<S> {
+    inner: S,
+}
+
+impl<S, Req> Service<Req> for CatchPanic<S>
+where
+    S: Service<Req, Error = Error>,
+    S::Response: 'static,
+    S::Future: 'static,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // The inner service could panic before returning its future
+        let mut future = match catch_unwind(AssertUnwindSafe(|| self.inner.call(req))) {
+            Ok(future) => Box::pin(future),
+            Err(payload) => return Box::pin(std::future::ready(Err(panic_error(payload)))),
+        };
+
+        Box::pin(poll_fn(move |cx| {
+            match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                Ok(poll) => poll,
+                Err(payload) => Poll::Ready(Err(panic_error(payload))),
+            }
+        }))
+    }
+}
+
+fn panic_error(payload: Box<dyn Any + Send>) -> Error {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    };
+    tracing::error!(panic = %message, "panic while handling a request");
+
+    Error::Internal(format!("panic: {message}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+    use tower::{service_fn, BoxError, ServiceExt};
+
+    #[tokio::test]
+    async fn test_panic_in_future() -> Result<(), BoxError> {
+        // GIVEN a service panicking on odd numbers
+        let mut service = CatchPanicLayer.layer(service_fn(|req: u32| async move {
+            if req % 2 == 1 {
+                panic!("odd number {req}");
+            }
+            Ok::<_, Error>(req)
+        }));
+
+        // WHEN calling the service
+        let res = service.ready().await?.call(1).await;
+
+        // THEN
+        // * the panic is converted into an error
+        // * the service keeps working
+        assert_that!(res).is_err().matches(
+            |err| matches!(err, Error::Internal(message) if message == "panic: odd number 1"),
+        );
+        let res = service.ready().await?.call(2).await;
+        assert_that!(res).is_ok().is_equal_to(2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_panic_in_call() -> Result<(), BoxError> {
+        // GIVEN a service panicking before returning a future
+        let mut service = CatchPanicLayer.layer(service_fn(
+            |_req: u32| -> std::future::Ready<Result<u32, Error>> { panic!("bad record") },
+        ));
+
+        // WHEN calling the service
+        let res = service.ready().await?.call(1).await;
+
+        // THEN the panic is converted into an error
+        assert_that!(res)
+            .is_err()
+            .matches(|err| matches!(err, Error::Internal(_)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use chrono::Duration;
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+use crate::{
+    adapters::clock::system::SystemClock,
+    ports::{
+        clock::ClockPort,
+        idempotency::{self, IdempotencyPort, Reservation},
+        ErrorChain,
+    },
+};
+
+/// Request that can carry an idempotency key
+pub trait IdempotentRequest {
+    fn idempotency_key(&self) -> Option<&str>;
+    /// Member the request acts on, scoping its idempotency key
+    fn member_id(&self) -> Uuid;
+}
+
+/// Layer caching responses of requests carrying an idempotency key
+///
+/// Retries with the same key within the window return the cached response instead of calling the
+/// inner service again. Requests without a key always go through. Keys are scoped by member, so
+/// members cannot get each other's responses.
+///
+/// The key is reserved before calling the inner service, so a concurrent retry fails with
+/// [`InFlight`](idempotency::Error::InFlight) instead of running the command twice. Only
+/// successful responses are cached, so a failed request can be retried with the same key.
+pub struct IdempotencyLayer<P> {
+    store: Arc<P>,
+    window: Duration,
+    clock: Arc<dyn ClockPort + Send + Sync>,
+}
+
+impl<P> IdempotencyLayer<P> {
+    pub fn new(store: Arc<P>, window: Duration) -> Self {
+        Self {
+            store,
+            window,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Read the current time from `clock` instead of the system clock
+    pub fn with_clock<C>(mut self, clock: Arc<C>) -> Self
+    where
+        C: ClockPort + Send + Sync + 'static,
+    {
+        self.clock = clock;
+        self
+    }
+}
+
+impl<P> Clone for IdempotencyLayer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            window: self.window,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for IdempotencyLayer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdempotencyLayer")
+            .field("store", &self.store)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, P> Layer<S> for IdempotencyLayer<P> {
+    type Service = Idempotency<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Idempotency {
+            inner,
+            store: self.store.clone(),
+            window: self.window,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+pub struct Idempotency<S, P> {
+    inner: S,
+    store: Arc<P>,
+    window: Duration,
+    clock: Arc<dyn ClockPort + Send + Sync>,
+}
+
+impl<S: Clone, P> Clone for Idempotency<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            store: self.store.clone(),
+            window: self.window,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug, P: fmt::Debug> fmt::Debug for Idempotency<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Idempotency")
+            .field("inner", &self.inner)
+            .field("store", &self.store)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, P, Req> Service<Req> for Idempotency<S, P>
+where
+    S: Service<Req> + Clone + 'static,
+    S::Response: Clone + Send + Sync + 'static,
+    S::Error: From<idempotency::Error>,
+    S::Future: 'static,
+    P: IdempotencyPort<S::Response> + 'static,
+    Req: IdempotentRequest + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // Keep the service that was polled ready for this request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let store = self.store.clone();
+        let window = self.window;
+        let clock = self.clock.clone();
+        let key = req
+            .idempotency_key()
+            .map(|key| format!("{}/{}", req.member_id(), key));
+        Box::pin(async move {
+            let Some(key) = key else {
+                return inner.call(req).await;
+            };
+
+            let now = clock.now();
+            match store.reserve(&key, now, now + window).await? {
+                Reservation::Reserved => {}
+                Reservation::InFlight => return Err(idempotency::Error::InFlight(key).into()),
+                Reservation::Completed(response) => return Ok(response),
+            }
+
+            let response = match inner.call(req).await {
+                Ok(response) => response,
+                Err(err) => {
+                    if let Err(release_err) = store.release(&key).await {
+                        // The key stays reserved until the window ends
+                        tracing::warn!(
+                            key,
+                            error = %ErrorChain(&release_err),
+                            "failed to release idempotency key"
+                        );
+                    }
+                    return Err(err);
+                }
+            };
+            // The command succeeded, so failing now would lead the client to apply it twice. The
+            // key stays reserved, and retries fail as in flight until the window ends.
+            if let Err(err) = store
+                .store_response(&key, response.clone(), clock.now() + window)
+                .await
+            {
+                tracing::warn!(key, error = %ErrorChain(&err), "failed to store response");
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{
+            clock::fixed::FixedClock, database::memory::MemoryDatabase,
+            idempotency::memory::MemoryIdempotencyStore,
+        },
+        commands::{
+            add_points::{AddPointsEvent, AddPointsRequest},
+            DomainLogic,
+        },
+        ports::{database::DatabasePort, idempotency::MockIdempotencyPort, member::MockMemberPort},
+    };
+    use chrono::Utc;
+    use speculoos::prelude::*;
+    use tower::{BoxError, ServiceExt};
+    use uuid::Uuid;
+
+    fn request(member_id: Uuid, idempotency_key: Option<&str>) -> AddPointsRequest {
+        AddPointsRequest {
+            member_id,
+            event: AddPointsEvent::Manual {
+                loyalty_points: 100,
+                reason: None,
+            },
+            idempotency_key: idempotency_key.map(ToString::to_string),
+            occurred_at: None,
+            order_reference: None,
+        }
+    }
+
+    fn member_port(times: usize) -> MockMemberPort {
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .times(times)
+            .returning(|member_id| {
+                Ok(crate::ports::member::Member {
+                    active_member: true,
+                    member_id,
+                    membership_since: Utc::now(),
+                    ..Default::default()
+                })
+            });
+        member
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_key() -> Result<(), BoxError> {
+        // GIVEN a domain logic wrapped in the idempotency layer
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(1)));
+        let mut service = IdempotencyLayer::new(
+            Arc::new(MemoryIdempotencyStore::default()),
+            Duration::minutes(5),
+        )
+        .layer(domain);
+
+        // WHEN calling the service twice with the same key
+        let first = ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, Some("KEY")))
+            .await?;
+        let second = ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, Some("KEY")))
+            .await?;
+
+        // THEN
+        // * both calls return the same response
+        // * points are only added once
+        assert_that!(second).is_equal_to(first);
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(100);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_without_key() -> Result<(), BoxError> {
+        // GIVEN a domain logic wrapped in the idempotency layer
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(2)));
+        let mut service = IdempotencyLayer::new(
+            Arc::new(MemoryIdempotencyStore::default()),
+            Duration::minutes(5),
+        )
+        .layer(domain);
+
+        // WHEN calling the service twice without key
+        ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, None))
+            .await?;
+        ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, None))
+            .await?;
+
+        // THEN points are added twice
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(200);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_key_scoped_by_member() -> Result<(), BoxError> {
+        // GIVEN a domain logic wrapped in the idempotency layer
+        let member_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let database = MemoryDatabase::default();
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(2)));
+        let mut service = IdempotencyLayer::new(
+            Arc::new(MemoryIdempotencyStore::default()),
+            Duration::minutes(5),
+        )
+        .layer(domain);
+
+        // WHEN two members use the same key
+        for member_id in member_ids {
+            let res = ServiceExt::<AddPointsRequest>::ready(&mut service)
+                .await?
+                .call(request(member_id, Some("KEY")))
+                .await?;
+            assert_that!(res.member_id).is_equal_to(member_id);
+        }
+
+        // THEN both members get their points
+        for member_id in member_ids {
+            let loyalty = database.get_loyalty_points(member_id).await?;
+            assert_that!(loyalty.points).is_equal_to(100);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_flight() -> Result<(), BoxError> {
+        // GIVEN a request with the key still in flight
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(0)));
+        let store = MemoryIdempotencyStore::default();
+        let now = Utc::now();
+        store
+            .reserve(
+                &format!("{}/KEY", member_id),
+                now,
+                now + Duration::minutes(5),
+            )
+            .await?;
+        let mut service =
+            IdempotencyLayer::new(Arc::new(store), Duration::minutes(5)).layer(domain);
+
+        // WHEN retrying it with the same key
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, Some("KEY")))
+            .await;
+
+        // THEN
+        // * the retry fails without adding points
+        // * the client can retry it later
+        assert_that!(res).is_err().matches(|err| {
+            matches!(
+                err,
+                crate::commands::Error::Idempotency(idempotency::Error::InFlight(_))
+            ) && err.is_retryable()
+        });
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_store_failure() -> Result<(), BoxError> {
+        // GIVEN an idempotency store failing to store responses
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(1)));
+        let mut store = MockIdempotencyPort::new();
+        store
+            .expect_reserve()
+            .returning(|_, _, _| Ok(Reservation::Reserved));
+        store
+            .expect_store_response()
+            .times(1)
+            .returning(|_, _, _| Err(idempotency::Error::Adapter("timeout".into())));
+        let mut service =
+            IdempotencyLayer::new(Arc::new(store), Duration::minutes(5)).layer(domain);
+
+        // WHEN calling the service with a key
+        let res = ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, Some("KEY")))
+            .await;
+
+        // THEN the points are added, and the call succeeds
+        assert_that!(res).is_ok();
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(100);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_uses_clock() -> Result<(), BoxError> {
+        // GIVEN a domain logic wrapped in the idempotency layer, with a fixed clock
+        let member_id = Uuid::new_v4();
+        let database = MemoryDatabase::default();
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member_port(2)));
+        let clock = FixedClock::new(crate::testing::fixtures::now());
+        let mut service = IdempotencyLayer::new(
+            Arc::new(MemoryIdempotencyStore::default()),
+            Duration::minutes(5),
+        )
+        .with_clock(Arc::new(clock.clone()))
+        .layer(domain);
+
+        // WHEN retrying with the same key within the window, then after it
+        for _ in 0..2 {
+            ServiceExt::<AddPointsRequest>::ready(&mut service)
+                .await?
+                .call(request(member_id, Some("KEY")))
+                .await?;
+        }
+        clock.advance(Duration::minutes(10));
+        ServiceExt::<AddPointsRequest>::ready(&mut service)
+            .await?
+            .call(request(member_id, Some("KEY")))
+            .await?;
+
+        // THEN the points are added again once the window ended
+        let loyalty = database.get_loyalty_points(member_id).await?;
+        assert_that!(loyalty.points).is_equal_to(200);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+//! Tower layers to wrap around [`DomainLogic`](crate::commands::DomainLogic)
+
+pub mod capture;
+pub mod catch_panic;
+pub mod idempotency;
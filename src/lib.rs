@@ -1,4 +1,17 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "service")]
 pub mod adapters;
+#[cfg(feature = "service")]
 pub mod commands;
+#[cfg(feature = "std")]
 pub mod domain;
+#[cfg(feature = "facade")]
+pub mod facade;
+#[cfg(feature = "service")]
+pub mod layers;
+pub mod points;
+#[cfg(feature = "service")]
 pub mod ports;
+#[cfg(all(test, feature = "service"))]
+mod testing;
@@ -0,0 +1,267 @@
+//! Points arithmetic and tier tables
+//!
+//! This module only depends on `core`, so embedded POS firmware can build the crate with
+//! `--no-default-features` and reuse the same earn math as the service.
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Tier {
+    None,
+    Basic,
+    Silver,
+    Gold,
+    Platinum,
+}
+
+impl Tier {
+    /// Tier for a number of continuous months of membership, or `None` for non-members
+    pub fn from_membership_months(membership_months: Option<u32>) -> Self {
+        match membership_months {
+            // Non-members
+            None => Tier::None,
+            // First year of continuous membership
+            Some(0..=11) => Tier::Basic,
+            // Second year of continuous membership
+            Some(12..=23) => Tier::Silver,
+            // Third year of continuous membership
+            Some(24..=35) => Tier::Gold,
+            // Fourth year and more
+            Some(_) => Tier::Platinum,
+        }
+    }
+
+    /// Minimum number of continuous months of membership for this tier, or `None` for non-members
+    pub fn min_membership_months(&self) -> Option<u32> {
+        match self {
+            Tier::None => None,
+            Tier::Basic => Some(0),
+            Tier::Silver => Some(12),
+            Tier::Gold => Some(24),
+            Tier::Platinum => Some(36),
+        }
+    }
+
+    /// Tier reached with more months of membership, if any
+    pub fn next(&self) -> Option<Tier> {
+        match self {
+            Tier::None => Some(Tier::Basic),
+            Tier::Basic => Some(Tier::Silver),
+            Tier::Silver => Some(Tier::Gold),
+            Tier::Gold => Some(Tier::Platinum),
+            Tier::Platinum => None,
+        }
+    }
+
+    /// Default number of points per currency unit on purchases
+    pub fn ratio(&self) -> i32 {
+        match self {
+            Tier::None => 0,
+            Tier::Basic => 10,
+            Tier::Silver => 12,
+            Tier::Gold => 15,
+            Tier::Platinum => 20,
+        }
+    }
+}
+
+/// Amount of money, in minor units of its currency (e.g. cents)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor_units: u32,
+    /// ISO 4217 code, validated as three uppercase ASCII letters
+    currency: [u8; 3],
+}
+
+/// Why an amount of money is invalid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoneyError {
+    NegativeAmount,
+    /// The amount is above [`Money::MAX_MINOR_UNITS`]
+    AmountTooLarge,
+    /// The currency is not an ISO 4217 code
+    InvalidCurrency,
+}
+
+impl core::fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MoneyError::NegativeAmount => f.write_str("amount cannot be negative"),
+            MoneyError::AmountTooLarge => f.write_str("amount is too large"),
+            MoneyError::InvalidCurrency => f.write_str("currency is not an ISO 4217 code"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MoneyError {}
+
+impl Money {
+    /// Largest amount accepted, in minor units
+    ///
+    /// This keeps whole units within an `i32` whatever the currency.
+    pub const MAX_MINOR_UNITS: i64 = i32::MAX as i64;
+
+    /// Amount of `minor_units` of `currency`, e.g. `Money::new(1099, "EUR")` for 10.99 EUR
+    pub fn new(minor_units: i64, currency: &str) -> Result<Self, MoneyError> {
+        let currency: [u8; 3] = currency
+            .as_bytes()
+            .try_into()
+            .map_err(|_| MoneyError::InvalidCurrency)?;
+        if !currency.iter().all(u8::is_ascii_uppercase) {
+            return Err(MoneyError::InvalidCurrency);
+        }
+        if minor_units < 0 {
+            return Err(MoneyError::NegativeAmount);
+        }
+        if minor_units > Self::MAX_MINOR_UNITS {
+            return Err(MoneyError::AmountTooLarge);
+        }
+
+        Ok(Self {
+            minor_units: minor_units as u32,
+            currency,
+        })
+    }
+
+    pub fn minor_units(&self) -> u32 {
+        self.minor_units
+    }
+
+    /// ISO 4217 code of the currency
+    pub fn currency(&self) -> &str {
+        // Validated as ASCII on creation
+        core::str::from_utf8(&self.currency).unwrap_or_default()
+    }
+
+    /// Number of digits after the decimal separator for the currency
+    ///
+    /// This is 2 for most currencies.
+    pub fn decimals(&self) -> u32 {
+        match &self.currency {
+            b"BIF" | b"CLP" | b"DJF" | b"GNF" | b"ISK" | b"JPY" | b"KMF" | b"KRW" | b"PYG"
+            | b"RWF" | b"UGX" | b"UYI" | b"VND" | b"VUV" | b"XAF" | b"XOF" | b"XPF" => 0,
+            b"BHD" | b"IQD" | b"JOD" | b"KWD" | b"LYD" | b"OMR" | b"TND" => 3,
+            _ => 2,
+        }
+    }
+
+    /// Whole currency units of the amount, rounded with `rounding`
+    pub fn whole_units(&self, rounding: Rounding) -> i32 {
+        let minor_units = self.minor_units;
+        let scale = 10u32.pow(self.decimals());
+        let whole_units = match rounding {
+            Rounding::Down => minor_units / scale,
+            Rounding::HalfUp => (minor_units + scale / 2) / scale,
+            Rounding::Up => minor_units.div_ceil(scale),
+        };
+        // At most `MAX_MINOR_UNITS`, so this fits
+        whole_units as i32
+    }
+}
+
+/// How purchase amounts are rounded to whole currency units before earning points
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Toward zero, so partial units never earn points
+    #[default]
+    Down,
+    /// To the nearest unit, with halves rounded up
+    HalfUp,
+    /// Away from zero, so any partial unit earns points
+    Up,
+}
+
+/// Points earned on a purchase, with `earn_ratio` points per whole currency unit
+///
+/// Points are capped at `i32::MAX` on overflow.
+pub fn purchase_points(purchase_amount: &Money, earn_ratio: i32, rounding: Rounding) -> i32 {
+    purchase_amount
+        .whole_units(rounding)
+        .saturating_mul(earn_ratio)
+}
+
+/// Apply a difference in points to a balance
+///
+/// This returns `None` if the balance would become negative or overflow.
+pub fn apply_delta(points: u32, delta_points: i32) -> Option<u32> {
+    points.checked_add_signed(delta_points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use speculoos::prelude::*;
+
+    #[test]
+    fn test_purchase_points() {
+        let eur = |minor_units| Money::new(minor_units, "EUR").unwrap();
+        assert_that!(purchase_points(
+            &eur(1099),
+            Tier::Gold.ratio(),
+            Rounding::Down
+        ))
+        .is_equal_to(150);
+        assert_that!(purchase_points(
+            &eur(50),
+            Tier::Platinum.ratio(),
+            Rounding::Down
+        ))
+        .is_equal_to(0);
+        assert_that!(purchase_points(
+            &eur(1099),
+            Tier::Gold.ratio(),
+            Rounding::HalfUp
+        ))
+        .is_equal_to(165);
+    }
+
+    #[rstest]
+    #[case(Money::new(1049, "EUR"), Rounding::HalfUp, Ok(10))]
+    #[case(Money::new(1050, "EUR"), Rounding::HalfUp, Ok(11))]
+    #[case(Money::new(1001, "EUR"), Rounding::Up, Ok(11))]
+    #[case(Money::new(1000, "EUR"), Rounding::Up, Ok(10))]
+    #[case(Money::new(1999, "EUR"), Rounding::Down, Ok(19))]
+    #[case(Money::new(1500, "JPY"), Rounding::Down, Ok(1500))]
+    #[case(Money::new(1500, "KWD"), Rounding::HalfUp, Ok(2))]
+    #[case(Money::new(-1, "EUR"), Rounding::Down, Err(MoneyError::NegativeAmount))]
+    #[case(Money::new(Money::MAX_MINOR_UNITS + 1, "JPY"), Rounding::Down, Err(MoneyError::AmountTooLarge))]
+    #[case(
+        Money::new(100, "eur"),
+        Rounding::Down,
+        Err(MoneyError::InvalidCurrency)
+    )]
+    #[case(
+        Money::new(100, "EURO"),
+        Rounding::Down,
+        Err(MoneyError::InvalidCurrency)
+    )]
+    fn test_whole_units(
+        #[case] money: Result<Money, MoneyError>,
+        #[case] rounding: Rounding,
+        #[case] expected: Result<i32, MoneyError>,
+    ) {
+        assert_that!(money.map(|money| money.whole_units(rounding))).is_equal_to(expected);
+    }
+
+    #[test]
+    fn test_min_membership_months() {
+        let mut tier = Some(Tier::Basic);
+        while let Some(current) = tier {
+            let months = current.min_membership_months();
+            assert_that!(Tier::from_membership_months(months)).is_equal_to(&current);
+            if let Some(months) = months.filter(|months| *months > 0) {
+                assert_that!(Tier::from_membership_months(Some(months - 1)))
+                    .is_not_equal_to(&current);
+            }
+            tier = current.next();
+        }
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        assert_that!(apply_delta(5, -5)).is_equal_to(Some(0));
+        assert_that!(apply_delta(5, 3)).is_equal_to(Some(8));
+        assert_that!(apply_delta(5, -6)).is_none();
+        assert_that!(apply_delta(u32::MAX, 1)).is_none();
+    }
+}
@@ -0,0 +1,31 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+
+use super::{AddContext, ContextError};
+use crate::domain::Campaign;
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait CampaignPort {
+    /// Promotional campaigns running at `at`
+    async fn get_active_campaigns(&self, at: DateTime<Utc>) -> Result<Vec<Campaign>, Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::borrow::Cow;
+
+use uuid::Uuid;
+
+use super::{AddContext, ContextError};
+use crate::domain::CaseLock;
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait CaseLockPort {
+    /// Current lock on a member, which might have expired
+    async fn get_lock(&self, member_id: Uuid) -> Result<Option<CaseLock>, Error>;
+    /// Store a lock, unless another agent holds an active lock on the same member
+    ///
+    /// An agent acquiring a lock they already hold replaces it, e.g. to extend it.
+    async fn acquire_lock(&self, case_lock: CaseLock) -> Result<(), Error>;
+    /// Remove the lock held by `holder` on a member
+    ///
+    /// Releasing a lock that does not exist, or already expired, succeeds.
+    async fn release_lock(&self, member_id: Uuid, holder: &str) -> Result<(), Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Domain-level error when another agent holds an active lock on the member
+    #[error("member {} is locked by {}", .0.member_id, .0.holder)]
+    AlreadyLocked(CaseLock),
+
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            err => err,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::borrow::Cow;
+
+use uuid::Uuid;
+
+use super::{AddContext, ContextError};
+use crate::domain::Reward;
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait CatalogPort {
+    async fn get_reward(&self, reward_id: Uuid) -> Result<Reward, Error>;
+    /// All rewards of the catalog, whatever the member's tier
+    async fn get_rewards(&self) -> Result<Vec<Reward>, Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Domain-level error when a reward does not exist
+    #[error("reward {0} does not exist")]
+    RewardDoesNotExist(Uuid),
+
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            err => err,
+        }
+    }
+}
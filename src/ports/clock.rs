@@ -0,0 +1,7 @@
+use chrono::{DateTime, Utc};
+
+#[mockall::automock]
+pub trait ClockPort {
+    /// Current time, e.g. to compute membership months or check expirations
+    fn now(&self) -> DateTime<Utc>;
+}
@@ -1,16 +1,256 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use crate::domain::{Loyalty, LoyaltyEvent};
+use super::{AddContext, ContextError, ErrorKind};
+use crate::domain::{
+    BalanceChange, CampaignRun, DomainEvent, EventNote, ExpiringLot, Loyalty, LoyaltyEvent,
+    MemberOverride, MemberRestriction, OutboxEntry, PartnerTransfer, PointsHold, TierEvaluation,
+};
 
 #[mockall::automock]
 #[async_trait::async_trait]
 pub trait DatabasePort {
     async fn get_loyalty_points(&self, member_id: Uuid) -> Result<Loyalty, Error>;
+    /// Loyalties of multiple members in a single call, in the same order as `member_ids`
+    ///
+    /// This otherwise behaves as `get_loyalty_points`: unknown members have an empty loyalty.
+    async fn get_loyalty_points_batch(&self, member_ids: &[Uuid]) -> Result<Vec<Loyalty>, Error>;
+    /// Loyalty events of a member, in chronological order
+    ///
+    /// Events are ordered by `sequence`, which is the order in which they were registered. This is
+    /// not necessarily the order in which they were created: an event awaiting approval is placed
+    /// when it is approved. Members without events return an empty list.
+    async fn get_loyalty_events(&self, member_id: Uuid) -> Result<Vec<LoyaltyEvent>, Error>;
+    /// Store a new loyalty event for a member
+    ///
+    /// Implementations must assign the event's `sequence`, continuing the member's event log.
+    /// Events removing points take them from the lots expiring soonest first.
+    ///
+    /// With an `expected_version`, this fails with [`Error::Conflict`] if the member's loyalty
+    /// changed since it was read, so commands can act on up-to-date state.
     async fn register_loyalty_event(
         &self,
         member_id: Uuid,
         loyalty_event: LoyaltyEvent,
+        expected_version: Option<u64>,
+    ) -> Result<Loyalty, Error>;
+    /// Store new loyalty events for multiple members in a single transaction
+    ///
+    /// Events are registered in order, as with `register_loyalty_event` without an expected
+    /// version. If any event is rejected, none of them are stored. This returns the loyalty of
+    /// each event's member after all events are registered, in the same order as `events`.
+    async fn register_loyalty_events_batch(
+        &self,
+        events: Vec<(Uuid, LoyaltyEvent)>,
+    ) -> Result<Vec<Loyalty>, Error>;
+    /// Store a new loyalty event, and add domain events to the outbox in the same transaction
+    ///
+    /// This otherwise behaves as `register_loyalty_event`. If the event is rejected, no domain
+    /// event is added to the outbox.
+    async fn register_loyalty_event_with_outbox(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+        expected_version: Option<u64>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error>;
+
+    /// Replace the events of a member up to the snapshot's `sequence` with the snapshot
+    ///
+    /// Unlike with `register_loyalty_event`, the snapshot keeps its `sequence`. This does not
+    /// change the member's points.
+    async fn compact_events(&self, member_id: Uuid, snapshot: LoyaltyEvent) -> Result<(), Error>;
+
+    /// Make pending points maturing at or before `until` available
+    ///
+    /// This returns the loyalties of all members that had points maturing, with the number of
+    /// points that matured. With `queue_matured`, a [`DomainEvent::PointsMatured`] is added to the
+    /// outbox for each of them in the same transaction.
+    async fn mature_points(
+        &self,
+        until: DateTime<Utc>,
+        queue_matured: bool,
+    ) -> Result<Vec<(Loyalty, u32)>, Error>;
+
+    /// Lots of available points expiring at or before `until`, with their member ID
+    ///
+    /// Lots are ordered by expiration date for each member.
+    async fn get_expired_lots(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, ExpiringLot)>, Error>;
+
+    /// Members whose points changed after the `checkpoint` position in the change feed
+    ///
+    /// Each member appears at most once, with their current points and the position of their
+    /// latest change, ordered by position. Positions are shared across members and increase with
+    /// every change to available or pending points, starting at 1. This returns at most `limit`
+    /// changes.
+    async fn get_balance_changes(
+        &self,
+        checkpoint: u64,
+        limit: usize,
+    ) -> Result<Vec<BalanceChange>, Error>;
+
+    /// Store a loyalty event that only affects the member's points once approved
+    async fn register_event_for_approval(
+        &self,
+        member_id: Uuid,
+        loyalty_event: LoyaltyEvent,
+    ) -> Result<(), Error>;
+    /// Events awaiting approval for a member, oldest first
+    async fn get_events_awaiting_approval(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<LoyaltyEvent>, Error>;
+    /// Register an event awaiting approval, as with `register_loyalty_event_with_outbox`
+    async fn approve_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error>;
+    /// Discard an event awaiting approval
+    async fn reject_event(&self, member_id: Uuid, event_id: Uuid) -> Result<(), Error>;
+    /// Events awaiting approval whose escrow expires at or before `until`, with their member ID
+    ///
+    /// Events are ordered by escrow expiration date for each member.
+    async fn get_expired_escrow_events(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error>;
+
+    /// Events with an unverified tier, with their member ID, oldest first for each member
+    async fn get_unverified_events(&self) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error>;
+    /// Mark an event's tier as verified, registering an adjustment event if the points differ
+    ///
+    /// Negative adjustments are taken from the event's pending points first, if they have not
+    /// matured yet. Both changes must be applied atomically, along with adding domain events to
+    /// the outbox.
+    async fn reconcile_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        adjustment: Option<LoyaltyEvent>,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error>;
+
+    /// Register an event reversing another one, linking both events together
+    ///
+    /// Negative reversals are taken from the event's pending points first, as with
+    /// `reconcile_event`. Events that are already linked, such as reversed events or transfers,
+    /// cannot be reversed. Both changes must be applied atomically, along with adding domain events
+    /// to the outbox.
+    async fn reverse_event(
+        &self,
+        member_id: Uuid,
+        event_id: Uuid,
+        reversal: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
     ) -> Result<Loyalty, Error>;
+
+    /// Move available points to a new hold
+    ///
+    /// This fails with [`Error::NegativePointsTotal`] if the member has fewer available points
+    /// than the hold. Expiring lots are left untouched until the hold is captured.
+    async fn place_hold(&self, member_id: Uuid, hold: PointsHold) -> Result<Loyalty, Error>;
+    /// Remove a hold and register an event debiting its points
+    ///
+    /// The event must remove exactly the hold's points. Both changes must be applied atomically,
+    /// along with adding domain events to the outbox.
+    async fn capture_hold(
+        &self,
+        member_id: Uuid,
+        hold_id: Uuid,
+        event: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error>;
+    /// Remove a hold, making its points available again
+    async fn release_hold(&self, member_id: Uuid, hold_id: Uuid) -> Result<Loyalty, Error>;
+
+    /// Register a member's welcome bonus, unless they already received it
+    ///
+    /// This fails with [`Error::WelcomeBonusAlreadyGranted`] if a previous call registered a
+    /// bonus for the member, even if their events were compacted since. Checking and recording
+    /// the grant must be atomic with registering the event and adding domain events to the
+    /// outbox, so concurrent calls grant at most one bonus.
+    async fn register_welcome_bonus(
+        &self,
+        member_id: Uuid,
+        bonus: LoyaltyEvent,
+        outbox: Vec<DomainEvent>,
+    ) -> Result<Loyalty, Error>;
+
+    /// All overrides granted to a member, oldest first
+    async fn get_member_overrides(&self, member_id: Uuid) -> Result<Vec<MemberOverride>, Error>;
+    async fn register_member_override(&self, member_override: MemberOverride) -> Result<(), Error>;
+
+    /// All restrictions placed on a member, including expired ones, oldest first
+    async fn get_member_restrictions(
+        &self,
+        member_id: Uuid,
+    ) -> Result<Vec<MemberRestriction>, Error>;
+    async fn register_member_restriction(
+        &self,
+        restriction: MemberRestriction,
+    ) -> Result<(), Error>;
+
+    /// Events tagged with a campaign, with their member ID, oldest first for each member
+    async fn get_campaign_events(
+        &self,
+        campaign_id: Uuid,
+    ) -> Result<Vec<(Uuid, LoyaltyEvent)>, Error>;
+    async fn get_campaign_run(&self, campaign_id: Uuid) -> Result<Option<CampaignRun>, Error>;
+    /// Store a campaign run, replacing any run with the same campaign ID
+    async fn save_campaign_run(&self, run: CampaignRun) -> Result<(), Error>;
+    /// Count points towards a campaign's issued points, unless they would exceed `budget`
+    ///
+    /// This returns whether the points were reserved. Concurrent reservations must never exceed
+    /// the budget together.
+    async fn reserve_campaign_budget(
+        &self,
+        campaign_id: Uuid,
+        points: u32,
+        budget: u64,
+    ) -> Result<bool, Error>;
+    /// Give back points reserved for a campaign that were not issued
+    async fn release_campaign_budget(&self, campaign_id: Uuid, points: u32) -> Result<(), Error>;
+
+    /// IDs of members with loyalty points or events, ordered by ID
+    ///
+    /// This returns at most `limit` members with an ID greater than `after`, to scan all members
+    /// page by page.
+    async fn get_member_ids(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<Uuid>, Error>;
+    /// Store the latest tier evaluation of a member, returning the previous one
+    ///
+    /// The evaluation is then part of the member's [`Loyalty`]. With `queue_tier_change`, if the
+    /// tier differs from the previous evaluation, a [`DomainEvent::TierChanged`] is added to the
+    /// outbox in the same transaction.
+    async fn save_tier_evaluation(
+        &self,
+        evaluation: TierEvaluation,
+        queue_tier_change: bool,
+    ) -> Result<Option<TierEvaluation>, Error>;
+
+    /// Oldest entries of the outbox, ordered by `outbox_id`
+    ///
+    /// This returns at most `limit` entries. Entries stay in the outbox until removed.
+    async fn get_outbox_entries(&self, limit: usize) -> Result<Vec<OutboxEntry>, Error>;
+    /// Remove a published entry from the outbox
+    ///
+    /// Removing an entry that is not in the outbox does nothing, so a relay can safely retry.
+    async fn remove_outbox_entry(&self, outbox_id: u64) -> Result<(), Error>;
+
+    /// All notes about a member's events, oldest first
+    async fn get_event_notes(&self, member_id: Uuid) -> Result<Vec<EventNote>, Error>;
+    async fn register_event_note(&self, note: EventNote) -> Result<(), Error>;
+
+    /// All transfers of a member to partner programs, oldest first
+    async fn get_partner_transfers(&self, member_id: Uuid) -> Result<Vec<PartnerTransfer>, Error>;
+    /// Store a new transfer, or replace the transfer with the same ID, e.g. to update its status
+    async fn save_partner_transfer(&self, transfer: PartnerTransfer) -> Result<(), Error>;
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -24,10 +264,71 @@ pub enum Error {
         delta_points: i32,
     },
 
+    /// Domain-level error when an event does not exist
+    #[error("event {0} does not exist")]
+    EventDoesNotExist(Uuid),
+
+    /// Domain-level error when a hold does not exist, e.g. it was already captured or released
+    #[error("hold {0} does not exist")]
+    HoldDoesNotExist(Uuid),
+
+    /// Domain-level error when a member already received their welcome bonus
+    #[error("member {0} already received their welcome bonus")]
+    WelcomeBonusAlreadyGranted(Uuid),
+
+    /// Domain-level error when an event is already linked to another one, e.g. reversed
+    #[error("event {0} is already linked to another event")]
+    EventAlreadyLinked(Uuid),
+
+    /// The member's loyalty changed since it was read
+    #[error("loyalty of member {member_id} is at version {current_version}, expected {expected_version}")]
+    Conflict {
+        member_id: Uuid,
+        expected_version: u64,
+        current_version: u64,
+    },
+
     /// Concrete adapter errors
     ///
     /// This could represent any errors from a concrete adapter that is not part of the domain
     /// model, such as connectivity, configuration, or permission errors.
-    #[error("adapter error: {0:?}")]
-    Adapter(Box<dyn std::error::Error + Send + Sync>),
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Transient adapter errors
+    ///
+    /// This represents errors from a concrete adapter where retrying the operation might succeed,
+    /// such as timeouts, throttling, or temporary connectivity issues.
+    #[error("adapter unavailable")]
+    Unavailable(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NegativePointsTotal { .. }
+            | Error::EventDoesNotExist(_)
+            | Error::HoldDoesNotExist(_)
+            | Error::WelcomeBonusAlreadyGranted(_)
+            | Error::EventAlreadyLinked(_)
+            | Error::Adapter(_) => ErrorKind::Fatal,
+            Error::Conflict { .. } | Error::Unavailable(_) => ErrorKind::Retryable,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Retryable
+    }
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            Error::Unavailable(source) => {
+                Error::Unavailable(Box::new(ContextError::new(context, source)))
+            }
+            err => err,
+        }
+    }
 }
@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+
+use uuid::Uuid;
+
+use super::{AddContext, ContextError};
+use crate::domain::{Drawing, DrawingEntry, DrawingResult};
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait DrawingPort {
+    async fn get_drawing(&self, drawing_id: Uuid) -> Result<Drawing, Error>;
+    /// Add entries for a member to a drawing
+    ///
+    /// Entries accumulate if the member enters the same drawing multiple times.
+    async fn register_entries(
+        &self,
+        drawing_id: Uuid,
+        member_id: Uuid,
+        entries: u32,
+    ) -> Result<(), Error>;
+    /// Entries for a drawing, with one item per member
+    async fn get_entries(&self, drawing_id: Uuid) -> Result<Vec<DrawingEntry>, Error>;
+    /// Store the outcome of a drawing
+    ///
+    /// This returns an error if the drawing already has a result.
+    async fn register_result(&self, result: DrawingResult) -> Result<(), Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Domain-level error when a drawing does not exist
+    #[error("drawing {0} does not exist")]
+    DrawingDoesNotExist(Uuid),
+
+    /// Domain-level error when a drawing already selected its winners
+    #[error("drawing {0} is already drawn")]
+    AlreadyDrawn(Uuid),
+
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            err => err,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::borrow::Cow;
+
+use super::{AddContext, ContextError};
+use crate::domain::DomainEvent;
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait EventPublisherPort {
+    /// Send an event to downstream consumers, e.g. through a message broker
+    async fn publish(&self, event: DomainEvent) -> Result<(), Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+        }
+    }
+}
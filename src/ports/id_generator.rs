@@ -0,0 +1,7 @@
+use uuid::Uuid;
+
+#[mockall::automock]
+pub trait IdGeneratorPort {
+    /// Generate a new unique identifier, e.g. for events or overrides
+    fn generate_id(&self) -> Uuid;
+}
@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+
+use super::{AddContext, ContextError, ErrorKind};
+
+/// Storage for command responses, keyed by the request's idempotency key
+///
+/// Keeping responses outside of the service lets inbound adapters (e.g. HTTP) stay stateless.
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait IdempotencyPort<T: Send + Sync + 'static> {
+    /// Reserve `key` until `expires_at`, unless another request already used it
+    ///
+    /// This must be atomic, so that only one of concurrent requests with the same key gets the
+    /// reservation. Entries that expired at `now` are ignored.
+    async fn reserve(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Reservation<T>, Error>;
+    /// Store the response of the request holding the reservation for `key`
+    async fn store_response(
+        &self,
+        key: &str,
+        response: T,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error>;
+    /// Release the reservation for `key` of a failed request, so it can be retried
+    async fn release(&self, key: &str) -> Result<(), Error>;
+}
+
+/// Outcome of reserving an idempotency key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reservation<T> {
+    /// The key was free, and the request can go through
+    Reserved,
+    /// Another request with the same key is still being handled
+    InFlight,
+    /// A previous request with the same key succeeded with this response
+    Completed(T),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Another request with the same key is still being handled
+    #[error("a request with idempotency key {0} is already in progress")]
+    InFlight(String),
+
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            // The request can be retried once the other one completes
+            Error::InFlight(_) => ErrorKind::Retryable,
+            Error::Adapter(_) => ErrorKind::Fatal,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Retryable
+    }
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            err => err,
+        }
+    }
+}
@@ -1,16 +1,40 @@
-use chrono::{DateTime, Utc};
+use std::borrow::Cow;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use uuid::Uuid;
 
+use super::{AddContext, ContextError, ErrorKind};
+
 #[mockall::automock]
 #[async_trait::async_trait]
 pub trait MemberPort {
     async fn get_member(&self, member_id: Uuid) -> Result<Member, Error>;
+    /// Members for multiple IDs, in the same order as `member_ids`
+    ///
+    /// Each ID has its own result, e.g. [`Error::MemberDoesNotExist`] for unknown members, while an
+    /// outer error fails the whole lookup.
+    async fn get_members(&self, member_ids: &[Uuid]) -> Result<Vec<Result<Member, Error>>, Error>;
 }
 
+/// Member data from the member service
+///
+/// Not all member services know about every profile field, so everything beyond membership status
+/// is optional and left to `None` when the adapter cannot populate it.
+#[derive(Clone, Debug, Default)]
 pub struct Member {
     pub member_id: Uuid,
     pub active_member: bool,
     pub membership_since: DateTime<Utc>,
+
+    /// Preferred locale of the member, as a BCP 47 language tag (e.g. `en-GB`)
+    pub locale: Option<String>,
+    /// Country of residence, as an ISO 3166-1 alpha-2 code (e.g. `GB`)
+    pub country: Option<String>,
+    pub birth_date: Option<NaiveDate>,
+    /// Whether the member agreed to receive marketing emails
+    pub email_opt_in: Option<bool>,
+    /// Marketing segments the member belongs to (e.g. `student`)
+    pub segment_tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -23,6 +47,44 @@ pub enum Error {
     ///
     /// This could represent any errors from a concrete adapter that is not part of the domain
     /// model, such as connectivity, configuration, or permission errors.
-    #[error("adapter error: {0:?}")]
-    Adapter(Box<dyn std::error::Error + Send + Sync>),
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Transient adapter errors
+    ///
+    /// This represents errors from a concrete adapter where retrying the operation might succeed,
+    /// such as timeouts, throttling, or temporary connectivity issues.
+    #[error("adapter unavailable")]
+    Unavailable(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// The member service is considered down after repeated failures
+    ///
+    /// Calls fail immediately instead of waiting for the service, until a cool-down passes.
+    #[error("circuit open after repeated failures")]
+    CircuitOpen,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::MemberDoesNotExist(_) | Error::Adapter(_) => ErrorKind::Fatal,
+            Error::Unavailable(_) | Error::CircuitOpen => ErrorKind::Retryable,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Retryable
+    }
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            Error::Unavailable(source) => {
+                Error::Unavailable(Box::new(ContextError::new(context, source)))
+            }
+            err => err,
+        }
+    }
 }
@@ -1,2 +1,139 @@
+use std::{borrow::Cow, fmt};
+
+pub mod campaign;
+pub mod case_lock;
+pub mod catalog;
+pub mod clock;
 pub mod database;
+pub mod drawing;
+pub mod event_publisher;
+pub mod id_generator;
+pub mod idempotency;
 pub mod member;
+pub mod partner_transfer;
+pub mod segment;
+
+/// Classification of port errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Transient error, such as a timeout or throttling: retrying might succeed
+    Retryable,
+    /// Error that will happen again if retried, such as a missing member or invalid input
+    Fatal,
+}
+
+/// Adapter error with a description of what the adapter was doing
+///
+/// This keeps the original error as its source, so the full chain is available when reporting.
+#[derive(Debug, thiserror::Error)]
+#[error("{context}")]
+pub struct ContextError {
+    context: Cow<'static, str>,
+    #[source]
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl ContextError {
+    pub fn new(
+        context: impl Into<Cow<'static, str>>,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    ) -> Self {
+        Self {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+/// Port errors that can carry context
+pub trait AddContext {
+    /// Add context to adapter errors
+    ///
+    /// Domain errors are returned unchanged, as they already describe what went wrong.
+    fn add_context(self, context: Cow<'static, str>) -> Self;
+}
+
+/// Context helpers for results of port calls
+pub trait ResultExt<T> {
+    fn context(self, context: impl Into<Cow<'static, str>>) -> Self;
+    /// Lazily evaluated version of `context`, to avoid formatting on success
+    fn with_context<C, F>(self, f: F) -> Self
+    where
+        C: Into<Cow<'static, str>>,
+        F: FnOnce() -> C;
+}
+
+impl<T, E: AddContext> ResultExt<T> for Result<T, E> {
+    fn context(self, context: impl Into<Cow<'static, str>>) -> Self {
+        self.map_err(|err| err.add_context(context.into()))
+    }
+
+    fn with_context<C, F>(self, f: F) -> Self
+    where
+        C: Into<Cow<'static, str>>,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|err| err.add_context(f().into()))
+    }
+}
+
+/// Display an error followed by its chain of sources, e.g. for logs
+pub struct ErrorChain<'a>(pub &'a (dyn std::error::Error + 'static));
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(err) = source {
+            write!(f, ": {err}")?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_with_context() {
+        let res: Result<(), database::Error> =
+            Err(database::Error::Adapter("connection reset".into()));
+
+        let err = res
+            .context("fetching loyalty")
+            .with_context(|| "adding points")
+            .unwrap_err();
+
+        assert_that!(ErrorChain(&err).to_string()).is_equal_to(
+            "adapter error: adding points: fetching loyalty: connection reset".to_string(),
+        );
+        assert_that!(err.is_retryable()).is_false();
+    }
+
+    #[test]
+    fn test_with_context_domain_error() {
+        let member_id = Uuid::new_v4();
+        let res: Result<(), member::Error> = Err(member::Error::MemberDoesNotExist(member_id));
+
+        let err = res.context("fetching member").unwrap_err();
+
+        assert_that!(ErrorChain(&err).to_string())
+            .is_equal_to(format!("member {member_id} does not exist"));
+    }
+
+    #[test]
+    fn test_error_chain_through_commands() {
+        let err: crate::commands::Error = member::Error::Unavailable("timeout".into())
+            .add_context("fetching member".into())
+            .into();
+
+        assert_that!(ErrorChain(&err).to_string()).is_equal_to(
+            "member port error: adapter unavailable: fetching member: timeout".to_string(),
+        );
+        assert_that!(err.is_retryable()).is_true();
+    }
+}
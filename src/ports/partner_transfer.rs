@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+use uuid::Uuid;
+
+use super::{AddContext, ContextError, ErrorKind};
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait PartnerTransferPort {
+    /// Credit `points` to the member's account with `partner`, converted to the partner's
+    /// currency, and return the partner's reference for the transfer
+    ///
+    /// Calls with the same transfer ID are retries of the same transfer, which partners must only
+    /// credit once.
+    async fn transfer(
+        &self,
+        transfer_id: Uuid,
+        partner: &str,
+        member_id: Uuid,
+        points: u32,
+    ) -> Result<String, Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Domain-level error when the partner refuses the transfer, e.g. for an unknown account
+    #[error("partner rejected the transfer: {0}")]
+    Rejected(String),
+
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Transient adapter errors
+    ///
+    /// This represents errors from a concrete adapter where retrying the operation might succeed,
+    /// such as timeouts, throttling, or temporary connectivity issues.
+    #[error("adapter unavailable")]
+    Unavailable(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Rejected(_) | Error::Adapter(_) => ErrorKind::Fatal,
+            Error::Unavailable(_) => ErrorKind::Retryable,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Retryable
+    }
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+            Error::Unavailable(source) => {
+                Error::Unavailable(Box::new(ContextError::new(context, source)))
+            }
+            err => err,
+        }
+    }
+}
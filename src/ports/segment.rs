@@ -0,0 +1,32 @@
+use std::borrow::Cow;
+
+use uuid::Uuid;
+
+use super::{AddContext, ContextError};
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait SegmentPort {
+    /// Marketing segments the member belongs to (e.g. `student`)
+    async fn get_segments(&self, member_id: Uuid) -> Result<Vec<String>, Error>;
+    /// Members of a marketing segment, always in the same order
+    async fn get_segment_members(&self, segment: &str) -> Result<Vec<Uuid>, Error>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Concrete adapter errors
+    ///
+    /// This could represent any errors from a concrete adapter that is not part of the domain
+    /// model, such as connectivity, configuration, or permission errors.
+    #[error("adapter error")]
+    Adapter(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AddContext for Error {
+    fn add_context(self, context: Cow<'static, str>) -> Self {
+        match self {
+            Error::Adapter(source) => Error::Adapter(Box::new(ContextError::new(context, source))),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Builders setting up a member in both the member port and the database
+//!
+//! Fixtures use a clock fixed at [`now`], so tiers and dates do not depend on when tests run:
+//!
+//! ```ignore
+//! let fixture = MemberBuilder::gold().with_points(1200).build().await?;
+//! let mut domain = fixture.domain;
+//! ```
+
+use chrono::{DateTime, Duration, Months, TimeZone, Utc};
+use mockall::predicate::eq;
+use std::sync::Arc;
+use tower::BoxError;
+use uuid::Uuid;
+
+use crate::{
+    adapters::{clock::fixed::FixedClock, database::memory::MemoryDatabase},
+    commands::DomainLogic,
+    domain::{Capability, Channel, LoyaltyEvent, MemberRestriction, Tier},
+    ports::{
+        database::DatabasePort,
+        member::{Member, MockMemberPort},
+    },
+};
+
+/// Time of the fixture's clock
+pub fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).unwrap()
+}
+
+/// Member known by the member port, with loyalty events in the database
+pub struct MemberBuilder {
+    member_id: Uuid,
+    active_member: bool,
+    membership_months: u32,
+    /// Country of residence, as an ISO 3166-1 alpha-2 code
+    country: Option<String>,
+    /// Marketing segments known by the member port
+    segment_tags: Option<Vec<String>>,
+    /// Events to register, in order
+    history: Vec<LoyaltyEvent>,
+    /// Capabilities suspended for a week from [`now`]
+    restrictions: Vec<Capability>,
+}
+
+/// Ports and domain logic for a member built with [`MemberBuilder`]
+pub struct Fixture {
+    pub member_id: Uuid,
+    /// Database shared with `domain`
+    pub database: MemoryDatabase,
+    pub domain: DomainLogic<MemoryDatabase, MockMemberPort>,
+}
+
+impl MemberBuilder {
+    /// Member with just enough months of membership for `tier`
+    ///
+    /// [`Tier::None`] gives a member without an active membership.
+    pub fn tier(tier: Tier) -> Self {
+        Self {
+            member_id: Uuid::new_v4(),
+            active_member: tier != Tier::None,
+            membership_months: tier.min_membership_months().unwrap_or(0),
+            country: None,
+            segment_tags: None,
+            history: Vec::new(),
+            restrictions: Vec::new(),
+        }
+    }
+
+    pub fn basic() -> Self {
+        Self::tier(Tier::Basic)
+    }
+
+    pub fn gold() -> Self {
+        Self::tier(Tier::Gold)
+    }
+
+    pub fn with_country(mut self, country: impl Into<String>) -> Self {
+        self.country = Some(country.into());
+        self
+    }
+
+    pub fn with_segment_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.segment_tags = Some(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Add an event crediting `points`
+    pub fn with_points(self, points: u32) -> Self {
+        self.with_history([points as i32])
+    }
+
+    /// Add events with these points, in order
+    pub fn with_history(mut self, history: impl IntoIterator<Item = i32>) -> Self {
+        self.history.extend(history.into_iter().map(event));
+        self
+    }
+
+    /// Add an event crediting `points` for an online purchase
+    pub fn with_purchase(mut self, points: u32) -> Self {
+        self.history.push(LoyaltyEvent {
+            channel: Some(Channel::Online),
+            ..event(points as i32)
+        });
+        self
+    }
+
+    /// Suspend the member's `capability` for a week from [`now`]
+    pub fn with_restriction(mut self, capability: Capability) -> Self {
+        self.restrictions.push(capability);
+        self
+    }
+
+    /// The member as returned by the member port
+    pub fn member(&self) -> Member {
+        Member {
+            member_id: self.member_id,
+            active_member: self.active_member,
+            membership_since: now() - Months::new(self.membership_months),
+            country: self.country.clone(),
+            segment_tags: self.segment_tags.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Register the member's history and create domain logic for them
+    pub async fn build(self) -> Result<Fixture, BoxError> {
+        let database = MemoryDatabase::default();
+        for event in &self.history {
+            database
+                .register_loyalty_event(self.member_id, event.clone(), None)
+                .await?;
+        }
+        for capability in &self.restrictions {
+            database
+                .register_member_restriction(MemberRestriction {
+                    restriction_id: Uuid::new_v4(),
+                    member_id: self.member_id,
+                    capability: *capability,
+                    reason: "SOME REASON".to_string(),
+                    restricted_by: "fraud-ops".to_string(),
+                    restricted_at: now(),
+                    expires_at: now() + Duration::days(7),
+                })
+                .await?;
+        }
+
+        let db_member = self.member();
+        let mut member = MockMemberPort::new();
+        member
+            .expect_get_member()
+            .with(eq(self.member_id))
+            .returning(move |_| Ok(db_member.clone()));
+        let domain = DomainLogic::new(Arc::new(database.clone()), Arc::new(member))
+            .with_clock(Arc::new(FixedClock::new(now())));
+
+        Ok(Fixture {
+            member_id: self.member_id,
+            database,
+            domain,
+        })
+    }
+}
+
+/// Loyalty event with only points set
+fn event(delta_points: i32) -> LoyaltyEvent {
+    LoyaltyEvent::new(Uuid::new_v4(), delta_points, "SOME REASON", now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculoos::prelude::*;
+
+    #[tokio::test]
+    async fn test_build() -> Result<(), BoxError> {
+        let builder = MemberBuilder::gold()
+            .with_points(1200)
+            .with_history([-200, 50])
+            .with_purchase(100);
+        assert_that!(builder.member().membership_since).is_equal_to(now() - Months::new(24));
+
+        let fixture = builder.build().await?;
+
+        let loyalty = fixture
+            .database
+            .get_loyalty_points(fixture.member_id)
+            .await?;
+        assert_that!(loyalty.points).is_equal_to(1150);
+        assert_that!(loyalty.events).has_length(4);
+        assert_that!(loyalty.events[3].channel).is_equal_to(Some(Channel::Online));
+
+        Ok(())
+    }
+}